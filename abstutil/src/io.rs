@@ -8,7 +8,7 @@ use std;
 use std::cmp::Ord;
 use std::collections::{BTreeMap, BTreeSet};
 use std::fs::File;
-use std::io::{stdout, BufReader, BufWriter, Error, ErrorKind, Read, Write};
+use std::io::{stdout, BufRead, BufReader, BufWriter, Error, ErrorKind, Read, Write};
 use std::path::Path;
 use std::time::Instant;
 
@@ -54,6 +54,60 @@ pub fn read_binary<T: DeserializeOwned>(path: &str, timer: &mut Timer) -> Result
     Ok(obj)
 }
 
+// Only reads the first thing written by write_binary. When that was a tuple, this is the first
+// element, so a small header can be checked without reading the rest of the file.
+pub fn read_binary_header<T: DeserializeOwned>(path: &str) -> Result<T, Error> {
+    let file = BufReader::new(File::open(path)?);
+    bincode::deserialize_from(file).map_err(|err| Error::new(ErrorKind::Other, err))
+}
+
+// Appends things to a file one at a time, for outputs too big to build up in memory first. Read
+// them back in the same order with BinaryStreamReader.
+pub struct BinaryStreamWriter {
+    file: BufWriter<File>,
+}
+
+impl BinaryStreamWriter {
+    pub fn new(path: &str) -> Result<BinaryStreamWriter, Error> {
+        std::fs::create_dir_all(std::path::Path::new(path).parent().unwrap())?;
+        Ok(BinaryStreamWriter {
+            file: BufWriter::new(File::create(path)?),
+        })
+    }
+
+    pub fn append<T: Serialize>(&mut self, obj: &T) -> Result<(), Error> {
+        bincode::serialize_into(&mut self.file, obj)
+            .map_err(|err| Error::new(ErrorKind::Other, err))
+    }
+
+    pub fn flush(&mut self) -> Result<(), Error> {
+        self.file.flush()
+    }
+}
+
+pub struct BinaryStreamReader {
+    file: BufReader<File>,
+}
+
+impl BinaryStreamReader {
+    pub fn open(path: &str) -> Result<BinaryStreamReader, Error> {
+        Ok(BinaryStreamReader {
+            file: BufReader::new(File::open(path)?),
+        })
+    }
+
+    // None at the end of the file. The caller has to ask for the same types in the same order
+    // they were appended.
+    pub fn read_next<T: DeserializeOwned>(&mut self) -> Result<Option<T>, Error> {
+        if self.file.fill_buf()?.is_empty() {
+            return Ok(None);
+        }
+        let obj: T = bincode::deserialize_from(&mut self.file)
+            .map_err(|err| Error::new(ErrorKind::Other, err))?;
+        Ok(Some(obj))
+    }
+}
+
 // For BTreeMaps with struct keys. See https://github.com/serde-rs/json/issues/402.
 
 pub fn serialize_btreemap<S: Serializer, K: Serialize, V: Serialize>(
@@ -12,8 +12,9 @@ pub use crate::collections::{contains_duplicates, retain_btreemap, wraparound_ge
 pub use crate::error::Error;
 pub use crate::io::{
     deserialize_btreemap, deserialize_multimap, find_next_file, find_prev_file, list_all_objects,
-    load_all_objects, read_binary, read_json, save_object, serialize_btreemap, serialize_multimap,
-    to_json, write_binary, write_json, FileWithProgress,
+    load_all_objects, read_binary, read_binary_header, read_json, save_object, serialize_btreemap,
+    serialize_multimap, to_json, write_binary, write_json, BinaryStreamReader, BinaryStreamWriter,
+    FileWithProgress,
 };
 pub use crate::logs::Warn;
 pub use crate::notes::note;
@@ -43,6 +43,9 @@ pub fn build_ch(path: String, map: &Map, timer: &mut Timer) {
     let mut nodes: HashMap<Node, NodeIndex<u32>> = HashMap::new();
 
     for l in map.all_lanes() {
+        if map.is_lane_removed(l.id) {
+            continue;
+        }
         let start = g.add_node(Node::Start(l.id));
         let end = g.add_node(Node::End(l.id));
         nodes.insert(Node::Start(l.id), start);
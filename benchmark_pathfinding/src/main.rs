@@ -53,14 +53,14 @@ fn main() {
         .map(|_| {
             let lane1 = loop {
                 let l = map.all_lanes().choose(&mut rng).unwrap();
-                if !l.is_parking() {
+                if !l.is_parking() && !map.is_lane_removed(l.id) {
                     break l.id;
                 }
             };
             let sidewalk = map.get_l(lane1).is_sidewalk();
             let lane2 = loop {
                 let l = map.all_lanes().choose(&mut rng).unwrap();
-                if l.id == lane1 {
+                if l.id == lane1 || map.is_lane_removed(l.id) {
                     continue;
                 }
                 if sidewalk && l.is_sidewalk() {
@@ -1,130 +1,348 @@
+use crate::srtm::Elevation;
 use abstutil::{retain_btreemap, Timer};
 use clipping::CPolygon;
-use geom::{PolyLine, Polygon, Pt2D};
+use geojson::{GeoJson, Geometry, Value};
+use geom::{Distance, GPSBounds, Line, LonLat, Polygon, Pt2D};
 use map_model::{raw_data, IntersectionType};
+use std::collections::HashSet;
+use std::fs::File;
+use std::io::{BufRead, BufReader};
 
-pub fn clip_map(map: &mut raw_data::Map, timer: &mut Timer) {
+// The boundary can come from an osmosis .poly file, a GeoJSON polygon, or a bounding box written
+// as "min_lon,min_lat,max_lon,max_lat".
+pub fn read_boundary(input: &str) -> Vec<LonLat> {
+    let mut pts = if input.ends_with(".poly") {
+        read_osmosis_polygon(input)
+    } else if input.ends_with(".geojson") || input.ends_with(".json") {
+        read_geojson_polygon(input)
+    } else if let Some(pts) = parse_bbox(input) {
+        pts
+    } else {
+        panic!("Don't know how to read a boundary from {}", input);
+    };
+    if pts[0] != *pts.last().unwrap() {
+        pts.push(pts[0]);
+    }
+    pts
+}
+
+fn read_osmosis_polygon(path: &str) -> Vec<LonLat> {
+    let mut pts: Vec<LonLat> = Vec::new();
+    for (idx, maybe_line) in BufReader::new(File::open(path).unwrap())
+        .lines()
+        .enumerate()
+    {
+        if idx == 0 || idx == 1 {
+            continue;
+        }
+        let line = maybe_line.unwrap();
+        if line == "END" {
+            break;
+        }
+        let parts: Vec<&str> = line.trim_start().split("    ").collect();
+        assert!(parts.len() == 2);
+        let lon = parts[0].parse::<f64>().unwrap();
+        let lat = parts[1].parse::<f64>().unwrap();
+        pts.push(LonLat::new(lon, lat));
+    }
+    pts
+}
+
+// Uses the outer ring of the first polygon found.
+fn read_geojson_polygon(path: &str) -> Vec<LonLat> {
+    let document: GeoJson = abstutil::read_json(path).unwrap();
+    let geometries: Vec<Geometry> = match document {
+        GeoJson::Geometry(g) => vec![g],
+        GeoJson::Feature(f) => f.geometry.into_iter().collect(),
+        GeoJson::FeatureCollection(c) => {
+            c.features.into_iter().filter_map(|f| f.geometry).collect()
+        }
+    };
+    for g in geometries {
+        let rings = match g.value {
+            Value::Polygon(rings) => rings,
+            Value::MultiPolygon(mut polygons) => {
+                if polygons.len() > 1 {
+                    println!("{} has a multipolygon; only using the first part", path);
+                }
+                polygons.remove(0)
+            }
+            _ => {
+                continue;
+            }
+        };
+        if rings.len() > 1 {
+            println!("{} has a polygon with holes; ignoring them", path);
+        }
+        return rings[0]
+            .iter()
+            .map(|pt| LonLat::new(pt[0], pt[1]))
+            .collect();
+    }
+    panic!("{} doesn't have a polygon", path);
+}
+
+fn parse_bbox(input: &str) -> Option<Vec<LonLat>> {
+    let parts: Vec<f64> = input
+        .split(',')
+        .map(|x| x.trim().parse::<f64>().ok())
+        .collect::<Option<Vec<f64>>>()?;
+    if parts.len() != 4 {
+        return None;
+    }
+    let (min_lon, min_lat, max_lon, max_lat) = (parts[0], parts[1], parts[2], parts[3]);
+    Some(vec![
+        LonLat::new(min_lon, min_lat),
+        LonLat::new(max_lon, min_lat),
+        LonLat::new(max_lon, max_lat),
+        LonLat::new(min_lon, max_lat),
+        LonLat::new(min_lon, min_lat),
+    ])
+}
+
+pub fn clip_map(map: &mut raw_data::Map, elevation: &Elevation, timer: &mut Timer) {
     timer.start("clipping map to boundary");
     let bounds = map.get_gps_bounds();
 
-    let boundary_poly = Polygon::new(&bounds.must_convert(&map.boundary_polygon));
-    let boundary_lines: Vec<PolyLine> = boundary_poly
-        .points()
+    let boundary_pts = bounds.must_convert(&map.boundary_polygon);
+    let boundary_poly = Polygon::new(&boundary_pts);
+    let boundary_lines: Vec<Line> = boundary_pts
         .windows(2)
-        .map(|pair| PolyLine::new(pair.to_vec()))
+        .filter_map(|pair| Line::maybe_new(pair[0], pair[1]))
         .collect();
 
-    // This is kind of indirect and slow, but first pass -- just remove roads that start or end
-    // outside the boundary polygon.
-    retain_btreemap(&mut map.roads, |_, r| {
-        let center_pts = bounds.must_convert(&r.points);
-        let first_in = boundary_poly.contains_pt(center_pts[0]);
-        let last_in = boundary_poly.contains_pt(*center_pts.last().unwrap());
-        first_in || last_in
-    });
-
+    // Cut every road at every place it crosses the boundary, keeping only the pieces inside. Each
+    // cut gets a new border intersection.
+    let mut next_road = map.roads.keys().map(|id| id.0 + 1).max().unwrap_or(0);
+    let mut next_intersection = map
+        .intersections
+        .keys()
+        .map(|id| id.0 + 1)
+        .max()
+        .unwrap_or(0);
     let road_ids: Vec<raw_data::StableRoadID> = map.roads.keys().cloned().collect();
     for id in road_ids {
-        let r = &map.roads[&id];
-        let center_pts = bounds.must_convert(&r.points);
-        let first_in = boundary_poly.contains_pt(center_pts[0]);
-        let last_in = boundary_poly.contains_pt(*center_pts.last().unwrap());
+        let orig_road = map.roads.remove(&id).unwrap();
+        let pieces = clip_polyline(
+            &bounds.must_convert(&orig_road.points),
+            &boundary_poly,
+            &boundary_lines,
+        );
+        if pieces.len() > 1 {
+            println!(
+                "OSM way {} dips out of bounds, splitting it into {} roads",
+                orig_road.osm_way_id,
+                pieces.len()
+            );
+        }
+        for (idx, piece) in pieces.into_iter().enumerate() {
+            let mut r = orig_road.clone();
+            r.points = bounds.must_convert_back(&piece.pts);
+            if piece.starts_at_border {
+                r.i1 = raw_data::StableIntersectionID(next_intersection);
+                next_intersection += 1;
+                map.intersections
+                    .insert(r.i1, border_intersection(r.points[0], elevation));
+            }
+            if piece.ends_at_border {
+                r.i2 = raw_data::StableIntersectionID(next_intersection);
+                next_intersection += 1;
+                map.intersections.insert(
+                    r.i2,
+                    border_intersection(*r.points.last().unwrap(), elevation),
+                );
+            }
+            let road_id = if idx == 0 {
+                id
+            } else {
+                next_road += 1;
+                raw_data::StableRoadID(next_road - 1)
+            };
+            map.roads.insert(road_id, r);
+        }
+    }
+
+    // Intersections outside the boundary don't belong to any road anymore.
+    let mut used_intersections: HashSet<raw_data::StableIntersectionID> = HashSet::new();
+    for r in map.roads.values() {
+        used_intersections.insert(r.i1);
+        used_intersections.insert(r.i2);
+    }
+    retain_btreemap(&mut map.intersections, |id, _| {
+        used_intersections.contains(id)
+    });
 
-        // Some roads start and end in-bounds, but dip out of bounds. Leave those alone for now.
-        if first_in && last_in {
+    let mut result_buildings = Vec::new();
+    for orig_bldg in map.buildings.drain(..) {
+        let pts = bounds.must_convert(&orig_bldg.points);
+        if pts.iter().all(|pt| boundary_poly.contains_pt(*pt)) {
+            result_buildings.push(orig_bldg);
             continue;
         }
+        for (points, inner_rings) in clip_polygon(
+            &orig_bldg.points,
+            &orig_bldg.inner_rings,
+            &boundary_pts,
+            &bounds,
+        ) {
+            let mut bldg = orig_bldg.clone();
+            bldg.points = points;
+            bldg.inner_rings = inner_rings;
+            result_buildings.push(bldg);
+        }
+    }
+    map.buildings = result_buildings;
 
-        let mut move_i = if first_in { r.i2 } else { r.i1 };
-
-        // The road crosses the boundary. If the intersection happens to have another connected
-        // road, then we need to copy the intersection before trimming it. This effectively
-        // disconnects too roads in the map that would be connected if we left in some
-        // partly-out-of-bounds road.
-        if map
-            .roads
-            .values()
-            .filter(|r2| r2.i1 == move_i || r2.i2 == move_i)
-            .count()
-            > 1
-        {
-            let copy = map.intersections[&move_i].clone();
-            // Nothing deletes intersections yet, so this is safe.
-            move_i = raw_data::StableIntersectionID(map.intersections.len());
-            map.intersections.insert(move_i, copy);
-            println!("Disconnecting {} from some other stuff", id);
-            // We don't need to mark the existing intersection as a border and make sure to split
-            // all other roads up too. That'll happen later in this loop.
+    let mut result_areas = Vec::new();
+    for orig_area in map.areas.drain(..) {
+        for (points, inner_rings) in clip_polygon(
+            &orig_area.points,
+            &orig_area.inner_rings,
+            &boundary_pts,
+            &bounds,
+        ) {
+            let mut area = orig_area.clone();
+            area.points = points;
+            area.inner_rings = inner_rings;
+            result_areas.push(area);
         }
+    }
+    map.areas = result_areas;
+
+    timer.stop("clipping map to boundary");
+}
+
+fn border_intersection(point: LonLat, elevation: &Elevation) -> raw_data::Intersection {
+    raw_data::Intersection {
+        point,
+        elevation: Distance::meters(elevation.get(point.longitude, point.latitude)),
+        intersection_type: IntersectionType::Border,
+        label: None,
+        // Made up where a road crosses the boundary
+        osm_node_id: None,
+    }
+}
 
-        let i = map.intersections.get_mut(&move_i).unwrap();
-        i.intersection_type = IntersectionType::Border;
+// Part of a line that's inside the boundary. The ends are either the ends of the original line,
+// or places where it crosses the boundary.
+pub struct Piece {
+    pub pts: Vec<Pt2D>,
+    pub starts_at_border: bool,
+    pub ends_at_border: bool,
+}
 
-        // Convert the road points to a PolyLine here. Loop roads were breaking!
-        let center = PolyLine::new(center_pts);
+pub fn clip_polyline(
+    pts: &Vec<Pt2D>,
+    boundary_poly: &Polygon,
+    boundary_lines: &Vec<Line>,
+) -> Vec<Piece> {
+    let mut pieces: Vec<Piece> = Vec::new();
+    let mut current: Option<Piece> = if boundary_poly.contains_pt(pts[0]) {
+        Some(Piece {
+            pts: vec![pts[0]],
+            starts_at_border: false,
+            ends_at_border: false,
+        })
+    } else {
+        None
+    };
 
-        // Now trim it.
-        let mut_r = map.roads.get_mut(&id).unwrap();
-        let border_pt = boundary_lines
+    for pair in pts.windows(2) {
+        let segment = match Line::maybe_new(pair[0], pair[1]) {
+            Some(l) => l,
+            None => {
+                continue;
+            }
+        };
+        let mut hits: Vec<Pt2D> = boundary_lines
             .iter()
-            .find_map(|l| center.intersection(l).map(|(pt, _)| pt))
-            .unwrap();
-        if first_in {
-            mut_r.points =
-                bounds.must_convert_back(center.get_slice_ending_at(border_pt).unwrap().points());
-            i.point = *mut_r.points.last().unwrap();
-            // This has no effect unless we made a copy of the intersection to disconnect it from
-            // other roads.
-            mut_r.i2 = move_i;
-        } else {
-            mut_r.points = bounds.must_convert_back(
-                center
-                    .reversed()
-                    .get_slice_ending_at(border_pt)
-                    .unwrap()
-                    .reversed()
-                    .points(),
-            );
-            i.point = mut_r.points[0];
-            mut_r.i1 = move_i;
+            .filter_map(|l| segment.intersection(l))
+            .collect();
+        hits.sort_by_key(|pt| pair[0].dist_to(*pt));
+        // Crossing exactly at a corner of the boundary hits two of its lines.
+        hits.dedup_by(|pt1, pt2| pt1.epsilon_eq(*pt2));
+
+        // Every crossing flips between inside and outside.
+        for hit in hits {
+            match current.take() {
+                Some(mut piece) => {
+                    piece.pts.push(hit);
+                    piece.ends_at_border = true;
+                    pieces.push(piece);
+                }
+                None => {
+                    current = Some(Piece {
+                        pts: vec![hit],
+                        starts_at_border: true,
+                        ends_at_border: false,
+                    });
+                }
+            }
+        }
+        if let Some(ref mut piece) = current {
+            piece.pts.push(pair[1]);
         }
     }
+    if let Some(piece) = current {
+        pieces.push(piece);
+    }
 
-    map.buildings.retain(|b| {
-        bounds
-            .must_convert(&b.points)
-            .into_iter()
-            .all(|pt| boundary_poly.contains_pt(pt))
-    });
+    // Crossings right on top of an existing point leave duplicates behind.
+    for piece in pieces.iter_mut() {
+        piece.pts.dedup_by(|pt1, pt2| pt1.epsilon_eq(*pt2));
+    }
+    pieces.retain(|piece| piece.pts.len() >= 2);
+    pieces
+}
 
-    let mut result_areas = Vec::new();
-    for orig_area in map.areas.drain(..) {
-        let mut boundary_pts = CPolygon::from_vec(
-            &boundary_poly
-                .points()
-                .into_iter()
-                .map(|pt| [pt.x(), pt.y()])
-                .collect(),
-        );
-        let mut area_pts = CPolygon::from_vec(
-            &bounds
-                .must_convert(&orig_area.points)
-                .into_iter()
-                .map(|pt| [pt.x(), pt.y()])
-                .collect(),
-        );
-        let results = area_pts.intersection(&mut boundary_pts);
-        for pts in results {
-            let mut area = orig_area.clone();
-            area.points = bounds
-                .must_convert_back(&pts.into_iter().map(|pt| Pt2D::new(pt[0], pt[1])).collect());
-            if area.points[0] != *area.points.last().unwrap() {
-                area.points.push(area.points[0]);
+// Returns each piece of the polygon inside the boundary, along with the pieces of inner rings
+// inside it.
+pub fn clip_polygon(
+    outer: &Vec<LonLat>,
+    inner_rings: &Vec<Vec<LonLat>>,
+    boundary_pts: &Vec<Pt2D>,
+    bounds: &GPSBounds,
+) -> Vec<(Vec<LonLat>, Vec<Vec<LonLat>>)> {
+    let mut pieces: Vec<(Vec<Pt2D>, Vec<Vec<LonLat>>)> =
+        clip_ring(&bounds.must_convert(outer), boundary_pts)
+            .into_iter()
+            .map(|pts| (pts, Vec::new()))
+            .collect();
+    for inner in inner_rings {
+        for hole in clip_ring(&bounds.must_convert(inner), boundary_pts) {
+            let center = Pt2D::center(&hole);
+            if let Some(piece) = pieces
+                .iter_mut()
+                .find(|piece| Polygon::new(&piece.0).contains_pt(center))
+            {
+                piece.1.push(bounds.must_convert_back(&hole));
             }
-            result_areas.push(area);
         }
     }
-    map.areas = result_areas;
+    pieces
+        .into_iter()
+        .map(|(pts, holes)| (bounds.must_convert_back(&pts), holes))
+        .collect()
+}
 
-    timer.stop("clipping map to boundary");
+// Each result is closed.
+fn clip_ring(pts: &Vec<Pt2D>, boundary_pts: &Vec<Pt2D>) -> Vec<Vec<Pt2D>> {
+    let mut boundary =
+        CPolygon::from_vec(&boundary_pts.iter().map(|pt| [pt.x(), pt.y()]).collect());
+    let mut polygon = CPolygon::from_vec(&pts.iter().map(|pt| [pt.x(), pt.y()]).collect());
+    polygon
+        .intersection(&mut boundary)
+        .into_iter()
+        .filter(|result| result.len() >= 3)
+        .map(|result| {
+            let mut points: Vec<Pt2D> = result
+                .into_iter()
+                .map(|pt| Pt2D::new(pt[0], pt[1]))
+                .collect();
+            if points[0] != *points.last().unwrap() {
+                points.push(points[0]);
+            }
+            points
+        })
+        .collect()
 }
@@ -1,43 +1,52 @@
 mod clip;
 mod neighborhoods;
 mod osm;
+mod pbf;
+mod profile;
 mod remove_disconnected;
 mod split_ways;
 mod srtm;
+mod traffic_signals;
 
-use crate::srtm::Elevation;
+pub use crate::clip::{clip_polygon, clip_polyline, Piece};
+pub use crate::profile::{ImportProfile, ParkingSource, ResidentialSource, TrafficSignalSource};
+pub use crate::srtm::{parse_tile_name, tile_name, Elevation};
 use abstutil::Timer;
-use geom::{Distance, FindClosest, GPSBounds, LonLat, PolyLine, Pt2D};
+use geom::{Distance, FindClosest, GPSBounds, PolyLine, Pt2D};
 use kml::ExtraShapes;
-use map_model::{raw_data, IntersectionType, LANE_THICKNESS};
-use std::fs::File;
-use std::io::{BufRead, BufReader};
+use map_model::{raw_data, LANE_THICKNESS};
 use std::path::Path;
 use structopt::StructOpt;
 
-const MAX_DIST_BTWN_INTERSECTION_AND_SIGNAL: Distance = Distance::const_meters(50.0);
 const MAX_DIST_BTWN_BLDG_PERMIT_AND_BLDG: Distance = Distance::const_meters(10.0);
 
 #[derive(StructOpt, Debug)]
 #[structopt(name = "convert_osm")]
 pub struct Flags {
-    /// OSM XML file to read
+    /// OSM XML or .osm.pbf file to read. XML must already be clipped to the boundary.
     #[structopt(long = "osm")]
     pub osm: String,
 
-    /// HGT with elevation data
-    #[structopt(long = "elevation")]
+    /// SRTM elevation data: one .hgt tile, or a directory with the .hgt tiles covering the map.
+    /// Optional; without it, the map is flat.
+    #[structopt(long = "elevation", default_value = "")]
     pub elevation: String,
 
+    /// JSON ImportProfile describing other datasets to use, like data/profiles/seattle.json.
+    /// Optional. The flags below override anything in it.
+    #[structopt(long = "profile", default_value = "")]
+    pub profile: String,
+
     /// KML with traffic signals. Optional.
     #[structopt(long = "traffic_signals", default_value = "")]
     pub traffic_signals: String,
 
-    /// KML with residential building permits. Optional.
+    /// KML with Seattle's residential building permits. Optional.
     #[structopt(long = "residential_buildings", default_value = "")]
     pub residential_buildings: String,
 
-    /// ExtraShapes file with blockface, produced using the kml crate. Optional.
+    /// ExtraShapes file with Seattle's blockface, produced using the kml crate. Optional; without
+    /// it, parking comes from OSM tags.
     #[structopt(long = "parking_shapes", default_value = "")]
     pub parking_shapes: String,
 
@@ -49,7 +58,8 @@ pub struct Flags {
     #[structopt(long = "neighborhoods", default_value = "")]
     pub neighborhoods: String,
 
-    /// Osmosis clipping polgon
+    /// Boundary to clip everything to: an osmosis .poly file, a GeoJSON polygon, or a bounding box
+    /// written as min_lon,min_lat,max_lon,max_lat
     #[structopt(long = "clip")]
     pub clip: String,
 
@@ -63,12 +73,21 @@ pub struct Flags {
 }
 
 pub fn convert(flags: &Flags, timer: &mut abstutil::Timer) -> raw_data::Map {
-    let elevation = Elevation::new(&flags.elevation).expect("loading .hgt failed");
+    let profile = ImportProfile::new(flags);
+    let boundary_polygon = clip::read_boundary(&flags.clip);
+    let elevation =
+        Elevation::new(&flags.elevation, &boundary_polygon, timer).expect("loading .hgt failed");
 
-    let mut map =
-        split_ways::split_up_roads(osm::osm_to_raw_roads(&flags.osm, timer), &elevation, timer);
-    map.boundary_polygon = read_osmosis_polygon(&flags.clip);
-    clip::clip_map(&mut map, timer);
+    let extract = osm::osm_to_raw_roads(&flags.osm, &boundary_polygon, timer);
+    let mut map = split_ways::split_up_roads(
+        (extract.roads, extract.buildings, extract.areas),
+        &extract.road_nodes,
+        &elevation,
+        timer,
+    );
+    map.boundary_polygon = boundary_polygon;
+    map.bike_parking = extract.bike_parking;
+    clip::clip_map(&mut map, &elevation, timer);
     remove_disconnected::remove_disconnected_roads(&mut map, timer);
 
     if flags.fast_dev {
@@ -77,22 +96,45 @@ pub fn convert(flags: &Flags, timer: &mut abstutil::Timer) -> raw_data::Map {
     // Do this after removing stuff.
     let gps_bounds = map.get_gps_bounds();
 
-    if !flags.residential_buildings.is_empty() {
-        handle_residences(&mut map, &gps_bounds, &flags.residential_buildings, timer);
-    }
-    if !flags.parking_shapes.is_empty() {
-        use_parking_hints(&mut map, &gps_bounds, &flags.parking_shapes, timer);
+    if let Some(ref source) = profile.residential_buildings {
+        handle_residences(&mut map, &gps_bounds, source, timer);
     }
-    if !flags.traffic_signals.is_empty() {
-        handle_traffic_signals(&mut map, &gps_bounds, &flags.traffic_signals, timer);
+    match profile.parking {
+        ParkingSource::OsmTags => {
+            use_osm_parking_tags(&mut map, timer);
+        }
+        ParkingSource::Blockface {
+            ref path,
+            ref category_attribute,
+            ref no_parking_categories,
+        } => {
+            use_parking_hints(
+                &mut map,
+                &gps_bounds,
+                path,
+                category_attribute,
+                no_parking_categories,
+                timer,
+            );
+        }
     }
-    if !flags.gtfs.is_empty() {
+    traffic_signals::apply(
+        &mut map,
+        &gps_bounds,
+        &extract.traffic_signals,
+        profile
+            .traffic_signals
+            .as_ref()
+            .map(|source| source.path.as_str()),
+        timer,
+    );
+    if let Some(ref path) = profile.gtfs {
         timer.start("load GTFS");
-        map.bus_routes = gtfs::load(&flags.gtfs).unwrap();
+        map.bus_routes = gtfs::load(path).unwrap();
         timer.stop("load GTFS");
     }
 
-    if !flags.neighborhoods.is_empty() {
+    if let Some(ref path) = profile.neighborhoods {
         timer.start("convert neighborhood polygons");
         let map_name = Path::new(&flags.output)
             .file_stem()
@@ -100,21 +142,53 @@ pub fn convert(flags: &Flags, timer: &mut abstutil::Timer) -> raw_data::Map {
             .to_os_string()
             .into_string()
             .unwrap();
-        neighborhoods::convert(&flags.neighborhoods, map_name, &gps_bounds);
+        neighborhoods::convert(path, map_name, &gps_bounds);
         timer.stop("convert neighborhood polygons");
     }
 
     map
 }
 
+fn use_osm_parking_tags(map: &mut raw_data::Map, timer: &mut Timer) {
+    timer.start("apply parking tags from OSM");
+    for r in map.roads.values_mut() {
+        let both = r.osm_tags.get("parking:lane:both").map(|v| has_parking(v));
+        // The right side is forwards, assuming people drive on the right.
+        if let Some(fwd) = r
+            .osm_tags
+            .get("parking:lane:right")
+            .map(|v| has_parking(v))
+            .or(both)
+        {
+            r.parking_lane_fwd = fwd;
+        }
+        if let Some(back) = r
+            .osm_tags
+            .get("parking:lane:left")
+            .map(|v| has_parking(v))
+            .or(both)
+        {
+            r.parking_lane_back = back;
+        }
+    }
+    timer.stop("apply parking tags from OSM");
+}
+
+// https://wiki.openstreetmap.org/wiki/Key:parking:lane
+fn has_parking(value: &str) -> bool {
+    value == "parallel" || value == "diagonal" || value == "perpendicular" || value == "marked"
+}
+
 fn use_parking_hints(
     map: &mut raw_data::Map,
     gps_bounds: &GPSBounds,
     path: &str,
+    category_attribute: &str,
+    no_parking_categories: &[String],
     timer: &mut Timer,
 ) {
     timer.start("apply parking hints");
-    println!("Loading blockface shapes from {}", path);
+    timer.note(format!("Loading blockface shapes from {}", path));
     let shapes: ExtraShapes = abstutil::read_binary(path, timer).expect("loading blockface failed");
 
     // Match shapes with the nearest road + direction (true for forwards)
@@ -142,9 +216,10 @@ fn use_parking_hints(
             // the threshold distance?
             let middle = PolyLine::new(pts).middle();
             if let Some(((r, fwds), _)) = closest.closest_pt(middle, LANE_THICKNESS * 5.0) {
-                let category = s.attributes.get("PARKING_CATEGORY");
-                let has_parking = category != Some(&"None".to_string())
-                    && category != Some(&"No Parking Allowed".to_string());
+                let has_parking = match s.attributes.get(category_attribute) {
+                    Some(category) => !no_parking_categories.contains(category),
+                    None => true,
+                };
                 // Blindly override prior values.
                 if fwds {
                     map.roads.get_mut(&r).unwrap().parking_lane_fwd = has_parking;
@@ -157,47 +232,10 @@ fn use_parking_hints(
     timer.stop("apply parking hints");
 }
 
-fn handle_traffic_signals(
-    map: &mut raw_data::Map,
-    gps_bounds: &GPSBounds,
-    path: &str,
-    timer: &mut Timer,
-) {
-    timer.start("handle traffic signals");
-    for shape in kml::load(path, gps_bounds, timer)
-        .expect("loading traffic signals failed")
-        .shapes
-        .into_iter()
-    {
-        // See https://www.seattle.gov/Documents/Departments/SDOT/GIS/Traffic_Signals_OD.pdf
-        if shape.points.len() > 1 {
-            panic!("Traffic signal has multiple points: {:?}", shape);
-        }
-        let pt = shape.points[0];
-        if gps_bounds.contains(pt) {
-            // TODO use a quadtree or some better way to match signals to the closest
-            // intersection
-            let closest_intersection = map
-                .intersections
-                .values_mut()
-                .min_by_key(|i| pt.gps_dist_meters(i.point))
-                .unwrap();
-            let dist = pt.gps_dist_meters(closest_intersection.point);
-            if dist <= MAX_DIST_BTWN_INTERSECTION_AND_SIGNAL {
-                if closest_intersection.intersection_type == IntersectionType::TrafficSignal {
-                    println!("WARNING: {:?} already has a traffic signal, but there's another one that's {} from it", closest_intersection, dist);
-                }
-                closest_intersection.intersection_type = IntersectionType::TrafficSignal;
-            }
-        }
-    }
-    timer.stop("handle traffic signals");
-}
-
 fn handle_residences(
     map: &mut raw_data::Map,
     gps_bounds: &GPSBounds,
-    path: &str,
+    source: &ResidentialSource,
     timer: &mut Timer,
 ) {
     timer.start("match residential permits with buildings");
@@ -208,7 +246,7 @@ fn handle_residences(
         closest.add_gps(idx, &b.points, gps_bounds);
     }
 
-    let shapes = kml::load(path, gps_bounds, timer)
+    let shapes = kml::load(&source.path, gps_bounds, timer)
         .expect("loading residential buildings failed")
         .shapes;
     timer.start_iter("handle residential permits", shapes.len());
@@ -226,7 +264,7 @@ fn handle_residences(
         }
         if let Some(num) = shape
             .attributes
-            .get("net_units")
+            .get(&source.units_attribute)
             .and_then(|n| usize::from_str_radix(n, 10).ok())
         {
             if let Some((idx, _)) = closest.closest_pt(
@@ -241,25 +279,3 @@ fn handle_residences(
     }
     timer.stop("match residential permits with buildings");
 }
-
-fn read_osmosis_polygon(path: &str) -> Vec<LonLat> {
-    let mut pts: Vec<LonLat> = Vec::new();
-    for (idx, maybe_line) in BufReader::new(File::open(path).unwrap())
-        .lines()
-        .enumerate()
-    {
-        if idx == 0 || idx == 1 {
-            continue;
-        }
-        let line = maybe_line.unwrap();
-        if line == "END" {
-            break;
-        }
-        let parts: Vec<&str> = line.trim_start().split("    ").collect();
-        assert!(parts.len() == 2);
-        let lon = parts[0].parse::<f64>().unwrap();
-        let lat = parts[1].parse::<f64>().unwrap();
-        pts.push(LonLat::new(lon, lat));
-    }
-    pts
-}
@@ -33,29 +33,31 @@ pub fn convert(geojson_path: &str, map_name: String, gps_bounds: &GPSBounds) {
 }
 
 fn convert_polygon(input: PolygonType, name: String, map_name: String, gps_bounds: &GPSBounds) {
-    if input.len() > 1 {
-        println!("{} has a polygon with an inner ring, skipping", name);
-        return;
-    }
-
-    let mut points: Vec<LonLat> = Vec::new();
-    for raw_pt in &input[0] {
-        assert_eq!(raw_pt.len(), 2);
-        let pt = LonLat::new(raw_pt[0], raw_pt[1]);
-        if gps_bounds.contains(pt) {
-            points.push(pt);
-        } else {
-            println!(
-                "Neighborhood polygon \"{}\" is out-of-bounds, skipping",
-                name
-            );
-            return;
+    let mut rings: Vec<Vec<LonLat>> = Vec::new();
+    for ring in &input {
+        let mut points: Vec<LonLat> = Vec::new();
+        for raw_pt in ring {
+            assert_eq!(raw_pt.len(), 2);
+            let pt = LonLat::new(raw_pt[0], raw_pt[1]);
+            if gps_bounds.contains(pt) {
+                points.push(pt);
+            } else {
+                println!(
+                    "Neighborhood polygon \"{}\" is out-of-bounds, skipping",
+                    name
+                );
+                return;
+            }
         }
+        rings.push(points);
     }
+    // The first ring is the outside; any others are holes.
+    let points = rings.remove(0);
     NeighborhoodBuilder {
         map_name,
         name,
         points,
+        inner_rings: rings,
     }
     .save();
 }
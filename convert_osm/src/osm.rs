@@ -1,17 +1,23 @@
+use crate::pbf;
+use aabb_quadtree::QuadTree;
 use abstutil::{FileWithProgress, Timer};
-use geom::LonLat;
+use geom::{ring_contains_pt, Bounds, Circle, Distance, GPSBounds, HashablePt2D, LonLat, Pt2D};
 use map_model::{raw_data, AreaType};
 use osm_xml;
 use std::collections::{BTreeMap, HashMap};
 
-pub fn osm_to_raw_roads(
-    osm_path: &str,
-    timer: &mut Timer,
-) -> (
-    Vec<raw_data::Road>,
-    Vec<raw_data::Building>,
-    Vec<raw_data::Area>,
-) {
+pub fn osm_to_raw_roads(osm_path: &str, boundary: &Vec<LonLat>, timer: &mut Timer) -> Extract {
+    let mut extract = if osm_path.ends_with(".osm.pbf") {
+        pbf::read(osm_path, boundary, timer)
+    } else {
+        // The XML is expected to be clipped to the boundary already, using osmosis.
+        read_xml(osm_path, timer)
+    };
+    extract.tag_buildings_with_landuse(timer);
+    extract
+}
+
+fn read_xml(osm_path: &str, timer: &mut Timer) -> Extract {
     let (reader, done) = FileWithProgress::new(osm_path).unwrap();
     let doc = osm_xml::OSM::parse(reader).expect("OSM parsing failed");
     println!(
@@ -22,20 +28,31 @@ pub fn osm_to_raw_roads(
     );
     done(timer);
 
-    let mut id_to_way: HashMap<i64, Vec<LonLat>> = HashMap::new();
-    let mut roads: Vec<raw_data::Road> = Vec::new();
-    let mut buildings: Vec<raw_data::Building> = Vec::new();
-    let mut areas: Vec<raw_data::Area> = Vec::new();
+    let mut extract = Extract::new();
+    for node in doc.nodes.values() {
+        let tags = tags_to_map(&node.tags);
+        if is_traffic_signal(&tags) {
+            extract
+                .traffic_signals
+                .push(LonLat::new(node.lon, node.lat));
+        }
+        if let Some(p) = get_bike_parking(node.id, LonLat::new(node.lon, node.lat), &tags) {
+            extract.bike_parking.push(p);
+        }
+    }
+
     timer.start_iter("processing OSM ways", doc.ways.len());
     for way in doc.ways.values() {
         timer.next();
 
         let mut valid = true;
         let mut pts = Vec::new();
+        let mut node_ids = Vec::new();
         for node_ref in &way.nodes {
             match doc.resolve_reference(node_ref) {
                 osm_xml::Reference::Node(node) => {
                     pts.push(LonLat::new(node.lon, node.lat));
+                    node_ids.push(node.id);
                 }
                 // Don't handle nested ways/relations yet
                 _ => {
@@ -46,10 +63,82 @@ pub fn osm_to_raw_roads(
         if !valid {
             continue;
         }
-        let tags = tags_to_map(&way.tags);
+        extract.add_way(way.id, pts, node_ids, tags_to_map(&way.tags));
+    }
+
+    timer.start_iter("processing OSM relations", doc.relations.len());
+    for rel in doc.relations.values() {
+        timer.next();
+        let mut members = Vec::new();
+        let mut unhandled = Vec::new();
+        for member in &rel.members {
+            match *member {
+                osm_xml::Member::Way(osm_xml::UnresolvedReference::Way(id), ref role) => {
+                    members.push((id, role.clone()));
+                }
+                _ => {
+                    unhandled.push(format!("{:?}", member));
+                }
+            }
+        }
+        extract.add_relation(rel.id, tags_to_map(&rel.tags), members, unhandled, timer);
+    }
+
+    extract
+}
+
+// Everything useful from OSM, no matter what format it came from
+pub struct Extract {
+    pub roads: Vec<raw_data::Road>,
+    pub buildings: Vec<raw_data::Building>,
+    pub areas: Vec<raw_data::Area>,
+    // Nodes tagged with a signal; they might not be at an intersection
+    pub traffic_signals: Vec<LonLat>,
+    pub bike_parking: Vec<raw_data::BikeParking>,
+    // The OSM node at every point of a road, so intersections can remember where they came from
+    pub road_nodes: HashMap<HashablePt2D, i64>,
+    // Any way might be part of a relation. Inner rings are often tagged as something by
+    // themselves.
+    id_to_way: HashMap<i64, Vec<LonLat>>,
+    // The value of the landuse tag and the outer ring
+    landuse: Vec<(String, Vec<LonLat>)>,
+}
+
+impl Extract {
+    pub fn new() -> Extract {
+        Extract {
+            roads: Vec::new(),
+            buildings: Vec::new(),
+            areas: Vec::new(),
+            traffic_signals: Vec::new(),
+            bike_parking: Vec::new(),
+            road_nodes: HashMap::new(),
+            id_to_way: HashMap::new(),
+            landuse: Vec::new(),
+        }
+    }
+
+    // node_ids match up with pts.
+    pub fn add_way(
+        &mut self,
+        id: i64,
+        pts: Vec<LonLat>,
+        node_ids: Vec<i64>,
+        tags: BTreeMap<String, String>,
+    ) {
+        if !is_road(&tags) {
+            self.id_to_way.insert(id, pts.clone());
+        }
+        if let Some(landuse) = get_landuse(&tags) {
+            self.add_landuse(landuse, pts.clone());
+        }
+
         if is_road(&tags) {
-            roads.push(raw_data::Road {
-                osm_way_id: way.id,
+            for (pt, node) in pts.iter().zip(node_ids) {
+                self.road_nodes.insert(pt.to_hashable(), node);
+            }
+            self.roads.push(raw_data::Road {
+                osm_way_id: id,
                 points: pts,
                 osm_tags: tags,
                 // We'll fill this out later
@@ -59,74 +148,186 @@ pub fn osm_to_raw_roads(
                 parking_lane_back: false,
             });
         } else if is_bldg(&tags) {
-            buildings.push(raw_data::Building {
-                osm_way_id: way.id,
+            self.buildings.push(raw_data::Building {
+                osm_way_id: id,
                 points: pts,
+                inner_rings: Vec::new(),
                 osm_tags: tags,
                 num_residential_units: None,
+                landuse: None,
             });
         } else if let Some(at) = get_area_type(&tags) {
-            areas.push(raw_data::Area {
+            self.areas.push(raw_data::Area {
                 area_type: at,
-                osm_id: way.id,
+                osm_id: id,
                 points: pts,
+                inner_rings: Vec::new(),
                 osm_tags: tags,
             });
-        } else {
-            // The way might be part of a relation later.
-            id_to_way.insert(way.id, pts);
         }
     }
 
-    timer.start_iter("processing OSM relations", doc.relations.len());
-    for rel in doc.relations.values() {
-        timer.next();
-        let tags = tags_to_map(&rel.tags);
-        if let Some(at) = get_area_type(&tags) {
-            if tags.get("type") == Some(&"multipolygon".to_string()) {
-                let mut ok = true;
-                let mut pts_per_way: Vec<Vec<LonLat>> = Vec::new();
-                for member in &rel.members {
-                    match *member {
-                        osm_xml::Member::Way(osm_xml::UnresolvedReference::Way(id), ref role) => {
-                            // If the way is clipped out, that's fine
-                            if let Some(pts) = id_to_way.get(&id) {
-                                if role == "outer" {
-                                    pts_per_way.push(pts.to_vec());
-                                } else {
-                                    println!(
-                                        "Relation {} has unhandled member role {}, ignoring it",
-                                        rel.id, role
-                                    );
-                                }
-                            }
-                        }
-                        _ => {
-                            println!("Relation {} refers to {:?}", rel.id, member);
-                            ok = false;
-                        }
-                    }
+    // Call after adding all of the ways. members are way IDs and their roles; unhandled describes
+    // any members that aren't ways.
+    pub fn add_relation(
+        &mut self,
+        id: i64,
+        tags: BTreeMap<String, String>,
+        members: Vec<(i64, String)>,
+        unhandled: Vec<String>,
+        timer: &mut Timer,
+    ) {
+        if tags.get("type") != Some(&"multipolygon".to_string()) {
+            return;
+        }
+        let bldg = is_bldg(&tags);
+        let area_type = get_area_type(&tags);
+        let landuse = get_landuse(&tags);
+        if !bldg && area_type.is_none() && landuse.is_none() {
+            return;
+        }
+        if !unhandled.is_empty() {
+            for member in unhandled {
+                timer.warn(format!("Relation {} refers to {}", id, member));
+            }
+            return;
+        }
+
+        let mut outer_ways: Vec<Vec<LonLat>> = Vec::new();
+        let mut inner_ways: Vec<Vec<LonLat>> = Vec::new();
+        for (way, role) in members {
+            // If the way is clipped out, that's fine
+            if let Some(pts) = self.id_to_way.get(&way) {
+                if pts.len() < 2 {
+                    continue;
                 }
-                if ok {
-                    let polygons = glue_multipolygon(pts_per_way);
-                    if polygons.is_empty() {
-                        println!("Relation {} failed to glue multipolygon", rel.id);
-                    } else {
-                        for points in polygons {
-                            areas.push(raw_data::Area {
-                                area_type: at,
-                                osm_id: rel.id,
-                                points,
-                                osm_tags: tags.clone(),
-                            });
-                        }
-                    }
+                // Old relations sometimes leave the role blank for outer ways.
+                if role == "outer" || role.is_empty() {
+                    outer_ways.push(pts.to_vec());
+                } else if role == "inner" {
+                    inner_ways.push(pts.to_vec());
+                } else {
+                    timer.warn(format!(
+                        "Relation {} has unhandled member role {}, ignoring it",
+                        id, role
+                    ));
                 }
             }
         }
+
+        let mut outers = Vec::new();
+        for (ring, closed) in glue_rings(outer_ways) {
+            if !closed {
+                timer.warn(format!(
+                    "Relation {} has an outer ring with missing ways, connecting its ends",
+                    id
+                ));
+            }
+            outers.push(ring);
+        }
+        let mut inners = Vec::new();
+        for (ring, closed) in glue_rings(inner_ways) {
+            // Connecting the ends of a partial hole would cut a random chunk out of the polygon.
+            if closed {
+                inners.push(ring);
+            } else {
+                timer.warn(format!(
+                    "Relation {} has an inner ring with missing ways, ignoring it",
+                    id
+                ));
+            }
+        }
+        let polygons = assemble_multipolygon(outers, inners, id, timer);
+        if polygons.is_empty() {
+            timer.warn(format!("Relation {} failed to glue multipolygon", id));
+        }
+        for (points, inner_rings) in polygons {
+            if let Some(ref landuse) = landuse {
+                self.add_landuse(landuse.clone(), points.clone());
+            }
+            if bldg {
+                self.buildings.push(raw_data::Building {
+                    osm_way_id: id,
+                    points,
+                    inner_rings,
+                    osm_tags: tags.clone(),
+                    num_residential_units: None,
+                    landuse: None,
+                });
+            } else if let Some(area_type) = area_type {
+                self.areas.push(raw_data::Area {
+                    area_type,
+                    osm_id: id,
+                    points,
+                    inner_rings,
+                    osm_tags: tags.clone(),
+                });
+            }
+        }
     }
 
-    (roads, buildings, areas)
+    fn add_landuse(&mut self, landuse: String, pts: Vec<LonLat>) {
+        if pts.len() >= 4 && pts[0] == *pts.last().unwrap() {
+            self.landuse.push((landuse, pts));
+        }
+    }
+
+    // Plenty of buildings are just tagged building=yes. The land use around them at least hints
+    // at what they are. Call after adding all of the ways and relations.
+    fn tag_buildings_with_landuse(&mut self, timer: &mut Timer) {
+        if self.landuse.is_empty() {
+            return;
+        }
+        let mut gps_bounds = GPSBounds::new();
+        for (_, pts) in &self.landuse {
+            for pt in pts {
+                gps_bounds.update(*pt);
+            }
+        }
+        let rings: Vec<Vec<Pt2D>> = self
+            .landuse
+            .iter()
+            .map(|(_, pts)| gps_bounds.must_convert(pts))
+            .collect();
+        let mut quadtree = QuadTree::default(gps_bounds.to_bounds().as_bbox());
+        for (idx, ring) in rings.iter().enumerate() {
+            quadtree.insert_with_box(idx, Bounds::from(ring).as_bbox());
+        }
+
+        timer.start_iter("tag buildings with land use", self.buildings.len());
+        let mut count = 0;
+        for b in self.buildings.iter_mut() {
+            timer.next();
+            let center = LonLat::new(
+                b.points.iter().map(|pt| pt.longitude).sum::<f64>() / (b.points.len() as f64),
+                b.points.iter().map(|pt| pt.latitude).sum::<f64>() / (b.points.len() as f64),
+            );
+            let pt = match Pt2D::from_gps(center, &gps_bounds) {
+                Some(pt) => pt,
+                None => continue,
+            };
+            // When areas overlap, prefer the first one seen, to stay deterministic.
+            if let Some(idx) = quadtree
+                .query(
+                    Circle::new(pt, Distance::meters(1.0))
+                        .get_bounds()
+                        .as_bbox(),
+                )
+                .into_iter()
+                .map(|(idx, _, _)| *idx)
+                .filter(|idx| ring_contains_pt(&rings[*idx], pt))
+                .min()
+            {
+                b.landuse = Some(self.landuse[idx].0.clone());
+                count += 1;
+            }
+        }
+        timer.note(format!(
+            "{} of {} buildings are inside a land use area",
+            count,
+            self.buildings.len()
+        ));
+    }
 }
 
 fn tags_to_map(raw_tags: &[osm_xml::Tag]) -> BTreeMap<String, String> {
@@ -136,7 +337,7 @@ fn tags_to_map(raw_tags: &[osm_xml::Tag]) -> BTreeMap<String, String> {
         .collect()
 }
 
-fn is_road(tags: &BTreeMap<String, String>) -> bool {
+pub fn is_road(tags: &BTreeMap<String, String>) -> bool {
     if !tags.contains_key("highway") {
         return false;
     }
@@ -175,11 +376,50 @@ fn is_road(tags: &BTreeMap<String, String>) -> bool {
     true
 }
 
-fn is_bldg(tags: &BTreeMap<String, String>) -> bool {
+// Pedestrian crossings near junctions can have signals too.
+pub fn is_traffic_signal(tags: &BTreeMap<String, String>) -> bool {
+    tags.get("highway") == Some(&"traffic_signals".to_string())
+        || tags.get("crossing") == Some(&"traffic_signals".to_string())
+}
+
+// Racks and bikeshare docks mapped as nodes
+pub fn get_bike_parking(
+    id: i64,
+    pt: LonLat,
+    tags: &BTreeMap<String, String>,
+) -> Option<raw_data::BikeParking> {
+    let is_bikeshare = match tags.get("amenity").map(|s| s.as_str()) {
+        Some("bicycle_parking") => false,
+        Some("bicycle_rental") => true,
+        _ => {
+            return None;
+        }
+    };
+    Some(raw_data::BikeParking {
+        point: pt,
+        is_bikeshare,
+        capacity: tags
+            .get("capacity")
+            .and_then(|cap| cap.parse::<usize>().ok()),
+        osm_node_id: id,
+    })
+}
+
+pub fn is_bldg(tags: &BTreeMap<String, String>) -> bool {
     tags.contains_key("building")
 }
 
-fn get_area_type(tags: &BTreeMap<String, String>) -> Option<AreaType> {
+// Only the kinds of land use that say something about the buildings inside
+pub fn get_landuse(tags: &BTreeMap<String, String>) -> Option<String> {
+    let landuse = tags.get("landuse")?;
+    if ["residential", "commercial", "retail", "industrial"].contains(&landuse.as_str()) {
+        Some(landuse.to_string())
+    } else {
+        None
+    }
+}
+
+pub fn get_area_type(tags: &BTreeMap<String, String>) -> Option<AreaType> {
     if tags.get("leisure") == Some(&"park".to_string()) {
         return Some(AreaType::Park);
     }
@@ -198,54 +438,68 @@ fn get_area_type(tags: &BTreeMap<String, String>) -> Option<AreaType> {
     None
 }
 
-// The result could be more than one disjoint polygon.
-fn glue_multipolygon(mut pts_per_way: Vec<Vec<LonLat>>) -> Vec<Vec<LonLat>> {
-    // First deal with all of the closed loops.
-    let mut polygons: Vec<Vec<LonLat>> = Vec::new();
-    pts_per_way.retain(|pts| {
-        if pts[0] == *pts.last().unwrap() {
-            polygons.push(pts.to_vec());
-            false
-        } else {
-            true
+// Joins ways end-to-end into closed rings. A ring might cross itself if the ways are garbage. Also
+// returns false for rings that only closed by connecting the ends, which happens when some of
+// the ways are clipped out.
+fn glue_rings(mut pts_per_way: Vec<Vec<LonLat>>) -> Vec<(Vec<LonLat>, bool)> {
+    let mut rings: Vec<(Vec<LonLat>, bool)> = Vec::new();
+    while let Some(mut ring) = pts_per_way.pop() {
+        let mut reversed = false;
+        let mut closed = true;
+        while ring[0] != *ring.last().unwrap() {
+            let glue_pt = *ring.last().unwrap();
+            if let Some(idx) = pts_per_way
+                .iter()
+                .position(|pts| pts[0] == glue_pt || *pts.last().unwrap() == glue_pt)
+            {
+                let mut append = pts_per_way.remove(idx);
+                if append[0] != glue_pt {
+                    append.reverse();
+                }
+                ring.pop();
+                ring.extend(append);
+            } else if !reversed {
+                reversed = true;
+                ring.reverse();
+                // Try again from the other end!
+            } else {
+                // Some ways of the multipolygon are clipped out. Connect the ends in the most
+                // straightforward way. Later polygon clipping will trim to the boundary.
+                ring.push(ring[0]);
+                closed = false;
+            }
+        }
+        if ring.len() >= 4 {
+            rings.push((ring, closed));
         }
-    });
-    if pts_per_way.is_empty() {
-        return polygons;
     }
+    rings
+}
 
-    // The main polygon
-    let mut result = pts_per_way.pop().unwrap();
-    let mut reversed = false;
-    while !pts_per_way.is_empty() {
-        let glue_pt = *result.last().unwrap();
-        if let Some(idx) = pts_per_way
-            .iter()
-            .position(|pts| pts[0] == glue_pt || *pts.last().unwrap() == glue_pt)
-        {
-            let mut append = pts_per_way.remove(idx);
-            if append[0] != glue_pt {
-                append.reverse();
+// Each outer ring becomes its own polygon, with whatever inner rings are inside of it.
+fn assemble_multipolygon(
+    outers: Vec<Vec<LonLat>>,
+    inners: Vec<Vec<LonLat>>,
+    rel_id: i64,
+    timer: &mut Timer,
+) -> Vec<(Vec<LonLat>, Vec<Vec<LonLat>>)> {
+    let mut polygons: Vec<(Vec<LonLat>, Vec<Vec<LonLat>>)> =
+        outers.into_iter().map(|ring| (ring, Vec::new())).collect();
+    for inner in inners {
+        if let Some(polygon) = polygons.iter_mut().find(|polygon| {
+            let bounds = GPSBounds::from(&polygon.0);
+            match Pt2D::from_gps(inner[0], &bounds) {
+                Some(pt) => ring_contains_pt(&bounds.must_convert(&polygon.0), pt),
+                None => false,
             }
-            result.pop();
-            result.extend(append);
+        }) {
+            polygon.1.push(inner);
         } else {
-            if reversed {
-                // Totally filter the thing out, since something clearly broke.
-                return Vec::new();
-            } else {
-                reversed = true;
-                result.reverse();
-                // Try again!
-            }
+            timer.warn(format!(
+                "Relation {} has an inner ring that isn't inside any outer ring, ignoring it",
+                rel_id
+            ));
         }
     }
-
-    // Some ways of the multipolygon are clipped out. Connect the ends in the most straightforward
-    // way. Later polygon clipping will trim to the boundary.
-    if result[0] != *result.last().unwrap() {
-        result.push(result[0]);
-    }
-    polygons.push(result);
     polygons
 }
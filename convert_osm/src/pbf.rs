@@ -0,0 +1,183 @@
+use crate::osm::{
+    get_area_type, get_bike_parking, get_landuse, is_bldg, is_road, is_traffic_signal, Extract,
+};
+use abstutil::Timer;
+use geom::{GPSBounds, LonLat, Polygon, Pt2D};
+use osmpbfreader::{OsmId, OsmObj, OsmPbfReader, Tags};
+use std::collections::{BTreeMap, HashMap, HashSet};
+use std::fs::File;
+
+// Reads a .osm.pbf file without holding the whole thing in memory. Only the relations, ways, and
+// nodes that might matter are kept, so this takes four passes over the file. Anything without a
+// single point inside the boundary is skipped, so the input doesn't have to be clipped first.
+pub fn read(path: &str, boundary: &Vec<LonLat>, timer: &mut Timer) -> Extract {
+    let mut reader = OsmPbfReader::new(File::open(path).unwrap());
+
+    // Ways can only be understood as areas once we know what relations they belong to.
+    timer.start("read OSM relations");
+    let mut relations: Vec<Relation> = Vec::new();
+    let mut relation_members: HashSet<i64> = HashSet::new();
+    for obj in reader.iter() {
+        if let OsmObj::Relation(rel) = obj.expect("OSM parsing failed") {
+            let tags = tags_to_map(&rel.tags);
+            if (!is_bldg(&tags) && get_area_type(&tags).is_none() && get_landuse(&tags).is_none())
+                || tags.get("type") != Some(&"multipolygon".to_string())
+            {
+                continue;
+            }
+            let mut members = Vec::new();
+            let mut unhandled = Vec::new();
+            for member in &rel.refs {
+                match member.member {
+                    OsmId::Way(id) => {
+                        relation_members.insert(id.0);
+                        members.push((id.0, member.role.to_string()));
+                    }
+                    other => {
+                        unhandled.push(format!("{:?}", other));
+                    }
+                }
+            }
+            relations.push(Relation {
+                id: rel.id.0,
+                tags,
+                members,
+                unhandled,
+            });
+        }
+    }
+    timer.stop("read OSM relations");
+
+    let clip = Clip::new(boundary);
+    let mut extract = Extract::new();
+
+    // A way is only worth keeping if one of its nodes is inside, so find those nodes first.
+    timer.start("find OSM nodes inside the boundary");
+    reader.rewind().unwrap();
+    let mut inside_nodes: HashSet<i64> = HashSet::new();
+    for obj in reader.iter() {
+        if let OsmObj::Node(node) = obj.expect("OSM parsing failed") {
+            let pt = LonLat::new(node.lon(), node.lat());
+            if !clip.contains(pt) {
+                continue;
+            }
+            inside_nodes.insert(node.id.0);
+            if !node.tags.is_empty() {
+                let tags = tags_to_map(&node.tags);
+                if is_traffic_signal(&tags) {
+                    extract.traffic_signals.push(pt);
+                }
+                if let Some(p) = get_bike_parking(node.id.0, pt, &tags) {
+                    extract.bike_parking.push(p);
+                }
+            }
+        }
+    }
+    timer.stop("find OSM nodes inside the boundary");
+
+    timer.start("read OSM ways");
+    reader.rewind().unwrap();
+    let mut ways: Vec<(i64, Vec<i64>, BTreeMap<String, String>)> = Vec::new();
+    let mut needed_nodes: HashSet<i64> = HashSet::new();
+    for obj in reader.iter() {
+        if let OsmObj::Way(way) = obj.expect("OSM parsing failed") {
+            if !way.nodes.iter().any(|n| inside_nodes.contains(&n.0)) {
+                continue;
+            }
+            let tags = tags_to_map(&way.tags);
+            if !is_road(&tags)
+                && !is_bldg(&tags)
+                && get_area_type(&tags).is_none()
+                && get_landuse(&tags).is_none()
+                && !relation_members.contains(&way.id.0)
+            {
+                continue;
+            }
+            let nodes: Vec<i64> = way.nodes.iter().map(|n| n.0).collect();
+            needed_nodes.extend(nodes.iter().cloned());
+            ways.push((way.id.0, nodes, tags));
+        }
+    }
+    timer.stop("read OSM ways");
+    drop(inside_nodes);
+
+    // Ways partly inside the boundary still need the rest of their nodes.
+    timer.start("read OSM nodes");
+    reader.rewind().unwrap();
+    let mut nodes: HashMap<i64, LonLat> = HashMap::new();
+    for obj in reader.iter() {
+        if let OsmObj::Node(node) = obj.expect("OSM parsing failed") {
+            if needed_nodes.contains(&node.id.0) {
+                nodes.insert(node.id.0, LonLat::new(node.lon(), node.lat()));
+            }
+        }
+    }
+    timer.stop("read OSM nodes");
+    timer.note(format!(
+        "OSM pbf has {} useful nodes, {} ways, {} relations",
+        nodes.len(),
+        ways.len(),
+        relations.len()
+    ));
+
+    timer.start_iter("processing OSM ways", ways.len());
+    for (id, node_ids, tags) in ways {
+        timer.next();
+        let pts: Option<Vec<LonLat>> = node_ids.iter().map(|n| nodes.get(n).cloned()).collect();
+        match pts {
+            Some(pts) => {
+                extract.add_way(id, pts, node_ids, tags);
+            }
+            None => {
+                timer.warn(format!(
+                    "Way {} refers to nodes that aren't in the file",
+                    id
+                ));
+            }
+        }
+    }
+
+    timer.start_iter("processing OSM relations", relations.len());
+    for rel in relations {
+        timer.next();
+        extract.add_relation(rel.id, rel.tags, rel.members, rel.unhandled, timer);
+    }
+
+    extract
+}
+
+struct Relation {
+    id: i64,
+    tags: BTreeMap<String, String>,
+    members: Vec<(i64, String)>,
+    unhandled: Vec<String>,
+}
+
+fn tags_to_map(tags: &Tags) -> BTreeMap<String, String> {
+    tags.iter()
+        .map(|(k, v)| (k.to_string(), v.to_string()))
+        .collect()
+}
+
+struct Clip {
+    gps_bounds: GPSBounds,
+    polygon: Polygon,
+}
+
+impl Clip {
+    fn new(boundary: &Vec<LonLat>) -> Clip {
+        let gps_bounds = GPSBounds::from(boundary);
+        let polygon = Polygon::new(&gps_bounds.must_convert(boundary));
+        Clip {
+            gps_bounds,
+            polygon,
+        }
+    }
+
+    fn contains(&self, pt: LonLat) -> bool {
+        match Pt2D::from_gps(pt, &self.gps_bounds) {
+            Some(pt) => self.polygon.contains_pt(pt),
+            None => false,
+        }
+    }
+}
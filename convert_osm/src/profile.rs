@@ -0,0 +1,85 @@
+use crate::Flags;
+use serde_derive::{Deserialize, Serialize};
+
+// Everything besides OSM that can enrich a map. None of it is required; an OSM extract alone makes
+// a usable map. Each city publishes these datasets differently, so the attribute names are
+// configurable. See data/profiles/seattle.json for an example.
+#[derive(Serialize, Deserialize, Debug, PartialEq)]
+pub struct ImportProfile {
+    pub traffic_signals: Option<TrafficSignalSource>,
+    pub residential_buildings: Option<ResidentialSource>,
+    pub parking: ParkingSource,
+    // GTFS directory
+    pub gtfs: Option<String>,
+    // GeoJSON with a name property for every polygon
+    pub neighborhoods: Option<String>,
+}
+
+// KML with one point per signal
+#[derive(Serialize, Deserialize, Debug, PartialEq)]
+pub struct TrafficSignalSource {
+    pub path: String,
+}
+
+// KML with one point per building, saying how many units it has
+#[derive(Serialize, Deserialize, Debug, PartialEq)]
+pub struct ResidentialSource {
+    pub path: String,
+    pub units_attribute: String,
+}
+
+#[derive(Serialize, Deserialize, Debug, PartialEq)]
+pub enum ParkingSource {
+    // parking:lane:{both,left,right} tags on each way
+    OsmTags,
+    // ExtraShapes with one line per side of each road, produced using the kml crate
+    Blockface {
+        path: String,
+        category_attribute: String,
+        // Any other category has parking
+        no_parking_categories: Vec<String>,
+    },
+}
+
+impl ImportProfile {
+    // Without a profile file, the individual flags describe Seattle's datasets.
+    pub fn new(flags: &Flags) -> ImportProfile {
+        let mut profile = if flags.profile.is_empty() {
+            ImportProfile {
+                traffic_signals: None,
+                residential_buildings: None,
+                parking: ParkingSource::OsmTags,
+                gtfs: None,
+                neighborhoods: None,
+            }
+        } else {
+            abstutil::read_json(&flags.profile).expect("loading import profile failed")
+        };
+
+        if !flags.traffic_signals.is_empty() {
+            profile.traffic_signals = Some(TrafficSignalSource {
+                path: flags.traffic_signals.clone(),
+            });
+        }
+        if !flags.residential_buildings.is_empty() {
+            profile.residential_buildings = Some(ResidentialSource {
+                path: flags.residential_buildings.clone(),
+                units_attribute: "net_units".to_string(),
+            });
+        }
+        if !flags.parking_shapes.is_empty() {
+            profile.parking = ParkingSource::Blockface {
+                path: flags.parking_shapes.clone(),
+                category_attribute: "PARKING_CATEGORY".to_string(),
+                no_parking_categories: vec!["None".to_string(), "No Parking Allowed".to_string()],
+            };
+        }
+        if !flags.gtfs.is_empty() {
+            profile.gtfs = Some(flags.gtfs.clone());
+        }
+        if !flags.neighborhoods.is_empty() {
+            profile.neighborhoods = Some(flags.neighborhoods.clone());
+        }
+        profile
+    }
+}
@@ -5,11 +5,12 @@ use map_model::{raw_data, IntersectionType};
 use std::collections::HashMap;
 
 pub fn split_up_roads(
-    (mut roads, buildings, areas): (
+    (roads, buildings, areas): (
         Vec<raw_data::Road>,
         Vec<raw_data::Building>,
         Vec<raw_data::Area>,
     ),
+    road_nodes: &HashMap<HashablePt2D, i64>,
     elevation: &srtm::Elevation,
     timer: &mut Timer,
 ) -> raw_data::Map {
@@ -17,29 +18,12 @@ pub fn split_up_roads(
 
     let mut next_intersection_id = 0;
 
-    // Normally one point to one intersection, but all points on a roundabout map to a single
-    // point.
-    let mut roundabout_centers: HashMap<raw_data::StableIntersectionID, LonLat> = HashMap::new();
+    // Roundabouts are kept as normal ways here. They get split up wherever another road enters,
+    // and map_model collapses each ring into one intersection later, once the geometry is known.
     let mut pt_to_intersection: HashMap<HashablePt2D, raw_data::StableIntersectionID> =
         HashMap::new();
 
-    roads.retain(|r| {
-        if r.osm_tags.get("junction") == Some(&"roundabout".to_string()) {
-            let id = raw_data::StableIntersectionID(next_intersection_id);
-            next_intersection_id += 1;
-
-            roundabout_centers.insert(id, LonLat::center(&r.points));
-            for pt in &r.points {
-                pt_to_intersection.insert(pt.to_hashable(), id);
-            }
-
-            false
-        } else {
-            true
-        }
-    });
-
-    // Find normal intersections
+    // Find intersections
     let mut counts_per_pt: HashMap<HashablePt2D, usize> = HashMap::new();
     for r in &roads {
         for (idx, raw_pt) in r.points.iter().enumerate() {
@@ -62,7 +46,6 @@ pub fn split_up_roads(
     let mut map = raw_data::Map::blank();
     map.buildings = buildings;
     map.areas = areas;
-    // All of the roundabout points will just keep moving the intersection
     for (pt, id) in &pt_to_intersection {
         map.intersections.insert(
             *id,
@@ -71,18 +54,7 @@ pub fn split_up_roads(
                 elevation: Distance::meters(elevation.get(pt.x(), pt.y())),
                 intersection_type: IntersectionType::StopSign,
                 label: None,
-            },
-        );
-    }
-    // Set roundabouts to their center
-    for (id, pt) in &roundabout_centers {
-        map.intersections.insert(
-            *id,
-            raw_data::Intersection {
-                point: *pt,
-                elevation: Distance::meters(elevation.get(pt.longitude, pt.latitude)),
-                intersection_type: IntersectionType::StopSign,
-                label: None,
+                osm_node_id: road_nodes.get(pt).cloned(),
             },
         );
     }
@@ -95,19 +67,10 @@ pub fn split_up_roads(
         r.points.clear();
         r.i1 = pt_to_intersection[&orig_road.points[0].to_hashable()];
 
-        for (idx, pt) in orig_road.points.iter().enumerate() {
+        for pt in &orig_road.points {
             r.points.push(pt.clone());
             if r.points.len() > 1 {
                 if let Some(i2) = pt_to_intersection.get(&pt.to_hashable()) {
-                    if roundabout_centers.contains_key(i2) && idx != orig_road.points.len() - 1 {
-                        panic!(
-                            "OSM way {} hits a roundabout in the middle of a way. idx {} of length {}",
-                            r.osm_way_id,
-                            idx,
-                            r.points.len()
-                        );
-                    }
-
                     r.i2 = *i2;
                     // Start a new road
                     map.roads
@@ -1,42 +1,198 @@
+use abstutil::Timer;
 use byteorder::{BigEndian, ReadBytesExt};
+use geom::LonLat;
+use std::collections::BTreeMap;
 use std::fs::File;
-use std::io;
+use std::io::{self, BufReader};
+use std::path::Path;
 
-// Assuming the 3-second arcs
-const GRID_DIM: usize = 1201;
+// Samples marked as missing
+const VOID: i16 = -32768;
 
+// Elevation from SRTM .hgt tiles. Each tile covers one degree of longitude and latitude and is
+// named after its southwest corner, like N47W123.hgt. Anything not covered by a tile is flat.
 pub struct Elevation {
-    lon_offset: f64,
-    lat_offset: f64,
-    data: Vec<i16>,
+    // Keyed by the (longitude, latitude) of the southwest corner
+    tiles: BTreeMap<(i64, i64), Tile>,
 }
 
 impl Elevation {
-    pub fn new(path: &str) -> Result<Elevation, io::Error> {
-        println!("Opening {}", path);
-        let mut f = File::open(path).unwrap();
-
+    // The path can be a single tile, a directory containing the tiles that cover the boundary, or
+    // empty to skip elevation entirely.
+    pub fn new(
+        path: &str,
+        boundary: &Vec<LonLat>,
+        timer: &mut Timer,
+    ) -> Result<Elevation, io::Error> {
         let mut e = Elevation {
-            // TODO dont hardcode
-            lon_offset: -122.0,
-            lat_offset: 47.0,
-            data: Vec::with_capacity(GRID_DIM.pow(2)),
+            tiles: BTreeMap::new(),
         };
-        // TODO off by one?
-        for _ in 0..GRID_DIM.pow(2) {
-            e.data.push(f.read_i16::<BigEndian>().unwrap());
+        if path.is_empty() {
+            timer.warn("No elevation data, so the map will be flat".to_string());
+            return Ok(e);
+        }
+
+        if Path::new(path).is_dir() {
+            let min_lon = boundary
+                .iter()
+                .map(|pt| pt.longitude)
+                .fold(f64::MAX, f64::min);
+            let max_lon = boundary
+                .iter()
+                .map(|pt| pt.longitude)
+                .fold(f64::MIN, f64::max);
+            let min_lat = boundary
+                .iter()
+                .map(|pt| pt.latitude)
+                .fold(f64::MAX, f64::min);
+            let max_lat = boundary
+                .iter()
+                .map(|pt| pt.latitude)
+                .fold(f64::MIN, f64::max);
+            for lon in (min_lon.floor() as i64)..=(max_lon.floor() as i64) {
+                for lat in (min_lat.floor() as i64)..=(max_lat.floor() as i64) {
+                    let tile_path = Path::new(path).join(tile_name(lon, lat));
+                    if tile_path.exists() {
+                        e.tiles.insert((lon, lat), Tile::load(&tile_path, timer)?);
+                    } else {
+                        timer.warn(format!(
+                            "Missing {}, so part of the map will be flat",
+                            tile_path.display()
+                        ));
+                    }
+                }
+            }
+        } else {
+            let corner = Path::new(path)
+                .file_name()
+                .and_then(|name| parse_tile_name(&name.to_string_lossy()))
+                .ok_or_else(|| {
+                    io::Error::new(
+                        io::ErrorKind::InvalidInput,
+                        format!("{} isn't named like N47W123.hgt", path),
+                    )
+                })?;
+            e.tiles.insert(corner, Tile::load(Path::new(path), timer)?);
         }
         Ok(e)
     }
 
-    // TODO plumb through u16 everyhere
+    // In meters
     pub fn get(&self, lon: f64, lat: f64) -> f64 {
-        // TODO assert the (lon, lat) match the offsets
-        // TODO not tons of confidence in any of this.
-        // TODO interpolate from the 4 matching tiles?
-        let x = ((lon - self.lon_offset).abs() * (GRID_DIM as f64)) as usize;
-        let y = ((lat - self.lat_offset).abs() * (GRID_DIM as f64)) as usize;
-        let i = x + (y * GRID_DIM);
-        f64::from(self.data[i])
+        let corner = (lon.floor() as i64, lat.floor() as i64);
+        match self.tiles.get(&corner) {
+            Some(tile) => tile.interpolate(lon - lon.floor(), lat - lat.floor()),
+            None => 0.0,
+        }
     }
 }
+
+struct Tile {
+    // 1201 for 3-arcsecond tiles, 3601 for 1-arcsecond
+    dim: usize,
+    // Rows go from north to south, columns from west to east. Neighboring tiles share the edge
+    // rows and columns.
+    data: Vec<i16>,
+}
+
+impl Tile {
+    fn load(path: &Path, timer: &mut Timer) -> Result<Tile, io::Error> {
+        timer.note(format!("Reading elevation from {}", path.display()));
+        let num_samples = (std::fs::metadata(path)?.len() / 2) as usize;
+        let dim = match num_samples {
+            1_442_401 => 1201,
+            12_967_201 => 3601,
+            _ => {
+                return Err(io::Error::new(
+                    io::ErrorKind::InvalidData,
+                    format!(
+                        "{} has {} samples; it's not a 1- or 3-arcsecond tile",
+                        path.display(),
+                        num_samples
+                    ),
+                ));
+            }
+        };
+
+        let mut f = BufReader::new(File::open(path)?);
+        let mut data = Vec::with_capacity(num_samples);
+        for _ in 0..num_samples {
+            data.push(f.read_i16::<BigEndian>()?);
+        }
+        Ok(Tile { dim, data })
+    }
+
+    fn sample(&self, col: usize, row: usize) -> Option<f64> {
+        let value = self.data[row * self.dim + col];
+        if value == VOID {
+            None
+        } else {
+            Some(f64::from(value))
+        }
+    }
+
+    // Bilinear interpolation between the 4 surrounding samples. dx and dy are in [0, 1), measured
+    // from the southwest corner. Void samples are skipped.
+    fn interpolate(&self, dx: f64, dy: f64) -> f64 {
+        let max = (self.dim - 1) as f64;
+        let x = dx * max;
+        let y = (1.0 - dy) * max;
+        let col = (x.floor() as usize).min(self.dim - 2);
+        let row = (y.floor() as usize).min(self.dim - 2);
+        let fx = x - col as f64;
+        let fy = y - row as f64;
+
+        let mut total = 0.0;
+        let mut total_weight = 0.0;
+        for (c, r, weight) in &[
+            (col, row, (1.0 - fx) * (1.0 - fy)),
+            (col + 1, row, fx * (1.0 - fy)),
+            (col, row + 1, (1.0 - fx) * fy),
+            (col + 1, row + 1, fx * fy),
+        ] {
+            if let Some(value) = self.sample(*c, *r) {
+                total += value * weight;
+                total_weight += weight;
+            }
+        }
+        if total_weight > 0.0 {
+            total / total_weight
+        } else {
+            0.0
+        }
+    }
+}
+
+pub fn tile_name(lon: i64, lat: i64) -> String {
+    format!(
+        "{}{:02}{}{:03}.hgt",
+        if lat >= 0 { 'N' } else { 'S' },
+        lat.abs(),
+        if lon >= 0 { 'E' } else { 'W' },
+        lon.abs()
+    )
+}
+
+// Returns the (longitude, latitude) of the southwest corner
+pub fn parse_tile_name(name: &str) -> Option<(i64, i64)> {
+    if name.len() < 7 || !name.is_ascii() {
+        return None;
+    }
+    let lat_sign = match &name[0..1] {
+        "N" | "n" => 1,
+        "S" | "s" => -1,
+        _ => {
+            return None;
+        }
+    };
+    let lon_sign = match &name[3..4] {
+        "E" | "e" => 1,
+        "W" | "w" => -1,
+        _ => {
+            return None;
+        }
+    };
+    let lat = name[1..3].parse::<i64>().ok()?;
+    let lon = name[4..7].parse::<i64>().ok()?;
+    Some((lon_sign * lon, lat_sign * lat))
+}
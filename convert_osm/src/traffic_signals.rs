@@ -0,0 +1,105 @@
+use abstutil::Timer;
+use geom::{Distance, FindClosest, GPSBounds, LonLat, Pt2D};
+use map_model::{raw_data, IntersectionType};
+use std::collections::BTreeSet;
+
+// OSM signal nodes are usually the junction node itself, or on the approaches just before it.
+const MAX_DIST_BTWN_INTERSECTION_AND_OSM_SIGNAL: Distance = Distance::const_meters(20.0);
+const MAX_DIST_BTWN_INTERSECTION_AND_KML_SIGNAL: Distance = Distance::const_meters(50.0);
+
+// Signals can come from OSM nodes and an optional KML. Every match from either becomes a signal,
+// but intersections that only one of them knows about are reported.
+pub fn apply(
+    map: &mut raw_data::Map,
+    gps_bounds: &GPSBounds,
+    osm_signals: &Vec<LonLat>,
+    kml_path: Option<&str>,
+    timer: &mut Timer,
+) {
+    timer.start("handle traffic signals");
+    let mut closest: FindClosest<raw_data::StableIntersectionID> =
+        FindClosest::new(&gps_bounds.to_bounds());
+    for (id, i) in &map.intersections {
+        // Signals don't make sense at the edge of the map.
+        if i.intersection_type == IntersectionType::Border {
+            continue;
+        }
+        if let Some(pt) = Pt2D::from_gps(i.point, gps_bounds) {
+            closest.add_pt(*id, pt);
+        }
+    }
+
+    let mut from_osm: BTreeSet<raw_data::StableIntersectionID> = BTreeSet::new();
+    for gps in osm_signals {
+        if let Some(pt) = Pt2D::from_gps(*gps, gps_bounds) {
+            if let Some((i, _)) = closest.closest_pt(pt, MAX_DIST_BTWN_INTERSECTION_AND_OSM_SIGNAL)
+            {
+                from_osm.insert(i);
+            }
+        }
+    }
+    timer.note(format!(
+        "{} OSM signal nodes matched {} intersections",
+        osm_signals.len(),
+        from_osm.len()
+    ));
+
+    let mut from_kml: BTreeSet<raw_data::StableIntersectionID> = BTreeSet::new();
+    if let Some(path) = kml_path {
+        for shape in kml::load(path, gps_bounds, timer)
+            .expect("loading traffic signals failed")
+            .shapes
+            .into_iter()
+        {
+            // See https://www.seattle.gov/Documents/Departments/SDOT/GIS/Traffic_Signals_OD.pdf
+            if shape.points.len() > 1 {
+                panic!("Traffic signal has multiple points: {:?}", shape);
+            }
+            let pt = match Pt2D::from_gps(shape.points[0], gps_bounds) {
+                Some(pt) => pt,
+                None => continue,
+            };
+            if let Some((i, i_pt)) =
+                closest.closest_pt(pt, MAX_DIST_BTWN_INTERSECTION_AND_KML_SIGNAL)
+            {
+                if !from_kml.insert(i) {
+                    timer.warn(format!(
+                        "{:?} already has a traffic signal, but there's another one that's {} \
+                         from it",
+                        map.intersections[&i],
+                        pt.dist_to(i_pt)
+                    ));
+                }
+            }
+        }
+
+        let only_osm: Vec<_> = from_osm.difference(&from_kml).collect();
+        let only_kml: Vec<_> = from_kml.difference(&from_osm).collect();
+        for i in &only_osm {
+            timer.warn(format!(
+                "Traffic signal at {} is only in OSM",
+                map.intersections[*i].point
+            ));
+        }
+        for i in &only_kml {
+            timer.warn(format!(
+                "Traffic signal at {} is only in {}",
+                map.intersections[*i].point, path
+            ));
+        }
+        if !only_osm.is_empty() || !only_kml.is_empty() {
+            timer.note(format!(
+                "OSM and {} disagree about traffic signals: {} are only in OSM, {} are only in the \
+                 KML",
+                path,
+                only_osm.len(),
+                only_kml.len()
+            ));
+        }
+    }
+
+    for i in from_osm.union(&from_kml) {
+        map.intersections.get_mut(i).unwrap().intersection_type = IntersectionType::TrafficSignal;
+    }
+    timer.stop("handle traffic signals");
+}
@@ -109,6 +109,7 @@ fn launch_test(test: &ABTest, ui: &mut UI, ctx: &mut EventCtx) -> Mode {
                 rng_seed,
                 run_name: format!("{} with {}", test.test_name, test.edits1_name),
                 edits_name: test.edits1_name.clone(),
+                ..current_flags.sim_flags.clone()
             },
             ..current_flags.clone()
         },
@@ -122,6 +123,7 @@ fn launch_test(test: &ABTest, ui: &mut UI, ctx: &mut EventCtx) -> Mode {
                 rng_seed,
                 run_name: format!("{} with {}", test.test_name, test.edits2_name),
                 edits_name: test.edits2_name.clone(),
+                ..current_flags.sim_flags.clone()
             },
             ..current_flags.clone()
         },
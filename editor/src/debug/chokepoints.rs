@@ -1,5 +1,6 @@
 use counter::Counter;
-use map_model::{IntersectionID, LaneID, PathStep};
+use geom::Duration;
+use map_model::{IntersectionID, LaneID, Map, PathStep};
 use sim::Sim;
 use std::collections::HashSet;
 
@@ -11,9 +12,8 @@ pub struct ChokepointsFinder {
 }
 
 impl ChokepointsFinder {
-    pub fn new(sim: &Sim) -> ChokepointsFinder {
+    pub fn new(sim: &Sim, map: &Map) -> ChokepointsFinder {
         let mut count_per_lane: Counter<LaneID, usize> = Counter::new();
-        let mut count_per_intersection: Counter<IntersectionID, usize> = Counter::new();
 
         let active = sim.active_agents();
         println!("Finding chokepoints from {} active agents", active.len());
@@ -25,9 +25,7 @@ impl ChokepointsFinder {
                         PathStep::Lane(l) | PathStep::ContraflowLane(l) => {
                             count_per_lane.update(vec![*l]);
                         }
-                        PathStep::Turn(t) => {
-                            count_per_intersection.update(vec![t.parent]);
-                        }
+                        PathStep::Turn(_) => {}
                     }
                 }
             }
@@ -39,11 +37,21 @@ impl ChokepointsFinder {
             .take(TOP_N)
             .map(|(l, _)| l)
             .collect();
-        let intersections: HashSet<IntersectionID> = count_per_intersection
-            .most_common_ordered()
+
+        // Intersections are ranked by how long agents have actually waited at them so far. Use the
+        // total rather than the mean, so one unlucky agent at a quiet intersection doesn't count.
+        let mut delay_per_intersection: Vec<(Duration, IntersectionID)> = sim
+            .get_all_intersection_stats(map)
+            .into_iter()
+            .map(|stats| (stats.delays().total(), stats.id))
+            .filter(|(delay, _)| *delay > Duration::ZERO)
+            .collect();
+        delay_per_intersection.sort();
+        let intersections: HashSet<IntersectionID> = delay_per_intersection
             .into_iter()
+            .rev()
             .take(TOP_N)
-            .map(|(i, _)| i)
+            .map(|(_, i)| i)
             .collect();
         ChokepointsFinder {
             lanes,
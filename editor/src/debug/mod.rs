@@ -15,13 +15,14 @@ use ezgui::{
     Color, EventCtx, EventLoopMode, GfxCtx, InputResult, Key, ModalMenu, ScrollingMenu, Text,
     TextBox, Wizard,
 };
-use map_model::RoadID;
+use map_model::{IntersectionID, RoadID};
 use std::collections::HashSet;
 
 pub struct DebugMode {
     state: State,
     common: CommonState,
     chokepoints: Option<chokepoints::ChokepointsFinder>,
+    merged_intersections: Option<HashSet<IntersectionID>>,
     show_original_roads: HashSet<RoadID>,
     connected_roads: connected_roads::ShowConnectedRoads,
     objects: objects::ObjectDebugger,
@@ -44,6 +45,7 @@ impl DebugMode {
             state: DebugMode::exploring_state(ctx),
             common: CommonState::new(),
             chokepoints: None,
+            merged_intersections: None,
             show_original_roads: HashSet::new(),
             connected_roads: connected_roads::ShowConnectedRoads::new(),
             objects: objects::ObjectDebugger::new(),
@@ -66,6 +68,7 @@ impl DebugMode {
                 vec![
                     (Some(Key::Escape), "quit"),
                     (Some(Key::C), "show/hide chokepoints"),
+                    (None, "show/hide merged intersections"),
                     (Some(Key::O), "clear original roads shown"),
                     (Some(Key::H), "unhide everything"),
                     (Some(Key::Num1), "show/hide buildings"),
@@ -105,6 +108,9 @@ impl DebugMode {
                         if mode.chokepoints.is_some() {
                             txt.add_line("Showing chokepoints".to_string());
                         }
+                        if let Some(ref merged) = mode.merged_intersections {
+                            txt.add_line(format!("Showing {} merged intersections", merged.len()));
+                        }
                         if !mode.show_original_roads.is_empty() {
                             txt.add_line(format!(
                                 "Showing {} original roads",
@@ -143,9 +149,24 @@ impl DebugMode {
                                 // TODO Nothing will actually exist. ;)
                                 mode.chokepoints = Some(chokepoints::ChokepointsFinder::new(
                                     &state.ui.primary.sim,
+                                    &state.ui.primary.map,
                                 ));
                             }
                         }
+                        if menu.action("show/hide merged intersections") {
+                            if mode.merged_intersections.is_some() {
+                                mode.merged_intersections = None;
+                            } else {
+                                let map = &state.ui.primary.map;
+                                mode.merged_intersections = Some(
+                                    map.all_intersections()
+                                        .iter()
+                                        .filter(|i| !map.get_merged_roads_into(i.id).is_empty())
+                                        .map(|i| i.id)
+                                        .collect(),
+                                );
+                            }
+                        }
                         if !mode.show_original_roads.is_empty() {
                             if menu.action("clear original roads shown") {
                                 mode.show_original_roads.clear();
@@ -306,6 +327,12 @@ impl DebugMode {
                             opts.override_colors.insert(ID::Intersection(*i), color);
                         }
                     }
+                    if let Some(ref merged) = mode.merged_intersections {
+                        let color = state.ui.cs.get_def("merged intersection", Color::PURPLE);
+                        for i in merged {
+                            opts.override_colors.insert(ID::Intersection(*i), color);
+                        }
+                    }
                     for l in &mode.connected_roads.lanes {
                         opts.override_colors.insert(
                             ID::Lane(*l),
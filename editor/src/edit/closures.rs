@@ -0,0 +1,84 @@
+use crate::helpers::ID;
+use ezgui::WrappedWizard;
+use geom::Duration;
+use map_model::{Closure, ClosureTarget, Map, MapEdits, TurnType};
+
+// What can be closed, given the current selection
+pub fn closure_candidates(id: ID, map: &Map) -> Vec<(String, ClosureTarget)> {
+    match id {
+        ID::Lane(l) => {
+            let lane = map.get_l(l);
+            vec![
+                (
+                    format!("just {} ({:?})", lane.id, lane.lane_type),
+                    ClosureTarget::Lane(lane.id),
+                ),
+                (
+                    format!("all of {}", lane.parent),
+                    ClosureTarget::Road(lane.parent),
+                ),
+            ]
+        }
+        ID::Intersection(i) => map
+            .get_i(i)
+            .turns
+            .iter()
+            .map(|t| map.get_t(*t))
+            .filter(|t| t.turn_type != TurnType::SharedSidewalkCorner)
+            .map(|t| {
+                (
+                    format!("{:?} turn from {} to {}", t.turn_type, t.id.src, t.id.dst),
+                    ClosureTarget::Turn(t.id),
+                )
+            })
+            .collect(),
+        _ => Vec::new(),
+    }
+}
+
+pub fn add_closure(
+    wizard: &mut WrappedWizard,
+    map: &Map,
+    candidates: &Vec<(String, ClosureTarget)>,
+) -> Option<MapEdits> {
+    let choices = candidates.clone();
+    let (_, target) = wizard.choose_something_no_keys::<ClosureTarget>(
+        "Close what?",
+        Box::new(move || choices.clone()),
+    )?;
+    let start = wizard.input_something(
+        "Closed starting when? (HH:MM:SS.MS)",
+        None,
+        Box::new(|line| Duration::parse(&line)),
+    )?;
+    // Anything not after the start is rejected, and the wizard asks again.
+    let end = wizard.input_something(
+        &format!("Reopened when? Must be after {} (HH:MM:SS.MS)", start),
+        None,
+        Box::new(move |line| Duration::parse(&line).filter(|end| *end > start)),
+    )?;
+
+    let mut edits = map.get_edits().clone();
+    edits
+        .closures
+        .push(Closure::new(target, start, end).unwrap());
+    Some(edits)
+}
+
+pub fn remove_closure(wizard: &mut WrappedWizard, map: &Map) -> Option<MapEdits> {
+    let choices: Vec<(String, usize)> = map
+        .get_edits()
+        .closures
+        .iter()
+        .enumerate()
+        .map(|(idx, c)| (c.describe(), idx))
+        .collect();
+    let (_, idx) = wizard.choose_something_no_keys::<usize>(
+        "Reopen which closure?",
+        Box::new(move || choices.clone()),
+    )?;
+
+    let mut edits = map.get_edits().clone();
+    edits.closures.remove(idx);
+    Some(edits)
+}
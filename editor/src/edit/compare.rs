@@ -0,0 +1,118 @@
+use crate::game::GameState;
+use crate::helpers::ID;
+use crate::render::{DrawCtx, DrawOptions, Renderable};
+use crate::ui::{ShowEverything, UI};
+use ezgui::{Color, EventCtx, GfxCtx, Key, ModalMenu, Text};
+use map_model::{ClosureTarget, EditsDiff, IntersectionID, MapEdits};
+
+// Shows what's different between the current edits and some other saved edits.
+pub struct EditsComparison {
+    menu: ModalMenu,
+    other_name: String,
+    diff: EditsDiff,
+    lines: Vec<String>,
+}
+
+impl EditsComparison {
+    pub fn new(other: &MapEdits, ctx: &EventCtx, ui: &UI) -> EditsComparison {
+        let current = ui.primary.map.get_edits();
+        EditsComparison {
+            menu: ModalMenu::new("Compare Map Edits", vec![(Some(Key::Escape), "quit")], ctx),
+            other_name: other.edits_name.clone(),
+            diff: current.diff(other),
+            lines: current.describe_diff(other),
+        }
+    }
+
+    // Returns true if done and we should go back to main edit mode.
+    pub fn event(&mut self, ctx: &mut EventCtx, ui: &UI) -> bool {
+        let mut txt = Text::prompt("Compare Map Edits");
+        txt.add_line(format!(
+            "{} vs {}",
+            ui.primary.map.get_edits().edits_name,
+            self.other_name
+        ));
+        if self.lines.is_empty() {
+            txt.add_line("No differences".to_string());
+        }
+        // TODO Scroll through these
+        for line in self.lines.iter().take(20) {
+            txt.add_line(line.clone());
+        }
+        if self.lines.len() > 20 {
+            txt.add_line(format!("... and {} more", self.lines.len() - 20));
+        }
+        self.menu.handle_event(ctx, Some(txt));
+        ctx.canvas.handle_event(ctx.input);
+
+        self.menu.action("quit")
+    }
+
+    pub fn draw(&self, g: &mut GfxCtx, state: &GameState) {
+        state.ui.draw(
+            g,
+            DrawOptions::new(),
+            &state.ui.primary.sim,
+            &ShowEverything::new(),
+        );
+
+        let color = state
+            .ui
+            .cs
+            .get_def("differences between map edits", Color::PURPLE.alpha(0.8));
+        let ctx = DrawCtx {
+            cs: &state.ui.cs,
+            map: &state.ui.primary.map,
+            draw_map: &state.ui.primary.draw_map,
+            sim: &state.ui.primary.sim,
+        };
+        let mut opts = DrawOptions::new();
+
+        let mut intersections: Vec<IntersectionID> = self
+            .diff
+            .intersection_types
+            .iter()
+            .chain(self.diff.stop_signs.iter())
+            .chain(self.diff.traffic_signals.iter())
+            .cloned()
+            .collect();
+        for r in &self.diff.roads {
+            g.draw_polygon(
+                color,
+                &state.ui.primary.map.get_r(*r).get_thick_polygon().unwrap(),
+            );
+        }
+        for l in &self.diff.lanes {
+            opts.override_colors.insert(ID::Lane(*l), color);
+            state.ui.primary.draw_map.get_l(*l).draw(g, &opts, &ctx);
+        }
+        for c in self
+            .diff
+            .closures_added
+            .iter()
+            .chain(self.diff.closures_removed.iter())
+        {
+            match c.target {
+                ClosureTarget::Road(r) => {
+                    g.draw_polygon(
+                        color,
+                        &state.ui.primary.map.get_r(r).get_thick_polygon().unwrap(),
+                    );
+                }
+                ClosureTarget::Lane(l) => {
+                    opts.override_colors.insert(ID::Lane(l), color);
+                    state.ui.primary.draw_map.get_l(l).draw(g, &opts, &ctx);
+                }
+                ClosureTarget::Turn(t) => {
+                    intersections.push(t.parent);
+                }
+            }
+        }
+        for i in intersections {
+            opts.override_colors.insert(ID::Intersection(i), color);
+            state.ui.primary.draw_map.get_i(i).draw(g, &opts, &ctx);
+        }
+
+        self.menu.draw(g);
+    }
+}
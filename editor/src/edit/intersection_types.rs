@@ -0,0 +1,37 @@
+use ezgui::WrappedWizard;
+use map_model::{IntersectionID, IntersectionType, Map, MapEdits};
+
+pub fn change_intersection_type(
+    wizard: &mut WrappedWizard,
+    map: &Map,
+    id: IntersectionID,
+) -> Option<MapEdits> {
+    let i = map.get_i(id);
+    let choices: Vec<(String, IntersectionType)> = vec![
+        ("stop signs".to_string(), IntersectionType::StopSign),
+        (
+            "a traffic signal".to_string(),
+            IntersectionType::TrafficSignal,
+        ),
+        ("nothing".to_string(), IntersectionType::Uncontrolled),
+        ("a roundabout".to_string(), IntersectionType::Roundabout),
+    ]
+    .into_iter()
+    .filter(|(_, it)| *it != i.intersection_type)
+    .collect();
+    let (_, new_type) = wizard.choose_something_no_keys::<IntersectionType>(
+        &format!("Control {} with what?", id),
+        Box::new(move || choices.clone()),
+    )?;
+
+    let mut edits = map.get_edits().clone();
+    // The old policy doesn't apply to the new type.
+    edits.stop_sign_overrides.remove(&id);
+    edits.traffic_signal_overrides.remove(&id);
+    if new_type == i.original_intersection_type {
+        edits.intersection_type_overrides.remove(&id);
+    } else {
+        edits.intersection_type_overrides.insert(id, new_type);
+    }
+    Some(edits)
+}
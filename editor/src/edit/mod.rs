@@ -1,3 +1,7 @@
+mod closures;
+mod compare;
+mod intersection_types;
+mod roads;
 mod stop_signs;
 mod traffic_signals;
 
@@ -5,12 +9,19 @@ use crate::common::CommonState;
 use crate::game::{GameState, Mode};
 use crate::helpers::ID;
 use crate::render::{
-    DrawCtx, DrawLane, DrawMap, DrawOptions, DrawTurn, Renderable, MIN_ZOOM_FOR_DETAIL,
+    DrawCtx, DrawIntersection, DrawLane, DrawMap, DrawOptions, DrawTurn, Renderable,
+    MIN_ZOOM_FOR_DETAIL,
 };
 use crate::ui::{ShowEverything, UI};
 use abstutil::Timer;
-use ezgui::{Color, EventCtx, EventLoopMode, GfxCtx, Key, ModalMenu, Text, Wizard, WrappedWizard};
-use map_model::{Lane, LaneID, LaneType, Map, MapEdits, Road, TurnID, TurnType};
+use ezgui::{
+    Color, EventCtx, EventLoopMode, GfxCtx, Key, LogScroller, ModalMenu, Text, Wizard,
+    WrappedWizard,
+};
+use map_model::{
+    ClosureTarget, IntersectionID, IntersectionType, Lane, LaneID, LaneType, Map, MapEdits, Road,
+    RoadID, TurnID, TurnType,
+};
 use std::collections::{BTreeSet, HashMap};
 
 pub enum EditMode {
@@ -19,6 +30,28 @@ pub enum EditMode {
     Loading(Wizard),
     EditingStopSign(stop_signs::StopSignEditor),
     EditingTrafficSignal(traffic_signals::TrafficSignalEditor),
+    EditingRoad(RoadID, Wizard),
+    ChangingIntersectionType(IntersectionID, Wizard),
+    AddingClosure(Vec<(String, ClosureTarget)>, Wizard),
+    RemovingClosure(Wizard),
+    PickingEditsToCompare(Wizard),
+    Comparing(compare::EditsComparison),
+    Merging(Wizard),
+}
+
+// Everything that goes through apply_map_edits can be undone.
+pub struct EditHistory {
+    undo_stack: Vec<MapEdits>,
+    redo_stack: Vec<MapEdits>,
+}
+
+impl EditHistory {
+    pub fn new() -> EditHistory {
+        EditHistory {
+            undo_stack: Vec::new(),
+            redo_stack: Vec::new(),
+        }
+    }
 }
 
 impl EditMode {
@@ -35,6 +68,11 @@ impl EditMode {
                         (Some(Key::Escape), "quit"),
                         (Some(Key::S), "save edits"),
                         (Some(Key::L), "load different edits"),
+                        (Some(Key::O), "reopen a closure"),
+                        (Some(Key::U), "undo"),
+                        (Some(Key::R), "redo"),
+                        (Some(Key::D), "compare with other edits"),
+                        (Some(Key::M), "merge in other edits"),
                     ],
                     CommonState::modal_menu_entries(),
                 ]
@@ -83,22 +121,66 @@ impl EditMode {
                 } else if menu.action("load different edits") {
                     state.mode = Mode::Edit(EditMode::Loading(Wizard::new()));
                     return EventLoopMode::InputOnly;
+                } else if !state.ui.primary.map.get_edits().closures.is_empty()
+                    && menu.action("reopen a closure")
+                {
+                    state.mode = Mode::Edit(EditMode::RemovingClosure(Wizard::new()));
+                    return EventLoopMode::InputOnly;
+                } else if menu.action("compare with other edits") {
+                    state.mode = Mode::Edit(EditMode::PickingEditsToCompare(Wizard::new()));
+                    return EventLoopMode::InputOnly;
+                } else if menu.action("merge in other edits") {
+                    state.mode = Mode::Edit(EditMode::Merging(Wizard::new()));
+                    return EventLoopMode::InputOnly;
+                }
+                if menu.action("undo") {
+                    undo(&mut state.ui, ctx);
+                } else if menu.action("redo") {
+                    redo(&mut state.ui, ctx);
+                }
+
+                if let Some(id) = state.ui.primary.current_selection {
+                    let candidates = closures::closure_candidates(id, &state.ui.primary.map);
+                    if !candidates.is_empty()
+                        && ctx
+                            .input
+                            .contextual_action(Key::C, "close something here for a while")
+                    {
+                        state.mode = Mode::Edit(EditMode::AddingClosure(candidates, Wizard::new()));
+                        return EventLoopMode::InputOnly;
+                    }
                 }
 
                 if let Some(ID::Lane(id)) = state.ui.primary.current_selection {
                     let lane = state.ui.primary.map.get_l(id);
                     let road = state.ui.primary.map.get_r(lane.parent);
 
-                    if lane.lane_type != LaneType::Sidewalk {
-                        if let Some(new_type) = next_valid_type(road, lane) {
-                            if ctx
-                                .input
-                                .contextual_action(Key::Space, &format!("toggle to {:?}", new_type))
-                            {
-                                let mut new_edits = state.ui.primary.map.get_edits().clone();
+                    if ctx
+                        .input
+                        .contextual_action(Key::A, &format!("add or remove lanes on {}", road.id))
+                    {
+                        state.mode = Mode::Edit(EditMode::EditingRoad(road.id, Wizard::new()));
+                        return EventLoopMode::InputOnly;
+                    }
+
+                    if let Some(new_type) = next_valid_type(road, lane) {
+                        if ctx
+                            .input
+                            .contextual_action(Key::Space, &format!("toggle to {:?}", new_type))
+                        {
+                            let mut new_edits = state.ui.primary.map.get_edits().clone();
+                            // If the whole road is overridden, change that instead.
+                            if let Some(spec) = new_edits.road_overrides.get_mut(&road.id) {
+                                let (fwds, idx) = road.dir_and_offset(lane.id);
+                                if fwds {
+                                    spec.fwd[idx] = new_type;
+                                } else {
+                                    spec.back[idx] = new_type;
+                                }
+                            } else {
                                 new_edits.lane_overrides.insert(lane.id, new_type);
-                                apply_map_edits(&mut state.ui, ctx, new_edits);
                             }
+                            apply_map_edits(&mut state.ui, ctx, new_edits);
                         }
                     }
                 }
@@ -121,6 +203,14 @@ impl EditMode {
                             traffic_signals::TrafficSignalEditor::new(id, ctx),
                         ));
                     }
+                    if state.ui.primary.map.get_i(id).intersection_type != IntersectionType::Border
+                        && ctx
+                            .input
+                            .contextual_action(Key::T, &format!("change control of {}", id))
+                    {
+                        state.mode =
+                            Mode::Edit(EditMode::ChangingIntersectionType(id, Wizard::new()));
+                    }
                 }
             }
             Mode::Edit(EditMode::Saving(ref mut wizard)) => {
@@ -135,10 +225,38 @@ impl EditMode {
                 }
             }
             Mode::Edit(EditMode::Loading(ref mut wizard)) => {
-                if let Some(new_edits) = load_edits(
+                if let Some(maybe_edits) = load_edits(
                     &state.ui.primary.map,
                     &mut wizard.wrap(ctx.input, ctx.canvas),
                     "Load which map edits?",
+                ) {
+                    if let Some(new_edits) = maybe_edits {
+                        apply_map_edits(&mut state.ui, ctx, new_edits);
+                    }
+                    state.mode = Mode::Edit(EditMode::new(ctx, &mut state.ui));
+                } else if wizard.aborted() {
+                    state.mode = Mode::Edit(EditMode::new(ctx, &mut state.ui));
+                }
+            }
+            Mode::Edit(EditMode::EditingRoad(r, ref mut wizard)) => {
+                if let Some(maybe_edits) = roads::edit_road(
+                    &mut wizard.wrap(ctx.input, ctx.canvas),
+                    &state.ui.primary.map,
+                    r,
+                ) {
+                    if let Some(new_edits) = maybe_edits {
+                        apply_map_edits(&mut state.ui, ctx, new_edits);
+                    }
+                    state.mode = Mode::Edit(EditMode::new(ctx, &mut state.ui));
+                } else if wizard.aborted() {
+                    state.mode = Mode::Edit(EditMode::new(ctx, &mut state.ui));
+                }
+            }
+            Mode::Edit(EditMode::ChangingIntersectionType(i, ref mut wizard)) => {
+                if let Some(new_edits) = intersection_types::change_intersection_type(
+                    &mut wizard.wrap(ctx.input, ctx.canvas),
+                    &state.ui.primary.map,
+                    i,
                 ) {
                     apply_map_edits(&mut state.ui, ctx, new_edits);
                     state.mode = Mode::Edit(EditMode::new(ctx, &mut state.ui));
@@ -146,6 +264,64 @@ impl EditMode {
                     state.mode = Mode::Edit(EditMode::new(ctx, &mut state.ui));
                 }
             }
+            Mode::Edit(EditMode::AddingClosure(ref candidates, ref mut wizard)) => {
+                if let Some(new_edits) = closures::add_closure(
+                    &mut wizard.wrap(ctx.input, ctx.canvas),
+                    &state.ui.primary.map,
+                    candidates,
+                ) {
+                    apply_map_edits(&mut state.ui, ctx, new_edits);
+                    state.mode = Mode::Edit(EditMode::new(ctx, &mut state.ui));
+                } else if wizard.aborted() {
+                    state.mode = Mode::Edit(EditMode::new(ctx, &mut state.ui));
+                }
+            }
+            Mode::Edit(EditMode::RemovingClosure(ref mut wizard)) => {
+                if let Some(new_edits) = closures::remove_closure(
+                    &mut wizard.wrap(ctx.input, ctx.canvas),
+                    &state.ui.primary.map,
+                ) {
+                    apply_map_edits(&mut state.ui, ctx, new_edits);
+                    state.mode = Mode::Edit(EditMode::new(ctx, &mut state.ui));
+                } else if wizard.aborted() {
+                    state.mode = Mode::Edit(EditMode::new(ctx, &mut state.ui));
+                }
+            }
+            Mode::Edit(EditMode::PickingEditsToCompare(ref mut wizard)) => {
+                if let Some(maybe_other) = load_edits(
+                    &state.ui.primary.map,
+                    &mut wizard.wrap(ctx.input, ctx.canvas),
+                    "Compare with which map edits?",
+                ) {
+                    state.mode = if let Some(other) = maybe_other {
+                        Mode::Edit(EditMode::Comparing(compare::EditsComparison::new(
+                            &other, ctx, &state.ui,
+                        )))
+                    } else {
+                        Mode::Edit(EditMode::new(ctx, &mut state.ui))
+                    };
+                } else if wizard.aborted() {
+                    state.mode = Mode::Edit(EditMode::new(ctx, &mut state.ui));
+                }
+            }
+            Mode::Edit(EditMode::Comparing(ref mut comparison)) => {
+                if comparison.event(ctx, &state.ui) {
+                    state.mode = Mode::Edit(EditMode::new(ctx, &mut state.ui));
+                }
+            }
+            Mode::Edit(EditMode::Merging(ref mut wizard)) => {
+                if let Some(maybe_edits) = merge_edits(
+                    &state.ui.primary.map,
+                    &mut wizard.wrap(ctx.input, ctx.canvas),
+                ) {
+                    if let Some(new_edits) = maybe_edits {
+                        apply_map_edits(&mut state.ui, ctx, new_edits);
+                    }
+                    state.mode = Mode::Edit(EditMode::new(ctx, &mut state.ui));
+                } else if wizard.aborted() {
+                    state.mode = Mode::Edit(EditMode::new(ctx, &mut state.ui));
+                }
+            }
             Mode::Edit(EditMode::EditingStopSign(ref mut editor)) => {
                 if editor.event(ctx, &mut state.ui) {
                     state.mode = Mode::Edit(EditMode::new(ctx, &mut state.ui));
@@ -214,19 +390,61 @@ impl EditMode {
                         );
                     }
                 }
+                for r in edits.road_overrides.keys() {
+                    g.draw_polygon(
+                        color,
+                        &state.ui.primary.map.get_r(*r).get_thick_polygon().unwrap(),
+                    );
+                }
                 for i in edits
-                    .stop_sign_overrides
+                    .intersection_type_overrides
                     .keys()
+                    .chain(edits.stop_sign_overrides.keys())
                     .chain(edits.traffic_signal_overrides.keys())
                 {
                     opts.override_colors.insert(ID::Intersection(*i), color);
                     state.ui.primary.draw_map.get_i(*i).draw(g, &opts, &ctx);
                 }
 
+                let closed_color = state
+                    .ui
+                    .cs
+                    .get_def("closed for construction", Color::ORANGE.alpha(0.8));
+                for c in &edits.closures {
+                    match c.target {
+                        ClosureTarget::Road(r) => {
+                            g.draw_polygon(
+                                closed_color,
+                                &state.ui.primary.map.get_r(r).get_thick_polygon().unwrap(),
+                            );
+                        }
+                        ClosureTarget::Lane(l) => {
+                            opts.override_colors.insert(ID::Lane(l), closed_color);
+                            state.ui.primary.draw_map.get_l(l).draw(g, &opts, &ctx);
+                        }
+                        ClosureTarget::Turn(t) => {
+                            opts.override_colors
+                                .insert(ID::Intersection(t.parent), closed_color);
+                            state
+                                .ui
+                                .primary
+                                .draw_map
+                                .get_i(t.parent)
+                                .draw(g, &opts, &ctx);
+                        }
+                    }
+                }
+
                 menu.draw(g);
             }
             Mode::Edit(EditMode::Saving(ref wizard))
-            | Mode::Edit(EditMode::Loading(ref wizard)) => {
+            | Mode::Edit(EditMode::Loading(ref wizard))
+            | Mode::Edit(EditMode::EditingRoad(_, ref wizard))
+            | Mode::Edit(EditMode::ChangingIntersectionType(_, ref wizard))
+            | Mode::Edit(EditMode::AddingClosure(_, ref wizard))
+            | Mode::Edit(EditMode::RemovingClosure(ref wizard))
+            | Mode::Edit(EditMode::PickingEditsToCompare(ref wizard))
+            | Mode::Edit(EditMode::Merging(ref wizard)) => {
                 state.ui.draw(
                     g,
                     DrawOptions::new(),
@@ -237,6 +455,9 @@ impl EditMode {
                 // TODO Still draw the diffs, yo
                 wizard.draw(g);
             }
+            Mode::Edit(EditMode::Comparing(ref comparison)) => {
+                comparison.draw(g, state);
+            }
             Mode::Edit(EditMode::EditingStopSign(ref editor)) => {
                 editor.draw(g, state);
             }
@@ -268,7 +489,7 @@ fn save_edits(mut wizard: WrappedWizard, map: &mut Map) -> Option<()> {
             edits.edits_name = name;
             map.apply_edits(edits, &mut Timer::new("name map edits"));
         }
-        map.get_edits().save();
+        map.get_edits().save(map);
     }
     Some(())
 }
@@ -276,6 +497,10 @@ fn save_edits(mut wizard: WrappedWizard, map: &mut Map) -> Option<()> {
 // For lane editing
 
 fn next_valid_type(r: &Road, l: &Lane) -> Option<LaneType> {
+    // Both halves of a two-way bike track have to change together, by editing the whole road.
+    if l.lane_type == LaneType::TwoWayBiking {
+        return None;
+    }
     let mut new_type = next_type(l.lane_type);
     while new_type != l.lane_type {
         if can_change_lane_type(r, l, new_type) {
@@ -290,10 +515,15 @@ fn next_type(lt: LaneType) -> LaneType {
     match lt {
         LaneType::Driving => LaneType::Parking,
         LaneType::Parking => LaneType::Biking,
-        LaneType::Biking => LaneType::Bus,
-        LaneType::Bus => LaneType::Driving,
+        LaneType::Biking => LaneType::BusAndBike,
+        LaneType::TwoWayBiking => LaneType::BusAndBike,
+        LaneType::BusAndBike => LaneType::Bus,
+        LaneType::Bus => LaneType::CenterTurn,
+        LaneType::CenterTurn => LaneType::Driving,
 
-        LaneType::Sidewalk => unreachable!(),
+        // Sidewalks can't become part of the road, but bikes can share them.
+        LaneType::Sidewalk => LaneType::SharedUse,
+        LaneType::SharedUse => LaneType::Sidewalk,
     }
 }
 
@@ -320,8 +550,22 @@ fn can_change_lane_type(r: &Road, l: &Lane, lt: LaneType) -> bool {
         } else {
             r.get_lane_types().1
         };
-        if (idx != 0 && types[idx - 1] == LaneType::Biking)
-            || types.get(idx + 1) == Some(&LaneType::Biking)
+        let bike_lanes = [LaneType::Biking, LaneType::TwoWayBiking];
+        if (idx != 0 && bike_lanes.contains(&types[idx - 1]))
+            || types.get(idx + 1).map(|lt| bike_lanes.contains(lt)) == Some(true)
+        {
+            return false;
+        }
+    }
+
+    // The center turn lane has to be in the center of a two-way road, and there's only one.
+    if lt == LaneType::CenterTurn {
+        let (fwd, back) = r.get_lane_types();
+        if idx != 0
+            || fwd.is_empty()
+            || back.is_empty()
+            || fwd[0] == LaneType::CenterTurn
+            || back[0] == LaneType::CenterTurn
         {
             return false;
         }
@@ -331,26 +575,81 @@ fn can_change_lane_type(r: &Road, l: &Lane, lt: LaneType) -> bool {
 }
 
 pub fn apply_map_edits(ui: &mut UI, ctx: &mut EventCtx, edits: MapEdits) {
+    let old_edits = ui.primary.map.get_edits().clone();
+    ui.primary.edit_history.undo_stack.push(old_edits);
+    ui.primary.edit_history.redo_stack.clear();
+    change_map_edits(ui, ctx, edits);
+}
+
+fn undo(ui: &mut UI, ctx: &mut EventCtx) {
+    if let Some(edits) = ui.primary.edit_history.undo_stack.pop() {
+        let current = ui.primary.map.get_edits().clone();
+        ui.primary.edit_history.redo_stack.push(current);
+        change_map_edits(ui, ctx, edits);
+    }
+}
+
+fn redo(ui: &mut UI, ctx: &mut EventCtx) {
+    if let Some(edits) = ui.primary.edit_history.redo_stack.pop() {
+        let current = ui.primary.map.get_edits().clone();
+        ui.primary.edit_history.undo_stack.push(current);
+        change_map_edits(ui, ctx, edits);
+    }
+}
+
+fn change_map_edits(ui: &mut UI, ctx: &mut EventCtx, edits: MapEdits) {
     let mut timer = Timer::new("apply map edits");
     ui.primary.current_flags.sim_flags.edits_name = edits.edits_name.clone();
-    let (lanes_changed, turns_deleted, turns_added) = ui.primary.map.apply_edits(edits, &mut timer);
+    let effects = ui.primary.map.apply_edits(edits, &mut timer);
 
-    for l in lanes_changed {
-        ui.primary.draw_map.lanes[l.0] = DrawLane::new(
-            ui.primary.map.get_l(l),
+    for l in effects.changed_lanes {
+        let lane = ui.primary.map.get_l(l);
+        let draw = DrawLane::new(
+            lane,
             &ui.primary.map,
-            !ui.primary.current_flags.dont_draw_lane_markings,
+            !ui.primary.current_flags.dont_draw_lane_markings && !ui.primary.map.is_lane_removed(l),
             &ui.cs,
             ctx.prerender,
             &mut timer,
         );
+        // Lanes added by the edits always come after all the existing ones.
+        if l.0 == ui.primary.draw_map.lanes.len() {
+            ui.primary.draw_map.lanes.push(draw);
+        } else {
+            ui.primary.draw_map.lanes[l.0] = draw;
+        }
+    }
+    if !effects.changed_roads.is_empty() {
+        ui.primary.draw_map.recreate_geometry(
+            &ui.primary.map,
+            &effects.changed_roads,
+            &effects.changed_intersections,
+            &ui.cs,
+            ctx.prerender,
+            &mut timer,
+        );
+        // The sim only knows about the old lanes.
+        ui.primary.reset_sim();
+    } else {
+        for i in &effects.changed_intersections {
+            ui.primary.draw_map.intersections[i.0] = DrawIntersection::new(
+                ui.primary.map.get_i(*i),
+                &ui.primary.map,
+                &ui.cs,
+                ctx.prerender,
+                &mut timer,
+            );
+        }
+        ui.primary
+            .sim
+            .handle_intersection_edits(&effects.changed_intersections, &ui.primary.map);
     }
     let mut lanes_of_modified_turns: BTreeSet<LaneID> = BTreeSet::new();
-    for t in turns_deleted {
+    for t in effects.deleted_turns {
         ui.primary.draw_map.turns.remove(&t);
         lanes_of_modified_turns.insert(t.src);
     }
-    for t in &turns_added {
+    for t in &effects.added_turns {
         lanes_of_modified_turns.insert(t.src);
     }
 
@@ -362,7 +661,7 @@ pub fn apply_map_edits(ui: &mut UI, ctx: &mut EventCtx, edits: MapEdits) {
             &ui.primary.map,
         );
     }
-    for t in turns_added {
+    for t in effects.added_turns {
         let turn = ui.primary.map.get_t(t);
         if turn.turn_type != TurnType::SharedSidewalkCorner {
             ui.primary.draw_map.turns.insert(
@@ -373,17 +672,63 @@ pub fn apply_map_edits(ui: &mut UI, ctx: &mut EventCtx, edits: MapEdits) {
     }
 }
 
-fn load_edits(map: &Map, wizard: &mut WrappedWizard, query: &str) -> Option<MapEdits> {
+// Returns Some(None) if the edits conflict or can't be loaded, and the user has acknowledged that.
+fn merge_edits(map: &Map, wizard: &mut WrappedWizard) -> Option<Option<MapEdits>> {
+    let other = match load_edits(map, wizard, "Merge which map edits into these?")? {
+        Some(edits) => edits,
+        None => {
+            return Some(None);
+        }
+    };
+    let base = match load_edits(
+        map,
+        wizard,
+        "Which edits did both start from? (no_edits if they started separately)",
+    )? {
+        Some(edits) => edits,
+        None => {
+            return Some(None);
+        }
+    };
+    match map.get_edits().merge(&other, &base, map) {
+        Ok(edits) => Some(Some(edits)),
+        Err(err) => {
+            if wizard.acknowledge(LogScroller::new(
+                "Can't merge map edits".to_string(),
+                vec![err.to_string()],
+            )) {
+                Some(None)
+            } else {
+                None
+            }
+        }
+    }
+}
+
+// Returns Some(None) if the edits can't be loaded and the user has acknowledged that.
+fn load_edits(map: &Map, wizard: &mut WrappedWizard, query: &str) -> Option<Option<MapEdits>> {
     // TODO Exclude current?
     let map_name = map.get_name().to_string();
-    wizard
-        .choose_something_no_keys::<MapEdits>(
-            query,
-            Box::new(move || {
-                let mut list = abstutil::load_all_objects("edits", &map_name);
-                list.push(("no_edits".to_string(), MapEdits::new(map_name.clone())));
-                list
-            }),
-        )
-        .map(|(_, e)| e)
+    let (name, _) = wizard.choose_something_no_keys::<String>(
+        query,
+        Box::new(move || {
+            let mut list = abstutil::list_all_objects("edits", &map_name);
+            list.push(("no_edits".to_string(), "no_edits".to_string()));
+            list
+        }),
+    )?;
+    // Edits that no longer match the map are reported and skipped.
+    match MapEdits::load(map, &name, &mut Timer::new("load map edits")) {
+        Ok(edits) => Some(Some(edits)),
+        Err(err) => {
+            if wizard.acknowledge(LogScroller::new(
+                "Can't load map edits".to_string(),
+                vec![err.to_string()],
+            )) {
+                Some(None)
+            } else {
+                None
+            }
+        }
+    }
 }
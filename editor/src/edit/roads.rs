@@ -0,0 +1,115 @@
+use ezgui::{LogScroller, WrappedWizard};
+use map_model::{contraflow_bike_track, LaneType, Map, MapEdits, RoadID, RoadSpec};
+
+// Returns Some(None) if the new lanes don't work and the user has acknowledged that.
+pub fn edit_road(wizard: &mut WrappedWizard, map: &Map, r: RoadID) -> Option<Option<MapEdits>> {
+    let road = map.get_r(r);
+    let (fwd, back) = road.get_lane_types();
+    let line = wizard.input_string_prefilled(
+        "Lanes from the center out, forwards/backwards (d=driving, p=parking, b=bike, t=two-way \
+         bike, u=bus, x=bus and bike, c=center turn, s=sidewalk, m=shared-use path)",
+        RoadSpec { fwd, back }.to_string(),
+    )?;
+
+    let problems = match RoadSpec::parse(line.clone()) {
+        Some(spec) => {
+            let problems = spec_problems(map, r, &spec);
+            if problems.is_empty() {
+                let mut edits = map.get_edits().clone();
+                // The current lane types already include these.
+                for l in road.all_lanes() {
+                    edits.lane_overrides.remove(&l);
+                }
+                edits.road_overrides.insert(r, spec);
+                return Some(Some(edits));
+            }
+            problems
+        }
+        None => vec![format!("{} isn't a valid list of lanes", line)],
+    };
+    if wizard.acknowledge(LogScroller::new(
+        format!("Can't change the lanes of {}", r),
+        problems,
+    )) {
+        Some(None)
+    } else {
+        None
+    }
+}
+
+fn spec_problems(map: &Map, r: RoadID, spec: &RoadSpec) -> Vec<String> {
+    let road = map.get_r(r);
+    let (old_fwd, old_back) = road.get_lane_types();
+    let mut problems = Vec::new();
+
+    for (side, old, new) in vec![
+        ("forwards", &old_fwd, &spec.fwd),
+        ("backwards", &old_back, &spec.back),
+    ] {
+        // Buildings and bus stops are attached to sidewalks.
+        let old_sidewalks = old.iter().filter(|lt| lt.supports_walking()).count();
+        let new_sidewalks = new.iter().filter(|lt| lt.supports_walking()).count();
+        if old_sidewalks != new_sidewalks {
+            problems.push(format!(
+                "The {} side has to keep {} sidewalks or shared-use paths",
+                side, old_sidewalks
+            ));
+        }
+
+        if let Some(idx) = new.iter().position(|lt| *lt == LaneType::Parking) {
+            if new.iter().filter(|lt| **lt == LaneType::Parking).count() > 1 {
+                problems.push(format!("The {} side has more than one parking lane", side));
+            }
+            if !new[0..idx].contains(&LaneType::Driving) {
+                problems.push(format!(
+                    "The parking lane on the {} side needs a driving lane closer to the center",
+                    side
+                ));
+            }
+        }
+
+        if new.iter().skip(1).any(|lt| *lt == LaneType::CenterTurn) {
+            problems.push("The center turn lane has to be in the center".to_string());
+        }
+    }
+
+    if spec.fwd.contains(&LaneType::CenterTurn) || spec.back.contains(&LaneType::CenterTurn) {
+        if spec.fwd.is_empty() || spec.back.is_empty() {
+            problems.push("Only two-way roads can have a center turn lane".to_string());
+        }
+        if spec.fwd.first() == Some(&LaneType::CenterTurn)
+            && spec.back.first() == Some(&LaneType::CenterTurn)
+        {
+            problems.push("Only one center turn lane is needed".to_string());
+        }
+    }
+
+    let num_two_way = spec
+        .fwd
+        .iter()
+        .chain(spec.back.iter())
+        .filter(|lt| **lt == LaneType::TwoWayBiking)
+        .count();
+    if num_two_way > 0
+        && (num_two_way != 2 || contraflow_bike_track(&spec.fwd, &spec.back).is_none())
+    {
+        problems.push(
+            "A two-way bike track goes on one side, with its contraflow half last on the other side"
+                .to_string(),
+        );
+    }
+
+    let has_bus_stops = road
+        .all_lanes()
+        .into_iter()
+        .any(|l| !map.get_l(l).bus_stops.is_empty());
+    if has_bus_stops
+        && !spec.fwd.iter().chain(spec.back.iter()).any(|lt| {
+            *lt == LaneType::Driving || *lt == LaneType::Bus || *lt == LaneType::BusAndBike
+        })
+    {
+        problems.push("Buses need a lane to reach the bus stops here".to_string());
+    }
+
+    problems
+}
@@ -57,10 +57,11 @@ impl GameState {
             .choose(&mut rng)
             .and_then(|b| ID::Building(b.id).canonical_point(&game.ui.primary))
             .or_else(|| {
-                game.ui
-                    .primary
-                    .map
-                    .all_lanes()
+                let map = &game.ui.primary.map;
+                map.all_lanes()
+                    .iter()
+                    .filter(|l| !map.is_lane_removed(l.id))
+                    .collect::<Vec<_>>()
                     .choose(&mut rng)
                     .and_then(|l| ID::Lane(l.id).canonical_point(&game.ui.primary))
             })
@@ -144,6 +144,12 @@ impl ID {
                         .collect::<Vec<StableRoadID>>()
                 ));
                 txt.add_line(format!("Originally {}", i.stable_id));
+                for m in map.get_merged_roads_into(id) {
+                    txt.add_line(format!(
+                        "Merged {} ({:?}): {} long, {} after trimming",
+                        m.road, m.reason, m.original_length, m.trimmed_length
+                    ));
+                }
             }
             ID::Turn(id) => {
                 let t = map.get_t(id);
@@ -160,8 +166,26 @@ impl ID {
                     "Dist along sidewalk: {}",
                     b.front_path.sidewalk.dist_along()
                 ));
-                if let Some(units) = b.num_residential_units {
-                    txt.add_line(format!("{} residential units", units));
+                txt.add_line(format!("{:?}", b.building_type));
+                if b.capacity.residential_units > 0 {
+                    txt.add_line(format!(
+                        "{} residential units{}",
+                        b.capacity.residential_units,
+                        if b.num_residential_units.is_some() {
+                            ""
+                        } else {
+                            " (estimated)"
+                        }
+                    ));
+                }
+                if b.capacity.jobs > 0 || b.capacity.visitors > 0 {
+                    txt.add_line(format!(
+                        "~{} jobs, ~{} visitors per day",
+                        b.capacity.jobs, b.capacity.visitors
+                    ));
+                }
+                if b.capacity.parking_spots > 0 {
+                    txt.add_line(format!("~{} parking spots", b.capacity.parking_spots));
                 }
                 styled_kv(&mut txt, &b.osm_tags);
             }
@@ -185,6 +185,7 @@ fn pick_neighborhood(map: &Map, mut wizard: WrappedWizard) -> Option<Neighborhoo
             name,
             map_name: map.get_name().to_string(),
             points: Vec::new(),
+            inner_rings: Vec::new(),
         })
     }
 }
@@ -8,7 +8,7 @@ use ezgui::{
     Color, Drawable, EventCtx, GfxCtx, Key, LogScroller, ModalMenu, Wizard, WrappedWizard,
 };
 use geom::{Distance, Duration, Line, Pt2D};
-use map_model::{IntersectionID, Map, Neighborhood};
+use map_model::{IntersectionID, Map, Neighborhood, OriginalIntersection};
 use sim::{BorderSpawnOverTime, OriginDestination, Scenario, SeedParkedCars, SpawnOverTime};
 use std::collections::BTreeMap;
 
@@ -170,7 +170,9 @@ impl ScenarioEditor {
                         .offset(-50.0, 0.0);
                     let dst = match s.goal {
                         OriginDestination::Neighborhood(ref n) => mapping[n].center,
-                        OriginDestination::Border(i) => ui.primary.map.get_i(i).point,
+                        OriginDestination::Border(ref i) => {
+                            Pt2D::from_gps(i.point, ui.primary.map.get_gps_bounds()).unwrap()
+                        }
                     }
                     .offset(50.0, 0.0);
                     // TODO Draw a self-loop or something
@@ -207,6 +209,7 @@ fn pick_scenario(map: &Map, mut wizard: WrappedWizard) -> Option<Scenario> {
             seed_parked_cars: Vec::new(),
             spawn_over_time: Vec::new(),
             border_spawn_over_time: Vec::new(),
+            tours: Vec::new(),
         })
     }
 }
@@ -265,6 +268,7 @@ fn edit_scenario(map: &Map, scenario: &mut Scenario, mut wizard: WrappedWizard)
                 stop_time: input_time(&mut wizard, "Stop spawning when?")?,
                 // TODO validate it's a border!
                 start_from_border: choose_intersection(
+                    map,
                     &mut wizard,
                     "Which border should the agents spawn at?",
                 )?,
@@ -337,12 +341,17 @@ fn input_weighted_usize(wizard: &mut WrappedWizard, query: &str) -> Option<Weigh
 }
 
 // TODO Validate the intersection exists? Let them pick it with the cursor?
-fn choose_intersection(wizard: &mut WrappedWizard, query: &str) -> Option<IntersectionID> {
-    wizard.input_something(
+fn choose_intersection(
+    map: &Map,
+    wizard: &mut WrappedWizard,
+    query: &str,
+) -> Option<OriginalIntersection> {
+    let id = wizard.input_something(
         query,
         None,
         Box::new(|line| usize::from_str_radix(&line, 10).ok().map(IntersectionID)),
-    )
+    )?;
+    Some(OriginalIntersection::to_permanent(id, map))
 }
 
 fn choose_origin_destination(
@@ -355,6 +364,6 @@ fn choose_origin_destination(
     if wizard.choose_string(query, vec![neighborhood, border])? == neighborhood {
         choose_neighborhood(map, wizard, query).map(OriginDestination::Neighborhood)
     } else {
-        choose_intersection(wizard, query).map(OriginDestination::Border)
+        choose_intersection(map, wizard, query).map(OriginDestination::Border)
     }
 }
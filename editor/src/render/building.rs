@@ -29,8 +29,17 @@ impl DrawBuilding {
                     BuildingType::Residence => {
                         cs.get_def("residential building", Color::rgb(218, 165, 32))
                     }
-                    BuildingType::Business => {
-                        cs.get_def("business building", Color::rgb(210, 105, 30))
+                    BuildingType::Retail => cs.get_def("retail building", Color::rgb(210, 105, 30)),
+                    BuildingType::Office => cs.get_def("office building", Color::rgb(160, 82, 45)),
+                    BuildingType::School => cs.get_def("school building", Color::rgb(205, 92, 92)),
+                    BuildingType::Hospital => {
+                        cs.get_def("hospital building", Color::rgb(219, 112, 147))
+                    }
+                    BuildingType::MixedUse => {
+                        cs.get_def("mixed-use building", Color::rgb(244, 164, 96))
+                    }
+                    BuildingType::ParkingGarage => {
+                        cs.get_def("parking garage", Color::rgb(112, 128, 144))
                     }
                     BuildingType::Unknown => {
                         cs.get_def("unknown building", Color::rgb_f(0.7, 0.7, 0.7))
@@ -39,6 +39,12 @@ impl DrawIntersection {
                 IntersectionType::TrafficSignal => {
                     cs.get_def("traffic signal intersection", Color::grey(0.4))
                 }
+                IntersectionType::Uncontrolled => {
+                    cs.get_def("uncontrolled intersection", Color::grey(0.5))
+                }
+                IntersectionType::Roundabout => {
+                    cs.get_def("roundabout intersection", Color::grey(0.45))
+                }
             },
             i.polygon.clone(),
         )];
@@ -34,6 +34,10 @@ impl DrawLane {
                 LaneType::Parking => cs.get_def("parking lane", Color::grey(0.2)),
                 LaneType::Sidewalk => cs.get_def("sidewalk", Color::grey(0.8)),
                 LaneType::Biking => cs.get_def("bike lane", Color::rgb(15, 125, 75)),
+                LaneType::TwoWayBiking => cs.get_def("two-way bike track", Color::rgb(10, 95, 55)),
+                LaneType::SharedUse => cs.get_def("shared-use path", Color::rgb(170, 190, 170)),
+                LaneType::CenterTurn => cs.get_def("center turn lane", Color::BLACK),
+                LaneType::BusAndBike => cs.get_def("bus and bike lane", Color::rgb(190, 110, 76)),
             },
             polygon.clone(),
         )];
@@ -45,11 +49,18 @@ impl DrawLane {
                 LaneType::Parking => {
                     draw.extend(calculate_parking_lines(lane, cs));
                 }
-                LaneType::Driving | LaneType::Bus => {
+                LaneType::Driving | LaneType::Bus | LaneType::BusAndBike => {
                     draw.extend(calculate_driving_lines(lane, road, cs, timer));
                     draw.extend(calculate_turn_markings(map, lane, cs, timer));
                 }
                 LaneType::Biking => {}
+                LaneType::TwoWayBiking | LaneType::SharedUse => {
+                    draw.extend(calculate_center_dashes(lane, cs, timer));
+                }
+                LaneType::CenterTurn => {
+                    draw.extend(calculate_center_turn_lines(lane, cs, timer));
+                    draw.extend(calculate_turn_markings(map, lane, cs, timer));
+                }
             };
             if lane.is_driving()
                 && map.get_i(lane.dst_i).intersection_type == IntersectionType::StopSign
@@ -208,6 +219,42 @@ fn calculate_driving_lines(
         .collect()
 }
 
+// Splits traffic going both ways within one lane.
+fn calculate_center_dashes(
+    lane: &Lane,
+    cs: &ColorScheme,
+    timer: &mut Timer,
+) -> Vec<(Color, Polygon)> {
+    let dash_separation = Distance::meters(1.5);
+    let dash_len = Distance::meters(1.0);
+
+    if lane.length() < dash_separation * 2.0 {
+        return Vec::new();
+    }
+    lane.lane_center_pts
+        .exact_slice(dash_separation, lane.length() - dash_separation)
+        .dashed_polygons(Distance::meters(0.25), dash_len, dash_separation)
+        .into_iter()
+        .map(|p| (cs.get_def("two-way lane divider", Color::WHITE), p))
+        .collect()
+}
+
+// Yellow lines on both edges, since traffic from both directions uses it.
+fn calculate_center_turn_lines(
+    lane: &Lane,
+    cs: &ColorScheme,
+    timer: &mut Timer,
+) -> Vec<(Color, Polygon)> {
+    let color = cs.get_def("center turn lane lines", Color::YELLOW);
+    vec![
+        lane.lane_center_pts.shift_left(LANE_THICKNESS / 2.0),
+        lane.lane_center_pts.shift_right(LANE_THICKNESS / 2.0),
+    ]
+    .into_iter()
+    .map(|edge| (color, edge.get(timer).make_polygons(Distance::meters(0.25))))
+    .collect()
+}
+
 fn calculate_stop_sign_line(
     road: &Road,
     lane: &Lane,
@@ -19,7 +19,7 @@ use map_model::{
 };
 use std::borrow::Borrow;
 use std::cell::RefCell;
-use std::collections::HashMap;
+use std::collections::{BTreeSet, HashMap};
 
 pub struct DrawMap {
     pub roads: Vec<DrawRoad>,
@@ -73,7 +73,7 @@ impl DrawMap {
             lanes.push(DrawLane::new(
                 l,
                 map,
-                !flags.dont_draw_lane_markings,
+                !flags.dont_draw_lane_markings && !map.is_lane_removed(l.id),
                 cs,
                 prerender,
                 timer,
@@ -82,7 +82,9 @@ impl DrawMap {
 
         let mut turn_to_lane_offset: HashMap<TurnID, usize> = HashMap::new();
         for l in map.all_lanes() {
-            DrawMap::compute_turn_to_lane_offset(&mut turn_to_lane_offset, l, map);
+            if !map.is_lane_removed(l.id) {
+                DrawMap::compute_turn_to_lane_offset(&mut turn_to_lane_offset, l, map);
+            }
         }
 
         timer.start_iter("make DrawTurns", map.all_turns().len());
@@ -183,36 +185,12 @@ impl DrawMap {
             map.get_boundary_polygon(),
         )]);
 
-        timer.start("create quadtree");
-        let mut quadtree = QuadTree::default(map.get_bounds().as_bbox());
-        // TODO use iter chain if everything was boxed as a renderable...
-        for obj in &roads {
-            quadtree.insert_with_box(obj.get_id(), obj.get_outline(map).get_bounds().as_bbox());
-        }
-        for obj in &lanes {
-            quadtree.insert_with_box(obj.get_id(), obj.get_outline(map).get_bounds().as_bbox());
-        }
-        for obj in &intersections {
-            quadtree.insert_with_box(obj.get_id(), obj.get_outline(map).get_bounds().as_bbox());
-        }
-        for obj in &buildings {
-            quadtree.insert_with_box(obj.get_id(), obj.get_outline(map).get_bounds().as_bbox());
-        }
-        for obj in &extra_shapes {
-            quadtree.insert_with_box(obj.get_id(), obj.get_outline(map).get_bounds().as_bbox());
-        }
-        // Don't put BusStops in the quadtree
-        for obj in &areas {
-            quadtree.insert_with_box(obj.get_id(), obj.get_outline(map).get_bounds().as_bbox());
-        }
-        timer.stop("create quadtree");
-
         timer.note(format!(
             "static DrawMap consumes {} MB on the GPU",
             abstutil::prettyprint_usize(prerender.get_total_bytes_uploaded() / 1024 / 1024)
         ));
 
-        DrawMap {
+        let mut draw_map = DrawMap {
             roads,
             lanes,
             intersections,
@@ -232,8 +210,98 @@ impl DrawMap {
                 agents_per_on: HashMap::new(),
             }),
 
-            quadtree,
+            quadtree: QuadTree::default(map.get_bounds().as_bbox()),
+        };
+        timer.start("create quadtree");
+        draw_map.rebuild_quadtree(map);
+        timer.stop("create quadtree");
+        draw_map
+    }
+
+    fn rebuild_quadtree(&mut self, map: &Map) {
+        let mut quadtree = QuadTree::default(map.get_bounds().as_bbox());
+        // TODO use iter chain if everything was boxed as a renderable...
+        for obj in &self.roads {
+            quadtree.insert_with_box(obj.get_id(), obj.get_outline(map).get_bounds().as_bbox());
+        }
+        for obj in &self.lanes {
+            // Lanes removed by map edits are still around, but shouldn't be drawn or selected.
+            if map.is_lane_removed(obj.id) {
+                continue;
+            }
+            quadtree.insert_with_box(obj.get_id(), obj.get_outline(map).get_bounds().as_bbox());
+        }
+        for obj in &self.intersections {
+            quadtree.insert_with_box(obj.get_id(), obj.get_outline(map).get_bounds().as_bbox());
+        }
+        for obj in &self.buildings {
+            quadtree.insert_with_box(obj.get_id(), obj.get_outline(map).get_bounds().as_bbox());
+        }
+        for obj in &self.extra_shapes {
+            quadtree.insert_with_box(obj.get_id(), obj.get_outline(map).get_bounds().as_bbox());
         }
+        // Don't put BusStops in the quadtree
+        for obj in &self.areas {
+            quadtree.insert_with_box(obj.get_id(), obj.get_outline(map).get_bounds().as_bbox());
+        }
+        self.quadtree = quadtree;
+    }
+
+    // Map edits that add or remove lanes change the shape of roads and intersections, and move
+    // sidewalks. The caller is responsible for the lanes and turns.
+    pub fn recreate_geometry(
+        &mut self,
+        map: &Map,
+        roads: &BTreeSet<RoadID>,
+        intersections: &BTreeSet<IntersectionID>,
+        cs: &ColorScheme,
+        prerender: &Prerender,
+        timer: &mut Timer,
+    ) {
+        for r in roads {
+            self.roads[r.0] = DrawRoad::new(map.get_r(*r), cs, prerender);
+        }
+        for i in intersections {
+            self.intersections[i.0] =
+                DrawIntersection::new(map.get_i(*i), map, cs, prerender, timer);
+        }
+        for s in map.all_bus_stops().values() {
+            if roads.contains(&map.get_l(s.id.sidewalk).parent) {
+                self.bus_stops
+                    .insert(s.id, DrawBusStop::new(s, map, cs, prerender));
+            }
+        }
+
+        self.draw_all_thick_roads = prerender.upload(
+            map.all_roads()
+                .iter()
+                .map(|r| {
+                    (
+                        cs.get_def("unzoomed road band", Color::BLACK),
+                        r.get_thick_polygon().get(timer),
+                    )
+                })
+                .collect(),
+        );
+        self.draw_all_unzoomed_intersections = prerender.upload_borrowed(
+            self.intersections
+                .iter()
+                .map(|i| {
+                    (
+                        cs.get_def("unzoomed intersection", Color::BLACK),
+                        &i.polygon,
+                    )
+                })
+                .collect(),
+        );
+        // Front paths are baked into this
+        let mut all_buildings: Vec<(Color, Polygon)> = Vec::new();
+        for b in map.all_buildings() {
+            all_buildings.extend(DrawBuilding::new(b, cs).1);
+        }
+        self.draw_all_buildings = prerender.upload(all_buildings);
+
+        self.rebuild_quadtree(map);
     }
 
     pub fn compute_turn_to_lane_offset(result: &mut HashMap<TurnID, usize>, l: &Lane, map: &Map) {
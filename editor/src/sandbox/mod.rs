@@ -1,3 +1,4 @@
+mod replay;
 mod route_explorer;
 mod route_viewer;
 mod show_activity;
@@ -38,6 +39,7 @@ enum State {
     Spawning(spawner::AgentSpawner),
     TimeTraveling,
     ExploringRoute(route_explorer::RouteExplorer),
+    Replaying(replay::Replay),
 }
 
 impl SandboxMode {
@@ -48,7 +50,7 @@ impl SandboxMode {
             following: None,
             route_viewer: route_viewer::RouteViewer::Inactive,
             show_activity: show_activity::ShowActivity::Inactive,
-            time_travel: time_travel::TimeTravel::new(ctx.canvas),
+            time_travel: time_travel::TimeTravel::new(),
             common: CommonState::new(),
             menu: ModalMenu::hacky_new(
                 "Sandbox Mode",
@@ -71,6 +73,7 @@ impl SandboxMode {
                         (Some(Key::L), "show/hide route for all agents"),
                         (Some(Key::A), "show/hide active traffic"),
                         (Some(Key::T), "start time traveling"),
+                        (Some(Key::P), "replay a recorded run"),
                     ],
                     CommonState::modal_menu_entries(),
                 ]
@@ -103,6 +106,12 @@ impl SandboxMode {
                     }
                     return EventLoopMode::Animation;
                 }
+                if let State::Replaying(ref mut replay) = mode.state {
+                    if replay.event(ctx, &state.ui) {
+                        mode.state = State::Paused;
+                    }
+                    return EventLoopMode::Animation;
+                }
 
                 let mut txt = Text::prompt("Sandbox Mode");
                 txt.add_line(state.ui.primary.sim.summary());
@@ -198,7 +207,11 @@ impl SandboxMode {
                 mode.show_activity.event(ctx, &mut state.ui, &mut mode.menu);
                 if mode.menu.action("start time traveling") {
                     mode.state = State::TimeTraveling;
-                    mode.time_travel.start(&state.ui);
+                    mode.time_travel.start(ctx, &state.ui);
+                    return EventLoopMode::Animation;
+                }
+                if mode.menu.action("replay a recorded run") {
+                    mode.state = State::Replaying(replay::Replay::new());
                     return EventLoopMode::Animation;
                 }
 
@@ -233,10 +246,16 @@ impl SandboxMode {
                                 .primary
                                 .sim
                                 .find_previous_savestate(state.ui.primary.sim.time());
-                            match prev_state
-                                .clone()
-                                .and_then(|path| Sim::load_savestate(path, None).ok())
-                            {
+                            match prev_state.clone().and_then(|path| {
+                                Sim::load_savestate(
+                                    path,
+                                    None,
+                                    &state.ui.primary.map,
+                                    state.ui.primary.current_flags.sim_flags.rng_seed,
+                                )
+                                .map_err(|e| println!("{}", e))
+                                .ok()
+                            }) {
                                 Some(new_sim) => {
                                     state.ui.primary.sim = new_sim;
                                     //*ctx.recalculate_current_selection = true;
@@ -252,10 +271,16 @@ impl SandboxMode {
                                 .primary
                                 .sim
                                 .find_next_savestate(state.ui.primary.sim.time());
-                            match next_state
-                                .clone()
-                                .and_then(|path| Sim::load_savestate(path, None).ok())
-                            {
+                            match next_state.clone().and_then(|path| {
+                                Sim::load_savestate(
+                                    path,
+                                    None,
+                                    &state.ui.primary.map,
+                                    state.ui.primary.current_flags.sim_flags.rng_seed,
+                                )
+                                .map_err(|e| println!("{}", e))
+                                .ok()
+                            }) {
                                 Some(new_sim) => {
                                     state.ui.primary.sim = new_sim;
                                     //*ctx.recalculate_current_selection = true;
@@ -305,6 +330,7 @@ impl SandboxMode {
                     State::Spawning(_) => unreachable!(),
                     State::TimeTraveling => unreachable!(),
                     State::ExploringRoute(_) => unreachable!(),
+                    State::Replaying(_) => unreachable!(),
                 }
             }
             _ => unreachable!(),
@@ -318,13 +344,7 @@ impl SandboxMode {
                     spawner.draw(g, &state.ui);
                 }
                 State::TimeTraveling => {
-                    state.ui.draw(
-                        g,
-                        DrawOptions::new(),
-                        &mode.time_travel,
-                        &ShowEverything::new(),
-                    );
-                    mode.time_travel.draw(g);
+                    mode.time_travel.draw(g, &state.ui);
                 }
                 State::ExploringRoute(ref explorer) => {
                     state.ui.draw(
@@ -335,6 +355,9 @@ impl SandboxMode {
                     );
                     explorer.draw(g, &state.ui);
                 }
+                State::Replaying(ref replay) => {
+                    replay.draw(g, &state.ui);
+                }
                 _ => {
                     state.ui.draw(
                         g,
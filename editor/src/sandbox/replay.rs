@@ -0,0 +1,76 @@
+use crate::render::DrawOptions;
+use crate::sandbox::time_travel::Player;
+use crate::ui::{ShowEverything, UI};
+use ezgui::{EventCtx, GfxCtx, Wizard, WrappedWizard};
+use map_model::Map;
+use sim::Trajectories;
+
+// Plays back trajectories recorded by a headless run, like a video. Unlike time travel, the run
+// doesn't have to happen in the editor or fit in memory.
+pub enum Replay {
+    PickingRun(Wizard),
+    Playing(Player<Trajectories>),
+}
+
+impl Replay {
+    pub fn new() -> Replay {
+        Replay::PickingRun(Wizard::new())
+    }
+
+    // Returns true if done.
+    pub fn event(&mut self, ctx: &mut EventCtx, ui: &UI) -> bool {
+        match self {
+            Replay::PickingRun(ref mut wizard) => {
+                if let Some(dir) =
+                    pick_run(&ui.primary.map, &mut wizard.wrap(ctx.input, ctx.canvas))
+                {
+                    match Trajectories::load(&dir) {
+                        Ok(trajectories) => {
+                            let title = format!("Replay of {}", trajectories.header.run_name);
+                            *self = Replay::Playing(Player::new(&title, trajectories, ctx));
+                        }
+                        Err(e) => {
+                            println!("Couldn't load {}: {}", dir, e);
+                            return true;
+                        }
+                    }
+                } else if wizard.aborted() {
+                    return true;
+                }
+                false
+            }
+            Replay::Playing(ref mut player) => player.event(ctx),
+        }
+    }
+
+    pub fn draw(&self, g: &mut GfxCtx, ui: &UI) {
+        match self {
+            Replay::PickingRun(ref wizard) => {
+                ui.draw(
+                    g,
+                    DrawOptions::new(),
+                    &ui.primary.sim,
+                    &ShowEverything::new(),
+                );
+                wizard.draw(g);
+            }
+            Replay::Playing(ref player) => {
+                player.draw(g, ui);
+            }
+        }
+    }
+}
+
+// Only runs recorded on the current map with the current edits
+fn pick_run(map: &Map, wizard: &mut WrappedWizard) -> Option<String> {
+    let dir = format!("{}_{}", map.get_name(), map.get_edits().edits_name);
+    let (run_name, _) = wizard.choose_something_no_keys::<String>(
+        "Replay which run?",
+        Box::new(move || abstutil::list_all_objects("trajectories", &dir)),
+    )?;
+    Some(sim::trajectories_dir(
+        map.get_name(),
+        &map.get_edits().edits_name,
+        &run_name,
+    ))
+}
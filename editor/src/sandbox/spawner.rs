@@ -310,7 +310,7 @@ fn spawn_agents_around(i: IntersectionID, ui: &mut UI) {
                     map,
                 );
             }
-        } else if lane.is_sidewalk() {
+        } else if lane.lane_type.supports_walking() {
             for _ in 0..5 {
                 sim.schedule_trip(
                     sim.time(),
@@ -1,18 +1,25 @@
-use crate::ui::UI;
-use abstutil::MultiMap;
-use ezgui::{Canvas, EventCtx, GfxCtx, Key, ModalMenu, Text};
+use crate::render::DrawOptions;
+use crate::ui::{ShowEverything, UI};
+use abstutil::{elapsed_seconds, MultiMap};
+use ezgui::{EventCtx, GfxCtx, Key, ModalMenu, Text, Wizard, WrappedWizard};
 use geom::Duration;
 use map_model::{Map, Traversable};
-use sim::{CarID, DrawCarInput, DrawPedestrianInput, GetDrawAgents, PedestrianID};
+use sim::{CarID, DrawCarInput, DrawPedestrianInput, GetDrawAgents, PedestrianID, Trajectories};
 use std::collections::BTreeMap;
+use std::time::Instant;
 
 pub struct TimeTravel {
-    menu: ModalMenu,
-    state_per_time: Vec<StateAtTime>,
-    current_idx: Option<usize>,
+    memory: Memory,
+    // Owns the memory while time traveling
+    player: Option<Player<Memory>>,
     should_record: bool,
 }
 
+struct Memory {
+    state_per_time: Vec<StateAtTime>,
+    current_idx: usize,
+}
+
 struct StateAtTime {
     time: Duration,
     cars: BTreeMap<CarID, DrawCarInput>,
@@ -22,52 +29,52 @@ struct StateAtTime {
 }
 
 impl TimeTravel {
-    pub fn new(canvas: &Canvas) -> TimeTravel {
+    pub fn new() -> TimeTravel {
         TimeTravel {
-            state_per_time: Vec::new(),
-            current_idx: None,
+            memory: Memory {
+                state_per_time: Vec::new(),
+                current_idx: 0,
+            },
+            player: None,
             should_record: false,
-            menu: ModalMenu::hacky_new(
-                "Time Traveler",
-                vec![
-                    (Some(Key::Escape), "quit"),
-                    (Some(Key::Comma), "rewind"),
-                    (Some(Key::Dot), "forwards"),
-                ],
-                canvas,
-            ),
         }
     }
 
-    pub fn start(&mut self, ui: &UI) {
-        assert!(self.current_idx.is_none());
+    pub fn start(&mut self, ctx: &EventCtx, ui: &UI) {
+        assert!(self.player.is_none());
         self.should_record = true;
         // In case we weren't already...
         self.record(ui);
-        self.current_idx = Some(self.state_per_time.len() - 1);
+        let mut memory = std::mem::replace(
+            &mut self.memory,
+            Memory {
+                state_per_time: Vec::new(),
+                current_idx: 0,
+            },
+        );
+        memory.current_idx = memory.state_per_time.len() - 1;
+        self.player = Some(Player::new("Time Traveler", memory, ctx));
     }
 
     // TODO Now that we take big jumps forward in the source sim, the time traveler sees the same
     // granularity when replaying.
     pub fn record(&mut self, ui: &UI) {
-        if !self.should_record {
+        // The sim doesn't run while time traveling.
+        if !self.should_record || self.player.is_some() {
             return;
         }
         let map = &ui.primary.map;
         let sim = &ui.primary.sim;
         let now = sim.time();
 
-        if let Some(ref state) = self.state_per_time.last() {
+        if let Some(ref state) = self.memory.state_per_time.last() {
             // Already have this
             if now == state.time {
                 return;
             }
             // We just loaded a new savestate or reset or something. Clear out our memory.
             if now < state.time {
-                self.state_per_time.clear();
-                if self.current_idx.is_some() {
-                    self.current_idx = Some(0);
-                }
+                self.memory.state_per_time.clear();
             }
         }
 
@@ -86,46 +93,261 @@ impl TimeTravel {
             state.peds_per_traversable.insert(draw.on, draw.id);
             state.peds.insert(draw.id, draw);
         }
-        self.state_per_time.push(state);
+        self.memory.state_per_time.push(state);
     }
 
     // Returns true if done.
     pub fn event(&mut self, ctx: &mut EventCtx) -> bool {
-        let idx = self.current_idx.unwrap();
-        self.menu.handle_event(
-            ctx,
-            Some(Text::prompt(&format!("Time Traveler at {}", self.time()))),
-        );
+        if self.player.as_mut().unwrap().event(ctx) {
+            self.memory = self.player.take().unwrap().into_recording();
+            return true;
+        }
+        false
+    }
+
+    pub fn draw(&self, g: &mut GfxCtx, ui: &UI) {
+        self.player.as_ref().unwrap().draw(g, ui);
+    }
+}
+
+// Anything that can be played back like a video
+pub trait Recording: GetDrawAgents {
+    fn start_time(&self) -> Duration;
+    fn end_time(&self) -> Duration;
+    // Shows the last frame at or before this time.
+    fn seek(&mut self, time: Duration) -> Result<(), std::io::Error>;
+    fn prev_frame(&mut self) -> Result<(), std::io::Error>;
+    fn next_frame(&mut self) -> Result<(), std::io::Error>;
+}
+
+// Plays back a recording, starting from whatever frame it's at.
+pub struct Player<R: Recording> {
+    title: String,
+    menu: ModalMenu,
+    recording: R,
+    // Shows the last frame at or before this
+    time: Duration,
+    // Recording seconds per real second. Negative plays backwards.
+    speed: f64,
+    // Only while playing
+    last_step: Option<Instant>,
+    jumping: Option<Wizard>,
+}
 
+impl<R: Recording> Player<R> {
+    pub fn new(title: &str, recording: R, ctx: &EventCtx) -> Player<R> {
+        Player {
+            title: title.to_string(),
+            menu: ModalMenu::new(
+                title,
+                vec![
+                    (Some(Key::Escape), "quit"),
+                    (Some(Key::Space), "play/pause"),
+                    (Some(Key::LeftBracket), "slow down playback"),
+                    (Some(Key::RightBracket), "speed up playback"),
+                    (Some(Key::R), "reverse playback"),
+                    (Some(Key::Comma), "step backwards"),
+                    (Some(Key::Dot), "step forwards"),
+                    (Some(Key::J), "jump to time"),
+                ],
+                ctx,
+            ),
+            time: recording.time(),
+            recording,
+            speed: 1.0,
+            last_step: None,
+            jumping: None,
+        }
+    }
+
+    pub fn into_recording(self) -> R {
+        self.recording
+    }
+
+    // Returns true if done.
+    pub fn event(&mut self, ctx: &mut EventCtx) -> bool {
+        if let Some(ref mut wizard) = self.jumping {
+            if let Some(time) = input_time(&mut wizard.wrap(ctx.input, ctx.canvas)) {
+                self.jumping = None;
+                self.seek(time);
+            } else if wizard.aborted() {
+                self.jumping = None;
+            }
+            return false;
+        }
+
+        let mut txt = Text::prompt(&self.title);
+        txt.add_line(format!("At {}", self.recording.time()));
+        txt.add_line(format!(
+            "Recorded from {} to {}",
+            self.recording.start_time(),
+            self.recording.end_time()
+        ));
+        if self.last_step.is_some() {
+            txt.add_line(format!("Playing at {}x", self.speed));
+        } else {
+            txt.add_line(format!("Paused, will play at {}x", self.speed));
+        }
+        self.menu.handle_event(ctx, Some(txt));
         ctx.canvas.handle_event(ctx.input);
 
-        if idx > 0 && self.menu.action("rewind") {
-            self.current_idx = Some(idx - 1);
-        } else if idx != self.state_per_time.len() - 1 && self.menu.action("forwards") {
-            self.current_idx = Some(idx + 1);
-        } else if self.menu.action("quit") {
-            self.current_idx = None;
+        if self.menu.action("quit") {
             return true;
         }
+        if self.menu.action("play/pause") {
+            self.last_step = if self.last_step.is_some() {
+                None
+            } else {
+                Some(Instant::now())
+            };
+        }
+        if self.menu.action("slow down playback") {
+            self.speed /= 2.0;
+        }
+        if self.menu.action("speed up playback") {
+            self.speed *= 2.0;
+        }
+        if self.menu.action("reverse playback") {
+            self.speed = -self.speed;
+        }
+        if self.menu.action("step backwards") {
+            self.last_step = None;
+            if let Err(e) = self.recording.prev_frame() {
+                println!("Couldn't load the previous frame: {}", e);
+            }
+            self.time = self.recording.time();
+        }
+        if self.menu.action("step forwards") {
+            self.last_step = None;
+            if let Err(e) = self.recording.next_frame() {
+                println!("Couldn't load the next frame: {}", e);
+            }
+            self.time = self.recording.time();
+        }
+        if self.menu.action("jump to time") {
+            self.last_step = None;
+            self.jumping = Some(Wizard::new());
+        }
+
+        if let Some(last_step) = self.last_step {
+            if ctx.input.nonblocking_is_update_event() {
+                ctx.input.use_update_event();
+                let dt = Duration::seconds(elapsed_seconds(last_step)) * self.speed;
+                self.last_step = Some(Instant::now());
+                self.seek(self.time + dt);
+            }
+        }
         false
     }
 
-    pub fn draw(&self, g: &mut GfxCtx) {
-        self.menu.draw(g);
+    pub fn draw(&self, g: &mut GfxCtx, ui: &UI) {
+        ui.draw(
+            g,
+            DrawOptions::new(),
+            &self.recording,
+            &ShowEverything::new(),
+        );
+        if let Some(ref wizard) = self.jumping {
+            wizard.draw(g);
+        } else {
+            self.menu.draw(g);
+        }
+    }
+
+    // Pauses when going past either end of the recording.
+    fn seek(&mut self, time: Duration) {
+        self.time = if time < self.recording.start_time() {
+            self.last_step = None;
+            self.recording.start_time()
+        } else if time > self.recording.end_time() {
+            self.last_step = None;
+            self.recording.end_time()
+        } else {
+            time
+        };
+        if let Err(e) = self.recording.seek(self.time) {
+            println!("Couldn't load the frame at {}: {}", self.time, e);
+            self.last_step = None;
+        }
+    }
+}
+
+fn input_time(wizard: &mut WrappedWizard) -> Option<Duration> {
+    wizard.input_something(
+        "Jump to what time?",
+        None,
+        Box::new(|line| Duration::parse(&line)),
+    )
+}
+
+impl Recording for Trajectories {
+    fn start_time(&self) -> Duration {
+        Trajectories::start_time(self)
+    }
+
+    fn end_time(&self) -> Duration {
+        Trajectories::end_time(self)
+    }
+
+    fn seek(&mut self, time: Duration) -> Result<(), std::io::Error> {
+        Trajectories::seek(self, time)
     }
 
+    fn prev_frame(&mut self) -> Result<(), std::io::Error> {
+        Trajectories::prev_frame(self)
+    }
+
+    fn next_frame(&mut self) -> Result<(), std::io::Error> {
+        Trajectories::next_frame(self)
+    }
+}
+
+impl Recording for Memory {
+    fn start_time(&self) -> Duration {
+        self.state_per_time[0].time
+    }
+
+    fn end_time(&self) -> Duration {
+        self.state_per_time.last().unwrap().time
+    }
+
+    fn seek(&mut self, time: Duration) -> Result<(), std::io::Error> {
+        self.current_idx = self
+            .state_per_time
+            .iter()
+            .rposition(|state| state.time <= time)
+            .unwrap_or(0);
+        Ok(())
+    }
+
+    fn prev_frame(&mut self) -> Result<(), std::io::Error> {
+        if self.current_idx > 0 {
+            self.current_idx -= 1;
+        }
+        Ok(())
+    }
+
+    fn next_frame(&mut self) -> Result<(), std::io::Error> {
+        if self.current_idx + 1 < self.state_per_time.len() {
+            self.current_idx += 1;
+        }
+        Ok(())
+    }
+}
+
+impl Memory {
     fn get_current_state(&self) -> &StateAtTime {
-        &self.state_per_time[self.current_idx.unwrap()]
+        &self.state_per_time[self.current_idx]
     }
 }
 
-impl GetDrawAgents for TimeTravel {
+impl GetDrawAgents for Memory {
     fn time(&self) -> Duration {
-        self.state_per_time[self.current_idx.unwrap()].time
+        self.get_current_state().time
     }
 
     fn step_count(&self) -> usize {
-        self.current_idx.unwrap()
+        self.current_idx
     }
 
     fn get_draw_car(&self, id: CarID, _map: &Map) -> Option<DrawCarInput> {
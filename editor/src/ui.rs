@@ -1,3 +1,4 @@
+use crate::edit::EditHistory;
 use crate::helpers::{ColorScheme, ID};
 use crate::render::{
     draw_vehicle, AgentCache, DrawCtx, DrawMap, DrawOptions, DrawPedestrian, Renderable,
@@ -408,6 +409,7 @@ pub struct PerMapUI {
 
     pub current_selection: Option<ID>,
     pub current_flags: Flags,
+    pub edit_history: EditHistory,
 }
 
 impl PerMapUI {
@@ -431,6 +433,7 @@ impl PerMapUI {
             sim,
             current_selection: None,
             current_flags: flags.clone(),
+            edit_history: EditHistory::new(),
         }
     }
 
@@ -440,6 +443,7 @@ impl PerMapUI {
             &self.map,
             self.current_flags.sim_flags.run_name.clone(),
             None,
+            self.current_flags.sim_flags.rng_seed,
         );
     }
 }
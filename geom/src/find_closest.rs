@@ -37,8 +37,28 @@ where
             .insert_with_box(key, Bounds::from(&pts).as_bbox());
     }
 
+    pub fn add_pt(&mut self, key: K, pt: Pt2D) {
+        self.geometries
+            .insert(key.clone(), pts_to_line_string(&vec![pt]));
+        let mut bounds = Bounds::new();
+        bounds.update(pt);
+        self.quadtree.insert_with_box(key, bounds.as_bbox());
+    }
+
     // Finds the closest point on the existing geometry to the query pt.
     pub fn closest_pt(&self, query_pt: Pt2D, max_dist_away: Distance) -> Option<(K, Pt2D)> {
+        self.all_close_pts(query_pt, max_dist_away)
+            .into_iter()
+            .next()
+            .map(|(key, pt, _)| (key, pt))
+    }
+
+    // Every key with geometry within max_dist_away of the query pt, closest first.
+    pub fn all_close_pts(
+        &self,
+        query_pt: Pt2D,
+        max_dist_away: Distance,
+    ) -> Vec<(K, Pt2D, Distance)> {
         let query_geom = geo::Point::new(query_pt.x(), query_pt.y());
         let query_bbox = Rect {
             top_left: Point {
@@ -51,16 +71,22 @@ where
             },
         };
 
-        self.quadtree
+        let mut results: Vec<(K, Pt2D, Distance)> = self
+            .quadtree
             .query(query_bbox)
             .into_iter()
             .filter_map(|(key, _, _)| {
-                if let geo::Closest::SinglePoint(pt) =
-                    self.geometries[&key].closest_point(&query_geom)
-                {
+                let geometry = &self.geometries[&key];
+                // A single point has no lines to be closest to.
+                let closest = if geometry.0.len() == 1 {
+                    geo::Closest::SinglePoint(geometry.0[0].into())
+                } else {
+                    geometry.closest_point(&query_geom)
+                };
+                if let geo::Closest::SinglePoint(pt) = closest {
                     let dist = Distance::meters(pt.euclidean_distance(&query_geom));
                     if dist <= max_dist_away {
-                        Some((key, pt, dist))
+                        Some((key.clone(), Pt2D::new(pt.x(), pt.y()), dist))
                     } else {
                         None
                     }
@@ -68,8 +94,9 @@ where
                     None
                 }
             })
-            .min_by_key(|(_, _, dist)| *dist)
-            .map(|(key, pt, _)| (key.clone(), Pt2D::new(pt.x(), pt.y())))
+            .collect();
+        results.sort_by_key(|(_, _, dist)| *dist);
+        results
     }
 }
 
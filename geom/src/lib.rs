@@ -19,7 +19,7 @@ pub use crate::duration::Duration;
 pub use crate::find_closest::FindClosest;
 pub use crate::gps::LonLat;
 pub use crate::line::{InfiniteLine, Line};
-pub use crate::polygon::{Polygon, Triangle};
+pub use crate::polygon::{ring_contains_pt, Polygon, Triangle};
 pub use crate::polyline::PolyLine;
 pub use crate::pt::{HashablePt2D, Pt2D};
 pub use crate::speed::Speed;
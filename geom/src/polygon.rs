@@ -1,13 +1,16 @@
-use crate::{Bounds, HashablePt2D, Pt2D};
+use crate::{Bounds, HashablePt2D, Line, Pt2D};
 use serde_derive::{Deserialize, Serialize};
 use std::f64;
 
 #[derive(Serialize, Deserialize, Clone, Debug)]
 pub struct Polygon {
+    // The outer ring, followed by any inner rings
     points: Vec<Pt2D>,
     // Groups of three indices make up the triangles
     // TODO u32 better for later, but then we can't index stuff!
     indices: Vec<usize>,
+    // Where each inner ring starts in points
+    holes: Vec<usize>,
 }
 
 // TODO The triangulation is a bit of a mess. Everything except for Polygon::new comes from
@@ -15,78 +18,113 @@ pub struct Polygon {
 
 impl Polygon {
     // TODO Should the first and last points match or not?
-    // Adapted from https://crates.io/crates/polygon2; couldn't use the crate directly because it
-    // depends on nightly.
     pub fn new(orig_pts: &Vec<Pt2D>) -> Polygon {
+        Polygon::with_holes(orig_pts, &Vec::new())
+    }
+
+    // The inner rings can be closed or not. They're cut into the outer ring by bridging each one
+    // to a visible point on the outside, then the whole thing is triangulated as one ring.
+    pub fn with_holes(orig_pts: &Vec<Pt2D>, orig_holes: &Vec<Vec<Pt2D>>) -> Polygon {
         assert!(orig_pts.len() >= 3);
 
-        let pts = if is_clockwise_polygon(orig_pts) {
+        let mut pts = if is_clockwise_polygon(orig_pts) {
             let mut new_pts = orig_pts.clone();
             new_pts.reverse();
             new_pts
         } else {
             orig_pts.clone()
         };
+        let mut ring: Vec<usize> = (0..pts.len()).collect();
 
-        let mut indices: Vec<usize> = Vec::new();
-        let mut avl = Vec::with_capacity(pts.len());
-        for i in 0..pts.len() {
-            avl.push(i);
-        }
-
-        let mut i = 0;
-        let mut al = pts.len();
-        while al > 3 {
-            let i0 = avl[i % al];
-            let i1 = avl[(i + 1) % al];
-            let i2 = avl[(i + 2) % al];
-
-            let tri = Triangle::new(pts[i0], pts[i1], pts[i2]);
-            let mut ear_found = false;
-            if tri.is_convex() {
-                ear_found = true;
-
-                for vi in avl.iter().take(al) {
-                    if *vi != i0 && *vi != i1 && *vi != i2 && tri.contains_pt(pts[*vi]) {
-                        ear_found = false;
-                        break;
-                    }
-                }
+        // Inner rings have to wind the opposite way from the outer ring.
+        let mut holes: Vec<usize> = Vec::new();
+        let mut hole_rings: Vec<Vec<usize>> = Vec::new();
+        for orig_hole in orig_holes {
+            let mut hole = orig_hole.clone();
+            if hole.len() > 1 && hole[0] == *hole.last().unwrap() {
+                hole.pop();
             }
-
-            if ear_found {
-                indices.push(i0);
-                indices.push(i1);
-                indices.push(i2);
-                avl.remove((i + 1) % al);
-                al -= 1;
-                i = 0;
-            } else if i > 3 * al {
-                break;
-            } else {
-                i += 1;
+            if hole.len() < 3 {
+                continue;
             }
+            if !is_clockwise_polygon(&hole) {
+                hole.reverse();
+            }
+            holes.push(pts.len());
+            hole_rings.push((pts.len()..pts.len() + hole.len()).collect());
+            pts.extend(hole);
         }
 
-        indices.push(avl[0]);
-        indices.push(avl[1]);
-        indices.push(avl[2]);
+        // Bridge the rightmost holes first, so that later bridges are less likely to cross them.
+        let rightmost = |hole: &Vec<usize>| -> usize {
+            *hole
+                .iter()
+                .max_by(|a, b| pts[**a].x().partial_cmp(&pts[**b].x()).unwrap())
+                .unwrap()
+        };
+        let mut bridge_order: Vec<usize> = (0..hole_rings.len()).collect();
+        bridge_order.sort_by(|a, b| {
+            pts[rightmost(&hole_rings[*b])]
+                .x()
+                .partial_cmp(&pts[rightmost(&hole_rings[*a])].x())
+                .unwrap()
+        });
+        for idx in bridge_order {
+            let hole = &hole_rings[idx];
+            let from = rightmost(hole);
+            let mut edges: Vec<(usize, usize)> = ring_edges(&ring);
+            for other in &hole_rings {
+                edges.extend(ring_edges(other));
+            }
+
+            let mut candidates: Vec<usize> = (0..ring.len()).collect();
+            candidates.sort_by(|a, b| {
+                pts[from]
+                    .dist_to(pts[ring[*a]])
+                    .cmp(&pts[from].dist_to(pts[ring[*b]]))
+            });
+            // If nothing is visible, the input is probably broken. Just use the closest point.
+            let at = candidates
+                .iter()
+                .find(|pos| bridge_is_clear(&pts, &edges, from, ring[**pos]))
+                .cloned()
+                .unwrap_or(candidates[0]);
+
+            // ... to, from, around the hole, from, to, ...
+            let start = hole.iter().position(|i| *i == from).unwrap();
+            let mut splice: Vec<usize> = hole[start..]
+                .iter()
+                .chain(hole[..start].iter())
+                .cloned()
+                .collect();
+            splice.push(from);
+            splice.push(ring[at]);
+            let rest = ring.split_off(at + 1);
+            ring.extend(splice);
+            ring.extend(rest);
+        }
 
         Polygon {
+            indices: earclip(&pts, ring),
             points: pts,
-            indices,
+            holes,
         }
     }
 
     pub fn precomputed(points: Vec<Pt2D>, indices: Vec<usize>) -> Polygon {
         assert!(indices.len() % 3 == 0);
-        Polygon { points, indices }
+        Polygon {
+            points,
+            indices,
+            holes: Vec::new(),
+        }
     }
 
     pub fn from_triangle(tri: &Triangle) -> Polygon {
         Polygon {
             points: vec![tri.pt1, tri.pt2, tri.pt3],
             indices: vec![0, 1, 2],
+            holes: Vec::new(),
         }
     }
 
@@ -107,27 +145,60 @@ impl Polygon {
     }
 
     pub fn contains_pt(&self, pt: Pt2D) -> bool {
+        // The triangles shouldn't cover the holes, but triangulation isn't always perfect.
         self.triangles().into_iter().any(|tri| tri.contains_pt(pt))
+            && !self
+                .inner_rings()
+                .into_iter()
+                .any(|ring| ring_contains_pt(ring, pt))
     }
 
     pub fn get_bounds(&self) -> Bounds {
         Bounds::from(&self.points)
     }
 
+    // In square meters, not counting holes
+    pub fn area(&self) -> f64 {
+        self.triangles().into_iter().map(|tri| tri.area()).sum()
+    }
+
     pub fn translate(&self, dx: f64, dy: f64) -> Polygon {
         Polygon {
             points: self.points.iter().map(|pt| pt.offset(dx, dy)).collect(),
             indices: self.indices.clone(),
+            holes: self.holes.clone(),
         }
     }
 
+    // Includes the points of any inner rings at the end.
     pub fn points(&self) -> &Vec<Pt2D> {
         &self.points
     }
 
+    pub fn outer_ring(&self) -> &[Pt2D] {
+        match self.holes.first() {
+            Some(end) => &self.points[..*end],
+            None => &self.points,
+        }
+    }
+
+    // Not closed
+    pub fn inner_rings(&self) -> Vec<&[Pt2D]> {
+        let mut rings = Vec::new();
+        for (idx, start) in self.holes.iter().enumerate() {
+            let end = self
+                .holes
+                .get(idx + 1)
+                .cloned()
+                .unwrap_or(self.points.len());
+            rings.push(&self.points[*start..end]);
+        }
+        rings
+    }
+
     pub fn center(&self) -> Pt2D {
         // TODO dedupe just out of fear of the first/last point being repeated
-        let mut pts: Vec<HashablePt2D> = self.points.iter().map(|pt| (*pt).into()).collect();
+        let mut pts: Vec<HashablePt2D> = self.outer_ring().iter().map(|pt| (*pt).into()).collect();
         pts.sort();
         pts.dedup();
         Pt2D::center(&pts.iter().map(|pt| Pt2D::from(*pt)).collect())
@@ -146,6 +217,7 @@ impl Polygon {
                 top_left.offset(0.0, height),
             ],
             indices: vec![0, 1, 2, 2, 3, 0],
+            holes: Vec::new(),
         }
     }
 }
@@ -162,6 +234,13 @@ impl Triangle {
         Triangle { pt1, pt2, pt3 }
     }
 
+    pub fn area(&self) -> f64 {
+        ((self.pt2.x() - self.pt1.x()) * (self.pt3.y() - self.pt1.y())
+            - (self.pt2.y() - self.pt1.y()) * (self.pt3.x() - self.pt1.x()))
+        .abs()
+            / 2.0
+    }
+
     fn is_convex(&self) -> bool {
         let x1 = self.pt1.x();
         let y1 = self.pt1.y();
@@ -210,3 +289,91 @@ fn is_clockwise_polygon(pts: &Vec<Pt2D>) -> bool {
     }
     sum > 0.0
 }
+
+// Adapted from https://crates.io/crates/polygon2; couldn't use the crate directly because it
+// depends on nightly. Works over a ring of indices into pts, which can visit the same point more
+// than once.
+fn earclip(pts: &Vec<Pt2D>, mut avl: Vec<usize>) -> Vec<usize> {
+    let mut indices: Vec<usize> = Vec::new();
+    let mut i = 0;
+    let mut al = avl.len();
+    while al > 3 {
+        let i0 = avl[i % al];
+        let i1 = avl[(i + 1) % al];
+        let i2 = avl[(i + 2) % al];
+
+        let tri = Triangle::new(pts[i0], pts[i1], pts[i2]);
+        let mut ear_found = false;
+        if tri.is_convex() {
+            ear_found = true;
+
+            for vi in avl.iter().take(al) {
+                if *vi != i0 && *vi != i1 && *vi != i2 && tri.contains_pt(pts[*vi]) {
+                    ear_found = false;
+                    break;
+                }
+            }
+        }
+
+        if ear_found {
+            indices.push(i0);
+            indices.push(i1);
+            indices.push(i2);
+            avl.remove((i + 1) % al);
+            al -= 1;
+            i = 0;
+        } else if i > 3 * al {
+            break;
+        } else {
+            i += 1;
+        }
+    }
+
+    indices.push(avl[0]);
+    indices.push(avl[1]);
+    indices.push(avl[2]);
+    indices
+}
+
+fn ring_edges(ring: &Vec<usize>) -> Vec<(usize, usize)> {
+    let mut edges: Vec<(usize, usize)> = ring.windows(2).map(|pair| (pair[0], pair[1])).collect();
+    edges.push((*ring.last().unwrap(), ring[0]));
+    edges
+}
+
+// Does the line between two points cross any edge? Edges touching either end don't count.
+fn bridge_is_clear(pts: &Vec<Pt2D>, edges: &Vec<(usize, usize)>, from: usize, to: usize) -> bool {
+    let bridge = match Line::maybe_new(pts[from], pts[to]) {
+        Some(l) => l,
+        None => {
+            return false;
+        }
+    };
+    for (a, b) in edges {
+        if [*a, *b]
+            .iter()
+            .any(|i| pts[*i].epsilon_eq(pts[from]) || pts[*i].epsilon_eq(pts[to]))
+        {
+            continue;
+        }
+        if let Some(edge) = Line::maybe_new(pts[*a], pts[*b]) {
+            if bridge.intersection(&edge).is_some() {
+                return false;
+            }
+        }
+    }
+    true
+}
+
+// Even-odd rule. The ring can be closed or not.
+pub fn ring_contains_pt(ring: &[Pt2D], pt: Pt2D) -> bool {
+    let mut inside = false;
+    for (p1, p2) in ring.iter().zip(ring.iter().cycle().skip(1)) {
+        if (p1.y() > pt.y()) != (p2.y() > pt.y())
+            && pt.x() < (p2.x() - p1.x()) * (pt.y() - p1.y()) / (p2.y() - p1.y()) + p1.x()
+        {
+            inside = !inside;
+        }
+    }
+    inside
+}
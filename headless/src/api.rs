@@ -0,0 +1,281 @@
+// A local control API, so scripts can drive a simulation without the GUI. On Unix, serve()
+// listens on a socket and handles one connection at a time. Each line sent is a JSON Request, and
+// each line sent back is the JSON Result of handling it. Everything happens on this thread in the
+// order requests arrive, so with a fixed --rng_seed, the same requests always produce the same
+// results.
+
+use abstutil::Timer;
+use geom::{Duration, Pt2D};
+use map_model::{IntersectionID, Map, PermanentMapEdits, Traversable, TurnID};
+use rand_xorshift::XorShiftRng;
+use serde_derive::{Deserialize, Serialize};
+use sim::{
+    AgentID, CarID, GetDrawAgents, IntersectionStats, PedestrianID, Scenario, Sim, TripID, TripSpec,
+};
+#[cfg(unix)]
+use std::io::{BufRead, BufReader, Write};
+#[cfg(unix)]
+use std::os::unix::fs::FileTypeExt;
+#[cfg(unix)]
+use std::os::unix::net::{UnixListener, UnixStream};
+
+#[derive(Deserialize, Debug)]
+pub enum Request {
+    GetTime,
+    // Advance the simulation by this much, which can't be negative. If writing a recording fails
+    // meanwhile, the simulation still advances, but the recording stops and the error is returned.
+    Step(Duration),
+    // Advance the simulation until this time. Recording failures are handled like Step.
+    RunUntil(Duration),
+    // Path to a scenario for this map. Starts over with a fresh simulation.
+    LoadScenario(String),
+    // Edits that change any lanes or roads start over with a fresh simulation, since the old one
+    // only knows about the old ones. Other edits apply to the running simulation.
+    ApplyEdits(PermanentMapEdits),
+    // Each trip starts at the given time, which can't be in the past
+    SpawnTrips(Vec<(Duration, TripSpec)>),
+    GetAgents,
+    GetTrips,
+    GetIntersection(IntersectionID),
+    // Delays and queue lengths so far
+    GetIntersectionStats(IntersectionID),
+    // Stop the server
+    Quit,
+}
+
+#[derive(Serialize, Debug)]
+pub enum Response {
+    Time(Duration),
+    EditsApplied {
+        sim_reset: bool,
+    },
+    Spawned(Vec<(Option<PedestrianID>, Option<CarID>)>),
+    Agents(Vec<AgentPosition>),
+    // When each trip started and finished
+    Trips(Vec<(TripID, Duration, Option<Duration>)>),
+    Intersection {
+        accepted: Vec<AgentID>,
+        waiting: Vec<(AgentID, TurnID)>,
+        in_overtime: bool,
+    },
+    IntersectionStats(IntersectionStats),
+}
+
+#[derive(Serialize, Debug)]
+pub struct AgentPosition {
+    pub id: AgentID,
+    pub trip: Option<TripID>,
+    // The front of cars
+    pub pos: Pt2D,
+    pub on: Traversable,
+}
+
+pub struct Server {
+    map: Map,
+    sim: Sim,
+    rng: XorShiftRng,
+    run_name: String,
+    rng_seed: Option<u8>,
+}
+
+#[cfg(unix)]
+pub fn serve(socket_path: &str, map: Map, sim: Sim, rng: XorShiftRng) {
+    // A previous server that didn't shut down cleanly leaves its socket behind. Don't clobber
+    // anything else, though.
+    if let Ok(metadata) = std::fs::symlink_metadata(socket_path) {
+        if !metadata.file_type().is_socket() {
+            panic!("{} already exists and isn't a socket", socket_path);
+        }
+        if let Err(e) = std::fs::remove_file(socket_path) {
+            panic!("Couldn't remove old socket {}: {}", socket_path, e);
+        }
+    }
+    let listener = match UnixListener::bind(socket_path) {
+        Ok(l) => l,
+        Err(e) => panic!("Couldn't listen on {}: {}", socket_path, e),
+    };
+    println!("Listening on {}", socket_path);
+
+    let mut server = Server::new(map, sim, rng);
+    for stream in listener.incoming() {
+        match stream {
+            Ok(stream) => {
+                if !server.handle_connection(stream) {
+                    break;
+                }
+            }
+            Err(e) => println!("Connection failed: {}", e),
+        }
+    }
+    if let Err(e) = std::fs::remove_file(socket_path) {
+        println!("Couldn't clean up {}: {}", socket_path, e);
+    }
+}
+
+impl Server {
+    pub fn new(map: Map, sim: Sim, rng: XorShiftRng) -> Server {
+        Server {
+            map,
+            run_name: sim.get_name().to_string(),
+            rng_seed: sim.get_rng_seed(),
+            sim,
+            rng,
+        }
+    }
+
+    // Returns false when the client asks to quit.
+    #[cfg(unix)]
+    fn handle_connection(&mut self, stream: UnixStream) -> bool {
+        let mut writer = stream.try_clone().unwrap();
+        for line in BufReader::new(stream).lines() {
+            let line = match line {
+                Ok(l) => l,
+                Err(e) => {
+                    println!("Connection broke: {}", e);
+                    return true;
+                }
+            };
+            if line.trim().is_empty() {
+                continue;
+            }
+            let result = match serde_json::from_str::<Request>(&line) {
+                Ok(Request::Quit) => {
+                    return false;
+                }
+                Ok(req) => self.handle(req),
+                Err(e) => Err(format!("Bad request: {}", e)),
+            };
+            let mut response = serde_json::to_string(&result).unwrap();
+            response.push('\n');
+            if let Err(e) = writer.write_all(response.as_bytes()) {
+                println!("Couldn't respond: {}", e);
+                return true;
+            }
+        }
+        true
+    }
+
+    // Quit only means something to a connection; here it just returns the time.
+    pub fn handle(&mut self, req: Request) -> Result<Response, String> {
+        match req {
+            Request::GetTime => {}
+            Request::Step(dt) => {
+                if dt < Duration::ZERO {
+                    return Err(format!("Can't step backwards by {}", dt));
+                }
+                self.sim.step(&self.map, dt);
+            }
+            Request::RunUntil(time) => {
+                if time < self.sim.time() {
+                    return Err(format!("It's already {}", self.sim.time()));
+                }
+                let dt = time - self.sim.time();
+                self.sim.step(&self.map, dt);
+            }
+            Request::LoadScenario(path) => {
+                let scenario: Scenario = abstutil::read_json(&path).map_err(|e| e.to_string())?;
+                if scenario.map_name != *self.map.get_name() {
+                    return Err(format!(
+                        "{} is for map {}, not {}",
+                        path,
+                        scenario.map_name,
+                        self.map.get_name()
+                    ));
+                }
+                let mut timer = Timer::throwaway();
+                self.sim = Sim::new(&self.map, self.run_name.clone(), None, self.rng_seed);
+                scenario.instantiate(&mut self.sim, &self.map, &mut self.rng, &mut timer);
+            }
+            Request::ApplyEdits(perma) => {
+                if perma.map_name != *self.map.get_name() {
+                    return Err(format!(
+                        "Edits {} are for map {}, not {}",
+                        perma.edits_name,
+                        perma.map_name,
+                        self.map.get_name()
+                    ));
+                }
+                let mut timer = Timer::new("apply map edits");
+                // Anything that doesn't match this map gets dropped with a warning.
+                let edits = perma.from_permanent(&self.map).get(&mut timer);
+                let lanes_changed = edits.lane_overrides != self.map.get_edits().lane_overrides;
+                let effects = self.map.apply_edits(edits, &mut timer);
+                // Like the editor, keep running through changes to intersections and closures.
+                let sim_reset = lanes_changed || !effects.changed_roads.is_empty();
+                if sim_reset {
+                    self.sim = Sim::new(&self.map, self.run_name.clone(), None, self.rng_seed);
+                } else {
+                    self.sim
+                        .handle_intersection_edits(&effects.changed_intersections, &self.map);
+                }
+                return Ok(Response::EditsApplied { sim_reset });
+            }
+            Request::SpawnTrips(trips) => {
+                self.sim.validate_trips(&trips, &self.map)?;
+                let ids = trips
+                    .into_iter()
+                    .map(|(time, spec)| self.sim.schedule_trip(time, spec, &self.map))
+                    .collect();
+                self.sim.spawn_all_trips(&self.map, &mut Timer::throwaway());
+                return Ok(Response::Spawned(ids));
+            }
+            Request::GetAgents => {
+                let mut agents = Vec::new();
+                for car in self.sim.get_all_draw_cars(&self.map) {
+                    let id = AgentID::Car(car.id);
+                    agents.push(AgentPosition {
+                        id,
+                        trip: self.sim.agent_to_trip(id),
+                        pos: car.body.last_pt(),
+                        on: car.on,
+                    });
+                }
+                for ped in self.sim.get_all_draw_peds(&self.map) {
+                    let id = AgentID::Pedestrian(ped.id);
+                    agents.push(AgentPosition {
+                        id,
+                        trip: self.sim.agent_to_trip(id),
+                        pos: ped.pos,
+                        on: ped.on,
+                    });
+                }
+                return Ok(Response::Agents(agents));
+            }
+            Request::GetTrips => {
+                return Ok(Response::Trips(self.sim.get_all_trip_times()));
+            }
+            Request::GetIntersection(id) => {
+                if self.map.maybe_get_i(id).is_none() {
+                    return Err(format!("{} doesn't exist", id));
+                }
+                // Sorted, so responses don't depend on hashing
+                let mut accepted: Vec<AgentID> =
+                    self.sim.get_accepted_agents(id).into_iter().collect();
+                accepted.sort();
+                return Ok(Response::Intersection {
+                    accepted,
+                    waiting: self.sim.get_waiting_agents(id),
+                    in_overtime: self.sim.is_in_overtime(id, &self.map),
+                });
+            }
+            Request::GetIntersectionStats(id) => {
+                if self.map.maybe_get_i(id).is_none() {
+                    return Err(format!("{} doesn't exist", id));
+                }
+                return Ok(Response::IntersectionStats(
+                    self.sim.get_intersection_stats(id),
+                ));
+            }
+            Request::Quit => {}
+        }
+        let errors = self.sim.take_recording_errors();
+        if !errors.is_empty() {
+            return Err(format!(
+                "At {}, stopped recording: {}",
+                self.sim.time(),
+                errors.join("; ")
+            ));
+        }
+        Ok(Response::Time(self.sim.time()))
+    }
+}
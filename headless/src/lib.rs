@@ -0,0 +1,2 @@
+// The control API lives in a library, so the tests can drive a Server without a socket.
+pub mod api;
@@ -1,6 +1,6 @@
 use abstutil::Timer;
 use geom::Duration;
-use sim::{GetDrawAgents, Scenario, SimFlags};
+use sim::{GetDrawAgents, Scenario, SimFlags, TourParams};
 use std::path::Path;
 use structopt::StructOpt;
 
@@ -18,6 +18,10 @@ struct Flags {
     #[structopt(long = "num_agents")]
     num_agents: Option<usize>,
 
+    /// Generate home-based daily tours for residents, instead of random trips
+    #[structopt(long = "tours")]
+    tours: bool,
+
     /// Enable cpuprofiler?
     #[structopt(long = "enable_profiler")]
     enable_profiler: bool,
@@ -25,6 +29,24 @@ struct Flags {
     /// Every 0.1s, pretend to draw everything to make sure there are no bugs.
     #[structopt(long = "paranoia")]
     paranoia: bool,
+
+    /// Append every event to this file while running
+    #[structopt(long = "record_events")]
+    record_events: Option<String>,
+
+    /// Record where every agent is this often (like "1.0" for every second), to replay in the
+    /// editor
+    #[structopt(long = "record_trajectories")]
+    record_trajectories: Option<String>,
+
+    /// When done, write delay and queue stats for every intersection to a JSON file
+    #[structopt(long = "intersection_stats")]
+    intersection_stats: bool,
+
+    /// Instead of running until done, listen on this Unix socket for requests. See api.rs.
+    #[cfg(unix)]
+    #[structopt(long = "serve")]
+    serve: Option<String>,
 }
 
 fn main() {
@@ -45,10 +67,19 @@ fn main() {
     let mut timer = Timer::new("setup headless");
     let (map, mut sim, mut rng) = flags.sim_flags.load(None, &mut timer);
 
-    if load.starts_with(Path::new("../data/raw_maps/"))
-        || load.starts_with(Path::new("../data/maps/"))
+    #[cfg(unix)]
+    let serving = flags.serve.is_some();
+    #[cfg(not(unix))]
+    let serving = false;
+
+    // When serving, the client decides what to run.
+    if !serving
+        && (load.starts_with(Path::new("../data/raw_maps/"))
+            || load.starts_with(Path::new("../data/maps/")))
     {
-        let s = if let Some(n) = flags.num_agents {
+        let s = if flags.tours {
+            Scenario::home_based_tours(&map, &TourParams::typical_weekday(), &mut rng, &mut timer)
+        } else if let Some(n) = flags.num_agents {
             Scenario::scaled_run(&map, n)
         } else {
             Scenario::small_run(&map)
@@ -57,6 +88,30 @@ fn main() {
     }
     timer.done();
 
+    if let Some(ref path) = flags.record_events {
+        if let Err(e) = sim.record_events(path) {
+            panic!("Can't record events to {}: {}", path, e);
+        }
+    }
+    if let Some(ref freq_str) = flags.record_trajectories {
+        let frequency = match Duration::parse(freq_str) {
+            Some(t) => t,
+            None => panic!("Couldn't parse time {}", freq_str),
+        };
+        match sim.record_trajectories(frequency) {
+            Ok(dir) => println!("Recording trajectories to {}", dir),
+            Err(e) => panic!("Can't record trajectories: {}", e),
+        }
+    }
+
+    #[cfg(unix)]
+    {
+        if let Some(ref socket_path) = flags.serve {
+            headless::api::serve(socket_path, map, sim, rng);
+            return;
+        }
+    }
+
     if flags.enable_profiler {
         cpuprofiler::PROFILER
             .lock()
@@ -85,7 +140,16 @@ fn main() {
         None,
     );
     timer.done();
+    for e in sim.take_recording_errors() {
+        println!("Stopped recording early: {}", e);
+    }
     println!("{:?}", sim.get_score());
+    if flags.intersection_stats {
+        match sim.write_intersection_stats(&map) {
+            Ok(path) => println!("Wrote intersection stats to {}", path),
+            Err(e) => panic!("Can't write intersection stats: {}", e),
+        }
+    }
     if flags.enable_profiler && save_at.is_none() {
         cpuprofiler::PROFILER.lock().unwrap().stop().unwrap();
     }
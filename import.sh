@@ -13,13 +13,16 @@ function get_if_needed {
 mkdir -p data/input data/raw_maps
 
 # TODO refactor a variant for .zips?
-if [ ! -f data/input/N47W122.hgt ]; then
-	get_if_needed \
-		https://dds.cr.usgs.gov/srtm/version2_1/SRTM1/Region_01/N47W122.hgt.zip \
-		data/input/N47W122.hgt.zip;
-	unzip -d data/input data/input/N47W122.hgt.zip;
-	rm -f data/input/N47W122.hgt.zip;
-fi
+# Tiles are named after their southwest corner. Seattle straddles these two.
+for tile in N47W122 N47W123; do
+	if [ ! -f data/input/$tile.hgt ]; then
+		get_if_needed \
+			https://dds.cr.usgs.gov/srtm/version2_1/SRTM1/Region_01/$tile.hgt.zip \
+			data/input/$tile.hgt.zip;
+		unzip -d data/input data/input/$tile.hgt.zip;
+		rm -f data/input/$tile.hgt.zip;
+	fi
+done
 
 if [ ! -d data/input/google_transit_2018_18_08/ ]; then
 	get_if_needed \
@@ -67,6 +70,13 @@ for poly in `ls data/polygons/`; do
 	fi
 done
 
+# The tests check that reading PBF matches reading XML.
+if [ ! -f data/input/montlake.osm.pbf ]; then
+	osmosis \
+		--read-xml enableDateParsing=no file=data/input/montlake.osm \
+		--write-pbf data/input/montlake.osm.pbf
+fi
+
 if [ ! -f data/shapes/blockface ]; then
 	# From http://data-seattlecitygis.opendata.arcgis.com/datasets/blockface
 	get_if_needed https://opendata.arcgis.com/datasets/a1458ad1abca41869b81f7c0db0cd777_0.kml data/input/blockface.kml;
@@ -84,14 +94,10 @@ for poly in `ls ../data/polygons/`; do
 	rm -rf ../data/neighborhoods/$name ../data/maps/${name}.abst;
 	RUST_BACKTRACE=1 cargo run --release -- \
 		--osm=../data/input/$name.osm \
-		--elevation=../data/input/N47W122.hgt \
-		--traffic_signals=../data/input/traffic_signals.kml \
-		--residential_buildings=../data/input/residential_buildings.kml \
-		--parking_shapes=../data/shapes/blockface \
-		--gtfs=../data/input/google_transit_2018_18_08 \
-		--neighborhoods=../data/input/neighborhoods.geojson \
+		--elevation=../data/input/ \
+		--profile=../data/profiles/seattle.json \
 		--clip=../data/polygons/$name.poly \
 		--output=../data/raw_maps/$name.abst
 done
 
-# To run manually: cargo run -- --osm=../data/input/montlake.osm --elevation=../data/input/N47W122.hgt --traffic_signals=../data/input/traffic_signals.kml --residential_buildings=../data/input/residential_buildings.kml --parking_shapes=../data/shapes/blockface --gtfs=../data/input/google_transit_2018_18_08 --neighborhoods=../data/input/neighborhoods.geojson --clip=../data/polygons/montlake.poly --output=../data/raw_maps/montlake.abst --fast_dev
+# To run manually: cargo run -- --osm=../data/input/montlake.osm --elevation=../data/input/ --profile=../data/profiles/seattle.json --clip=../data/polygons/montlake.poly --output=../data/raw_maps/montlake.abst --fast_dev
@@ -20,7 +20,7 @@ pub enum AreaType {
     Water,
 }
 
-#[derive(Serialize, Deserialize, Debug)]
+#[derive(Serialize, Deserialize, Debug, Clone)]
 pub struct Area {
     pub id: AreaID,
     pub area_type: AreaType,
@@ -0,0 +1,41 @@
+use crate::{LaneType, Map, Position};
+use serde_derive::{Deserialize, Serialize};
+use std::fmt;
+
+#[derive(Clone, Copy, Debug, Eq, Hash, PartialEq, PartialOrd, Ord, Serialize, Deserialize)]
+pub struct BikeParkingID(pub usize);
+
+impl fmt::Display for BikeParkingID {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "BikeParkingID({0})", self.0)
+    }
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub enum BikeParkingType {
+    // People lock up their own bike here.
+    Rack,
+    // Bikes are rented from one dock and returned to any other.
+    Bikeshare,
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq)]
+pub struct BikeParking {
+    pub id: BikeParkingID,
+    pub parking_type: BikeParkingType,
+    // How many bikes fit
+    pub capacity: usize,
+    pub sidewalk_pos: Position,
+    // The equivalent position on the nearest driving/bike lane
+    pub driving_pos: Position,
+    pub osm_node_id: i64,
+}
+
+impl BikeParking {
+    // Map edits can remove the lane bikes use to reach this, or make it off-limits to bikes.
+    pub fn is_reachable(&self, map: &Map) -> bool {
+        let lane = self.driving_pos.lane();
+        let lt = map.get_l(lane).lane_type;
+        !map.is_lane_removed(lane) && (lt == LaneType::Driving || lt.is_for_bikes())
+    }
+}
@@ -15,7 +15,7 @@ impl fmt::Display for BuildingID {
     }
 }
 
-#[derive(Serialize, Deserialize, Debug)]
+#[derive(Serialize, Deserialize, Debug, Clone)]
 pub struct FrontPath {
     pub bldg: BuildingID,
     pub sidewalk: Position,
@@ -26,18 +26,38 @@ pub struct FrontPath {
 #[derive(Clone, Copy, Debug, Eq, Hash, PartialEq, Serialize, Deserialize)]
 pub enum BuildingType {
     Residence,
-    Business,
+    Retail,
+    Office,
+    School,
+    Hospital,
+    // Shops or offices on the ground floor, apartments above
+    MixedUse,
+    ParkingGarage,
     Unknown,
 }
 
-#[derive(Serialize, Deserialize, Debug)]
+// Rough estimates from the footprint, number of levels, and type. Good enough to weight trip ends,
+// not to count people.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Serialize, Deserialize)]
+pub struct BuildingCapacity {
+    pub residential_units: usize,
+    pub residents: usize,
+    pub jobs: usize,
+    // Students, patients, shoppers, etc over a day
+    pub visitors: usize,
+    pub parking_spots: usize,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone)]
 pub struct Building {
     pub id: BuildingID,
     pub building_type: BuildingType,
     pub polygon: Polygon,
     pub osm_tags: BTreeMap<String, String>,
     pub osm_way_id: i64,
+    // Only from external data, like permits
     pub num_residential_units: Option<usize>,
+    pub capacity: BuildingCapacity,
 
     pub front_path: FrontPath,
 }
@@ -51,6 +71,11 @@ impl Building {
         self.front_path.sidewalk.lane()
     }
 
+    // How strongly this building draws trips that don't start at home
+    pub fn attraction(&self) -> usize {
+        self.capacity.jobs + self.capacity.visitors
+    }
+
     pub fn get_name(&self) -> String {
         self.osm_tags
             .get("name")
@@ -37,7 +37,7 @@ impl BusStop {
     }
 }
 
-#[derive(Serialize, Deserialize, Debug)]
+#[derive(Serialize, Deserialize, Debug, Clone)]
 pub struct BusRoute {
     pub id: BusRouteID,
     pub name: String,
@@ -0,0 +1,457 @@
+mod perma;
+
+pub use self::perma::{
+    OriginalIntersection, OriginalLane, OriginalRoad, OriginalTurn, PermanentMapEdits,
+};
+use crate::{
+    ControlStopSign, ControlTrafficSignal, IntersectionID, IntersectionType, LaneID, LaneType, Map,
+    RoadID, RoadSpec, TurnID,
+};
+use abstutil::{deserialize_btreemap, serialize_btreemap, Error, Timer};
+use geom::Duration;
+use serde_derive::{Deserialize, Serialize};
+use std::collections::{BTreeMap, BTreeSet};
+
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct MapEdits {
+    pub(crate) map_name: String,
+    pub edits_name: String,
+    pub lane_overrides: BTreeMap<LaneID, LaneType>,
+    // Replaces all of the lanes of a road, so lanes can be added, removed, and reordered.
+    #[serde(default)]
+    pub road_overrides: BTreeMap<RoadID, RoadSpec>,
+    // Switches between stop signs, traffic signals, and no control at all. Applied before the stop
+    // sign and traffic signal overrides, which have to match the new type.
+    #[serde(default)]
+    pub intersection_type_overrides: BTreeMap<IntersectionID, IntersectionType>,
+    // TODO Storing the entire thing is maybe a bit dramatic, but works for now.
+    pub stop_sign_overrides: BTreeMap<IntersectionID, ControlStopSign>,
+    pub traffic_signal_overrides: BTreeMap<IntersectionID, ControlTrafficSignal>,
+    // Older edits don't have any.
+    #[serde(default)]
+    pub closures: Vec<Closure>,
+}
+
+impl MapEdits {
+    pub fn new(map_name: String) -> MapEdits {
+        MapEdits {
+            map_name,
+            // Something has to fill this out later
+            edits_name: "no_edits".to_string(),
+            lane_overrides: BTreeMap::new(),
+            road_overrides: BTreeMap::new(),
+            intersection_type_overrides: BTreeMap::new(),
+            stop_sign_overrides: BTreeMap::new(),
+            traffic_signal_overrides: BTreeMap::new(),
+            closures: Vec::new(),
+        }
+    }
+
+    pub fn describe(&self) -> String {
+        format!(
+            "map edits \"{}\" ({} lanes, {} roads, {} intersection types, {} stop signs, {} \
+             traffic signals, {} closures)",
+            self.edits_name,
+            self.lane_overrides.len(),
+            self.road_overrides.len(),
+            self.intersection_type_overrides.len(),
+            self.stop_sign_overrides.len(),
+            self.traffic_signal_overrides.len(),
+            self.closures.len(),
+        )
+    }
+
+    pub fn save(&self, map: &Map) {
+        abstutil::save_object(
+            "edits",
+            &self.map_name,
+            &self.edits_name,
+            &PermanentMapEdits::to_permanent(self, map),
+        );
+    }
+
+    pub fn load(map: &Map, edits_name: &str, timer: &mut Timer) -> Result<MapEdits, Error> {
+        if edits_name == "no_edits" {
+            return Ok(MapEdits::new(map.get_name().to_string()));
+        }
+        PermanentMapEdits::load(map.get_name(), edits_name, map, timer)
+    }
+
+    // Everything that's different in other, compared to self.
+    pub fn diff(&self, other: &MapEdits) -> EditsDiff {
+        EditsDiff {
+            lanes: diff_keys(&self.lane_overrides, &other.lane_overrides),
+            roads: diff_keys(&self.road_overrides, &other.road_overrides),
+            intersection_types: diff_keys(
+                &self.intersection_type_overrides,
+                &other.intersection_type_overrides,
+            ),
+            stop_signs: diff_keys(&self.stop_sign_overrides, &other.stop_sign_overrides),
+            traffic_signals: diff_keys(
+                &self.traffic_signal_overrides,
+                &other.traffic_signal_overrides,
+            ),
+            closures_added: other
+                .closures
+                .iter()
+                .filter(|c| !self.closures.contains(c))
+                .cloned()
+                .collect(),
+            closures_removed: self
+                .closures
+                .iter()
+                .filter(|c| !other.closures.contains(c))
+                .cloned()
+                .collect(),
+        }
+    }
+
+    // One line per change from self to other.
+    pub fn describe_diff(&self, other: &MapEdits) -> Vec<String> {
+        let diff = self.diff(other);
+        let mut lines = Vec::new();
+        for l in &diff.lanes {
+            lines.push(format!(
+                "{}: {} -> {}",
+                l,
+                describe_override(self.lane_overrides.get(l)),
+                describe_override(other.lane_overrides.get(l))
+            ));
+        }
+        for r in &diff.roads {
+            lines.push(format!(
+                "{}: {} -> {}",
+                r,
+                describe_spec(self.road_overrides.get(r)),
+                describe_spec(other.road_overrides.get(r))
+            ));
+        }
+        for i in &diff.intersection_types {
+            lines.push(format!(
+                "{}: {} -> {}",
+                i,
+                describe_type(self.intersection_type_overrides.get(i)),
+                describe_type(other.intersection_type_overrides.get(i))
+            ));
+        }
+        for i in &diff.stop_signs {
+            lines.push(format!("{}: stop sign changed", i));
+        }
+        for i in &diff.traffic_signals {
+            lines.push(format!("{}: traffic signal changed", i));
+        }
+        for c in &diff.closures_added {
+            lines.push(format!("added: {}", c.describe()));
+        }
+        for c in &diff.closures_removed {
+            lines.push(format!("removed: {}", c.describe()));
+        }
+        lines
+    }
+
+    // Combine self and other, which were both made starting from base. Whatever only one side
+    // changed is kept, including removing an override. If both change the same thing in different
+    // ways, or change overlapping parts of the map -- a lane and the road it belongs to, or an
+    // intersection's type and its stop sign or traffic signal -- that's a conflict that somebody
+    // has to resolve manually. The map should have self applied.
+    pub fn merge(&self, other: &MapEdits, base: &MapEdits, map: &Map) -> Result<MapEdits, Error> {
+        for edits in &[other, base] {
+            if edits.map_name != self.map_name {
+                return Err(Error::new(format!(
+                    "Can't merge edits for {} and {}",
+                    self.map_name, edits.map_name
+                )));
+            }
+        }
+
+        let mut conflicts = Vec::new();
+        let mut merged = base.clone();
+        merged.edits_name = format!("{}_and_{}", self.edits_name, other.edits_name);
+        merged.lane_overrides = merge_overrides(
+            &base.lane_overrides,
+            &self.lane_overrides,
+            &other.lane_overrides,
+            &mut conflicts,
+        );
+        merged.road_overrides = merge_overrides(
+            &base.road_overrides,
+            &self.road_overrides,
+            &other.road_overrides,
+            &mut conflicts,
+        );
+        merged.intersection_type_overrides = merge_overrides(
+            &base.intersection_type_overrides,
+            &self.intersection_type_overrides,
+            &other.intersection_type_overrides,
+            &mut conflicts,
+        );
+        merged.stop_sign_overrides = merge_overrides(
+            &base.stop_sign_overrides,
+            &self.stop_sign_overrides,
+            &other.stop_sign_overrides,
+            &mut conflicts,
+        );
+        merged.traffic_signal_overrides = merge_overrides(
+            &base.traffic_signal_overrides,
+            &self.traffic_signal_overrides,
+            &other.traffic_signal_overrides,
+            &mut conflicts,
+        );
+        // Closures don't conflict with each other; overlapping ones are fine.
+        merged.closures = base
+            .closures
+            .iter()
+            .filter(|c| self.closures.contains(c) && other.closures.contains(c))
+            .cloned()
+            .collect();
+        for c in self.closures.iter().chain(other.closures.iter()) {
+            if !base.closures.contains(c) && !merged.closures.contains(c) {
+                merged.closures.push(c.clone());
+            }
+        }
+
+        let ours = base.diff(self);
+        let theirs = base.diff(other);
+        for (side1, side2, edits1) in &[(&ours, &theirs, self), (&theirs, &ours, other)] {
+            for l in &side1.lanes {
+                // Lanes removed by a road edit on either side or added by one on the other side
+                // can't be traced back to a road.
+                let parent = match map.maybe_get_l(*l) {
+                    Some(lane) => lane.parent,
+                    None => {
+                        conflicts.push(format!(
+                            "{} (changed by {}, but not on the map anymore)",
+                            l, edits1.edits_name
+                        ));
+                        continue;
+                    }
+                };
+                if side2.roads.contains(&parent) {
+                    conflicts.push(format!("{} and its road {}", l, parent));
+                }
+            }
+            for i in &side1.intersection_types {
+                if side2.stop_signs.contains(i) || side2.traffic_signals.contains(i) {
+                    conflicts.push(format!("the type and control of {}", i));
+                }
+            }
+        }
+
+        if conflicts.is_empty() {
+            Ok(merged)
+        } else {
+            Err(Error::new(format!(
+                "{} and {} both change {}",
+                self.edits_name,
+                other.edits_name,
+                conflicts.join(", ")
+            )))
+        }
+    }
+}
+
+#[derive(Debug, PartialEq)]
+pub struct EditsDiff {
+    pub lanes: BTreeSet<LaneID>,
+    pub roads: BTreeSet<RoadID>,
+    pub intersection_types: BTreeSet<IntersectionID>,
+    pub stop_signs: BTreeSet<IntersectionID>,
+    pub traffic_signals: BTreeSet<IntersectionID>,
+    pub closures_added: Vec<Closure>,
+    pub closures_removed: Vec<Closure>,
+}
+
+impl EditsDiff {
+    pub fn is_empty(&self) -> bool {
+        self.lanes.is_empty()
+            && self.roads.is_empty()
+            && self.intersection_types.is_empty()
+            && self.stop_signs.is_empty()
+            && self.traffic_signals.is_empty()
+            && self.closures_added.is_empty()
+            && self.closures_removed.is_empty()
+    }
+}
+
+fn diff_keys<K: Ord + Copy, V: PartialEq>(
+    before: &BTreeMap<K, V>,
+    after: &BTreeMap<K, V>,
+) -> BTreeSet<K> {
+    let mut keys = BTreeSet::new();
+    for (k, v) in before {
+        if after.get(k) != Some(v) {
+            keys.insert(*k);
+        }
+    }
+    for k in after.keys() {
+        if !before.contains_key(k) {
+            keys.insert(*k);
+        }
+    }
+    keys
+}
+
+// A 3-way merge of one kind of override. A missing key means no override.
+fn merge_overrides<K: Ord + Copy + std::fmt::Display, V: PartialEq + Clone>(
+    base: &BTreeMap<K, V>,
+    ours: &BTreeMap<K, V>,
+    theirs: &BTreeMap<K, V>,
+    conflicts: &mut Vec<String>,
+) -> BTreeMap<K, V> {
+    let keys: BTreeSet<K> = base
+        .keys()
+        .chain(ours.keys())
+        .chain(theirs.keys())
+        .cloned()
+        .collect();
+    let mut merged = BTreeMap::new();
+    for k in keys {
+        let (b, o, t) = (base.get(&k), ours.get(&k), theirs.get(&k));
+        let result = if o == t || t == b {
+            o
+        } else if o == b {
+            t
+        } else {
+            conflicts.push(k.to_string());
+            o
+        };
+        if let Some(v) = result {
+            merged.insert(k, v.clone());
+        }
+    }
+    merged
+}
+
+fn describe_override(lt: Option<&LaneType>) -> String {
+    match lt {
+        Some(lt) => format!("{:?}", lt),
+        None => "original".to_string(),
+    }
+}
+
+fn describe_spec(spec: Option<&RoadSpec>) -> String {
+    match spec {
+        Some(spec) => spec.to_string(),
+        None => "original".to_string(),
+    }
+}
+
+fn describe_type(it: Option<&IntersectionType>) -> String {
+    match it {
+        Some(it) => format!("{:?}", it),
+        None => "original".to_string(),
+    }
+}
+
+// What Map::apply_edits changed, so callers can update anything derived from the map.
+pub struct EditEffects {
+    // Includes lanes that were added or removed
+    pub changed_lanes: BTreeSet<LaneID>,
+    // Roads whose geometry changed
+    pub changed_roads: BTreeSet<RoadID>,
+    // Intersections whose geometry or control changed
+    pub changed_intersections: BTreeSet<IntersectionID>,
+    pub deleted_turns: BTreeSet<TurnID>,
+    pub added_turns: BTreeSet<TurnID>,
+}
+
+impl EditEffects {
+    pub fn is_empty(&self) -> bool {
+        self.changed_lanes.is_empty()
+            && self.changed_roads.is_empty()
+            && self.changed_intersections.is_empty()
+            && self.deleted_turns.is_empty()
+            && self.added_turns.is_empty()
+    }
+}
+
+#[derive(Serialize, Deserialize, Clone, Copy, Debug, PartialEq, Eq, PartialOrd, Ord)]
+pub enum ClosureTarget {
+    Road(RoadID),
+    Lane(LaneID),
+    // Just one movement through an intersection
+    Turn(TurnID),
+}
+
+// Construction, a street festival, etc. Nothing can enter the target between start and end.
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq)]
+pub struct Closure {
+    pub target: ClosureTarget,
+    pub start: Duration,
+    pub end: Duration,
+}
+
+impl Closure {
+    pub fn new(target: ClosureTarget, start: Duration, end: Duration) -> Result<Closure, Error> {
+        if end <= start {
+            return Err(Error::new(format!(
+                "A closure can't end at {} before it starts at {}",
+                end, start
+            )));
+        }
+        Ok(Closure { target, start, end })
+    }
+
+    pub fn is_active(&self, time: Duration) -> bool {
+        self.start <= time && time < self.end
+    }
+
+    // Where agents might be waiting for this closure to end
+    pub fn affected_intersections(&self, map: &Map) -> Vec<IntersectionID> {
+        match self.target {
+            ClosureTarget::Road(r) => {
+                let r = map.get_r(r);
+                vec![r.src_i, r.dst_i]
+            }
+            ClosureTarget::Lane(l) => {
+                let l = map.get_l(l);
+                vec![l.src_i, l.dst_i]
+            }
+            ClosureTarget::Turn(t) => vec![t.parent],
+        }
+    }
+
+    pub fn describe(&self) -> String {
+        let what = match self.target {
+            ClosureTarget::Road(r) => r.to_string(),
+            ClosureTarget::Lane(l) => l.to_string(),
+            ClosureTarget::Turn(t) => t.to_string(),
+        };
+        format!("{} closed from {} to {}", what, self.start, self.end)
+    }
+}
+
+// When every closure is active, by what it closes, so checking one traversable doesn't scan all
+// of them
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub(crate) struct ClosureIndex {
+    #[serde(
+        serialize_with = "serialize_btreemap",
+        deserialize_with = "deserialize_btreemap"
+    )]
+    times: BTreeMap<ClosureTarget, Vec<(Duration, Duration)>>,
+}
+
+impl ClosureIndex {
+    pub fn new(closures: &Vec<Closure>) -> ClosureIndex {
+        let mut times = BTreeMap::new();
+        for c in closures {
+            times
+                .entry(c.target)
+                .or_insert_with(Vec::new)
+                .push((c.start, c.end));
+        }
+        ClosureIndex { times }
+    }
+
+    pub fn is_active(&self, target: ClosureTarget, time: Duration) -> bool {
+        self.times
+            .get(&target)
+            .map(|times| {
+                times
+                    .iter()
+                    .any(|(start, end)| *start <= time && time < *end)
+            })
+            .unwrap_or(false)
+    }
+}
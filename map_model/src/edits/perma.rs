@@ -0,0 +1,667 @@
+use crate::{
+    Closure, ClosureTarget, ControlStopSign, ControlTrafficSignal, Cycle, IntersectionID,
+    IntersectionType, LaneID, LaneType, Map, MapEdits, RoadID, RoadSpec, TurnID, TurnPriority,
+};
+use abstutil::{Error, Timer, Warn};
+use geom::{Distance, Duration, LonLat, Pt2D};
+use serde_derive::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+
+// LaneIDs, RoadIDs, and IntersectionIDs are just indices, reassigned whenever the map is
+// reconverted. Anything serialized to disk refers to things in terms of the original OSM data
+// instead, and gets resolved against the current map when it's loaded.
+
+// Intersections are matched up by the OSM node they came from. Some don't have one, like where a
+// road crosses the map boundary, and merging intersections together drops one of the nodes, so
+// the position is kept as a fallback.
+#[derive(Serialize, Deserialize, Clone, Copy, Debug, PartialEq)]
+pub struct OriginalIntersection {
+    // Missing from edits saved before this was recorded
+    #[serde(default)]
+    pub osm_node_id: Option<i64>,
+    pub point: LonLat,
+}
+
+// OSM ways get split at every intersection, so the way ID alone isn't enough.
+#[derive(Serialize, Deserialize, Clone, Copy, Debug, PartialEq)]
+pub struct OriginalRoad {
+    pub osm_way_id: i64,
+    pub i1: OriginalIntersection,
+    pub i2: OriginalIntersection,
+}
+
+// Lanes are counted from the center of the road, like Road::dir_and_offset. forwards is relative
+// to i1 -> i2.
+#[derive(Serialize, Deserialize, Clone, Copy, Debug, PartialEq)]
+pub struct OriginalLane {
+    pub parent: OriginalRoad,
+    pub forwards: bool,
+    pub idx: usize,
+}
+
+#[derive(Serialize, Deserialize, Clone, Copy, Debug, PartialEq)]
+pub struct OriginalTurn {
+    pub parent: OriginalIntersection,
+    pub src: OriginalLane,
+    pub dst: OriginalLane,
+}
+
+// Intersections move slightly when we convert between GPS and map-space, and geometry cleanup like
+// merging short roads moves them further, but they stay near the OSM nodes they came from.
+const INTERSECTION_MATCH_THRESHOLD: Distance = Distance::const_meters(10.0);
+
+impl OriginalIntersection {
+    pub fn to_permanent(id: IntersectionID, map: &Map) -> OriginalIntersection {
+        let i = map.get_i(id);
+        OriginalIntersection {
+            osm_node_id: i.osm_node_id,
+            point: i.point.to_gps(map.get_gps_bounds()).unwrap(),
+        }
+    }
+
+    pub fn from_permanent(&self, map: &Map) -> Option<IntersectionID> {
+        self.by_osm_node(map).or_else(|| self.by_point(map))
+    }
+
+    fn by_osm_node(&self, map: &Map) -> Option<IntersectionID> {
+        self.osm_node_id
+            .and_then(|node| map.find_i_by_osm_node_id(node))
+    }
+
+    // Guesses wrong when intersections are close together, so only a fallback.
+    fn by_point(&self, map: &Map) -> Option<IntersectionID> {
+        let pt = Pt2D::from_gps(self.point, map.get_gps_bounds())?;
+        map.all_intersections()
+            .iter()
+            .filter(|i| i.point.approx_eq(pt, INTERSECTION_MATCH_THRESHOLD))
+            .min_by_key(|i| i.point.dist_to(pt))
+            .map(|i| i.id)
+    }
+
+    // How far off this is from being the intersection, or None if it's not.
+    fn mismatch(&self, id: IntersectionID, map: &Map) -> Option<Distance> {
+        if let Some(found) = self.by_osm_node(map) {
+            return if found == id {
+                Some(Distance::ZERO)
+            } else {
+                None
+            };
+        }
+        let pt = Pt2D::from_gps(self.point, map.get_gps_bounds())?;
+        let actual = map.get_i(id).point;
+        if actual.approx_eq(pt, INTERSECTION_MATCH_THRESHOLD) {
+            Some(actual.dist_to(pt))
+        } else {
+            None
+        }
+    }
+}
+
+impl OriginalRoad {
+    pub fn to_permanent(id: RoadID, map: &Map) -> OriginalRoad {
+        let r = map.get_r(id);
+        OriginalRoad {
+            osm_way_id: r.osm_way_id,
+            i1: OriginalIntersection::to_permanent(r.src_i, map),
+            i2: OriginalIntersection::to_permanent(r.dst_i, map),
+        }
+    }
+
+    pub fn from_permanent(&self, map: &Map) -> Option<RoadID> {
+        self.find(map).map(|(r, _)| r)
+    }
+
+    // Also returns true if the road now points the opposite way. Of the pieces of the OSM way,
+    // picks the one with endpoints matching the original ones, or closest to them for endpoints
+    // without an OSM node.
+    fn find(&self, map: &Map) -> Option<(RoadID, bool)> {
+        let mut best: Option<(Distance, RoadID, bool)> = None;
+        for road in map.all_roads() {
+            if road.osm_way_id != self.osm_way_id {
+                continue;
+            }
+            for (end1, end2, reversed) in &[
+                (road.src_i, road.dst_i, false),
+                (road.dst_i, road.src_i, true),
+            ] {
+                let dist = match (self.i1.mismatch(*end1, map), self.i2.mismatch(*end2, map)) {
+                    (Some(d1), Some(d2)) => d1 + d2,
+                    _ => {
+                        continue;
+                    }
+                };
+                if best.map(|(d, _, _)| dist < d).unwrap_or(true) {
+                    best = Some((dist, road.id, *reversed));
+                }
+            }
+        }
+        best.map(|(_, r, reversed)| (r, reversed))
+    }
+
+    fn endpoints(&self) -> Vec<OriginalIntersection> {
+        vec![self.i1, self.i2]
+    }
+}
+
+impl OriginalLane {
+    pub fn to_permanent(id: LaneID, map: &Map) -> OriginalLane {
+        let r = map.get_parent(id);
+        let (forwards, idx) = r.dir_and_offset(id);
+        OriginalLane {
+            parent: OriginalRoad::to_permanent(r.id, map),
+            forwards,
+            idx,
+        }
+    }
+
+    pub fn from_permanent(&self, map: &Map) -> Option<LaneID> {
+        let (r, reversed) = self.parent.find(map)?;
+        let r = map.get_r(r);
+        let children = if self.forwards != reversed {
+            &r.children_forwards
+        } else {
+            &r.children_backwards
+        };
+        children.get(self.idx).map(|(l, _)| *l)
+    }
+}
+
+impl OriginalTurn {
+    pub fn to_permanent(id: TurnID, map: &Map) -> OriginalTurn {
+        OriginalTurn {
+            parent: OriginalIntersection::to_permanent(id.parent, map),
+            src: OriginalLane::to_permanent(id.src, map),
+            dst: OriginalLane::to_permanent(id.dst, map),
+        }
+    }
+
+    fn intersections(&self) -> Vec<OriginalIntersection> {
+        let mut all = vec![self.parent];
+        all.extend(self.src.parent.endpoints());
+        all.extend(self.dst.parent.endpoints());
+        all
+    }
+
+    pub fn from_permanent(&self, map: &Map) -> Option<TurnID> {
+        let id = TurnID {
+            parent: self.parent.from_permanent(map)?,
+            src: self.src.from_permanent(map)?,
+            dst: self.dst.from_permanent(map)?,
+        };
+        map.maybe_get_t(id).map(|t| t.id)
+    }
+}
+
+// The on-disk form of MapEdits.
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct PermanentMapEdits {
+    pub map_name: String,
+    pub edits_name: String,
+    pub lane_overrides: Vec<(OriginalLane, LaneType)>,
+    // The spec is relative to i1 -> i2.
+    pub road_overrides: Vec<(OriginalRoad, RoadSpec)>,
+    pub intersection_type_overrides: Vec<(OriginalIntersection, IntersectionType)>,
+    pub stop_sign_overrides: Vec<PermanentStopSign>,
+    pub traffic_signal_overrides: Vec<PermanentTrafficSignal>,
+    #[serde(default)]
+    pub closures: Vec<PermanentClosure>,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct PermanentStopSign {
+    pub id: OriginalIntersection,
+    pub turns: Vec<(OriginalTurn, TurnPriority)>,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct PermanentTrafficSignal {
+    pub id: OriginalIntersection,
+    pub cycles: Vec<PermanentCycle>,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct PermanentCycle {
+    pub priority_turns: Vec<OriginalTurn>,
+    pub yield_turns: Vec<OriginalTurn>,
+    pub duration: Duration,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub enum PermanentClosureTarget {
+    Road(OriginalRoad),
+    Lane(OriginalLane),
+    Turn(OriginalTurn),
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct PermanentClosure {
+    pub target: PermanentClosureTarget,
+    pub start: Duration,
+    pub end: Duration,
+}
+
+impl PermanentMapEdits {
+    pub fn to_permanent(edits: &MapEdits, map: &Map) -> PermanentMapEdits {
+        PermanentMapEdits {
+            map_name: edits.map_name.clone(),
+            edits_name: edits.edits_name.clone(),
+            lane_overrides: edits
+                .lane_overrides
+                .iter()
+                .map(|(l, lt)| (OriginalLane::to_permanent(*l, map), *lt))
+                .collect(),
+            road_overrides: edits
+                .road_overrides
+                .iter()
+                .map(|(r, spec)| (OriginalRoad::to_permanent(*r, map), spec.clone()))
+                .collect(),
+            intersection_type_overrides: edits
+                .intersection_type_overrides
+                .iter()
+                .map(|(i, it)| (OriginalIntersection::to_permanent(*i, map), *it))
+                .collect(),
+            stop_sign_overrides: edits
+                .stop_sign_overrides
+                .values()
+                .map(|ss| PermanentStopSign {
+                    id: OriginalIntersection::to_permanent(ss.id, map),
+                    turns: ss
+                        .turns
+                        .iter()
+                        .map(|(t, pri)| (OriginalTurn::to_permanent(*t, map), *pri))
+                        .collect(),
+                })
+                .collect(),
+            traffic_signal_overrides: edits
+                .traffic_signal_overrides
+                .values()
+                .map(|ts| PermanentTrafficSignal {
+                    id: OriginalIntersection::to_permanent(ts.id, map),
+                    cycles: ts
+                        .cycles
+                        .iter()
+                        .map(|c| PermanentCycle {
+                            priority_turns: c
+                                .priority_turns
+                                .iter()
+                                .map(|t| OriginalTurn::to_permanent(*t, map))
+                                .collect(),
+                            yield_turns: c
+                                .yield_turns
+                                .iter()
+                                .map(|t| OriginalTurn::to_permanent(*t, map))
+                                .collect(),
+                            duration: c.duration,
+                        })
+                        .collect(),
+                })
+                .collect(),
+            closures: edits
+                .closures
+                .iter()
+                .map(|c| PermanentClosure {
+                    target: match c.target {
+                        ClosureTarget::Road(r) => {
+                            PermanentClosureTarget::Road(OriginalRoad::to_permanent(r, map))
+                        }
+                        ClosureTarget::Lane(l) => {
+                            PermanentClosureTarget::Lane(OriginalLane::to_permanent(l, map))
+                        }
+                        ClosureTarget::Turn(t) => {
+                            PermanentClosureTarget::Turn(OriginalTurn::to_permanent(t, map))
+                        }
+                    },
+                    start: c.start,
+                    end: c.end,
+                })
+                .collect(),
+        }
+    }
+
+    // Anything that doesn't match up with the current map anymore is dropped, with a warning
+    // describing it. Road overrides add, remove, and reorder lanes, so the lanes and turns that
+    // everything else refers to might only exist after they're applied. Those are resolved against
+    // a copy of the map with the road overrides applied first.
+    pub fn from_permanent(self, map: &Map) -> Warn<MapEdits> {
+        let mut warnings = Vec::new();
+        for i in self.all_intersections() {
+            if i.by_osm_node(map).is_some() {
+                continue;
+            }
+            if let Some(id) = i.by_point(map) {
+                if let Some(node) = i.osm_node_id {
+                    warnings.push(format!(
+                        "OSM node {} isn't an intersection anymore; guessing {} by position",
+                        node, id
+                    ));
+                } else if map.get_i(id).osm_node_id.is_some() {
+                    warnings.push(format!(
+                        "Matched {} by position; save the edits again to refer to its OSM node",
+                        id
+                    ));
+                }
+            }
+        }
+
+        let mut edits = MapEdits::new(self.map_name);
+        edits.edits_name = self.edits_name;
+
+        for (r, spec) in self.road_overrides {
+            if let Some((id, reversed)) = r.find(map) {
+                let spec = if reversed {
+                    RoadSpec {
+                        fwd: spec.back,
+                        back: spec.fwd,
+                    }
+                } else {
+                    spec
+                };
+                edits.road_overrides.insert(id, spec);
+            } else {
+                warnings.push(format!(
+                    "Road override to {} no longer applies: {:?}",
+                    spec.to_string(),
+                    r
+                ));
+            }
+        }
+
+        let rearranged;
+        let map = if edits.road_overrides == map.get_edits().road_overrides {
+            map
+        } else {
+            let mut copy = map.copy_without_pathfinder();
+            let mut road_edits = copy.get_edits().clone();
+            road_edits.road_overrides = edits.road_overrides.clone();
+            copy.apply_edits(road_edits, &mut Timer::throwaway());
+            rearranged = copy;
+            &rearranged
+        };
+
+        for (l, lt) in self.lane_overrides {
+            if let Some(id) = l.from_permanent(map) {
+                edits.lane_overrides.insert(id, lt);
+            } else {
+                warnings.push(format!(
+                    "Lane override to {:?} no longer applies: {:?}",
+                    lt, l
+                ));
+            }
+        }
+
+        for (i, it) in self.intersection_type_overrides {
+            if let Some(id) = i.from_permanent(map) {
+                edits.intersection_type_overrides.insert(id, it);
+            } else {
+                warnings.push(format!(
+                    "Changing the intersection to {:?} no longer applies: {:?}",
+                    it, i
+                ));
+            }
+        }
+
+        for ss in self.stop_sign_overrides {
+            match resolve_stop_sign(&ss, &edits.intersection_type_overrides, map) {
+                Ok(resolved) => {
+                    edits.stop_sign_overrides.insert(resolved.id, resolved);
+                }
+                Err(err) => {
+                    warnings.push(format!(
+                        "Stop sign at {:?} no longer applies: {}",
+                        ss.id, err
+                    ));
+                }
+            }
+        }
+
+        for ts in self.traffic_signal_overrides {
+            match resolve_traffic_signal(&ts, &edits.intersection_type_overrides, map) {
+                Ok(resolved) => {
+                    edits.traffic_signal_overrides.insert(resolved.id, resolved);
+                }
+                Err(err) => {
+                    warnings.push(format!(
+                        "Traffic signal at {:?} no longer applies: {}",
+                        ts.id, err
+                    ));
+                }
+            }
+        }
+
+        for c in self.closures {
+            let target = match c.target {
+                PermanentClosureTarget::Road(ref r) => {
+                    r.from_permanent(map).map(ClosureTarget::Road)
+                }
+                PermanentClosureTarget::Lane(ref l) => {
+                    l.from_permanent(map).map(ClosureTarget::Lane)
+                }
+                PermanentClosureTarget::Turn(ref t) => {
+                    t.from_permanent(map).map(ClosureTarget::Turn)
+                }
+            };
+            if let Some(target) = target {
+                match Closure::new(target, c.start, c.end) {
+                    Ok(closure) => {
+                        edits.closures.push(closure);
+                    }
+                    Err(err) => {
+                        warnings.push(format!("Skipping closure {:?}: {}", c, err));
+                    }
+                }
+            } else {
+                warnings.push(format!("Closure no longer applies: {:?}", c));
+            }
+        }
+
+        Warn::warnings(edits, warnings)
+    }
+
+    // Everything the edits refer to, each once
+    fn all_intersections(&self) -> Vec<OriginalIntersection> {
+        let mut all = Vec::new();
+        for (l, _) in &self.lane_overrides {
+            all.extend(l.parent.endpoints());
+        }
+        for (r, _) in &self.road_overrides {
+            all.extend(r.endpoints());
+        }
+        for (i, _) in &self.intersection_type_overrides {
+            all.push(*i);
+        }
+        for ss in &self.stop_sign_overrides {
+            all.push(ss.id);
+            for (t, _) in &ss.turns {
+                all.extend(t.intersections());
+            }
+        }
+        for ts in &self.traffic_signal_overrides {
+            all.push(ts.id);
+            for c in &ts.cycles {
+                for t in c.priority_turns.iter().chain(c.yield_turns.iter()) {
+                    all.extend(t.intersections());
+                }
+            }
+        }
+        for c in &self.closures {
+            match c.target {
+                PermanentClosureTarget::Road(ref r) => all.extend(r.endpoints()),
+                PermanentClosureTarget::Lane(ref l) => all.extend(l.parent.endpoints()),
+                PermanentClosureTarget::Turn(ref t) => all.extend(t.intersections()),
+            }
+        }
+        let mut unique: Vec<OriginalIntersection> = Vec::new();
+        for i in all {
+            if !unique.contains(&i) {
+                unique.push(i);
+            }
+        }
+        unique
+    }
+
+    // Load the edits, reporting anything that no longer applies. Edits saved before they referred
+    // to OSM IDs are migrated, assuming the map hasn't been reconverted since then.
+    pub fn load(
+        map_name: &str,
+        edits_name: &str,
+        map: &Map,
+        timer: &mut Timer,
+    ) -> Result<MapEdits, Error> {
+        let path = format!("../data/edits/{}/{}.json", map_name, edits_name);
+        let edits = match abstutil::read_json::<PermanentMapEdits>(&path) {
+            Ok(perma) => perma.from_permanent(map),
+            Err(err) => match abstutil::read_json::<MapEdits>(&path) {
+                Ok(old) => migrate_raw_ids(old, map),
+                Err(_) => {
+                    return Err(Error::new(err.to_string()));
+                }
+            },
+        };
+        Ok(edits.with_context(timer, format!("Loading {} edits", edits_name)))
+    }
+}
+
+// The old format refers to everything by LaneID, RoadID, and so on. Keep whatever still exists.
+fn migrate_raw_ids(old: MapEdits, map: &Map) -> Warn<MapEdits> {
+    let mut warnings = vec![format!(
+        "{} uses raw IDs; save it again to switch to OSM IDs",
+        old.edits_name
+    )];
+    let lane_exists = |l: LaneID| map.maybe_get_l(l).is_some();
+    let turn_exists = |t: &TurnID| map.maybe_get_t(*t).is_some();
+    let mut edits = MapEdits::new(old.map_name);
+    edits.edits_name = old.edits_name;
+
+    for (l, lt) in old.lane_overrides {
+        if lane_exists(l) {
+            edits.lane_overrides.insert(l, lt);
+        } else {
+            warnings.push(format!("Lane override to {:?} for missing {}", lt, l));
+        }
+    }
+    for (r, spec) in old.road_overrides {
+        if map.maybe_get_r(r).is_some() {
+            edits.road_overrides.insert(r, spec);
+        } else {
+            warnings.push(format!(
+                "Road override to {} for missing {}",
+                spec.to_string(),
+                r
+            ));
+        }
+    }
+    for (i, it) in old.intersection_type_overrides {
+        if map.maybe_get_i(i).is_some() {
+            edits.intersection_type_overrides.insert(i, it);
+        } else {
+            warnings.push(format!("Changing missing {} to {:?}", i, it));
+        }
+    }
+    for (i, ss) in old.stop_sign_overrides {
+        if map.maybe_get_i(i).is_some() && ss.turns.keys().all(turn_exists) {
+            edits.stop_sign_overrides.insert(i, ss);
+        } else {
+            warnings.push(format!("Stop sign at {} refers to missing turns", i));
+        }
+    }
+    for (i, ts) in old.traffic_signal_overrides {
+        if map.maybe_get_i(i).is_some()
+            && ts.cycles.iter().all(|c| {
+                c.priority_turns.iter().all(turn_exists) && c.yield_turns.iter().all(turn_exists)
+            })
+        {
+            edits.traffic_signal_overrides.insert(i, ts);
+        } else {
+            warnings.push(format!("Traffic signal at {} refers to missing turns", i));
+        }
+    }
+    for c in old.closures {
+        let exists = match c.target {
+            ClosureTarget::Road(r) => map.maybe_get_r(r).is_some(),
+            ClosureTarget::Lane(l) => lane_exists(l),
+            ClosureTarget::Turn(t) => turn_exists(&t),
+        };
+        if exists {
+            edits.closures.push(c);
+        } else {
+            warnings.push(format!("Closure of something missing: {}", c.describe()));
+        }
+    }
+
+    Warn::warnings(edits, warnings)
+}
+
+// The map might not have the intersection type overrides applied yet.
+fn effective_type(
+    id: IntersectionID,
+    type_overrides: &BTreeMap<IntersectionID, IntersectionType>,
+    map: &Map,
+) -> IntersectionType {
+    type_overrides
+        .get(&id)
+        .cloned()
+        .unwrap_or_else(|| map.get_i(id).original_intersection_type)
+}
+
+fn resolve_stop_sign(
+    ss: &PermanentStopSign,
+    type_overrides: &BTreeMap<IntersectionID, IntersectionType>,
+    map: &Map,
+) -> Result<ControlStopSign, String> {
+    let id = ss
+        .id
+        .from_permanent(map)
+        .ok_or_else(|| "intersection is gone".to_string())?;
+    if effective_type(id, type_overrides, map) != IntersectionType::StopSign {
+        return Err(format!("{} isn't a stop sign anymore", id));
+    }
+    let mut turns: BTreeMap<TurnID, TurnPriority> = BTreeMap::new();
+    for (t, pri) in &ss.turns {
+        let turn = t
+            .from_permanent(map)
+            .ok_or_else(|| format!("turn {:?} is gone", t))?;
+        turns.insert(turn, *pri);
+    }
+    if turns.len() != map.get_i(id).turns.len() {
+        return Err(format!(
+            "{} has {} turns now, but the edits only cover {}",
+            id,
+            map.get_i(id).turns.len(),
+            turns.len()
+        ));
+    }
+    Ok(ControlStopSign { id, turns })
+}
+
+fn resolve_traffic_signal(
+    ts: &PermanentTrafficSignal,
+    type_overrides: &BTreeMap<IntersectionID, IntersectionType>,
+    map: &Map,
+) -> Result<ControlTrafficSignal, String> {
+    let id = ts
+        .id
+        .from_permanent(map)
+        .ok_or_else(|| "intersection is gone".to_string())?;
+    if effective_type(id, type_overrides, map) != IntersectionType::TrafficSignal {
+        return Err(format!("{} isn't a traffic signal anymore", id));
+    }
+    let mut cycles = Vec::new();
+    for (idx, c) in ts.cycles.iter().enumerate() {
+        let mut cycle = Cycle::new(id, idx);
+        cycle.duration = c.duration;
+        for t in &c.priority_turns {
+            cycle.priority_turns.insert(
+                t.from_permanent(map)
+                    .ok_or_else(|| format!("turn {:?} is gone", t))?,
+            );
+        }
+        for t in &c.yield_turns {
+            cycle.yield_turns.insert(
+                t.from_permanent(map)
+                    .ok_or_else(|| format!("turn {:?} is gone", t))?,
+            );
+        }
+        cycles.push(cycle);
+    }
+    Ok(ControlTrafficSignal { id, cycles })
+}
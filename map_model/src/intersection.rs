@@ -20,9 +20,13 @@ pub enum IntersectionType {
     StopSign,
     TrafficSignal,
     Border,
+    // Nothing controls this intersection; agents go whenever nothing conflicting is happening.
+    Uncontrolled,
+    // Entering traffic yields to anybody already circulating.
+    Roundabout,
 }
 
-#[derive(Serialize, Deserialize, Debug)]
+#[derive(Serialize, Deserialize, Debug, Clone)]
 pub struct Intersection {
     pub id: IntersectionID,
     // TODO Describe what this represents.
@@ -33,8 +37,11 @@ pub struct Intersection {
     pub elevation: Distance,
 
     pub intersection_type: IntersectionType,
+    // Before any edits
+    pub original_intersection_type: IntersectionType,
     pub label: Option<String>,
     pub stable_id: raw_data::StableIntersectionID,
+    pub osm_node_id: Option<i64>,
 
     // Note that a lane may belong to both incoming_lanes and outgoing_lanes.
     // TODO narrow down when and why. is it just sidewalks in weird cases?
@@ -69,6 +76,23 @@ impl Intersection {
             .collect()
     }
 
+    // Sidewalks and shared-use paths
+    pub fn get_incoming_walking_lanes(&self, map: &Map) -> Vec<LaneID> {
+        self.incoming_lanes
+            .iter()
+            .filter(|l| map.get_l(**l).lane_type.supports_walking())
+            .cloned()
+            .collect()
+    }
+
+    pub fn get_outgoing_walking_lanes(&self, map: &Map) -> Vec<LaneID> {
+        self.outgoing_lanes
+            .iter()
+            .filter(|l| map.get_l(**l).lane_type.supports_walking())
+            .cloned()
+            .collect()
+    }
+
     pub fn get_zorder(&self, map: &Map) -> isize {
         // TODO Not sure min makes sense -- what about a 1 and a 0? Prefer the nonzeros. If there's
         // a -1 and a 1... need to see it to know what to do.
@@ -24,6 +24,15 @@ pub enum LaneType {
     Sidewalk,
     Biking,
     Bus,
+    // Bikes in both directions, separated from traffic. See contraflow_bike_track for how the two
+    // directions are represented.
+    TwoWayBiking,
+    // Pedestrians and bikes mix.
+    SharedUse,
+    // Only for vehicles about to turn left.
+    CenterTurn,
+    // Buses and bikes, but not cars.
+    BusAndBike,
 }
 
 impl LaneType {
@@ -32,12 +41,35 @@ impl LaneType {
             LaneType::Driving => true,
             LaneType::Biking => true,
             LaneType::Bus => true,
+            LaneType::TwoWayBiking => true,
+            LaneType::SharedUse => true,
+            LaneType::CenterTurn => true,
+            LaneType::BusAndBike => true,
+            LaneType::Parking | LaneType::Sidewalk => false,
+        }
+    }
+
+    pub fn supports_walking(self) -> bool {
+        self == LaneType::Sidewalk || self == LaneType::SharedUse
+    }
+
+    // Not counting driving lanes, which bikes can use too.
+    pub fn is_for_bikes(self) -> bool {
+        match self {
+            LaneType::Biking
+            | LaneType::TwoWayBiking
+            | LaneType::SharedUse
+            | LaneType::BusAndBike => true,
             _ => false,
         }
     }
+
+    pub fn is_for_buses(self) -> bool {
+        self == LaneType::Bus || self == LaneType::BusAndBike
+    }
 }
 
-#[derive(Serialize, Deserialize, Debug)]
+#[derive(Serialize, Deserialize, Debug, Clone)]
 pub struct Lane {
     pub id: LaneID,
     pub parent: RoadID,
@@ -106,6 +138,16 @@ impl Lane {
         self.lane_center_pts.length()
     }
 
+    // Rise over run, from src_i to dst_i. Positive is uphill.
+    pub fn grade(&self, map: &Map) -> f64 {
+        let rise = map.get_i(self.dst_i).elevation - map.get_i(self.src_i).elevation;
+        let run = self.length();
+        if run == Distance::ZERO {
+            return 0.0;
+        }
+        rise / run
+    }
+
     pub fn dump_debug(&self) {
         println!(
             "\nlet lane_center_l{}_pts = {}",
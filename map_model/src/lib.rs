@@ -1,4 +1,5 @@
 mod area;
+mod bike_parking;
 mod building;
 mod bus_stop;
 mod edits;
@@ -16,15 +17,22 @@ mod traversable;
 mod turn;
 
 pub use crate::area::{Area, AreaID, AreaType};
-pub use crate::building::{Building, BuildingID, BuildingType, FrontPath};
+pub use crate::bike_parking::{BikeParking, BikeParkingID, BikeParkingType};
+pub use crate::building::{Building, BuildingCapacity, BuildingID, BuildingType, FrontPath};
 pub use crate::bus_stop::{BusRoute, BusRouteID, BusStop, BusStopID};
-pub use crate::edits::MapEdits;
+pub use crate::edits::{
+    Closure, ClosureTarget, EditEffects, EditsDiff, MapEdits, OriginalIntersection, OriginalLane,
+    OriginalRoad, OriginalTurn, PermanentMapEdits,
+};
 pub use crate::intersection::{Intersection, IntersectionID, IntersectionType};
 pub use crate::lane::{Lane, LaneID, LaneType, PARKING_SPOT_LENGTH};
-pub use crate::make::RoadSpec;
+pub use crate::make::{
+    classify_building, contraflow_bike_track, estimate_building_capacity, MergeReason, MergedRoad,
+    RoadSpec,
+};
 pub use crate::map::Map;
 pub use crate::neighborhood::{FullNeighborhoodInfo, Neighborhood, NeighborhoodBuilder};
-pub use crate::pathfind::{Path, PathRequest, PathStep};
+pub use crate::pathfind::{Path, PathRequest, PathStep, PathfinderOptions};
 pub use crate::road::{DirectedRoadID, Road, RoadID};
 pub use crate::stop_signs::ControlStopSign;
 pub use crate::traffic_signals::{ControlTrafficSignal, Cycle};
@@ -35,8 +43,10 @@ use geom::Distance;
 
 pub const LANE_THICKNESS: Distance = Distance::const_meters(2.5);
 
+impl Cloneable for ClosureTarget {}
 impl Cloneable for ControlTrafficSignal {}
 impl Cloneable for IntersectionID {}
+impl Cloneable for IntersectionType {}
 impl Cloneable for MapEdits {}
 impl Cloneable for Neighborhood {}
 impl Cloneable for NeighborhoodBuilder {}
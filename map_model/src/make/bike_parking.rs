@@ -0,0 +1,80 @@
+use crate::make::sidewalk_finder::find_sidewalk_points;
+use crate::{raw_data, BikeParking, BikeParkingID, BikeParkingType, Map};
+use abstutil::Timer;
+use geom::{Distance, HashablePt2D, Pt2D};
+use std::collections::HashSet;
+
+// When OSM doesn't say how many bikes fit
+const DEFAULT_RACK_CAPACITY: usize = 8;
+const DEFAULT_DOCK_CAPACITY: usize = 15;
+
+pub fn make_bike_parking(
+    map: &Map,
+    input: &Vec<raw_data::BikeParking>,
+    timer: &mut Timer,
+) -> Vec<BikeParking> {
+    timer.start("make bike parking");
+    let mut query: Vec<(HashablePt2D, &raw_data::BikeParking)> = Vec::new();
+    for p in input {
+        if let Some(pt) = Pt2D::from_gps(p.point, map.get_gps_bounds()) {
+            query.push((pt.into(), p));
+        }
+    }
+    let sidewalk_pts = find_sidewalk_points(
+        map.get_bounds(),
+        query
+            .iter()
+            .map(|(pt, _)| *pt)
+            .collect::<HashSet<HashablePt2D>>(),
+        map.all_lanes(),
+        Distance::meters(20.0),
+        timer,
+    );
+
+    let mut results: Vec<BikeParking> = Vec::new();
+    for (pt, orig) in query {
+        let sidewalk_pos = if let Some(pos) = sidewalk_pts.get(&pt) {
+            *pos
+        } else {
+            timer.warn(format!(
+                "Bike parking at OSM node {} isn't near a sidewalk",
+                orig.osm_node_id
+            ));
+            continue;
+        };
+        let sidewalk = sidewalk_pos.lane();
+        let driving_lane = if let Some(l) = map.get_parent(sidewalk).sidewalk_to_bike(sidewalk) {
+            l
+        } else {
+            timer.warn(format!(
+                "Bike parking at OSM node {} is next to {}, which has no driving/bike lane",
+                orig.osm_node_id, sidewalk
+            ));
+            continue;
+        };
+
+        let (parking_type, default_capacity) = if orig.is_bikeshare {
+            (BikeParkingType::Bikeshare, DEFAULT_DOCK_CAPACITY)
+        } else {
+            (BikeParkingType::Rack, DEFAULT_RACK_CAPACITY)
+        };
+        results.push(BikeParking {
+            id: BikeParkingID(results.len()),
+            parking_type,
+            capacity: orig
+                .capacity
+                .filter(|cap| *cap > 0)
+                .unwrap_or(default_capacity),
+            sidewalk_pos,
+            driving_pos: sidewalk_pos.equiv_pos(driving_lane, map),
+            osm_node_id: orig.osm_node_id,
+        });
+    }
+    timer.note(format!(
+        "{} of {} bike racks and bikeshare docks are usable",
+        results.len(),
+        input.len()
+    ));
+    timer.stop("make bike parking");
+    results
+}
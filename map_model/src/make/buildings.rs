@@ -1,5 +1,5 @@
 use crate::make::sidewalk_finder::find_sidewalk_points;
-use crate::{raw_data, Building, BuildingID, BuildingType, FrontPath, Lane};
+use crate::{raw_data, Building, BuildingCapacity, BuildingID, BuildingType, FrontPath, Lane};
 use abstutil::Timer;
 use geom::{Bounds, Distance, GPSBounds, HashablePt2D, Line, Polygon, Pt2D};
 use std::collections::{BTreeMap, HashSet};
@@ -43,11 +43,30 @@ pub fn make_all_buildings(
             }
             let line = trim_front_path(&points, Line::new(bldg_center.into(), sidewalk_pt));
 
+            let holes: Vec<Vec<Pt2D>> = input[idx]
+                .inner_rings
+                .iter()
+                .map(|ring| gps_bounds.must_convert(ring))
+                .collect();
+
+            let building_type = classify_building(
+                input[idx].num_residential_units,
+                input[idx].landuse.as_ref().map(|s| s.as_str()),
+                &input[idx].osm_tags,
+            );
+            let polygon = Polygon::with_holes(&points, &holes);
+            let capacity = estimate_building_capacity(
+                building_type,
+                input[idx].num_residential_units,
+                &input[idx].osm_tags,
+                &polygon,
+            );
+
             let id = BuildingID(results.len());
             results.push(Building {
                 id,
-                building_type: classify(input[idx].num_residential_units, &input[idx].osm_tags),
-                polygon: Polygon::new(&points),
+                building_type,
+                polygon,
                 osm_tags: input[idx].osm_tags.clone(),
                 osm_way_id: input[idx].osm_way_id,
                 front_path: FrontPath {
@@ -56,6 +75,7 @@ pub fn make_all_buildings(
                     line,
                 },
                 num_residential_units: input[idx].num_residential_units,
+                capacity,
             });
         }
     }
@@ -84,29 +104,145 @@ fn trim_front_path(bldg_points: &Vec<Pt2D>, path: Line) -> Line {
     path
 }
 
-fn classify(num_residential_units: Option<usize>, tags: &BTreeMap<String, String>) -> BuildingType {
-    if num_residential_units.is_some() {
-        return BuildingType::Residence;
+// Rules of thumb for how much floor area each person takes up, in square meters
+const AREA_PER_RESIDENTIAL_UNIT: f64 = 80.0;
+const RESIDENTS_PER_UNIT: f64 = 2.1;
+const AREA_PER_RETAIL_JOB: f64 = 50.0;
+const AREA_PER_RETAIL_VISITOR: f64 = 5.0;
+const AREA_PER_OFFICE_JOB: f64 = 25.0;
+const AREA_PER_STUDENT: f64 = 10.0;
+const STUDENTS_PER_SCHOOL_JOB: f64 = 12.0;
+const AREA_PER_HOSPITAL_JOB: f64 = 40.0;
+const AREA_PER_HOSPITAL_VISITOR: f64 = 20.0;
+const AREA_PER_PARKING_SPOT: f64 = 30.0;
+
+// Amenities that people visit to buy something. Others, like places of worship, libraries, and
+// fire stations, don't say much about the building.
+const RETAIL_AMENITIES: [&str; 12] = [
+    "bank",
+    "bar",
+    "cafe",
+    "fast_food",
+    "food_court",
+    "ice_cream",
+    "marketplace",
+    "pharmacy",
+    "pub",
+    "restaurant",
+    "cinema",
+    "theatre",
+];
+
+// The landuse comes from the area around the building, and only matters when the building's own
+// tags say nothing.
+pub fn classify_building(
+    num_residential_units: Option<usize>,
+    landuse: Option<&str>,
+    tags: &BTreeMap<String, String>,
+) -> BuildingType {
+    let building = tags.get("building").map(|s| s.as_str()).unwrap_or("yes");
+    let amenity = tags.get("amenity").map(|s| s.as_str());
+
+    if building == "parking" || amenity == Some("parking") {
+        return BuildingType::ParkingGarage;
     }
-    if tags.get("building") == Some(&"apartments".to_string()) {
-        return BuildingType::Residence;
+    if ["school", "kindergarten", "college", "university"].contains(&building)
+        || amenity.map_or(false, |a| {
+            ["school", "kindergarten", "college", "university"].contains(&a)
+        })
+    {
+        return BuildingType::School;
     }
-    if tags.get("building") == Some(&"residential".to_string()) {
-        return BuildingType::Residence;
+    if building == "hospital" || amenity == Some("hospital") || amenity == Some("clinic") {
+        return BuildingType::Hospital;
     }
-    if tags.get("building") == Some(&"house".to_string()) {
+
+    let residential = num_residential_units.is_some()
+        || [
+            "apartments",
+            "residential",
+            "house",
+            "detached",
+            "semidetached_house",
+            "terrace",
+            "dormitory",
+        ]
+        .contains(&building);
+    let retail = tags.contains_key("shop")
+        || amenity.map_or(false, |a| RETAIL_AMENITIES.contains(&a))
+        || ["retail", "supermarket", "kiosk"].contains(&building);
+    let office = tags.contains_key("office") || ["office", "commercial"].contains(&building);
+    if residential && (retail || office) {
+        return BuildingType::MixedUse;
+    }
+    if residential {
         return BuildingType::Residence;
     }
-
-    if tags.contains_key(&"shop".to_string()) || tags.contains_key(&"amenity".to_string()) {
-        return BuildingType::Business;
+    if retail {
+        return BuildingType::Retail;
     }
-    if tags.get("building") == Some(&"commercial".to_string()) {
-        return BuildingType::Business;
+    if office {
+        return BuildingType::Office;
     }
-    if tags.get("building") == Some(&"retail".to_string()) {
-        return BuildingType::Business;
+
+    // Plain building=yes is very common, so fall back to the land use around it.
+    match landuse {
+        Some("residential") => BuildingType::Residence,
+        Some("retail") => BuildingType::Retail,
+        Some("commercial") => BuildingType::Office,
+        _ => BuildingType::Unknown,
     }
+}
+
+pub fn estimate_building_capacity(
+    building_type: BuildingType,
+    num_residential_units: Option<usize>,
+    tags: &BTreeMap<String, String>,
+    polygon: &Polygon,
+) -> BuildingCapacity {
+    let levels = tags
+        .get("building:levels")
+        .and_then(|l| l.parse::<f64>().ok())
+        .filter(|l| *l >= 1.0)
+        .unwrap_or(1.0);
+    let floor_area = polygon.area() * levels;
 
-    BuildingType::Unknown
+    let units = |area: f64| {
+        num_residential_units
+            .unwrap_or_else(|| ((area / AREA_PER_RESIDENTIAL_UNIT) as usize).max(1))
+    };
+    let mut capacity = BuildingCapacity::default();
+    match building_type {
+        BuildingType::Residence => {
+            capacity.residential_units = units(floor_area);
+        }
+        BuildingType::Retail => {
+            capacity.jobs = (floor_area / AREA_PER_RETAIL_JOB) as usize;
+            capacity.visitors = (floor_area / AREA_PER_RETAIL_VISITOR) as usize;
+        }
+        BuildingType::Office => {
+            capacity.jobs = (floor_area / AREA_PER_OFFICE_JOB) as usize;
+        }
+        BuildingType::School => {
+            capacity.visitors = (floor_area / AREA_PER_STUDENT) as usize;
+            capacity.jobs = (capacity.visitors as f64 / STUDENTS_PER_SCHOOL_JOB) as usize;
+        }
+        BuildingType::Hospital => {
+            capacity.jobs = (floor_area / AREA_PER_HOSPITAL_JOB) as usize;
+            capacity.visitors = (floor_area / AREA_PER_HOSPITAL_VISITOR) as usize;
+        }
+        BuildingType::MixedUse => {
+            // Assume the ground floor is retail and the rest is residential.
+            let ground = polygon.area();
+            capacity.residential_units = units((floor_area - ground).max(ground));
+            capacity.jobs = (ground / AREA_PER_RETAIL_JOB) as usize;
+            capacity.visitors = (ground / AREA_PER_RETAIL_VISITOR) as usize;
+        }
+        BuildingType::ParkingGarage => {
+            capacity.parking_spots = (floor_area / AREA_PER_PARKING_SPOT) as usize;
+        }
+        BuildingType::Unknown => {}
+    }
+    capacity.residents = (capacity.residential_units as f64 * RESIDENTS_PER_UNIT).round() as usize;
+    capacity
 }
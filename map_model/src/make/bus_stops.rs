@@ -45,8 +45,10 @@ pub fn make_bus_stops(
 
     for (id, dists_set) in stops_per_sidewalk.consume().into_iter() {
         let road = map.get_parent(id);
-        if let Ok(driving_lane) = road.find_closest_lane(id, vec![LaneType::Driving, LaneType::Bus])
-        {
+        if let Ok(driving_lane) = road.find_closest_lane(
+            id,
+            vec![LaneType::Driving, LaneType::Bus, LaneType::BusAndBike],
+        ) {
             let mut dists: Vec<(Distance, HashablePt2D)> = dists_set.into_iter().collect();
             dists.sort_by_key(|(dist, _)| *dist);
             for (idx, (dist_along, orig_pt)) in dists.into_iter().enumerate() {
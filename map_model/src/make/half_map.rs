@@ -1,6 +1,6 @@
 use crate::{
     make, raw_data, Area, AreaID, Building, Intersection, IntersectionID, IntersectionType, Lane,
-    LaneID, Road, RoadID, Turn, TurnID, LANE_THICKNESS,
+    LaneID, MergedRoad, Road, RoadID, Turn, TurnID, LANE_THICKNESS,
 };
 use abstutil::Timer;
 use geom::{Bounds, GPSBounds, Polygon, Pt2D};
@@ -15,6 +15,7 @@ pub struct HalfMap {
     pub areas: Vec<Area>,
 
     pub turn_lookup: Vec<TurnID>,
+    pub merged_roads: Vec<MergedRoad>,
 }
 
 pub fn make_half_map(
@@ -32,6 +33,7 @@ pub fn make_half_map(
         buildings: Vec::new(),
         areas: Vec::new(),
         turn_lookup: Vec::new(),
+        merged_roads: initial_map.merged_roads.clone(),
     };
 
     let road_id_mapping: BTreeMap<raw_data::StableRoadID, RoadID> = initial_map
@@ -56,9 +58,11 @@ pub fn make_half_map(
             turns: Vec::new(),
             elevation: raw_i.elevation,
             // Might change later
-            intersection_type: raw_i.intersection_type,
+            intersection_type: i.intersection_type,
+            original_intersection_type: i.intersection_type,
             label: raw_i.label.clone(),
             stable_id: i.id,
+            osm_node_id: raw_i.osm_node_id,
             incoming_lanes: Vec::new(),
             outgoing_lanes: Vec::new(),
             roads: i.roads.iter().map(|id| road_id_mapping[id]).collect(),
@@ -126,6 +130,11 @@ pub fn make_half_map(
                 bus_stops: Vec::new(),
             });
         }
+        // Both halves of a two-way bike track occupy the same space.
+        if let Some((track, contraflow)) = road.two_way_track() {
+            half_map.lanes[contraflow.0].lane_center_pts =
+                half_map.lanes[track.0].lane_center_pts.reversed();
+        }
         if road.get_name() == "???" {
             timer.warn(format!(
                 "{} has no name. Tags: {:?}",
@@ -138,6 +147,7 @@ pub fn make_half_map(
     for i in half_map.intersections.iter_mut() {
         if is_border(i, &half_map.lanes) {
             i.intersection_type = IntersectionType::Border;
+            i.original_intersection_type = IntersectionType::Border;
             continue;
         }
 
@@ -190,7 +200,13 @@ pub fn make_half_map(
         half_map.areas.push(Area {
             id: AreaID(idx),
             area_type: a.area_type,
-            polygon: Polygon::new(&pts),
+            polygon: Polygon::with_holes(
+                &pts,
+                &a.inner_rings
+                    .iter()
+                    .map(|ring| gps_bounds.must_convert(ring))
+                    .collect(),
+            ),
             osm_tags: a.osm_tags.clone(),
             osm_id: a.osm_id,
         });
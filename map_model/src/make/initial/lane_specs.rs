@@ -23,6 +23,11 @@ pub fn get_lane_types(
         return (vec![LaneType::Driving, LaneType::Sidewalk], Vec::new());
     }
     if tags.get("highway") == Some(&"footway".to_string()) {
+        if tags.get("bicycle") == Some(&"designated".to_string())
+            || tags.get("bicycle") == Some(&"yes".to_string())
+        {
+            return (vec![LaneType::SharedUse], Vec::new());
+        }
         return (vec![LaneType::Sidewalk], Vec::new());
     }
 
@@ -52,20 +57,46 @@ pub fn get_lane_types(
         driving_lanes_per_side.pop();
     }
 
+    // Buses and bikes share one lane, instead of getting one each.
+    let shared_bus_bike_lane =
+        has_bus_lane && tags.get("cycleway") == Some(&"share_busway".to_string());
     let has_bike_lane = tags.get("cycleway") == Some(&"lane".to_string());
+    // One track on the forwards side carries bikes both ways.
+    let has_two_way_bike_track = tags.get("cycleway") == Some(&"track".to_string())
+        && tags.get("oneway:bicycle") == Some(&"no".to_string());
+    let has_center_turn_lane = !oneway
+        && tags.get("lanes:both_ways") == Some(&"1".to_string())
+        && tags.get("turn:lanes:both_ways") == Some(&"left".to_string());
     let has_sidewalk = tags.get("highway") != Some(&"motorway".to_string())
         && tags.get("highway") != Some(&"motorway_link".to_string());
     // TODO Bus/bike and parking lanes can coexist, but then we have to make sure cars are fine
     // with merging in/out of the bus/bike lane to park. ><
     //let has_parking = has_sidewalk && !has_bus_lane && !has_bike_lane;
 
-    let mut fwd_side = driving_lanes_per_side.clone();
-    if has_bus_lane {
-        fwd_side.push(LaneType::Bus);
+    let mut fwd_side = Vec::new();
+    if has_center_turn_lane {
+        fwd_side.push(LaneType::CenterTurn);
     }
-    if has_bike_lane {
-        fwd_side.push(LaneType::Biking);
+    fwd_side.extend(driving_lanes_per_side.clone());
+    if shared_bus_bike_lane {
+        fwd_side.push(LaneType::BusAndBike);
+    } else {
+        if has_bus_lane {
+            fwd_side.push(LaneType::Bus);
+        }
+        if has_bike_lane {
+            fwd_side.push(LaneType::Biking);
+        }
     }
+    if has_two_way_bike_track {
+        fwd_side.push(LaneType::TwoWayBiking);
+    }
+    // The contraflow half of the track goes last on the other side.
+    let contraflow_track = if has_two_way_bike_track {
+        vec![LaneType::TwoWayBiking]
+    } else {
+        Vec::new()
+    };
     // TODO Should we warn when a link road has parking assigned to it from the blockface?
     let is_link = match tags.get("highway") {
         Some(hwy) => hwy.ends_with("_link"),
@@ -82,19 +113,25 @@ pub fn get_lane_types(
         // Only residential streets have a sidewalk on the other side of a one-way.
         // Ignore off-side parking, since cars don't know how to park on lanes without a driving
         // lane in that direction too.
-        let back_side = if has_sidewalk && tags.get("highway") == Some(&"residential".to_string()) {
-            vec![LaneType::Sidewalk]
-        } else {
-            Vec::new()
-        };
+        let mut back_side =
+            if has_sidewalk && tags.get("highway") == Some(&"residential".to_string()) {
+                vec![LaneType::Sidewalk]
+            } else {
+                Vec::new()
+            };
+        back_side.extend(contraflow_track);
         (fwd_side, back_side)
     } else {
         let mut back_side = driving_lanes_per_side;
-        if has_bus_lane {
-            back_side.push(LaneType::Bus);
-        }
-        if has_bike_lane {
-            back_side.push(LaneType::Biking);
+        if shared_bus_bike_lane {
+            back_side.push(LaneType::BusAndBike);
+        } else {
+            if has_bus_lane {
+                back_side.push(LaneType::Bus);
+            }
+            if has_bike_lane {
+                back_side.push(LaneType::Biking);
+            }
         }
         if parking_lane_back && !is_link {
             back_side.push(LaneType::Parking);
@@ -102,10 +139,26 @@ pub fn get_lane_types(
         if has_sidewalk {
             back_side.push(LaneType::Sidewalk);
         }
+        back_side.extend(contraflow_track);
         (fwd_side, back_side)
     }
 }
 
+// Vehicles only move along a lane in its direction, so a two-way bike track needs a lane pointing
+// each way. The track itself goes on one side of the road, and its contraflow half goes last on
+// the other side. The map gives the contraflow half the track's geometry, reversed, so it doesn't
+// take up any more room. Returns true if the contraflow half is on the back side.
+pub fn contraflow_bike_track(fwd: &[LaneType], back: &[LaneType]) -> Option<bool> {
+    let is_track = |lt: &LaneType| *lt == LaneType::TwoWayBiking;
+    if back.last().map(is_track) == Some(true) && fwd.iter().any(is_track) {
+        Some(true)
+    } else if fwd.last().map(is_track) == Some(true) && back.iter().any(is_track) {
+        Some(false)
+    } else {
+        None
+    }
+}
+
 #[derive(Debug, PartialEq, Serialize, Deserialize)]
 pub struct LaneSpec {
     pub lane_type: LaneType,
@@ -135,8 +188,9 @@ pub fn get_lane_specs(r: &raw_data::Road, id: raw_data::StableRoadID) -> Vec<Lan
     specs
 }
 
-// This is a convenient way for the synthetic map editor to plumb instructions here.
-#[derive(Serialize, Deserialize)]
+// This is a convenient way for the synthetic map editor to plumb instructions here. Map edits also
+// use it to override all of the lanes of a road.
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq)]
 pub struct RoadSpec {
     pub fwd: Vec<LaneType>,
     pub back: Vec<LaneType>,
@@ -186,6 +240,10 @@ impl RoadSpec {
             LaneType::Sidewalk => 's',
             LaneType::Biking => 'b',
             LaneType::Bus => 'u',
+            LaneType::TwoWayBiking => 't',
+            LaneType::SharedUse => 'm',
+            LaneType::CenterTurn => 'c',
+            LaneType::BusAndBike => 'x',
         }
     }
 
@@ -196,6 +254,10 @@ impl RoadSpec {
             's' => Some(LaneType::Sidewalk),
             'b' => Some(LaneType::Biking),
             'u' => Some(LaneType::Bus),
+            't' => Some(LaneType::TwoWayBiking),
+            'm' => Some(LaneType::SharedUse),
+            'c' => Some(LaneType::CenterTurn),
+            'x' => Some(LaneType::BusAndBike),
             _ => None,
         }
     }
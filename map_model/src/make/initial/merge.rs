@@ -1,63 +1,111 @@
 use crate::make::initial::{geometry, InitialMap};
 use crate::raw_data::{StableIntersectionID, StableRoadID};
+use crate::{raw_data, IntersectionType};
 use abstutil::Timer;
 use geom::Distance;
+use serde_derive::{Deserialize, Serialize};
 use std::collections::HashSet;
 
-pub fn short_roads(map: &mut InitialMap, timer: &mut Timer) {
-    if false {
-        // I228
-        merge(map, StableRoadID(311), timer);
+// A record of one road that was removed by merging its two intersections together.
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq)]
+pub struct MergedRoad {
+    pub road: StableRoadID,
+    pub reason: MergeReason,
+    pub original_length: Distance,
+    pub trimmed_length: Distance,
+    pub deleted_i: StableIntersectionID,
+    pub kept_i: StableIntersectionID,
+}
 
-        // I201
-        merge(map, StableRoadID(240), timer);
+#[derive(Serialize, Deserialize, Clone, Copy, Debug, PartialEq)]
+pub enum MergeReason {
+    Roundabout,
+    ShortRoad,
+}
 
-        // I37
-        merge(map, StableRoadID(91), timer);
+// OSM draws a roundabout as a ring of ways tagged junction=roundabout, split up wherever another
+// road enters. Collapse each ring into one intersection controlled as a roundabout.
+pub fn roundabouts(map: &mut InitialMap, data: &raw_data::Map, timer: &mut Timer) {
+    let ring_roads: Vec<StableRoadID> = map
+        .roads
+        .keys()
+        .filter(|id| {
+            let junction = data.roads[id].osm_tags.get("junction");
+            junction == Some(&"roundabout".to_string()) || junction == Some(&"circular".to_string())
+        })
+        .cloned()
+        .collect();
+    if ring_roads.is_empty() {
+        return;
+    }
+    timer.note(format!("Collapsing {} roundabout ways", ring_roads.len()));
+    for r in ring_roads {
+        // Merging the rest of the ring turns the last piece into a loop, which gets removed.
+        if !map.roads.contains_key(&r) {
+            continue;
+        }
+        let i = merge(map, r, MergeReason::Roundabout, timer);
+        map.intersections.get_mut(&i).unwrap().intersection_type = IntersectionType::Roundabout;
+    }
+}
 
-        // I40
-        merge(map, StableRoadID(59), timer);
+// Roads shorter than this after trimming are probably just connectors between two halves of the
+// same real-world intersection, like where dual carriageways cross.
+const SHORT_ROAD_THRESHOLD: Distance = Distance::const_meters(5.0);
 
-        // I25
-        merge(map, StableRoadID(389), timer);
-        merge(map, StableRoadID(22), timer);
+// Consolidate each cluster of intersections joined by short roads into one intersection with one
+// control.
+pub fn short_roads(map: &mut InitialMap, timer: &mut Timer) {
+    let orig_count = map.roads.len();
+    let mut skip: HashSet<StableRoadID> = HashSet::new();
+
+    // Every time we change a road, other roads we might've already processed could shorten, so
+    // we have to redo everything. Note that order of merging doesn't SEEM to matter much...
+    // tried tackling the shortest roads first, no effect.
+    loop {
+        let candidate = map
+            .roads
+            .values()
+            .find(|r| !skip.contains(&r.id) && r.trimmed_center_pts.length() < SHORT_ROAD_THRESHOLD)
+            .map(|r| r.id);
+        let r = match candidate {
+            Some(r) => r,
+            None => break,
+        };
+        if let Some(reason) = why_not_merge(map, r) {
+            timer.note(format!("Not merging short road {}: {}", r, reason));
+            skip.insert(r);
+            continue;
+        }
+        merge(map, r, MergeReason::ShortRoad, timer);
     }
 
-    if false {
-        let mut look_at: HashSet<StableIntersectionID> = HashSet::new();
-        let orig_count = map.roads.len();
+    timer.note(format!(
+        "Merged {} short roads",
+        orig_count - map.roads.len()
+    ));
+}
 
-        // Every time we change a road, other roads we might've already processed could shorten, so
-        // we have to redo everything. Note that order of merging doesn't SEEM to matter much...
-        // tried tackling the shortest roads first, no effect.
-        loop {
-            if let Some(r) = map
-                .roads
-                .values()
-                .find(|r| r.trimmed_center_pts.length() < Distance::meters(5.0))
-            {
-                look_at.insert(merge(map, r.id, timer));
-            } else {
-                break;
-            }
+fn why_not_merge(map: &InitialMap, id: StableRoadID) -> Option<String> {
+    let r = &map.roads[&id];
+    for i in vec![r.src_i, r.dst_i] {
+        let intersection = &map.intersections[&i];
+        if intersection.intersection_type == IntersectionType::Border {
+            return Some(format!("{} is a border", i));
         }
-
-        timer.note(format!(
-            "Deleted {} tiny roads",
-            orig_count - map.roads.len()
-        ));
-        for id in look_at {
-            if map.intersections.contains_key(&id) {
-                timer.note(format!("Check for merged roads near {}", id));
-            }
+        // Dead-ends and roads that just continue through aren't complex intersections.
+        if intersection.roads.len() < 3 {
+            return Some(format!("{} only has {} roads", i, intersection.roads.len()));
         }
     }
+    None
 }
 
 // Returns the retained intersection.
 fn merge(
     map: &mut InitialMap,
     merge_road: StableRoadID,
+    reason: MergeReason,
     timer: &mut Timer,
 ) -> StableIntersectionID {
     // Arbitrarily kill off the first intersection and keep the second one.
@@ -69,9 +117,26 @@ fn merge(
             r.original_center_pts.length(),
             r.trimmed_center_pts.length()
         ));
+        map.merged_roads.push(MergedRoad {
+            road: merge_road,
+            reason,
+            original_length: r.original_center_pts.length(),
+            trimmed_length: r.trimmed_center_pts.length(),
+            deleted_i: r.src_i,
+            kept_i: r.dst_i,
+        });
 
         (r.src_i, r.dst_i)
     };
+    // Only one control for the whole thing. A signal anywhere in the cluster wins.
+    let merged_type = strongest_control(
+        map.intersections[&delete_i].intersection_type,
+        map.intersections[&keep_i].intersection_type,
+    );
+    map.intersections
+        .get_mut(&keep_i)
+        .unwrap()
+        .intersection_type = merged_type;
     // Show what we're about to delete
     map.save(Some(delete_i));
     map.roads.remove(&merge_road);
@@ -149,3 +214,18 @@ fn merge(
 
     keep_i
 }
+
+fn strongest_control(it1: IntersectionType, it2: IntersectionType) -> IntersectionType {
+    let rank = |it| match it {
+        IntersectionType::Border => 0,
+        IntersectionType::Uncontrolled => 1,
+        IntersectionType::StopSign => 2,
+        IntersectionType::Roundabout => 3,
+        IntersectionType::TrafficSignal => 4,
+    };
+    if rank(it1) >= rank(it2) {
+        it1
+    } else {
+        it2
+    }
+}
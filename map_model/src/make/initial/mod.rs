@@ -3,8 +3,11 @@ mod geometry;
 pub mod lane_specs;
 mod merge;
 
+pub use self::geometry::intersection_polygon;
+pub use self::merge::{MergeReason, MergedRoad};
+
 use crate::raw_data::{StableIntersectionID, StableRoadID};
-use crate::{raw_data, LANE_THICKNESS};
+use crate::{raw_data, IntersectionType, LANE_THICKNESS};
 use abstutil::Timer;
 use geom::{Bounds, Distance, GPSBounds, PolyLine, Pt2D};
 use serde_derive::{Deserialize, Serialize};
@@ -19,6 +22,9 @@ pub struct InitialMap {
     pub bounds: Bounds,
     pub focus_on: Option<StableIntersectionID>,
     versions_saved: usize,
+
+    // Every road that got merged away, for debugging
+    pub merged_roads: Vec<MergedRoad>,
 }
 
 #[derive(Serialize, Deserialize)]
@@ -50,6 +56,7 @@ pub struct Intersection {
     pub id: StableIntersectionID,
     pub polygon: Vec<Pt2D>,
     pub roads: BTreeSet<StableRoadID>,
+    pub intersection_type: IntersectionType,
 }
 
 impl InitialMap {
@@ -67,15 +74,17 @@ impl InitialMap {
             bounds: bounds.clone(),
             focus_on: None,
             versions_saved: 0,
+            merged_roads: Vec::new(),
         };
 
-        for stable_id in data.intersections.keys() {
+        for (stable_id, i) in &data.intersections {
             m.intersections.insert(
                 *stable_id,
                 Intersection {
                     id: *stable_id,
                     polygon: Vec::new(),
                     roads: BTreeSet::new(),
+                    intersection_type: i.intersection_type,
                 },
             );
         }
@@ -104,13 +113,22 @@ impl InitialMap {
             let lane_specs = lane_specs::get_lane_specs(r, *stable_id);
             let mut fwd_width = Distance::ZERO;
             let mut back_width = Distance::ZERO;
+            let mut fwd_types = Vec::new();
+            let mut back_types = Vec::new();
             for l in &lane_specs {
                 if l.reverse_pts {
                     back_width += LANE_THICKNESS;
+                    back_types.push(l.lane_type);
                 } else {
                     fwd_width += LANE_THICKNESS;
+                    fwd_types.push(l.lane_type);
                 }
             }
+            match lane_specs::contraflow_bike_track(&fwd_types, &back_types) {
+                Some(true) => back_width -= LANE_THICKNESS,
+                Some(false) => fwd_width -= LANE_THICKNESS,
+                None => {}
+            }
 
             // TODO I can't find anything online that describes how to interpret the given OSM
             // geometry of one-ways. I'm interpreting the way as the edge of the road (and only
@@ -148,6 +166,7 @@ impl InitialMap {
 
         fix_ramps::fix_ramps(&mut m, timer);
 
+        merge::roundabouts(&mut m, data, timer);
         merge::short_roads(&mut m, timer);
 
         m
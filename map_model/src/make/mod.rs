@@ -1,13 +1,17 @@
+mod bike_parking;
 mod buildings;
 mod bus_stops;
 mod half_map;
 mod initial;
+mod road_edits;
 mod sidewalk_finder;
 mod turns;
 
-pub use self::buildings::make_all_buildings;
+pub use self::bike_parking::make_bike_parking;
+pub use self::buildings::{classify_building, estimate_building_capacity, make_all_buildings};
 pub use self::bus_stops::{make_bus_stops, verify_bus_routes};
 pub use self::half_map::make_half_map;
-pub use self::initial::lane_specs::{get_lane_types, RoadSpec};
-pub use self::initial::InitialMap;
+pub use self::initial::lane_specs::{contraflow_bike_track, get_lane_types, RoadSpec};
+pub use self::initial::{InitialMap, MergeReason, MergedRoad};
+pub use self::road_edits::{recalculate_geometry, respec_road};
 pub use self::turns::make_all_turns;
@@ -0,0 +1,235 @@
+use crate::make::initial;
+use crate::raw_data::StableRoadID;
+use crate::{
+    Intersection, IntersectionID, Lane, LaneID, LaneType, Road, RoadID, RoadSpec, LANE_THICKNESS,
+};
+use abstutil::Timer;
+use geom::{Distance, PolyLine, Polygon};
+use std::collections::{BTreeMap, BTreeSet};
+
+// Replaces all of the lanes of a road to match the spec. Lanes keep their IDs when possible, so
+// that buildings, bus stops, and other edits referring to them stay valid. Lanes that aren't
+// needed anymore can't be deleted without shifting every other LaneID, so they're detached from
+// the road and intersections and recorded in removed_lanes, to be reused by later edits. Returns
+// every lane that was added, changed, or removed. Geometry isn't updated here; call
+// recalculate_geometry after.
+pub fn respec_road(
+    r: RoadID,
+    spec: &RoadSpec,
+    roads: &mut Vec<Road>,
+    lanes: &mut Vec<Lane>,
+    intersections: &mut Vec<Intersection>,
+    removed_lanes: &mut BTreeSet<LaneID>,
+    timer: &mut Timer,
+) -> BTreeSet<LaneID> {
+    let mut changed: BTreeSet<LaneID> = BTreeSet::new();
+    let mut spare: Vec<LaneID> = removed_lanes
+        .iter()
+        .filter(|l| lanes[l.0].parent == r)
+        .cloned()
+        .collect();
+
+    let (fwd, leftover_fwd) = assign_ids(
+        &roads[r.0].children_forwards,
+        &spec.fwd,
+        &mut spare,
+        lanes.len(),
+    );
+    let (back, leftover_back) = assign_ids(
+        &roads[r.0].children_backwards,
+        &spec.back,
+        &mut spare,
+        lanes.len() + fwd.iter().filter(|(id, _)| id.0 >= lanes.len()).count(),
+    );
+
+    let road = &mut roads[r.0];
+    for i in vec![road.src_i, road.dst_i] {
+        let all_lanes = &*lanes;
+        intersections[i.0]
+            .incoming_lanes
+            .retain(|l| all_lanes[l.0].parent != r);
+        intersections[i.0]
+            .outgoing_lanes
+            .retain(|l| all_lanes[l.0].parent != r);
+    }
+
+    for (forwards, (id, lt)) in fwd
+        .iter()
+        .map(|pair| (true, pair))
+        .chain(back.iter().map(|pair| (false, pair)))
+    {
+        let (src_i, dst_i) = if forwards {
+            (road.src_i, road.dst_i)
+        } else {
+            (road.dst_i, road.src_i)
+        };
+        intersections[src_i.0].outgoing_lanes.push(*id);
+        intersections[dst_i.0].incoming_lanes.push(*id);
+        changed.insert(*id);
+        removed_lanes.remove(id);
+
+        if id.0 == lanes.len() {
+            lanes.push(Lane {
+                id: *id,
+                parent: r,
+                lane_type: *lt,
+                // Filled out by recalculate_geometry
+                lane_center_pts: road.center_pts.clone(),
+                src_i,
+                dst_i,
+                building_paths: Vec::new(),
+                bus_stops: Vec::new(),
+            });
+        } else {
+            let l = &mut lanes[id.0];
+            l.lane_type = *lt;
+            l.src_i = src_i;
+            l.dst_i = dst_i;
+        }
+    }
+
+    for id in leftover_fwd.into_iter().chain(leftover_back.into_iter()) {
+        if !lanes[id.0].building_paths.is_empty() || !lanes[id.0].bus_stops.is_empty() {
+            timer.warn(format!(
+                "Removing {}, but buildings or bus stops still refer to it",
+                id
+            ));
+        }
+        changed.insert(id);
+        removed_lanes.insert(id);
+    }
+
+    road.children_forwards = fwd;
+    road.children_backwards = back;
+    changed
+}
+
+// Reuse a lane on this side of the road of the same type, then one that's also for walking (or
+// also not), then one removed from this road earlier. Sidewalks never turn into something else,
+// since buildings and bus stops are attached to them. Returns the new children and the old lanes
+// that weren't reused.
+fn assign_ids(
+    old: &Vec<(LaneID, LaneType)>,
+    types: &Vec<LaneType>,
+    spare: &mut Vec<LaneID>,
+    mut next_id: usize,
+) -> (Vec<(LaneID, LaneType)>, Vec<LaneID>) {
+    let mut available: Vec<(LaneID, LaneType)> = old.clone();
+    let mut result = Vec::new();
+    for lt in types {
+        let id = if let Some(idx) = available.iter().position(|(_, old_lt)| old_lt == lt) {
+            available.remove(idx).0
+        } else if let Some(idx) = available
+            .iter()
+            .position(|(_, old_lt)| old_lt.supports_walking() == lt.supports_walking())
+        {
+            available.remove(idx).0
+        } else if !spare.is_empty() {
+            spare.remove(0)
+        } else {
+            next_id += 1;
+            LaneID(next_id - 1)
+        };
+        result.push((id, *lt));
+    }
+    (result, available.into_iter().map(|(id, _)| id).collect())
+}
+
+// After the lanes of some roads change, their widths do too. Recalculate the polygons of all
+// intersections touching these roads, the trimmed center lines of every road touching those
+// intersections, and the geometry of those roads' lanes. Returns the roads and intersections
+// whose geometry changed.
+pub fn recalculate_geometry(
+    changed_roads: &BTreeSet<RoadID>,
+    roads: &mut Vec<Road>,
+    lanes: &mut Vec<Lane>,
+    intersections: &mut Vec<Intersection>,
+    timer: &mut Timer,
+) -> (BTreeSet<RoadID>, BTreeSet<IntersectionID>) {
+    let mut affected_intersections: BTreeSet<IntersectionID> = BTreeSet::new();
+    for r in changed_roads {
+        affected_intersections.insert(roads[r.0].src_i);
+        affected_intersections.insert(roads[r.0].dst_i);
+    }
+
+    let mut affected_roads: BTreeSet<RoadID> = BTreeSet::new();
+    for id in &affected_intersections {
+        let i = &intersections[id.0];
+        let mut initial_roads: BTreeMap<StableRoadID, initial::Road> = BTreeMap::new();
+        let mut id_mapping: BTreeMap<StableRoadID, RoadID> = BTreeMap::new();
+        for r in &i.roads {
+            let road = &roads[r.0];
+            initial_roads.insert(
+                road.stable_id,
+                initial::Road {
+                    id: road.stable_id,
+                    src_i: intersections[road.src_i.0].stable_id,
+                    dst_i: intersections[road.dst_i.0].stable_id,
+                    original_center_pts: road.original_center_pts.clone(),
+                    trimmed_center_pts: untrim_at(road, i.id),
+                    fwd_width: LANE_THICKNESS * (road.lanes_wide().0 as f64),
+                    back_width: LANE_THICKNESS * (road.lanes_wide().1 as f64),
+                    lane_specs: Vec::new(),
+                },
+            );
+            id_mapping.insert(road.stable_id, *r);
+        }
+
+        let initial_i = initial::Intersection {
+            id: i.stable_id,
+            polygon: Vec::new(),
+            roads: initial_roads.keys().cloned().collect(),
+            intersection_type: i.intersection_type,
+        };
+        let pts = initial::intersection_polygon(&initial_i, &mut initial_roads, timer);
+        intersections[id.0].polygon = Polygon::new(&pts);
+
+        for (stable_id, initial_road) in initial_roads {
+            let r = id_mapping[&stable_id];
+            roads[r.0].center_pts = initial_road.trimmed_center_pts;
+            affected_roads.insert(r);
+        }
+    }
+
+    for r in &affected_roads {
+        let road = &roads[r.0];
+        for (forwards, children) in vec![
+            (true, &road.children_forwards),
+            (false, &road.children_backwards),
+        ] {
+            let unshifted_pts = if forwards {
+                road.center_pts.clone()
+            } else {
+                road.center_pts.reversed()
+            };
+            for (offset, (id, _)) in children.iter().enumerate() {
+                // Same as make_half_map
+                let width = LANE_THICKNESS * (0.5 + (offset as f64));
+                lanes[id.0].lane_center_pts = unshifted_pts
+                    .shift_right(width)
+                    .with_context(timer, format!("shift for {}", id));
+            }
+        }
+        // Same as make_half_map
+        if let Some((track, contraflow)) = road.two_way_track() {
+            lanes[contraflow.0].lane_center_pts = lanes[track.0].lane_center_pts.reversed();
+        }
+    }
+
+    (affected_roads, affected_intersections)
+}
+
+// The road's center line, restored to its original length at one end.
+fn untrim_at(road: &Road, i: IntersectionID) -> PolyLine {
+    let orig = &road.original_center_pts;
+    if road.src_i == i {
+        if let Some((dist, _)) = orig.dist_along_of_point(road.center_pts.last_pt()) {
+            return orig.exact_slice(Distance::ZERO, dist);
+        }
+    } else if let Some((dist, _)) = orig.dist_along_of_point(road.center_pts.first_pt()) {
+        return orig.exact_slice(dist, orig.length());
+    }
+    // If the original line doesn't match up (maybe after merging short roads), settle for only
+    // trimming further.
+    road.center_pts.clone()
+}
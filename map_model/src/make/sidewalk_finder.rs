@@ -20,7 +20,7 @@ pub fn find_sidewalk_points(
     timer.start_iter("index lanes", lanes.len());
     for l in lanes {
         timer.next();
-        if l.is_sidewalk() {
+        if l.lane_type.supports_walking() {
             closest.add(l.id, &l.lane_center_pts);
         }
     }
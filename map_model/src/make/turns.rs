@@ -17,9 +17,14 @@ pub fn make_all_turns(
 ) -> Vec<Turn> {
     assert!(i.intersection_type != IntersectionType::Border);
 
-    let mut turns: Vec<Turn> = Vec::new();
-    turns.extend(make_vehicle_turns(i, roads, lanes, timer));
-    turns.extend(make_walking_turns(i, roads, lanes, timer));
+    let walking_turns = make_walking_turns(i, roads, lanes, timer);
+    // Between two shared-use paths, bikes just use the walking turn.
+    let walking_ids: HashSet<TurnID> = walking_turns.iter().map(|t| t.id).collect();
+    let mut turns: Vec<Turn> = make_vehicle_turns(i, roads, lanes, timer)
+        .into_iter()
+        .filter(|t| !walking_ids.contains(&t.id))
+        .collect();
+    turns.extend(walking_turns);
     let turns = ensure_unique(turns);
 
     // Make sure every incoming lane has a turn originating from it, and every outgoing lane has a
@@ -75,7 +80,7 @@ fn make_vehicle_turns(
     for r in &roads {
         let (t1, t2) = r.get_lane_types();
         for lt in t1.into_iter().chain(t2.into_iter()) {
-            lane_types.insert(lt);
+            lane_types.insert(turn_class(lt));
         }
     }
     lane_types.remove(&LaneType::Parking);
@@ -112,8 +117,8 @@ fn make_vehicle_turns(
 
                 // If we fell back to driving lanes for both incoming and outgoing and it's not
                 // time, then skip. This should prevent duplicates.
-                if lanes[incoming[0].0].lane_type != lane_type
-                    && lanes[outgoing[0].0].lane_type != lane_type
+                if turn_class(lanes[incoming[0].0].lane_type) != lane_type
+                    && turn_class(lanes[outgoing[0].0].lane_type) != lane_type
                 {
                     continue;
                 }
@@ -166,7 +171,16 @@ fn make_vehicle_turns(
         }
     }
 
-    result.into_iter().filter_map(|x| x).collect()
+    result
+        .into_iter()
+        .filter_map(|x| x)
+        // Center turn lanes are only for turning left.
+        .filter(|t| {
+            lanes[t.id.src.0].lane_type != LaneType::CenterTurn
+                || t.turn_type == TurnType::Left
+                || i.is_dead_end()
+        })
+        .collect()
 }
 
 fn make_vehicle_turns_for_dead_end(
@@ -346,15 +360,32 @@ fn turn_id(parent: IntersectionID, src: LaneID, dst: LaneID) -> TurnID {
     TurnID { parent, src, dst }
 }
 
+// Prefers a real sidewalk, but shared-use paths work too.
 fn get_sidewalk<'a>(lanes: &'a Vec<Lane>, children: &Vec<(LaneID, LaneType)>) -> Option<&'a Lane> {
     for (id, lt) in children {
         if *lt == LaneType::Sidewalk {
             return Some(&lanes[id.0]);
         }
     }
+    for (id, lt) in children {
+        if *lt == LaneType::SharedUse {
+            return Some(&lanes[id.0]);
+        }
+    }
     None
 }
 
+// Vehicle turns are generated between lanes of the same class, so that a two-way bike track
+// connects to regular bike lanes.
+fn turn_class(lt: LaneType) -> LaneType {
+    match lt {
+        LaneType::CenterTurn => LaneType::Driving,
+        LaneType::TwoWayBiking | LaneType::SharedUse => LaneType::Biking,
+        LaneType::BusAndBike => LaneType::Bus,
+        _ => lt,
+    }
+}
+
 fn filter_vehicle_lanes(lanes: &Vec<(LaneID, LaneType)>, preferred: LaneType) -> Vec<LaneID> {
     let preferred = filter_lanes(lanes, preferred);
     if !preferred.is_empty() {
@@ -366,7 +397,13 @@ fn filter_vehicle_lanes(lanes: &Vec<(LaneID, LaneType)>, preferred: LaneType) ->
 fn filter_lanes(lanes: &Vec<(LaneID, LaneType)>, filter: LaneType) -> Vec<LaneID> {
     lanes
         .iter()
-        .filter_map(|(id, lt)| if *lt == filter { Some(*id) } else { None })
+        .filter_map(|(id, lt)| {
+            if turn_class(*lt) == filter {
+                Some(*id)
+            } else {
+                None
+            }
+        })
         .collect()
 }
 
@@ -1,14 +1,16 @@
+use crate::edits::ClosureIndex;
 use crate::make::get_lane_types;
-use crate::pathfind::Pathfinder;
+use crate::pathfind::{Pathfinder, PathfinderOptions};
 use crate::{
-    make, raw_data, Area, AreaID, Building, BuildingID, BusRoute, BusRouteID, BusStop, BusStopID,
-    ControlStopSign, ControlTrafficSignal, Intersection, IntersectionID, IntersectionType, Lane,
-    LaneID, LaneType, MapEdits, Path, PathRequest, Position, Road, RoadID, Turn, TurnID,
+    make, raw_data, Area, AreaID, BikeParking, BikeParkingID, Building, BuildingID, BusRoute,
+    BusRouteID, BusStop, BusStopID, ClosureTarget, ControlStopSign, ControlTrafficSignal,
+    EditEffects, Intersection, IntersectionID, IntersectionType, Lane, LaneID, LaneType, MapEdits,
+    MergedRoad, Path, PathRequest, Position, Road, RoadID, RoadSpec, Traversable, Turn, TurnID,
     TurnPriority,
 };
 use abstutil;
 use abstutil::{deserialize_btreemap, serialize_btreemap, Error, Timer};
-use geom::{Bounds, GPSBounds, Polygon};
+use geom::{Bounds, Duration, GPSBounds, Line, Polygon};
 use serde_derive::{Deserialize, Serialize};
 use std::collections::{BTreeMap, BTreeSet, HashSet, VecDeque};
 use std::io;
@@ -18,7 +20,11 @@ use std::path;
 pub struct Map {
     roads: Vec<Road>,
     lanes: Vec<Lane>,
+    // Lanes removed by edits stay around, so that LaneIDs don't shift. They don't belong to any
+    // road or intersection.
+    removed_lanes: BTreeSet<LaneID>,
     intersections: Vec<Intersection>,
+    intersections_by_osm_node: BTreeMap<i64, IntersectionID>,
     #[serde(
         serialize_with = "serialize_btreemap",
         deserialize_with = "deserialize_btreemap"
@@ -31,6 +37,7 @@ pub struct Map {
     )]
     bus_stops: BTreeMap<BusStopID, BusStop>,
     bus_routes: Vec<BusRoute>,
+    bike_parking: Vec<BikeParking>,
     areas: Vec<Area>,
     boundary_polygon: Polygon,
 
@@ -41,12 +48,17 @@ pub struct Map {
     gps_bounds: GPSBounds,
     bounds: Bounds,
 
-    turn_lookup: Vec<TurnID>,
+    // Turns deleted by edits leave a hole, so the indices of the others don't shift.
+    turn_lookup: Vec<Option<TurnID>>,
+    // Roads that didn't make it into the final map, because they were merged into intersections
+    merged_roads: Vec<MergedRoad>,
     // TODO Argh, hack, initialization order is hard!
     pathfinder: Option<Pathfinder>,
 
     name: String,
     edits: MapEdits,
+    // Derived from the edits
+    closures: ClosureIndex,
 }
 
 impl Map {
@@ -79,21 +91,30 @@ impl Map {
         let mut m = Map {
             roads: half_map.roads,
             lanes: half_map.lanes,
+            removed_lanes: BTreeSet::new(),
+            intersections_by_osm_node: half_map
+                .intersections
+                .iter()
+                .filter_map(|i| i.osm_node_id.map(|node| (node, i.id)))
+                .collect(),
             intersections: half_map.intersections,
             turns: half_map.turns,
             buildings: half_map.buildings,
             bus_stops: BTreeMap::new(),
             bus_routes: Vec::new(),
+            bike_parking: Vec::new(),
             areas: half_map.areas,
             boundary_polygon: Polygon::new(&gps_bounds.must_convert(&data.boundary_polygon)),
             stop_signs: BTreeMap::new(),
             traffic_signals: BTreeMap::new(),
             gps_bounds,
             bounds,
-            turn_lookup: half_map.turn_lookup,
+            turn_lookup: half_map.turn_lookup.into_iter().map(Some).collect(),
+            merged_roads: half_map.merged_roads,
             pathfinder: None,
             name: name.clone(),
             edits: MapEdits::new(name),
+            closures: ClosureIndex::new(&Vec::new()),
         };
 
         // Extra setup that's annoying to do as HalfMap, since we want to pass around a Map.
@@ -109,7 +130,9 @@ impl Map {
                     IntersectionType::TrafficSignal => {
                         traffic_signals.insert(i.id, ControlTrafficSignal::new(&m, i.id, timer));
                     }
-                    IntersectionType::Border => {}
+                    IntersectionType::Border
+                    | IntersectionType::Uncontrolled
+                    | IntersectionType::Roundabout => {}
                 };
             }
             m.stop_signs = stop_signs;
@@ -126,7 +149,8 @@ impl Map {
 
             m.bus_routes = make::verify_bus_routes(&m, routes, timer);
         }
-        m.pathfinder = Some(Pathfinder::new(&m));
+        m.bike_parking = make::make_bike_parking(&m, &data.bike_parking, timer);
+        m.pathfinder = Some(Pathfinder::new(&m, PathfinderOptions::default()));
 
         timer.stop("finalize Map");
         m
@@ -156,11 +180,21 @@ impl Map {
         &self.areas
     }
 
+    // Lanes removed by map edits are still in all_lanes(), so that LaneIDs index into it, but
+    // callers have to skip them.
+    pub fn is_lane_removed(&self, id: LaneID) -> bool {
+        self.removed_lanes.contains(&id)
+    }
+
     pub fn maybe_get_r(&self, id: RoadID) -> Option<&Road> {
         self.roads.get(id.0)
     }
 
+    // None for lanes removed by map edits
     pub fn maybe_get_l(&self, id: LaneID) -> Option<&Lane> {
+        if self.is_lane_removed(id) {
+            return None;
+        }
         self.lanes.get(id.0)
     }
 
@@ -204,6 +238,10 @@ impl Map {
         &self.intersections[id.0]
     }
 
+    pub fn find_i_by_osm_node_id(&self, id: i64) -> Option<IntersectionID> {
+        self.intersections_by_osm_node.get(&id).cloned()
+    }
+
     pub fn get_t(&self, id: TurnID) -> &Turn {
         &self.turns[&id]
     }
@@ -224,8 +262,30 @@ impl Map {
         &self.traffic_signals[&id]
     }
 
+    pub fn get_merged_roads(&self) -> &Vec<MergedRoad> {
+        &self.merged_roads
+    }
+
+    // Every road that was merged away to form this intersection, including ones merged into
+    // intersections that were later merged into this one. In the order they were merged.
+    pub fn get_merged_roads_into(&self, id: IntersectionID) -> Vec<&MergedRoad> {
+        let mut intersections = HashSet::new();
+        intersections.insert(self.get_i(id).stable_id);
+        let mut result = Vec::new();
+        // An intersection can't be kept by a merge after it's been deleted, so walking backwards
+        // finds the whole chain.
+        for m in self.merged_roads.iter().rev() {
+            if intersections.contains(&m.kept_i) {
+                intersections.insert(m.deleted_i);
+                result.push(m);
+            }
+        }
+        result.reverse();
+        result
+    }
+
     pub fn lookup_turn_by_idx(&self, idx: usize) -> Option<TurnID> {
-        self.turn_lookup.get(idx).cloned()
+        self.turn_lookup.get(idx).and_then(|t| *t)
     }
 
     // All these helpers should take IDs and return objects.
@@ -258,7 +318,7 @@ impl Map {
             .filter(|t| t.id.src == l)
             .collect();
         // Sidewalks are bidirectional
-        if lane.is_sidewalk() {
+        if lane.lane_type.supports_walking() {
             for t in &self.get_i(lane.src_i).turns {
                 if t.src == l {
                     turns.push(self.get_t(*t));
@@ -331,6 +391,14 @@ impl Map {
         &self.bus_stops[&stop]
     }
 
+    pub fn all_bike_parking(&self) -> &Vec<BikeParking> {
+        &self.bike_parking
+    }
+
+    pub fn get_bike_parking(&self, id: BikeParkingID) -> &BikeParking {
+        &self.bike_parking[id.0]
+    }
+
     pub fn get_all_bus_routes(&self) -> &Vec<BusRoute> {
         &self.bus_routes
     }
@@ -464,6 +532,10 @@ impl Map {
             ts.cycles
                 .iter()
                 .any(|c| c.get_priority(t) != TurnPriority::Banned)
+        } else if self.get_i(t.parent).intersection_type == IntersectionType::Uncontrolled
+            || self.get_i(t.parent).intersection_type == IntersectionType::Roundabout
+        {
+            true
         } else {
             // Border nodes have no turns...
             panic!("{}'s intersection isn't a stop sign or traffic signal", t);
@@ -523,10 +595,54 @@ impl Map {
         self.pathfinder.as_ref().unwrap().pathfind(req, self)
     }
 
+    pub fn get_pathfinder_options(&self) -> PathfinderOptions {
+        self.pathfinder.as_ref().unwrap().get_options()
+    }
+
+    // Rebuilds the pathfinder, which is slow.
+    pub fn set_pathfinder_options(&mut self, options: PathfinderOptions, timer: &mut Timer) {
+        if self.get_pathfinder_options() == options {
+            return;
+        }
+        timer.start("rebuild pathfinder");
+        self.pathfinder = Some(Pathfinder::new(self, options));
+        timer.stop("rebuild pathfinder");
+    }
+
     pub fn pathfind_slow(&self, req: PathRequest) -> Option<Path> {
         crate::pathfind::slow::shortest_distance(self, req)
     }
 
+    // Like pathfind, but avoids anything closed at this time.
+    pub fn pathfind_at(&self, req: PathRequest, time: Duration) -> Option<Path> {
+        self.pathfinder
+            .as_ref()
+            .unwrap()
+            .pathfind_at(req, self, time)
+    }
+
+    // Closing a lane or road only blocks the turns leading into it, so that anybody already there
+    // can still leave.
+    pub fn is_closed(&self, on: Traversable, time: Duration) -> bool {
+        let lane = match on {
+            Traversable::Lane(l) => l,
+            Traversable::Turn(t) => {
+                if self.closures.is_active(ClosureTarget::Turn(t), time) {
+                    return true;
+                }
+                t.dst
+            }
+        };
+        self.closures.is_active(ClosureTarget::Lane(lane), time)
+            || self
+                .closures
+                .is_active(ClosureTarget::Road(self.get_l(lane).parent), time)
+    }
+
+    pub fn any_closures_at(&self, time: Duration) -> bool {
+        self.edits.closures.iter().any(|c| c.is_active(time))
+    }
+
     pub fn should_use_transit(
         &self,
         start: Position,
@@ -544,60 +660,181 @@ impl Map {
         &self.edits
     }
 
-    // new_edits assumed to be valid. Returns actual lanes that changed, turns deleted, turns added.
-    pub fn apply_edits(
-        &mut self,
-        new_edits: MapEdits,
-        timer: &mut Timer,
-    ) -> (BTreeSet<LaneID>, BTreeSet<TurnID>, BTreeSet<TurnID>) {
+    // For trying out edits without touching this map. The pathfinder is expensive to copy and
+    // rebuild, so the copy can't pathfind.
+    pub(crate) fn copy_without_pathfinder(&self) -> Map {
+        Map {
+            roads: self.roads.clone(),
+            lanes: self.lanes.clone(),
+            removed_lanes: self.removed_lanes.clone(),
+            intersections: self.intersections.clone(),
+            intersections_by_osm_node: self.intersections_by_osm_node.clone(),
+            turns: self.turns.clone(),
+            buildings: self.buildings.clone(),
+            bus_stops: self.bus_stops.clone(),
+            bus_routes: self.bus_routes.clone(),
+            bike_parking: self.bike_parking.clone(),
+            areas: self.areas.clone(),
+            boundary_polygon: self.boundary_polygon.clone(),
+            stop_signs: self.stop_signs.clone(),
+            traffic_signals: self.traffic_signals.clone(),
+            gps_bounds: self.gps_bounds.clone(),
+            bounds: self.bounds.clone(),
+            turn_lookup: self.turn_lookup.clone(),
+            merged_roads: self.merged_roads.clone(),
+            pathfinder: None,
+            name: self.name.clone(),
+            edits: self.edits.clone(),
+            closures: self.closures.clone(),
+        }
+    }
+
+    // new_edits assumed to be valid.
+    pub fn apply_edits(&mut self, new_edits: MapEdits, timer: &mut Timer) -> EditEffects {
+        // Lanes are added and removed first, so the other edits see the new lanes.
+        let mut all_road_edits: BTreeMap<RoadID, RoadSpec> = BTreeMap::new();
+        for (id, spec) in &new_edits.road_overrides {
+            if self.edits.road_overrides.get(id) != Some(spec) {
+                all_road_edits.insert(*id, spec.clone());
+            }
+        }
+        for id in self.edits.road_overrides.keys() {
+            if !new_edits.road_overrides.contains_key(id) {
+                all_road_edits.insert(*id, self.get_original_spec(*id));
+            }
+        }
+
+        let mut changed_lanes = BTreeSet::new();
+        let mut changed_roads = BTreeSet::new();
+        let mut changed_intersections = BTreeSet::new();
+        for (id, spec) in &all_road_edits {
+            changed_lanes.extend(make::respec_road(
+                *id,
+                spec,
+                &mut self.roads,
+                &mut self.lanes,
+                &mut self.intersections,
+                &mut self.removed_lanes,
+                timer,
+            ));
+        }
+        if !all_road_edits.is_empty() {
+            let (roads, intersections) = make::recalculate_geometry(
+                &all_road_edits.keys().cloned().collect(),
+                &mut self.roads,
+                &mut self.lanes,
+                &mut self.intersections,
+                timer,
+            );
+            for r in &roads {
+                changed_lanes.extend(self.roads[r.0].all_lanes());
+                self.update_sidewalk_attachments(*r, timer);
+            }
+            changed_roads = roads;
+            changed_intersections = intersections;
+        }
+
+        // Then switch intersection control, so the stop sign and traffic signal edits below apply
+        // to the new type.
+        let mut all_type_edits: BTreeMap<IntersectionID, IntersectionType> = BTreeMap::new();
+        for (id, it) in &new_edits.intersection_type_overrides {
+            if self.intersections[id.0].intersection_type != *it {
+                all_type_edits.insert(*id, *it);
+            }
+        }
+        for id in self.edits.intersection_type_overrides.keys() {
+            let i = &self.intersections[id.0];
+            if !new_edits.intersection_type_overrides.contains_key(id)
+                && i.intersection_type != i.original_intersection_type
+            {
+                all_type_edits.insert(*id, i.original_intersection_type);
+            }
+        }
+        for (id, it) in &all_type_edits {
+            let i = &mut self.intersections[id.0];
+            if i.intersection_type == IntersectionType::Border || *it == IntersectionType::Border {
+                timer.warn(format!(
+                    "Can't change {} from {:?} to {:?}",
+                    id, i.intersection_type, it
+                ));
+                continue;
+            }
+            i.intersection_type = *it;
+            // Lane markings depend on the control.
+            changed_lanes.extend(i.incoming_lanes.clone());
+            self.stop_signs.remove(id);
+            self.traffic_signals.remove(id);
+            // Turns get recalculated below, and the default policy for the new type created.
+            changed_intersections.insert(*id);
+        }
+
         // Ignore if there's no change from current
         let mut all_lane_edits: BTreeMap<LaneID, LaneType> = BTreeMap::new();
         let mut all_stop_sign_edits: BTreeMap<IntersectionID, ControlStopSign> = BTreeMap::new();
         let mut all_traffic_signals: BTreeMap<IntersectionID, ControlTrafficSignal> =
             BTreeMap::new();
         for (id, lt) in &new_edits.lane_overrides {
-            if self.edits.lane_overrides.get(id) != Some(lt) {
+            // Rearranging the road resets the lane types, so apply these again.
+            if self.edits.lane_overrides.get(id) != Some(lt)
+                || all_road_edits.contains_key(&self.lanes[id.0].parent)
+            {
                 all_lane_edits.insert(*id, *lt);
             }
         }
         for (id, ss) in &new_edits.stop_sign_overrides {
-            if self.edits.stop_sign_overrides.get(id) != Some(ss) {
+            // Changing the type resets the policy, so apply these again.
+            if self.edits.stop_sign_overrides.get(id) != Some(ss) || all_type_edits.contains_key(id)
+            {
                 all_stop_sign_edits.insert(*id, ss.clone());
             }
         }
         for (id, ts) in &new_edits.traffic_signal_overrides {
-            if self.edits.traffic_signal_overrides.get(id) != Some(ts) {
+            if self.edits.traffic_signal_overrides.get(id) != Some(ts)
+                || all_type_edits.contains_key(id)
+            {
                 all_traffic_signals.insert(*id, ts.clone());
             }
         }
 
         // May need to revert some previous changes
         for id in self.edits.lane_overrides.keys() {
-            if !new_edits.lane_overrides.contains_key(id) {
-                all_lane_edits.insert(*id, self.get_original_lt(*id));
+            if !new_edits.lane_overrides.contains_key(id) && !self.is_lane_removed(*id) {
+                all_lane_edits.insert(*id, self.get_original_lt(*id, &new_edits.road_overrides));
             }
         }
         for id in self.edits.stop_sign_overrides.keys() {
-            if !new_edits.stop_sign_overrides.contains_key(id) {
+            if !new_edits.stop_sign_overrides.contains_key(id)
+                && self.intersections[id.0].intersection_type == IntersectionType::StopSign
+            {
                 all_stop_sign_edits.insert(*id, ControlStopSign::new(self, *id, timer));
             }
         }
         for id in self.edits.traffic_signal_overrides.keys() {
-            if !new_edits.traffic_signal_overrides.contains_key(id) {
+            if !new_edits.traffic_signal_overrides.contains_key(id)
+                && self.intersections[id.0].intersection_type == IntersectionType::TrafficSignal
+            {
                 all_traffic_signals.insert(*id, ControlTrafficSignal::new(self, *id, timer));
             }
         }
 
         timer.note(format!(
-            "Total diff: {} lanes, {} stop signs, {} traffic signals",
+            "Total diff: {} roads, {} intersection types, {} lanes, {} stop signs, {} traffic \
+             signals",
+            all_road_edits.len(),
+            all_type_edits.len(),
             all_lane_edits.len(),
             all_stop_sign_edits.len(),
             all_traffic_signals.len()
         ));
 
-        let mut changed_lanes = BTreeSet::new();
-        let mut changed_intersections = BTreeSet::new();
         for (id, lt) in all_lane_edits {
+            if self.is_lane_removed(id) {
+                timer.warn(format!(
+                    "Can't change the type of {}; it's been removed",
+                    id
+                ));
+                continue;
+            }
             changed_lanes.insert(id);
 
             let l = &mut self.lanes[id.0];
@@ -618,9 +855,12 @@ impl Map {
         // Recompute turns and intersection policy
         let mut delete_turns = BTreeSet::new();
         let mut add_turns = BTreeSet::new();
-        for id in changed_intersections {
+        for id in changed_intersections.clone() {
             // TODO Did something affect a border intersection?
             let i = &mut self.intersections[id.0];
+            if i.intersection_type == IntersectionType::Border {
+                continue;
+            }
 
             let mut old_turns = Vec::new();
             for id in i.turns.drain(..) {
@@ -628,18 +868,27 @@ impl Map {
                 delete_turns.insert(id);
             }
 
-            for t in make::make_all_turns(i, &self.roads, &self.lanes, timer) {
+            let new_turns = make::make_all_turns(i, &self.roads, &self.lanes, timer);
+            for old in &old_turns {
+                if !new_turns.iter().any(|t| t.id == old.id) {
+                    self.turn_lookup[old.lookup_idx] = None;
+                }
+            }
+            for mut t in new_turns {
                 add_turns.insert(t.id);
                 i.turns.push(t.id);
-                if let Some(_existing_t) = old_turns.iter().find(|turn| turn.id == t.id) {
-                    // TODO Except for lookup_idx
-                    //assert_eq!(t, *existing_t);
+                if let Some(existing_t) = old_turns.iter().find(|turn| turn.id == t.id) {
+                    t.lookup_idx = existing_t.lookup_idx;
+                } else if let Some(idx) = self.turn_lookup.iter().position(|id| id.is_none()) {
+                    t.lookup_idx = idx;
+                    self.turn_lookup[idx] = Some(t.id);
+                } else {
+                    t.lookup_idx = self.turn_lookup.len();
+                    self.turn_lookup.push(Some(t.id));
                 }
                 self.turns.insert(t.id, t);
             }
 
-            // TODO Deal with turn_lookup
-
             // Do this before applying intersection policy edits.
             match i.intersection_type {
                 IntersectionType::StopSign => {
@@ -650,13 +899,22 @@ impl Map {
                     self.traffic_signals
                         .insert(id, ControlTrafficSignal::new(self, id, timer));
                 }
-                IntersectionType::Border => {}
+                IntersectionType::Border
+                | IntersectionType::Uncontrolled
+                | IntersectionType::Roundabout => {}
             }
         }
 
         // Make sure all of the turns of modified intersections are re-added in the pathfinder;
         // they might've become banned. Lane markings may also change based on turn priorities.
         for (id, ss) in all_stop_sign_edits {
+            if self.intersections[id.0].intersection_type != IntersectionType::StopSign {
+                timer.warn(format!(
+                    "Ignoring stop sign edits for {}; it's not a stop sign",
+                    id
+                ));
+                continue;
+            }
             self.stop_signs.insert(id, ss);
             for t in &self.get_i(id).turns {
                 add_turns.insert(*t);
@@ -666,6 +924,13 @@ impl Map {
             }
         }
         for (id, ts) in all_traffic_signals {
+            if self.intersections[id.0].intersection_type != IntersectionType::TrafficSignal {
+                timer.warn(format!(
+                    "Ignoring traffic signal edits for {}; it's not a traffic signal",
+                    id
+                ));
+                continue;
+            }
             self.traffic_signals.insert(id, ts);
             for t in &self.get_i(id).turns {
                 add_turns.insert(*t);
@@ -675,21 +940,83 @@ impl Map {
             }
         }
 
-        let mut pathfinder = self.pathfinder.take().unwrap();
-        pathfinder.apply_edits(&delete_turns, &add_turns, self);
-        self.pathfinder = Some(pathfinder);
+        // Copies from copy_without_pathfinder don't have one to update.
+        if let Some(mut pathfinder) = self.pathfinder.take() {
+            if all_road_edits.is_empty() {
+                pathfinder.apply_edits(&delete_turns, &add_turns, self);
+            } else {
+                // The graphs have nodes per directed road and the walking graph can't be edited at
+                // all, so just start over.
+                pathfinder = Pathfinder::new(self, pathfinder.get_options());
+            }
+            self.pathfinder = Some(pathfinder);
+        }
 
+        self.closures = ClosureIndex::new(&new_edits.closures);
         self.edits = new_edits;
-        (changed_lanes, delete_turns, add_turns)
+        EditEffects {
+            changed_lanes,
+            changed_roads,
+            changed_intersections,
+            deleted_turns: delete_turns,
+            added_turns: add_turns,
+        }
+    }
+
+    // Bus stops and buildings stay attached to the same sidewalks, but the sidewalks may have
+    // moved.
+    fn update_sidewalk_attachments(&mut self, r: RoadID, timer: &mut Timer) {
+        for l in self.roads[r.0].all_lanes() {
+            let lane = &self.lanes[l.0];
+            if !lane.lane_type.supports_walking() {
+                continue;
+            }
+
+            for id in &lane.bus_stops {
+                let old_dist = self.bus_stops[id].sidewalk_pos.dist_along();
+                let sidewalk_pos = Position::new(l, std::cmp::min(old_dist, lane.length()));
+                match self.roads[r.0].find_closest_lane(
+                    l,
+                    vec![LaneType::Driving, LaneType::Bus, LaneType::BusAndBike],
+                ) {
+                    Ok(driving_lane) => {
+                        let driving_pos = sidewalk_pos.equiv_pos(driving_lane, self);
+                        let stop = self.bus_stops.get_mut(id).unwrap();
+                        stop.sidewalk_pos = sidewalk_pos;
+                        stop.driving_pos = driving_pos;
+                    }
+                    Err(_) => {
+                        timer.warn(format!("{} has no driving lane next to it anymore", id));
+                    }
+                }
+            }
+
+            for b in &lane.building_paths {
+                let front_path = &mut self.buildings[b.0].front_path;
+                let dist = std::cmp::min(front_path.sidewalk.dist_along(), lane.length());
+                front_path.sidewalk = Position::new(l, dist);
+                front_path.line = Line::new(front_path.line.pt1(), lane.dist_along(dist).0);
+            }
+        }
+    }
+
+    fn get_original_spec(&self, id: RoadID) -> RoadSpec {
+        let r = self.get_r(id);
+        let (fwd, back) = get_lane_types(&r.osm_tags, r.parking_lane_fwd, r.parking_lane_back);
+        RoadSpec { fwd, back }
     }
 
-    fn get_original_lt(&self, id: LaneID) -> LaneType {
+    fn get_original_lt(&self, id: LaneID, road_overrides: &BTreeMap<RoadID, RoadSpec>) -> LaneType {
         let parent = self.get_parent(id);
-        let (side1, side2) = get_lane_types(
-            &parent.osm_tags,
-            parent.parking_lane_fwd,
-            parent.parking_lane_back,
-        );
+        let (side1, side2) = if let Some(spec) = road_overrides.get(&parent.id) {
+            (spec.fwd.clone(), spec.back.clone())
+        } else {
+            get_lane_types(
+                &parent.osm_tags,
+                parent.parking_lane_fwd,
+                parent.parking_lane_back,
+            )
+        };
         let (fwds, idx) = parent.dir_and_offset(id);
         if fwds {
             side1[idx]
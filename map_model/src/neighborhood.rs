@@ -14,23 +14,28 @@ pub struct NeighborhoodBuilder {
     pub map_name: String,
     pub name: String,
     pub points: Vec<LonLat>,
+    // Areas excluded from the neighborhood. Each ring is closed.
+    #[serde(default)]
+    pub inner_rings: Vec<Vec<LonLat>>,
 }
 
 impl NeighborhoodBuilder {
     pub fn finalize(&self, gps_bounds: &GPSBounds) -> Neighborhood {
         assert!(self.points.len() >= 3);
+        let convert = |pts: &Vec<LonLat>| -> Vec<Pt2D> {
+            pts.iter()
+                .map(|pt| {
+                    Pt2D::from_gps(*pt, gps_bounds)
+                        .expect(&format!("Polygon {} has bad pt {}", self.name, pt))
+                })
+                .collect()
+        };
         Neighborhood {
             map_name: self.map_name.clone(),
             name: self.name.clone(),
-            polygon: Polygon::new(
-                &self
-                    .points
-                    .iter()
-                    .map(|pt| {
-                        Pt2D::from_gps(*pt, gps_bounds)
-                            .expect(&format!("Polygon {} has bad pt {}", self.name, pt))
-                    })
-                    .collect(),
+            polygon: Polygon::with_holes(
+                &convert(&self.points),
+                &self.inner_rings.iter().map(convert).collect(),
             ),
         }
     }
@@ -58,6 +63,14 @@ impl NeighborhoodBuilder {
             )?;
         }
         writeln!(f, "END")?;
+        // Holes are sections starting with !
+        for (idx, ring) in self.inner_rings.iter().enumerate() {
+            writeln!(f, "!{}", idx + 2)?;
+            for gps in ring {
+                writeln!(f, "     {}    {}", gps.longitude, gps.latitude)?;
+            }
+            writeln!(f, "END")?;
+        }
         writeln!(f, "END")?;
 
         println!("Exported {}", path);
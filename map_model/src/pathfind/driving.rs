@@ -6,6 +6,10 @@ use petgraph::stable_graph::StableGraph;
 use serde_derive::{Deserialize, Serialize};
 use std::collections::{BTreeMap, BTreeSet, VecDeque};
 
+// When climbs are penalized, every meter gained costs as much as going this many meters on flat
+// ground.
+const CLIMB_PENALTY: f64 = 10.0;
+
 // TODO Make the graph smaller by considering RoadID, or even (directed?) bundles of roads based on
 // OSM way.
 #[derive(Serialize, Deserialize, Debug)]
@@ -17,6 +21,7 @@ pub struct VehiclePathfinder {
     )]
     nodes: BTreeMap<DirectedRoadID, NodeIndex<u32>>,
     lane_types: Vec<LaneType>,
+    penalize_climbs: bool,
 }
 
 pub enum Outcome {
@@ -26,11 +31,12 @@ pub enum Outcome {
 }
 
 impl VehiclePathfinder {
-    pub fn new(map: &Map, lane_types: Vec<LaneType>) -> VehiclePathfinder {
+    pub fn new(map: &Map, lane_types: Vec<LaneType>, penalize_climbs: bool) -> VehiclePathfinder {
         let mut g = VehiclePathfinder {
             graph: StableGraph::new(),
             nodes: BTreeMap::new(),
             lane_types,
+            penalize_climbs,
         };
 
         for r in map.all_roads() {
@@ -71,8 +77,14 @@ impl VehiclePathfinder {
             let dst = self.get_node(t.id.dst, map);
             // First length arbitrarily wins.
             if self.graph.find_edge(src, dst).is_none() {
-                self.graph
-                    .add_edge(src, dst, src_l.length() + t.geom.length());
+                let mut cost = src_l.length() + t.geom.length();
+                if self.penalize_climbs {
+                    let grade = src_l.grade(map);
+                    if grade > 0.0 {
+                        cost += src_l.length() * grade * CLIMB_PENALTY;
+                    }
+                }
+                self.graph.add_edge(src, dst, cost);
             }
         }
     }
@@ -5,7 +5,7 @@ mod walking;
 use self::driving::{Outcome, VehiclePathfinder};
 use self::walking::SidewalkPathfinder;
 use crate::{BusRouteID, BusStopID, LaneID, LaneType, Map, Position, Traversable, TurnID};
-use geom::{Distance, PolyLine};
+use geom::{Distance, Duration, PolyLine};
 use serde_derive::{Deserialize, Serialize};
 use std::collections::{BTreeSet, VecDeque};
 
@@ -231,6 +231,10 @@ impl Path {
     pub fn get_steps(&self) -> &VecDeque<PathStep> {
         &self.steps
     }
+
+    pub fn get_end_dist(&self) -> Distance {
+        self.end_dist
+    }
 }
 
 #[derive(Debug, Clone)]
@@ -284,6 +288,20 @@ fn validate(map: &Map, steps: &Vec<PathStep>) {
     }
 }
 
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq)]
+pub struct PathfinderOptions {
+    // Cyclists go out of their way to avoid hills.
+    pub bikes_avoid_climbs: bool,
+}
+
+impl Default for PathfinderOptions {
+    fn default() -> PathfinderOptions {
+        PathfinderOptions {
+            bikes_avoid_climbs: false,
+        }
+    }
+}
+
 #[derive(Serialize, Deserialize, Debug)]
 pub struct Pathfinder {
     car_graph: VehiclePathfinder,
@@ -291,19 +309,49 @@ pub struct Pathfinder {
     bus_graph: VehiclePathfinder,
     walking_graph: SidewalkPathfinder,
     walking_with_transit_graph: SidewalkPathfinder,
+    options: PathfinderOptions,
 }
 
 impl Pathfinder {
-    pub fn new(map: &Map) -> Pathfinder {
+    pub fn new(map: &Map, options: PathfinderOptions) -> Pathfinder {
         Pathfinder {
-            car_graph: VehiclePathfinder::new(map, vec![LaneType::Driving]),
-            bike_graph: VehiclePathfinder::new(map, vec![LaneType::Driving, LaneType::Biking]),
-            bus_graph: VehiclePathfinder::new(map, vec![LaneType::Driving, LaneType::Bus]),
+            car_graph: VehiclePathfinder::new(
+                map,
+                vec![LaneType::Driving, LaneType::CenterTurn],
+                false,
+            ),
+            bike_graph: VehiclePathfinder::new(
+                map,
+                vec![
+                    LaneType::Driving,
+                    LaneType::CenterTurn,
+                    LaneType::Biking,
+                    LaneType::TwoWayBiking,
+                    LaneType::SharedUse,
+                    LaneType::BusAndBike,
+                ],
+                options.bikes_avoid_climbs,
+            ),
+            bus_graph: VehiclePathfinder::new(
+                map,
+                vec![
+                    LaneType::Driving,
+                    LaneType::CenterTurn,
+                    LaneType::Bus,
+                    LaneType::BusAndBike,
+                ],
+                false,
+            ),
             walking_graph: SidewalkPathfinder::new(map, false),
             walking_with_transit_graph: SidewalkPathfinder::new(map, true),
+            options,
         }
     }
 
+    pub fn get_options(&self) -> PathfinderOptions {
+        self.options
+    }
+
     pub fn pathfind(&self, req: PathRequest, map: &Map) -> Option<Path> {
         // Weird case, but it can happen for walking from a building path to a bus stop that're
         // actually at the same spot.
@@ -315,8 +363,8 @@ impl Pathfinder {
             ));
         }
 
-        let outcome = if map.get_l(req.start.lane()).is_sidewalk() {
-            match self.walking_graph.pathfind(&req, map) {
+        let outcome = if is_walking(&req, map) {
+            match self.walking_graph.pathfind(&req, map, None) {
                 Some(path) => Outcome::Success(path),
                 None => Outcome::Failure,
             }
@@ -334,6 +382,30 @@ impl Pathfinder {
         }
     }
 
+    pub fn pathfind_at(&self, req: PathRequest, map: &Map, time: Duration) -> Option<Path> {
+        if req.start == req.end || !map.any_closures_at(time) {
+            return self.pathfind(req, map);
+        }
+
+        if is_walking(&req, map) {
+            self.walking_graph.pathfind(&req, map, Some(time))
+        } else {
+            // The vehicle graphs are per directed road, so they can't express closing just one
+            // lane or turn. Only fall back to the slow pathfinder when the usual path runs into a
+            // closure.
+            let path = self.pathfind(req.clone(), map)?;
+            let blocked = path.get_steps().iter().any(|step| match step {
+                PathStep::Turn(t) => map.is_closed(Traversable::Turn(*t), time),
+                _ => false,
+            });
+            if blocked {
+                slow::shortest_distance_at(map, req, time)
+            } else {
+                Some(path)
+            }
+        }
+    }
+
     pub fn should_use_transit(
         &self,
         map: &Map,
@@ -355,3 +427,9 @@ impl Pathfinder {
         self.bus_graph.apply_edits(delete_turns, add_turns, map);
     }
 }
+
+// Bikes can ride along shared-use paths, but pedestrians walk there too.
+fn is_walking(req: &PathRequest, map: &Map) -> bool {
+    let lt = map.get_l(req.start.lane()).lane_type;
+    lt == LaneType::Sidewalk || (lt == LaneType::SharedUse && !req.can_use_bike_lanes)
+}
@@ -1,10 +1,19 @@
 use crate::{LaneType, Map, Path, PathRequest, PathStep, Position, Traversable};
-use geom::{Distance, Pt2D};
+use geom::{Distance, Duration, Pt2D};
 use ordered_float::NotNan;
 use std::collections::{BinaryHeap, HashMap};
 
 // Only for vehicle paths, no walking support.
 pub fn shortest_distance(map: &Map, req: PathRequest) -> Option<Path> {
+    pathfind(map, req, None)
+}
+
+// Avoids anything closed at this time.
+pub fn shortest_distance_at(map: &Map, req: PathRequest, time: Duration) -> Option<Path> {
+    pathfind(map, req, Some(time))
+}
+
+fn pathfind(map: &Map, req: PathRequest, closed_at: Option<Duration>) -> Option<Path> {
     // TODO using first_pt here and in heuristic_dist is particularly bad for walking
     // directions
     let goal_pt = req.end.pt(map);
@@ -12,6 +21,7 @@ pub fn shortest_distance(map: &Map, req: PathRequest) -> Option<Path> {
         goal_pt,
         can_use_bike_lanes: req.can_use_bike_lanes,
         can_use_bus_lanes: req.can_use_bus_lanes,
+        closed_at,
     }
     .pathfind(map, req.start, req.end)?;
     assert_eq!(
@@ -29,6 +39,7 @@ struct SlowPathfinder {
     goal_pt: Pt2D,
     can_use_bike_lanes: bool,
     can_use_bus_lanes: bool,
+    closed_at: Option<Duration>,
 }
 
 impl SlowPathfinder {
@@ -42,9 +53,13 @@ impl SlowPathfinder {
                 {
                     if !map.is_turn_allowed(turn.id) {
                         // Skip
-                    } else if !self.can_use_bike_lanes && next.lane_type == LaneType::Biking {
+                    } else if self
+                        .closed_at
+                        .map(|t| map.is_closed(Traversable::Turn(turn.id), t))
+                        .unwrap_or(false)
+                    {
                         // Skip
-                    } else if !self.can_use_bus_lanes && next.lane_type == LaneType::Bus {
+                    } else if !self.can_use(next.lane_type) {
                         // Skip
                     } else {
                         results.push(PathStep::Turn(turn.id));
@@ -59,6 +74,18 @@ impl SlowPathfinder {
         results
     }
 
+    fn can_use(&self, lt: LaneType) -> bool {
+        match lt {
+            LaneType::Driving | LaneType::CenterTurn => true,
+            LaneType::Biking | LaneType::TwoWayBiking | LaneType::SharedUse => {
+                self.can_use_bike_lanes
+            }
+            LaneType::Bus => self.can_use_bus_lanes,
+            LaneType::BusAndBike => self.can_use_bike_lanes || self.can_use_bus_lanes,
+            LaneType::Parking | LaneType::Sidewalk => false,
+        }
+    }
+
     fn pathfind(&self, map: &Map, start: Position, end: Position) -> Option<Vec<PathStep>> {
         // This should be deterministic, since cost ties would be broken by PathStep.
         let mut queue: BinaryHeap<(NotNan<f64>, PathStep)> = BinaryHeap::new();
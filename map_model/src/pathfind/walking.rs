@@ -1,10 +1,11 @@
 use crate::{
     BusRouteID, BusStopID, DirectedRoadID, IntersectionID, LaneID, LaneType, Map, Path,
-    PathRequest, PathStep, Position,
+    PathRequest, PathStep, Position, Traversable,
 };
 use abstutil::{deserialize_btreemap, serialize_btreemap};
-use geom::Distance;
+use geom::{Distance, Duration};
 use petgraph::graph::{Graph, NodeIndex};
+use petgraph::visit::EdgeRef;
 use serde_derive::{Deserialize, Serialize};
 use std::collections::BTreeMap;
 
@@ -26,6 +27,9 @@ enum Edge {
     RideBus(BusStopID, BusStopID, BusRouteID),
 }
 
+// astar can't skip edges, so make closed crossings prohibitively expensive and check the result.
+const CLOSED_PENALTY: Distance = Distance::const_meters(1_000_000.0);
+
 impl SidewalkPathfinder {
     pub fn new(map: &Map, use_transit: bool) -> SidewalkPathfinder {
         let mut g = SidewalkPathfinder {
@@ -91,15 +95,27 @@ impl SidewalkPathfinder {
         } else {
             &r.children_backwards
         };
+        // Match make_walking_turns, which prefers real sidewalks.
         for (id, lt) in lanes {
             if *lt == LaneType::Sidewalk {
                 return *id;
             }
         }
+        for (id, lt) in lanes {
+            if *lt == LaneType::SharedUse {
+                return *id;
+            }
+        }
         panic!("{} has no sidewalk", dr);
     }
 
-    pub fn pathfind(&self, req: &PathRequest, map: &Map) -> Option<Path> {
+    // If closed_at is specified, avoid anything closed at that time.
+    pub fn pathfind(
+        &self,
+        req: &PathRequest,
+        map: &Map,
+        closed_at: Option<Duration>,
+    ) -> Option<Path> {
         // Special-case one-step paths.
         if req.start.lane() == req.end.lane() {
             assert!(req.start.dist_along() != req.end.dist_along());
@@ -127,7 +143,19 @@ impl SidewalkPathfinder {
             start_node,
             |n| n == end_node,
             |e| match e.weight() {
-                Edge::Cross(dist) => *dist,
+                Edge::Cross(dist) => {
+                    if let Some(time) = closed_at {
+                        if self.is_crossing_closed(
+                            self.graph[e.source()],
+                            self.graph[e.target()],
+                            map,
+                            time,
+                        ) {
+                            return *dist + CLOSED_PENALTY;
+                        }
+                    }
+                    *dist
+                }
                 // Free for now
                 Edge::RideBus(_, _, _) => Distance::ZERO,
             },
@@ -155,7 +183,14 @@ impl SidewalkPathfinder {
             let back_t = map.get_turn_between(lane1.id, l2, lane1.src_i);
             // TODO If both are available, we sort of need to lookahead to pick the better one.
             // Oh well.
-            if fwd_t.is_some() {
+            let use_fwd = match (fwd_t, back_t) {
+                (Some(t), Some(_)) => closed_at
+                    .map(|time| !map.is_closed(Traversable::Turn(t), time))
+                    .unwrap_or(true),
+                (Some(_), None) => true,
+                (None, _) => false,
+            };
+            if use_fwd {
                 if current_i != Some(lane1.dst_i) {
                     steps.push(PathStep::Lane(lane1.id));
                 }
@@ -180,9 +215,36 @@ impl SidewalkPathfinder {
             unreachable!();
         }
 
+        if let Some(time) = closed_at {
+            // The first step is where the pedestrian already is, so they can always leave it.
+            if steps
+                .iter()
+                .skip(1)
+                .any(|s| map.is_closed(s.as_traversable(), time))
+            {
+                return None;
+            }
+        }
+
         Some(Path::new(map, steps, req.end.dist_along()))
     }
 
+    // Closed if every turn between the two sidewalks is.
+    fn is_crossing_closed(
+        &self,
+        from: DirectedRoadID,
+        to: DirectedRoadID,
+        map: &Map,
+        time: Duration,
+    ) -> bool {
+        let l1 = map.get_l(self.get_sidewalk(from, map));
+        let l2 = self.get_sidewalk(to, map);
+        vec![l1.src_i, l1.dst_i]
+            .into_iter()
+            .filter_map(|i| map.get_turn_between(l1.id, l2, i))
+            .all(|t| map.is_closed(Traversable::Turn(t), time))
+    }
+
     // Attempt the pathfinding and see if riding a bus is a step.
     pub fn should_use_transit(
         &self,
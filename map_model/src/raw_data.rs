@@ -32,6 +32,8 @@ pub struct Map {
     pub buildings: Vec<Building>,
     pub bus_routes: Vec<Route>,
     pub areas: Vec<Area>,
+    #[serde(default)]
+    pub bike_parking: Vec<BikeParking>,
 
     pub boundary_polygon: Vec<LonLat>,
     pub coordinates_in_world_space: bool,
@@ -45,6 +47,7 @@ impl Map {
             buildings: Vec::new(),
             bus_routes: Vec::new(),
             areas: Vec::new(),
+            bike_parking: Vec::new(),
             boundary_polygon: Vec::new(),
             coordinates_in_world_space: false,
         }
@@ -110,15 +113,25 @@ pub struct Intersection {
     pub elevation: Distance,
     pub intersection_type: IntersectionType,
     pub label: Option<String>,
+    // None for intersections that aren't an OSM node, like where a road crosses the boundary
+    #[serde(default)]
+    pub osm_node_id: Option<i64>,
 }
 
 #[derive(Clone, PartialEq, Debug, Serialize, Deserialize)]
 pub struct Building {
     // last point never the first?
     pub points: Vec<LonLat>,
+    // Courtyards and the like. Each ring is closed.
+    #[serde(default)]
+    pub inner_rings: Vec<Vec<LonLat>>,
     pub osm_tags: BTreeMap<String, String>,
     pub osm_way_id: i64,
     pub num_residential_units: Option<usize>,
+    // The landuse tag of the area surrounding the building, if any. Not from the building's own
+    // tags.
+    #[serde(default)]
+    pub landuse: Option<String>,
 }
 
 #[derive(Clone, PartialEq, Debug, Serialize, Deserialize)]
@@ -126,6 +139,19 @@ pub struct Area {
     pub area_type: AreaType,
     // last point is always the same as the first
     pub points: Vec<LonLat>,
+    // Islands in a lake, clearings in a park, etc. Each ring is closed.
+    #[serde(default)]
+    pub inner_rings: Vec<Vec<LonLat>>,
     pub osm_tags: BTreeMap<String, String>,
     pub osm_id: i64,
 }
+
+// amenity=bicycle_parking or amenity=bicycle_rental
+#[derive(Clone, PartialEq, Debug, Serialize, Deserialize)]
+pub struct BikeParking {
+    pub point: LonLat,
+    pub is_bikeshare: bool,
+    // From the capacity tag, if there is one
+    pub capacity: Option<usize>,
+    pub osm_node_id: i64,
+}
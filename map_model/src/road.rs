@@ -1,4 +1,4 @@
-use crate::{raw_data, IntersectionID, LaneID, LaneType, LANE_THICKNESS};
+use crate::{make, raw_data, IntersectionID, LaneID, LaneType, LANE_THICKNESS};
 use abstutil::{Error, Warn};
 use geom::{Distance, PolyLine, Polygon, Speed};
 use serde_derive::{Deserialize, Serialize};
@@ -53,7 +53,7 @@ impl fmt::Display for DirectedRoadID {
 }
 
 // These're bidirectional (possibly)
-#[derive(Serialize, Deserialize, Debug)]
+#[derive(Serialize, Deserialize, Debug, Clone)]
 pub struct Road {
     pub id: RoadID,
     pub osm_tags: BTreeMap<String, String>,
@@ -71,7 +71,7 @@ pub struct Road {
     pub src_i: IntersectionID,
     pub dst_i: IntersectionID,
 
-    // For debugging.
+    // Untrimmed. Needed to recalculate intersection geometry after editing lanes.
     pub original_center_pts: PolyLine,
 
     // Need to retain for map editing.
@@ -87,6 +87,33 @@ impl Road {
         )
     }
 
+    // The (track, contraflow half) of a two-way bike track, if this road has one.
+    pub fn two_way_track(&self) -> Option<(LaneID, LaneID)> {
+        let (fwd, back) = self.get_lane_types();
+        let (track_side, contraflow_side) = if make::contraflow_bike_track(&fwd, &back)? {
+            (&self.children_forwards, &self.children_backwards)
+        } else {
+            (&self.children_backwards, &self.children_forwards)
+        };
+        let track = track_side
+            .iter()
+            .find(|(_, lt)| *lt == LaneType::TwoWayBiking)?
+            .0;
+        Some((track, contraflow_side.last()?.0))
+    }
+
+    // How wide each side of the road is, in lanes
+    pub fn lanes_wide(&self) -> (usize, usize) {
+        let (mut fwd, mut back) = (self.children_forwards.len(), self.children_backwards.len());
+        let (fwd_types, back_types) = self.get_lane_types();
+        match make::contraflow_bike_track(&fwd_types, &back_types) {
+            Some(true) => back -= 1,
+            Some(false) => fwd -= 1,
+            None => {}
+        }
+        (fwd, back)
+    }
+
     pub fn is_forwards(&self, lane: LaneID) -> bool {
         self.dir_and_offset(lane).0
     }
@@ -141,13 +168,13 @@ impl Road {
             self.children_forwards[0..idx]
                 .iter()
                 .rev()
-                .find(|(_, lt)| *lt == LaneType::Driving || *lt == LaneType::Biking)
+                .find(|(_, lt)| *lt == LaneType::Driving || lt.is_for_bikes())
                 .map(|(id, _)| *id)
         } else {
             self.children_backwards[0..idx]
                 .iter()
                 .rev()
-                .find(|(_, lt)| *lt == LaneType::Driving || *lt == LaneType::Biking)
+                .find(|(_, lt)| *lt == LaneType::Driving || lt.is_for_bikes())
                 .map(|(id, _)| *id)
         }
     }
@@ -158,12 +185,12 @@ impl Road {
         if fwds {
             self.children_forwards[idx..]
                 .iter()
-                .find(|(_, lt)| *lt == LaneType::Sidewalk)
+                .find(|(_, lt)| lt.supports_walking())
                 .map(|(id, _)| *id)
         } else {
             self.children_backwards[idx..]
                 .iter()
-                .find(|(_, lt)| *lt == LaneType::Sidewalk)
+                .find(|(_, lt)| lt.supports_walking())
                 .map(|(id, _)| *id)
         }
     }
@@ -241,7 +268,7 @@ impl Road {
             &self.children_backwards
         };
         // Deal with one-ways and sidewalks on both sides
-        if list.len() == 1 && list[0].1 == LaneType::Sidewalk {
+        if list.len() == 1 && list[0].1.supports_walking() {
             list = if dir {
                 &self.children_backwards
             } else {
@@ -292,8 +319,9 @@ impl Road {
     }
 
     pub fn get_thick_polygon(&self) -> Warn<Polygon> {
-        let width_right = (self.children_forwards.len() as f64) * LANE_THICKNESS;
-        let width_left = (self.children_backwards.len() as f64) * LANE_THICKNESS;
+        let (fwd, back) = self.lanes_wide();
+        let width_right = (fwd as f64) * LANE_THICKNESS;
+        let width_left = (back as f64) * LANE_THICKNESS;
         let total_width = width_right + width_left;
         if width_right >= width_left {
             self.center_pts
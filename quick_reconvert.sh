@@ -24,7 +24,7 @@ rm -rf data/neighborhoods/$name;
 cd convert_osm;
 RUST_BACKTRACE=1 cargo run $release_mode -- \
 	--osm=../data/input/$name.osm \
-	--elevation=../data/input/N47W122.hgt \
+	--elevation=../data/input/ \
 	--clip=../data/polygons/$name.poly \
 	--output=../data/raw_maps/$name.abst
 
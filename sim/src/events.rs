@@ -1,8 +1,12 @@
-use crate::{AgentID, CarID, ParkingSpot, PedestrianID};
-use map_model::{BuildingID, BusStopID, IntersectionID, LaneID, Traversable};
+use crate::{AgentID, CarID, ParkingSpot, PedestrianID, PersonID, TripID};
+use abstutil::{BinaryStreamReader, BinaryStreamWriter};
+use geom::Duration;
+use map_model::{
+    BikeParkingID, BuildingID, BusStopID, IntersectionID, LaneID, Traversable, TurnID,
+};
 use serde_derive::{Deserialize, Serialize};
 
-#[derive(Debug, PartialEq, Eq, Serialize, Deserialize)]
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
 pub enum Event {
     CarReachedParkingSpot(CarID, ParkingSpot),
     CarOrBikeReachedBorder(CarID, IntersectionID),
@@ -18,7 +22,134 @@ pub enum Event {
     PedLeavesBus(PedestrianID, CarID),
 
     BikeStoppedAtSidewalk(CarID, LaneID),
+    // The bike has to go on to another rack or dock
+    BikeParkingFull(CarID, BikeParkingID),
+    // The pedestrian has to walk to another dock
+    BikeshareDockEmpty(PedestrianID, BikeParkingID),
+    // A bikeshare bike that no dock had room to take back, left on the sidewalk here
+    BikeAbandoned(CarID, LaneID),
 
-    // TODO Remove this one
+    // The next leg couldn't start, usually because there's no path. Whoever was making the trip
+    // is stuck where the last leg ended.
+    TripAborted(TripID),
+    // The person's next trip couldn't even start, so they stay put and move on to the one after.
+    PersonTripSkipped(PersonID, BuildingID),
+
+    // The agent starts on this lane
+    AgentSpawned(AgentID, Traversable),
+    // The agent finished a leg of their trip and isn't in the simulation anymore. The next leg
+    // might be the same person as a different agent. Pedestrians riding a bus finish that leg when
+    // they get off.
+    AgentDespawned(AgentID),
+
+    // There are so many of these that the Sim only keeps them while recording events.
+    // Moving from a lane to a turn or from a turn to a lane
     AgentEntersTraversable(AgentID, Traversable),
+    TurnAccepted(AgentID, TurnID),
+    // Only when the agent starts waiting, not for every retry after that
+    TurnDenied(AgentID, TurnID),
+}
+
+impl Event {
+    pub fn is_high_volume(&self) -> bool {
+        match self {
+            Event::AgentEntersTraversable(_, _)
+            | Event::TurnAccepted(_, _)
+            | Event::TurnDenied(_, _) => true,
+            _ => false,
+        }
+    }
+}
+
+// Written first in an event log, to say which run it describes.
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct EventLogHeader {
+    pub map_name: String,
+    pub edits_name: String,
+    pub run_name: String,
+    // When recording began
+    pub start_time: Duration,
+}
+
+// Appends every event to a file as it happens, so a run can be examined afterwards.
+pub(crate) struct EventRecorder {
+    path: String,
+    file: BinaryStreamWriter,
+}
+
+impl EventRecorder {
+    pub fn new(path: &str, header: &EventLogHeader) -> Result<EventRecorder, std::io::Error> {
+        let mut file = BinaryStreamWriter::new(path)?;
+        file.append(header)?;
+        Ok(EventRecorder {
+            path: path.to_string(),
+            file,
+        })
+    }
+
+    pub fn record(&mut self, time: Duration, ev: &Event) -> Result<(), std::io::Error> {
+        self.file.append(&(time, ev)).map_err(|e| self.describe(e))
+    }
+
+    // Called after every step, so the log is complete up to the last step even if the simulation
+    // crashes later.
+    pub fn flush(&mut self) -> Result<(), std::io::Error> {
+        self.file.flush().map_err(|e| self.describe(e))
+    }
+
+    fn describe(&self, e: std::io::Error) -> std::io::Error {
+        std::io::Error::new(
+            e.kind(),
+            format!("Couldn't record events to {}: {}", self.path, e),
+        )
+    }
+}
+
+// Reads back a file written by Sim::record_events, one event at a time.
+pub struct EventLog {
+    pub header: EventLogHeader,
+    path: String,
+    file: BinaryStreamReader,
+    // Set after the first error, since nothing after it can be trusted
+    done: bool,
+}
+
+impl EventLog {
+    pub fn open(path: &str) -> Result<EventLog, std::io::Error> {
+        let mut file = BinaryStreamReader::open(path)?;
+        let header = file.read_next()?.ok_or_else(|| {
+            std::io::Error::new(
+                std::io::ErrorKind::UnexpectedEof,
+                format!("{} is empty", path),
+            )
+        })?;
+        Ok(EventLog {
+            header,
+            path: path.to_string(),
+            file,
+            done: false,
+        })
+    }
+}
+
+impl Iterator for EventLog {
+    type Item = Result<(Duration, Event), std::io::Error>;
+
+    // Events come in the order they happened. If the recording simulation crashed partway through
+    // writing an event, everything before that is still returned, then one error, then nothing.
+    fn next(&mut self) -> Option<Result<(Duration, Event), std::io::Error>> {
+        if self.done {
+            return None;
+        }
+        match self.file.read_next() {
+            Ok(x) => x.map(Ok),
+            Err(e) => {
+                self.done = true;
+                Some(Err(std::io::Error::new(
+                    e.kind(),
+                    format!("Stopped reading {} early: {}", self.path, e),
+                )))
+            }
+        }
+    }
 }
@@ -6,27 +6,38 @@ mod render;
 mod router;
 mod scheduler;
 mod sim;
+mod trajectories;
 mod transit;
 mod trips;
 
-pub use self::events::Event;
+pub(crate) use self::events::EventRecorder;
+pub use self::events::{Event, EventLog, EventLogHeader};
 pub use self::make::{
     ABTest, ABTestResults, BorderSpawnOverTime, OriginDestination, Scenario, SeedParkedCars,
-    SimFlags, SpawnOverTime, TripSpawner, TripSpec,
+    SimFlags, SpawnOverTime, Tour, TourLeg, TourParams, TripSpawner, TripSpec,
+};
+pub use self::mechanics::{
+    bike_speed_on_grade, walking_speed_on_grade, CycleStats, DelayStats, IntersectionStats,
+    QueueLengthWindow,
 };
 pub(crate) use self::mechanics::{
-    DrivingSimState, IntersectionSimState, ParkingSimState, WalkingSimState,
+    BikeParkingSimState, DrivingSimState, IntersectionSimState, ParkingSimState, WalkingSimState,
 };
 pub use self::query::{Benchmark, ScoreSummary, SimStats, Summary};
 pub(crate) use self::router::{ActionAtEnd, Router};
 pub(crate) use self::scheduler::{Command, Scheduler};
-pub use self::sim::Sim;
+pub use self::sim::{SavestateHeader, Sim};
+pub(crate) use self::trajectories::TrajectoryRecorder;
+pub use self::trajectories::{trajectories_dir, Trajectories, TrajectoryHeader};
 pub(crate) use self::transit::TransitSimState;
+pub use self::trips::{Person, PersonSpec, PersonTrip, TripMode};
 pub(crate) use self::trips::{TripLeg, TripManager};
 pub use crate::render::{CarStatus, DrawCarInput, DrawPedestrianInput, GetDrawAgents};
 use abstutil::Cloneable;
 use geom::{Distance, Duration, Speed};
-use map_model::{BuildingID, BusStopID, IntersectionID, LaneID, LaneType, Map, Path, Position};
+use map_model::{
+    BikeParkingID, BuildingID, BusStopID, IntersectionID, LaneID, LaneType, Map, Path, Position,
+};
 use serde_derive::{Deserialize, Serialize};
 use std::fmt;
 
@@ -104,6 +115,15 @@ impl fmt::Display for TripID {
     }
 }
 
+#[derive(Clone, Copy, Debug, Eq, Hash, PartialEq, PartialOrd, Ord, Serialize, Deserialize)]
+pub struct PersonID(pub usize);
+
+impl fmt::Display for PersonID {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "PersonID({0})", self.0)
+    }
+}
+
 #[derive(Serialize, Deserialize, Clone, Copy, PartialEq, Eq, Debug, Hash, PartialOrd, Ord)]
 pub enum VehicleType {
     Car,
@@ -227,7 +247,7 @@ impl SidewalkSpot {
     ) -> SidewalkSpot {
         // TODO Consider precomputing this.
         let sidewalk = map
-            .find_closest_lane(spot.lane, vec![LaneType::Sidewalk])
+            .find_closest_lane(spot.lane, vec![LaneType::Sidewalk, LaneType::SharedUse])
             .unwrap();
         SidewalkSpot {
             connection: SidewalkPOI::ParkingSpot(spot),
@@ -244,7 +264,7 @@ impl SidewalkSpot {
     }
 
     pub fn bike_rack(sidewalk: LaneID, map: &Map) -> Option<SidewalkSpot> {
-        assert!(map.get_l(sidewalk).is_sidewalk());
+        assert!(map.get_l(sidewalk).lane_type.supports_walking());
         let driving_lane = map.get_parent(sidewalk).sidewalk_to_bike(sidewalk)?;
         // TODO Arbitrary, but safe
         let sidewalk_pos = Position::new(sidewalk, map.get_l(sidewalk).length() / 2.0);
@@ -255,6 +275,14 @@ impl SidewalkSpot {
         })
     }
 
+    pub fn bike_parking(id: BikeParkingID, map: &Map) -> SidewalkSpot {
+        let p = map.get_bike_parking(id);
+        SidewalkSpot {
+            sidewalk_pos: p.sidewalk_pos,
+            connection: SidewalkPOI::BikeParking(id, p.driving_pos),
+        }
+    }
+
     pub fn bus_stop(stop: BusStopID, map: &Map) -> SidewalkSpot {
         SidewalkSpot {
             sidewalk_pos: map.get_bs(stop).sidewalk_pos,
@@ -263,7 +291,7 @@ impl SidewalkSpot {
     }
 
     pub fn start_at_border(i: IntersectionID, map: &Map) -> Option<SidewalkSpot> {
-        let lanes = map.get_i(i).get_outgoing_lanes(map, LaneType::Sidewalk);
+        let lanes = map.get_i(i).get_outgoing_walking_lanes(map);
         if lanes.is_empty() {
             None
         } else {
@@ -275,7 +303,7 @@ impl SidewalkSpot {
     }
 
     pub fn end_at_border(i: IntersectionID, map: &Map) -> Option<SidewalkSpot> {
-        let lanes = map.get_i(i).get_incoming_lanes(map, LaneType::Sidewalk);
+        let lanes = map.get_i(i).get_incoming_walking_lanes(map);
         if lanes.is_empty() {
             None
         } else {
@@ -288,7 +316,7 @@ impl SidewalkSpot {
 
     pub fn suddenly_appear(l: LaneID, dist: Distance, map: &Map) -> SidewalkSpot {
         let lane = map.get_l(l);
-        assert!(lane.is_sidewalk());
+        assert!(lane.lane_type.supports_walking());
         assert!(dist <= lane.length());
         SidewalkSpot {
             sidewalk_pos: Position::new(l, dist),
@@ -306,6 +334,8 @@ pub enum SidewalkPOI {
     Border(IntersectionID),
     // The equivalent position on the nearest driving/bike lane
     BikeRack(Position),
+    // A rack or bikeshare dock from the map, with the same equivalent position
+    BikeParking(BikeParkingID, Position),
     SuddenlyAppear,
 }
 
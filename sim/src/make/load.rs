@@ -2,7 +2,7 @@ use crate::{Scenario, Sim};
 use abstutil;
 use abstutil::Timer;
 use geom::Duration;
-use map_model::{Map, MapEdits};
+use map_model::{Map, MapEdits, PathfinderOptions};
 use rand::{FromEntropy, SeedableRng};
 use rand_xorshift::XorShiftRng;
 use std::path::{Path, PathBuf};
@@ -30,6 +30,10 @@ pub struct SimFlags {
     /// Name of map edits. Shouldn't be a full path or have the ".json"
     #[structopt(long = "edits_name", default_value = "no_edits")]
     pub edits_name: String,
+
+    /// Route bikes around hills
+    #[structopt(long = "bikes_avoid_climbs")]
+    pub bikes_avoid_climbs: bool,
 }
 
 impl SimFlags {
@@ -44,6 +48,7 @@ impl SimFlags {
             rng_seed: Some(42),
             run_name: run_name.to_string(),
             edits_name: "no_edits".to_string(),
+            bikes_avoid_climbs: false,
         }
     }
 
@@ -67,15 +72,24 @@ impl SimFlags {
             assert_eq!(self.edits_name, "no_edits");
             timer.note(format!("Resuming from {}", self.load.display()));
 
-            timer.start("read sim savestate");
-            let sim: Sim =
-                abstutil::read_json(self.load.to_str().unwrap()).expect("loading sim state failed");
-            timer.stop("read sim savestate");
+            let path = self.load.to_str().unwrap().to_string();
+            let header = match Sim::read_savestate_header(&path) {
+                Ok(h) => h,
+                Err(e) => panic!("Can't resume: {}", e),
+            };
 
             let mut map: Map =
-                abstutil::read_binary(&format!("../data/maps/{}.abst", sim.map_name), timer)
+                abstutil::read_binary(&format!("../data/maps/{}.abst", header.map_name), timer)
                     .unwrap();
-            apply_edits(&mut map, &sim.edits_name, timer);
+            apply_edits(&mut map, &header.edits_name, timer);
+            self.set_pathfinder_options(&mut map, timer);
+
+            timer.start("read sim savestate");
+            let sim = match Sim::load_savestate(path, None, &map, self.rng_seed) {
+                Ok(s) => s,
+                Err(e) => panic!("Can't resume: {}", e),
+            };
+            timer.stop("read sim savestate");
 
             (map, sim, rng)
         } else if self.load.starts_with(Path::new("../data/scenarios/")) {
@@ -91,12 +105,14 @@ impl SimFlags {
                 abstutil::read_binary(&format!("../data/maps/{}.abst", scenario.map_name), timer)
                     .unwrap();
             apply_edits(&mut map, &self.edits_name, timer);
+            self.set_pathfinder_options(&mut map, timer);
 
             let mut sim = Sim::new(
                 &map,
                 // TODO or the scenario name if no run name
                 self.run_name.clone(),
                 savestate_every,
+                self.rng_seed,
             );
             scenario.instantiate(&mut sim, &map, &mut rng, timer);
 
@@ -107,9 +123,10 @@ impl SimFlags {
             let mut map = Map::new(self.load.to_str().unwrap(), timer)
                 .expect(&format!("Couldn't load map from {}", self.load.display()));
             apply_edits(&mut map, &self.edits_name, timer);
+            self.set_pathfinder_options(&mut map, timer);
 
             timer.start("create sim");
-            let sim = Sim::new(&map, self.run_name.clone(), savestate_every);
+            let sim = Sim::new(&map, self.run_name.clone(), savestate_every, self.rng_seed);
             timer.stop("create sim");
 
             (map, sim, rng)
@@ -119,9 +136,10 @@ impl SimFlags {
             let mut map: Map = abstutil::read_binary(self.load.to_str().unwrap(), timer)
                 .expect(&format!("Couldn't load map from {}", self.load.display()));
             apply_edits(&mut map, &self.edits_name, timer);
+            self.set_pathfinder_options(&mut map, timer);
 
             timer.start("create sim");
-            let sim = Sim::new(&map, self.run_name.clone(), savestate_every);
+            let sim = Sim::new(&map, self.run_name.clone(), savestate_every, self.rng_seed);
             timer.stop("create sim");
 
             (map, sim, rng)
@@ -129,17 +147,22 @@ impl SimFlags {
             panic!("Don't know how to load {}", self.load.display());
         }
     }
+
+    fn set_pathfinder_options(&self, map: &mut Map, timer: &mut Timer) {
+        map.set_pathfinder_options(
+            PathfinderOptions {
+                bikes_avoid_climbs: self.bikes_avoid_climbs,
+            },
+            timer,
+        );
+    }
 }
 
 fn apply_edits(map: &mut Map, edits_name: &str, timer: &mut Timer) {
     if edits_name == "no_edits" {
         return;
     }
-    let edits: MapEdits = abstutil::read_json(&format!(
-        "../data/edits/{}/{}.json",
-        map.get_name(),
-        edits_name
-    ))
-    .unwrap();
+    let edits = MapEdits::load(map, edits_name, timer)
+        .unwrap_or_else(|err| panic!("Couldn't load map edits {}: {}", edits_name, err));
     map.apply_edits(edits, timer);
 }
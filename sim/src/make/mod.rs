@@ -2,6 +2,7 @@ mod a_b_test;
 mod load;
 mod scenario;
 mod spawner;
+mod tours;
 
 pub use self::a_b_test::{ABTest, ABTestResults};
 pub use self::load::SimFlags;
@@ -9,3 +10,4 @@ pub use self::scenario::{
     BorderSpawnOverTime, OriginDestination, Scenario, SeedParkedCars, SpawnOverTime,
 };
 pub use self::spawner::{TripSpawner, TripSpec};
+pub use self::tours::{Tour, TourLeg, TourParams};
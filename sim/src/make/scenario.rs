@@ -1,3 +1,4 @@
+use crate::make::tours::{generate_tours, Tour, TourParams};
 use crate::{
     CarID, DrivingGoal, ParkingSpot, SidewalkSpot, Sim, TripSpec, VehicleSpec, VehicleType,
     BIKE_LENGTH, MAX_CAR_LENGTH, MIN_CAR_LENGTH,
@@ -6,7 +7,8 @@ use abstutil;
 use abstutil::{fork_rng, Timer, WeightedUsizeChoice};
 use geom::{Distance, Duration, Speed};
 use map_model::{
-    BuildingID, FullNeighborhoodInfo, IntersectionID, LaneType, Map, Position, RoadID,
+    BuildingID, FullNeighborhoodInfo, IntersectionID, LaneType, Map, OriginalIntersection,
+    Position, RoadID,
 };
 use rand::seq::SliceRandom;
 use rand::Rng;
@@ -22,6 +24,8 @@ pub struct Scenario {
     pub seed_parked_cars: Vec<SeedParkedCars>,
     pub spawn_over_time: Vec<SpawnOverTime>,
     pub border_spawn_over_time: Vec<BorderSpawnOverTime>,
+    #[serde(default)]
+    pub tours: Vec<Tour>,
 }
 
 // SpawnOverTime and BorderSpawnOverTime should be kept separate. Agents in SpawnOverTime pick
@@ -47,8 +51,7 @@ pub struct BorderSpawnOverTime {
     // TODO use https://docs.rs/rand/0.5.5/rand/distributions/struct.Normal.html
     pub start_time: Duration,
     pub stop_time: Duration,
-    // TODO A serialized Scenario won't last well as the map changes...
-    pub start_from_border: IntersectionID,
+    pub start_from_border: OriginalIntersection,
     pub goal: OriginDestination,
     pub percent_use_transit: f64,
 }
@@ -110,12 +113,25 @@ impl Scenario {
             }
         }
 
+        timer.start_iter("Tours", self.tours.len());
+        for t in &self.tours {
+            timer.next();
+            t.spawn(rng, sim, &mut reserved_cars);
+        }
+
         timer.start_iter("BorderSpawnOverTime", self.border_spawn_over_time.len());
         for s in &self.border_spawn_over_time {
             timer.next();
-            s.spawn_peds(rng, sim, &neighborhoods, map, timer);
-            s.spawn_cars(rng, sim, &neighborhoods, map, timer);
-            s.spawn_bikes(rng, sim, &neighborhoods, map, timer);
+            if let Some(i) = s.start_from_border.from_permanent(map) {
+                s.spawn_peds(i, rng, sim, &neighborhoods, map, timer);
+                s.spawn_cars(i, rng, sim, &neighborhoods, map, timer);
+                s.spawn_bikes(i, rng, sim, &neighborhoods, map, timer);
+            } else {
+                timer.warn(format!(
+                    "Border {:?} doesn't exist in this map anymore",
+                    s.start_from_border
+                ));
+            }
         }
 
         sim.spawn_all_trips(map, timer);
@@ -156,11 +172,12 @@ impl Scenario {
                     num_bikes: 10,
                     start_time: Duration::ZERO,
                     stop_time: Duration::seconds(5.0),
-                    start_from_border: i.id,
+                    start_from_border: OriginalIntersection::to_permanent(i.id, map),
                     goal: OriginDestination::Neighborhood("_everywhere_".to_string()),
                     percent_use_transit: 0.5,
                 })
                 .collect(),
+            tours: Vec::new(),
         };
         for i in map.all_outgoing_borders() {
             s.spawn_over_time.push(SpawnOverTime {
@@ -168,7 +185,7 @@ impl Scenario {
                 start_time: Duration::ZERO,
                 stop_time: Duration::seconds(5.0),
                 start_from_neighborhood: "_everywhere_".to_string(),
-                goal: OriginDestination::Border(i.id),
+                goal: OriginDestination::Border(OriginalIntersection::to_permanent(i.id, map)),
                 percent_biking: 0.5,
                 percent_use_transit: 0.5,
             });
@@ -197,6 +214,33 @@ impl Scenario {
                 percent_use_transit: 0.5,
             }],
             border_spawn_over_time: Vec::new(),
+            tours: Vec::new(),
+        }
+    }
+
+    // Instead of random origins and destinations, residents make daily tours between buildings
+    // that attract trips.
+    pub fn home_based_tours(
+        map: &Map,
+        params: &TourParams,
+        rng: &mut XorShiftRng,
+        timer: &mut Timer,
+    ) -> Scenario {
+        timer.start("generate home-based tours");
+        let tours = generate_tours(map, params, rng, timer);
+        timer.stop("generate home-based tours");
+        Scenario {
+            scenario_name: "home_based_tours".to_string(),
+            map_name: map.get_name().to_string(),
+            seed_parked_cars: vec![SeedParkedCars {
+                neighborhood: "_everywhere_".to_string(),
+                cars_per_building: WeightedUsizeChoice {
+                    weights: vec![5, 5],
+                },
+            }],
+            spawn_over_time: Vec::new(),
+            border_spawn_over_time: Vec::new(),
+            tours,
         }
     }
 
@@ -379,6 +423,7 @@ impl SpawnOverTime {
 impl BorderSpawnOverTime {
     fn spawn_peds(
         &self,
+        start_from_border: IntersectionID,
         rng: &mut XorShiftRng,
         sim: &mut Sim,
         neighborhoods: &HashMap<String, FullNeighborhoodInfo>,
@@ -389,12 +434,12 @@ impl BorderSpawnOverTime {
             return;
         }
 
-        let start = if let Some(s) = SidewalkSpot::start_at_border(self.start_from_border, map) {
+        let start = if let Some(s) = SidewalkSpot::start_at_border(start_from_border, map) {
             s
         } else {
             timer.warn(format!(
                 "Can't start_at_border for {} without sidewalk",
-                self.start_from_border
+                start_from_border
             ));
             return;
         };
@@ -439,6 +484,7 @@ impl BorderSpawnOverTime {
 
     fn spawn_cars(
         &self,
+        start_from_border: IntersectionID,
         rng: &mut XorShiftRng,
         sim: &mut Sim,
         neighborhoods: &HashMap<String, FullNeighborhoodInfo>,
@@ -449,12 +495,12 @@ impl BorderSpawnOverTime {
             return;
         }
         let starting_driving_lanes = map
-            .get_i(self.start_from_border)
+            .get_i(start_from_border)
             .get_outgoing_lanes(map, LaneType::Driving);
         if starting_driving_lanes.is_empty() {
             timer.warn(format!(
                 "Can't start car at border for {}",
-                self.start_from_border
+                start_from_border
             ));
             return;
         }
@@ -494,6 +540,7 @@ impl BorderSpawnOverTime {
 
     fn spawn_bikes(
         &self,
+        start_from_border: IntersectionID,
         rng: &mut XorShiftRng,
         sim: &mut Sim,
         neighborhoods: &HashMap<String, FullNeighborhoodInfo>,
@@ -504,10 +551,10 @@ impl BorderSpawnOverTime {
             return;
         }
         let mut starting_biking_lanes = map
-            .get_i(self.start_from_border)
+            .get_i(start_from_border)
             .get_outgoing_lanes(map, LaneType::Biking);
         for l in map
-            .get_i(self.start_from_border)
+            .get_i(start_from_border)
             .get_outgoing_lanes(map, LaneType::Driving)
         {
             if map.get_parent(l).supports_bikes() {
@@ -519,7 +566,7 @@ impl BorderSpawnOverTime {
         {
             timer.warn(format!(
                 "Can't start bike at border for {}",
-                self.start_from_border
+                start_from_border
             ));
             return;
         }
@@ -552,8 +599,7 @@ impl BorderSpawnOverTime {
 #[derive(Clone, Serialize, Deserialize, Debug)]
 pub enum OriginDestination {
     Neighborhood(String),
-    // TODO A serialized Scenario won't last well as the map changes...
-    Border(IntersectionID),
+    Border(OriginalIntersection),
 }
 
 impl OriginDestination {
@@ -569,8 +615,17 @@ impl OriginDestination {
             OriginDestination::Neighborhood(ref n) => Some(DrivingGoal::ParkNear(
                 *neighborhoods[n].buildings.choose(rng).unwrap(),
             )),
-            OriginDestination::Border(i) => {
-                let goal = DrivingGoal::end_at_border(*i, lane_types, map);
+            OriginDestination::Border(ref border) => {
+                let i = if let Some(i) = border.from_permanent(map) {
+                    i
+                } else {
+                    timer.warn(format!(
+                        "Border {:?} doesn't exist in this map anymore",
+                        border
+                    ));
+                    return None;
+                };
+                let goal = DrivingGoal::end_at_border(i, lane_types, map);
                 if goal.is_none() {
                     timer.warn(format!(
                         "Can't spawn a car ending at border {}; no appropriate lanes there",
@@ -594,8 +649,17 @@ impl OriginDestination {
                 *neighborhoods[n].buildings.choose(rng).unwrap(),
                 map,
             )),
-            OriginDestination::Border(i) => {
-                let goal = SidewalkSpot::end_at_border(*i, map);
+            OriginDestination::Border(ref border) => {
+                let i = if let Some(i) = border.from_permanent(map) {
+                    i
+                } else {
+                    timer.warn(format!(
+                        "Border {:?} doesn't exist in this map anymore",
+                        border
+                    ));
+                    return None;
+                };
+                let goal = SidewalkSpot::end_at_border(i, map);
                 if goal.is_none() {
                     timer.warn(format!("Can't end_at_border for {} without a sidewalk", i));
                 }
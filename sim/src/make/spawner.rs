@@ -1,13 +1,15 @@
 use crate::{
     CarID, Command, CreateCar, CreatePedestrian, DrivingGoal, ParkingSimState, ParkingSpot,
-    PedestrianID, Scheduler, SidewalkPOI, SidewalkSpot, TripLeg, TripManager, VehicleSpec,
-    VehicleType,
+    PedestrianID, PersonID, Scheduler, SidewalkPOI, SidewalkSpot, TripLeg, TripManager,
+    VehicleSpec, VehicleType,
 };
-use abstutil::Timer;
+use abstutil::{deserialize_btreemap, serialize_btreemap, Timer};
 use geom::{Duration, Speed};
-use map_model::{BusRouteID, BusStopID, Map, Path, PathRequest, Position};
+use map_model::{
+    BikeParkingID, BikeParkingType, BusRouteID, BusStopID, Map, Path, PathRequest, Position,
+};
 use serde_derive::{Deserialize, Serialize};
-use std::collections::BTreeSet;
+use std::collections::BTreeMap;
 
 #[derive(Debug, Serialize, Deserialize, PartialEq)]
 pub enum TripSpec {
@@ -35,6 +37,14 @@ pub enum TripSpec {
         goal: DrivingGoal,
         ped_speed: Speed,
     },
+    // Rent a bike from a dock, and return it to whichever dock near the goal has room
+    UsingBikeshare {
+        start: SidewalkSpot,
+        dock: BikeParkingID,
+        vehicle: VehicleSpec,
+        goal: DrivingGoal,
+        ped_speed: Speed,
+    },
     UsingTransit {
         start: SidewalkSpot,
         route: BusRouteID,
@@ -47,14 +57,26 @@ pub enum TripSpec {
 
 #[derive(Serialize, Deserialize, PartialEq)]
 pub struct TripSpawner {
-    parked_cars_claimed: BTreeSet<CarID>,
-    trips: Vec<(Duration, Option<PedestrianID>, Option<CarID>, TripSpec)>,
+    // Who's using each parked car. People drive their own car on every trip, so only they can
+    // claim it again.
+    #[serde(
+        serialize_with = "serialize_btreemap",
+        deserialize_with = "deserialize_btreemap"
+    )]
+    parked_cars_claimed: BTreeMap<CarID, Option<PersonID>>,
+    trips: Vec<(
+        Duration,
+        Option<PedestrianID>,
+        Option<CarID>,
+        Option<PersonID>,
+        TripSpec,
+    )>,
 }
 
 impl TripSpawner {
     pub fn new() -> TripSpawner {
         TripSpawner {
-            parked_cars_claimed: BTreeSet::new(),
+            parked_cars_claimed: BTreeMap::new(),
             trips: Vec::new(),
         }
     }
@@ -64,86 +86,145 @@ impl TripSpawner {
         start_time: Duration,
         ped_id: Option<PedestrianID>,
         car_id: Option<CarID>,
+        person: Option<PersonID>,
         spec: TripSpec,
         map: &Map,
         parking: &ParkingSimState,
     ) {
         // TODO We'll want to repeat this validation when we spawn stuff later for a second leg...
-        match &spec {
+        if let Err(err) = self.validate_trip(person, &spec, map, parking) {
+            panic!("{}", err);
+        }
+        if let TripSpec::UsingParkedCar { spot, .. } = &spec {
+            let car_id = parking.get_car_at_spot(*spot).unwrap().vehicle.id;
+            self.parked_cars_claimed.insert(car_id, person);
+        }
+
+        self.trips.push((start_time, ped_id, car_id, person, spec));
+    }
+
+    // Everything schedule_trip would panic about. The trip might still fail to start later, if
+    // there's no path.
+    pub fn validate_trip(
+        &self,
+        person: Option<PersonID>,
+        spec: &TripSpec,
+        map: &Map,
+        parking: &ParkingSimState,
+    ) -> Result<(), String> {
+        match spec {
             TripSpec::CarAppearing {
                 start_pos,
                 vehicle_spec,
                 goal,
                 ..
             } => {
+                let lane = map
+                    .maybe_get_l(start_pos.lane())
+                    .ok_or_else(|| format!("{} doesn't exist", start_pos.lane()))?;
+                validate_driving_goal(goal, map)?;
                 if start_pos.dist_along() < vehicle_spec.length {
-                    panic!(
+                    return Err(format!(
                         "Can't spawn a car at {}; too close to the start",
                         start_pos.dist_along()
-                    );
+                    ));
                 }
-                if start_pos.dist_along() >= map.get_l(start_pos.lane()).length() {
-                    panic!(
+                if start_pos.dist_along() >= lane.length() {
+                    return Err(format!(
                         "Can't spawn a car at {}; {} isn't that long",
                         start_pos.dist_along(),
                         start_pos.lane()
-                    );
+                    ));
                 }
                 match goal {
                     DrivingGoal::Border(_, end_lane) => {
                         if start_pos.lane() == *end_lane
                             && start_pos.dist_along() == map.get_l(*end_lane).length()
                         {
-                            panic!("Can't start a car at the edge of a border already");
+                            return Err(
+                                "Can't start a car at the edge of a border already".to_string()
+                            );
                         }
                     }
                     DrivingGoal::ParkNear(_) => {}
                 }
             }
-            TripSpec::UsingParkedCar { spot, .. } => {
-                let car_id = parking.get_car_at_spot(*spot).unwrap().vehicle.id;
-                if self.parked_cars_claimed.contains(&car_id) {
-                    panic!(
-                        "A TripSpec wants to use {}, which is already claimed",
-                        car_id
-                    );
+            TripSpec::UsingParkedCar {
+                start, spot, goal, ..
+            } => {
+                validate_sidewalk_spot(start, map)?;
+                validate_driving_goal(goal, map)?;
+                let car_id = parking
+                    .get_car_at_spot(*spot)
+                    .ok_or_else(|| format!("No car is parked at {:?}", spot))?
+                    .vehicle
+                    .id;
+                if let Some(claimant) = self.parked_cars_claimed.get(&car_id) {
+                    if claimant.is_none() || *claimant != person {
+                        return Err(format!(
+                            "A TripSpec wants to use {}, which is already claimed by {:?}",
+                            car_id, claimant
+                        ));
+                    }
                 }
-                self.parked_cars_claimed.insert(car_id);
             }
             TripSpec::JustWalking { start, goal, .. } => {
+                validate_sidewalk_spot(start, map)?;
+                validate_sidewalk_spot(goal, map)?;
                 if start == goal {
-                    panic!(
+                    return Err(format!(
                         "A trip just walking from {:?} to {:?} doesn't make sense",
                         start, goal
-                    );
+                    ));
                 }
             }
             TripSpec::UsingBike { start, goal, .. } => {
+                validate_sidewalk_spot(start, map)?;
+                validate_driving_goal(goal, map)?;
                 if SidewalkSpot::bike_rack(start.sidewalk_pos.lane(), map).is_none() {
-                    panic!(
+                    return Err(format!(
                         "Can't start biking from {}; no biking or driving lane nearby?",
                         start.sidewalk_pos.lane()
-                    );
+                    ));
                 }
-                if let DrivingGoal::ParkNear(_) = goal {
-                    let last_lane = goal.goal_pos(map).lane();
-                    // If bike_to_sidewalk works, then SidewalkSpot::bike_rack should too.
-                    if map
-                        .get_parent(last_lane)
-                        .bike_to_sidewalk(last_lane)
-                        .is_none()
-                    {
-                        panic!(
-                            "Can't fulfill {:?} for a bike trip; no sidewalk near {}",
-                            goal, last_lane
-                        );
+                validate_bike_goal(goal, map)?;
+            }
+            TripSpec::UsingBikeshare {
+                start, dock, goal, ..
+            } => {
+                validate_sidewalk_spot(start, map)?;
+                validate_driving_goal(goal, map)?;
+                if dock.0 >= map.all_bike_parking().len() {
+                    return Err(format!("{} doesn't exist", dock));
+                }
+                if map.get_bike_parking(*dock).parking_type != BikeParkingType::Bikeshare {
+                    return Err(format!("Can't rent a bike from {}; it's just a rack", dock));
+                }
+                if let DrivingGoal::Border(_, _) = goal {
+                    return Err(format!(
+                        "Bikeshare bikes have to be returned to a dock, not taken to {:?}",
+                        goal
+                    ));
+                }
+                validate_bike_goal(goal, map)?;
+            }
+            TripSpec::UsingTransit {
+                start,
+                stop1,
+                stop2,
+                goal,
+                ..
+            } => {
+                validate_sidewalk_spot(start, map)?;
+                validate_sidewalk_spot(goal, map)?;
+                for stop in &[stop1, stop2] {
+                    if map.maybe_get_bs(**stop).is_none() {
+                        return Err(format!("{} doesn't exist", stop));
                     }
                 }
             }
-            TripSpec::UsingTransit { .. } => {}
-        };
-
-        self.trips.push((start_time, ped_id, car_id, spec));
+        }
+        Ok(())
     }
 
     pub fn spawn_all(
@@ -158,15 +239,20 @@ impl TripSpawner {
             map,
             self.trips
                 .iter()
-                .map(|(_, _, _, spec)| spec.get_pathfinding_request(map, parking))
+                .map(|(start_time, _, _, _, spec)| {
+                    (spec.get_pathfinding_request(map, parking), *start_time)
+                })
                 .collect(),
             timer,
         );
-        for ((start_time, ped_id, car_id, spec), (req, maybe_path)) in
+        for ((start_time, ped_id, car_id, person, spec), (req, maybe_path)) in
             self.trips.drain(..).zip(paths)
         {
             if maybe_path.is_none() {
                 timer.warn(format!("{:?} couldn't find the first path {:?}", spec, req));
+                if let Some(p) = person {
+                    trips.person_trip_failed_to_start(start_time, p, scheduler);
+                }
                 continue;
             }
             let path = maybe_path.unwrap();
@@ -186,7 +272,7 @@ impl TripSpawner {
                             SidewalkSpot::building(b, map),
                         ));
                     }
-                    let trip = trips.new_trip(start_time, legs);
+                    let trip = trips.new_trip(start_time, person, legs);
                     let router = goal.make_router(path, map, vehicle.vehicle_type);
                     scheduler.push(
                         start_time,
@@ -202,8 +288,14 @@ impl TripSpawner {
                     ped_speed,
                 } => {
                     let vehicle = &parking.get_car_at_spot(spot).unwrap().vehicle;
-                    match start.connection {
-                        SidewalkPOI::Building(b) => assert_eq!(vehicle.owner, Some(b)),
+                    match (&start.connection, person) {
+                        (SidewalkPOI::Building(b), None) => {
+                            assert_eq!(vehicle.owner, Some(*b));
+                        }
+                        // People can drive their car from anywhere they left it.
+                        (SidewalkPOI::Building(_), Some(p)) => {
+                            assert_eq!(trips.get_person(p).unwrap().car, Some(vehicle.id));
+                        }
                         _ => unreachable!(),
                     };
 
@@ -223,7 +315,7 @@ impl TripSpawner {
                         }
                         DrivingGoal::Border(_, _) => {}
                     }
-                    let trip = trips.new_trip(start_time, legs);
+                    let trip = trips.new_trip(start_time, person, legs);
 
                     scheduler.push(
                         start_time,
@@ -244,6 +336,7 @@ impl TripSpawner {
                 } => {
                     let trip = trips.new_trip(
                         start_time,
+                        person,
                         vec![TripLeg::Walk(ped_id.unwrap(), ped_speed, goal.clone())],
                     );
 
@@ -280,7 +373,43 @@ impl TripSpawner {
                         }
                         DrivingGoal::Border(_, _) => {}
                     };
-                    let trip = trips.new_trip(start_time, legs);
+                    let trip = trips.new_trip(start_time, person, legs);
+
+                    scheduler.push(
+                        start_time,
+                        Command::SpawnPed(CreatePedestrian {
+                            id: ped_id.unwrap(),
+                            speed: ped_speed,
+                            start,
+                            goal: walk_to,
+                            path,
+                            trip,
+                        }),
+                    );
+                }
+                TripSpec::UsingBikeshare {
+                    start,
+                    dock,
+                    vehicle,
+                    goal,
+                    ped_speed,
+                } => {
+                    let walk_to = SidewalkSpot::bike_parking(dock, map);
+                    let mut legs = vec![
+                        TripLeg::Walk(ped_id.unwrap(), ped_speed, walk_to.clone()),
+                        TripLeg::Drive(vehicle.make(car_id.unwrap(), None), goal.clone()),
+                    ];
+                    match goal {
+                        DrivingGoal::ParkNear(b) => {
+                            legs.push(TripLeg::Walk(
+                                ped_id.unwrap(),
+                                ped_speed,
+                                SidewalkSpot::building(b, map),
+                            ));
+                        }
+                        DrivingGoal::Border(_, _) => {}
+                    };
+                    let trip = trips.new_trip(start_time, person, legs);
 
                     scheduler.push(
                         start_time,
@@ -305,6 +434,7 @@ impl TripSpawner {
                     let walk_to = SidewalkSpot::bus_stop(stop1, map);
                     let trip = trips.new_trip(
                         start_time,
+                        person,
                         vec![
                             TripLeg::Walk(ped_id.unwrap(), ped_speed, walk_to.clone()),
                             TripLeg::RideBus(ped_id.unwrap(), route, stop2),
@@ -367,6 +497,12 @@ impl TripSpec {
                 can_use_bike_lanes: false,
                 can_use_bus_lanes: false,
             },
+            TripSpec::UsingBikeshare { start, dock, .. } => PathRequest {
+                start: start.sidewalk_pos,
+                end: SidewalkSpot::bike_parking(*dock, map).sidewalk_pos,
+                can_use_bike_lanes: false,
+                can_use_bus_lanes: false,
+            },
             TripSpec::UsingTransit { start, stop1, .. } => PathRequest {
                 start: start.sidewalk_pos,
                 end: SidewalkSpot::bus_stop(*stop1, map).sidewalk_pos,
@@ -377,19 +513,72 @@ impl TripSpec {
     }
 }
 
+fn validate_bike_goal(goal: &DrivingGoal, map: &Map) -> Result<(), String> {
+    if let DrivingGoal::ParkNear(_) = goal {
+        let last_lane = goal.goal_pos(map).lane();
+        // If bike_to_sidewalk works, then SidewalkSpot::bike_rack should too.
+        if map
+            .get_parent(last_lane)
+            .bike_to_sidewalk(last_lane)
+            .is_none()
+        {
+            return Err(format!(
+                "Can't fulfill {:?} for a bike trip; no sidewalk near {}",
+                goal, last_lane
+            ));
+        }
+    }
+    Ok(())
+}
+
+fn validate_sidewalk_spot(spot: &SidewalkSpot, map: &Map) -> Result<(), String> {
+    let lane = spot.sidewalk_pos.lane();
+    if map.maybe_get_l(lane).is_none() {
+        return Err(format!("{} doesn't exist", lane));
+    }
+    Ok(())
+}
+
+fn validate_driving_goal(goal: &DrivingGoal, map: &Map) -> Result<(), String> {
+    match goal {
+        DrivingGoal::ParkNear(b) => {
+            if map.maybe_get_b(*b).is_none() {
+                return Err(format!("{} doesn't exist", b));
+            }
+        }
+        DrivingGoal::Border(i, l) => {
+            if map.maybe_get_i(*i).is_none() {
+                return Err(format!("{} doesn't exist", i));
+            }
+            if map.maybe_get_l(*l).is_none() {
+                return Err(format!("{} doesn't exist", l));
+            }
+        }
+    }
+    Ok(())
+}
+
 fn calculate_paths(
     map: &Map,
-    requests: Vec<PathRequest>,
+    requests: Vec<(PathRequest, Duration)>,
     timer: &mut Timer,
 ) -> Vec<(PathRequest, Option<Path>)> {
+    // People starting their next trip spawn one at a time; a thread pool isn't worth it.
+    if requests.len() == 1 {
+        return requests
+            .into_iter()
+            .map(|(req, start_time)| (req.clone(), map.pathfind_at(req, start_time)))
+            .collect();
+    }
+
     scoped_threadpool::Pool::new(num_cpus::get() as u32).scoped(|scope| {
         let (tx, rx) = std::sync::mpsc::channel();
         let mut results: Vec<(PathRequest, Option<Path>)> = Vec::new();
-        for (idx, req) in requests.into_iter().enumerate() {
+        for (idx, (req, start_time)) in requests.into_iter().enumerate() {
             results.push((req.clone(), None));
             let tx = tx.clone();
             scope.execute(move || {
-                tx.send((idx, map.pathfind(req))).unwrap();
+                tx.send((idx, map.pathfind_at(req, start_time))).unwrap();
             });
         }
         drop(tx);
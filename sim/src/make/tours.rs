@@ -0,0 +1,276 @@
+use crate::{CarID, PersonSpec, PersonTrip, Scenario, Sim, TripMode};
+use abstutil::Timer;
+use geom::{Distance, Duration, Pt2D, Speed};
+use map_model::{BikeParkingType, Building, BuildingID, BuildingType, Map};
+use rand::distributions::{Distribution, WeightedIndex};
+use rand::Rng;
+use rand_xorshift::XorShiftRng;
+use serde_derive::{Deserialize, Serialize};
+use std::collections::HashSet;
+
+// Destinations are first sampled by attraction, then these candidates are weighed by travel time.
+const NUM_CANDIDATES: usize = 10;
+// A destination this much farther away is 1/e times as likely to be picked.
+const TRAVEL_TIME_DECAY: Duration = Duration::const_seconds(15.0 * 60.0);
+// Would-be drivers walk instead when every leg of their tour is this short.
+const MAX_WALKING_DIST: Distance = Distance::const_meters(800.0);
+
+#[derive(Clone, Serialize, Deserialize, Debug)]
+pub struct TourParams {
+    // Of all the residents, how many leave home today
+    pub percent_residents: f64,
+    // Of the tours, how many stop to shop on the way home from work
+    pub percent_shopping: f64,
+    pub percent_biking: f64,
+    // Of the people biking, how many rent from a bikeshare dock instead of owning a bike. Only
+    // matters if the map has docks.
+    pub percent_bikeshare: f64,
+    pub percent_use_transit: f64,
+    // The earliest anybody leaves home
+    pub day_start: Duration,
+}
+
+impl TourParams {
+    pub fn typical_weekday() -> TourParams {
+        TourParams {
+            percent_residents: 0.5,
+            percent_shopping: 0.3,
+            percent_biking: 0.1,
+            percent_bikeshare: 0.3,
+            percent_use_transit: 0.2,
+            day_start: Duration::ZERO,
+        }
+    }
+}
+
+// Somebody leaving home, going through a few destinations, and coming back home. All legs use the
+// same mode and the same vehicle; a driver parks near each stop and drives on from there.
+#[derive(Clone, Serialize, Deserialize, Debug)]
+pub struct Tour {
+    pub home: BuildingID,
+    pub mode: TripMode,
+    pub legs: Vec<TourLeg>,
+}
+
+#[derive(Clone, Serialize, Deserialize, Debug)]
+pub struct TourLeg {
+    pub depart: Duration,
+    pub from: BuildingID,
+    pub to: BuildingID,
+}
+
+// Every resident might go home -> work -> shop -> home, with the shopping stop being optional.
+pub fn generate_tours(
+    map: &Map,
+    params: &TourParams,
+    rng: &mut XorShiftRng,
+    timer: &mut Timer,
+) -> Vec<Tour> {
+    let work = Attractions::new(map, |b| b.capacity.jobs);
+    let shops = Attractions::new(map, |b| match b.building_type {
+        BuildingType::Retail | BuildingType::MixedUse => b.capacity.visitors,
+        _ => 0,
+    });
+    if work.is_empty() {
+        timer.warn("No buildings have jobs, so nobody has anywhere to go".to_string());
+        return Vec::new();
+    }
+
+    let mut tours = Vec::new();
+    timer.start_iter("generate tours for residents", map.all_buildings().len());
+    for b in map.all_buildings() {
+        timer.next();
+        if b.building_type != BuildingType::Residence && b.building_type != BuildingType::MixedUse {
+            continue;
+        }
+        for _ in 0..b.capacity.residents {
+            if rng.gen_bool(params.percent_residents) {
+                tours.push(make_tour(b.id, params, &work, &shops, map, rng));
+            }
+        }
+    }
+    timer.note(format!(
+        "Generated {} tours, with {} legs total",
+        tours.len(),
+        tours.iter().map(|t| t.legs.len()).sum::<usize>()
+    ));
+    tours
+}
+
+fn make_tour(
+    home: BuildingID,
+    params: &TourParams,
+    work: &Attractions,
+    shops: &Attractions,
+    map: &Map,
+    rng: &mut XorShiftRng,
+) -> Tour {
+    let mode = if rng.gen_bool(params.percent_biking) {
+        if rng.gen_bool(params.percent_bikeshare)
+            && map
+                .all_bike_parking()
+                .iter()
+                .any(|p| p.parking_type == BikeParkingType::Bikeshare)
+        {
+            TripMode::Bikeshare
+        } else {
+            TripMode::Bike
+        }
+    } else if rng.gen_bool(params.percent_use_transit) {
+        TripMode::Transit
+    } else {
+        TripMode::Drive
+    };
+
+    let work_bldg = work.pick(center(home, map), mode, map, rng);
+    let leave_home = params.day_start + rand_time(rng, Duration::ZERO, Duration::minutes(120));
+    let mut legs = vec![TourLeg {
+        depart: leave_home,
+        from: home,
+        to: work_bldg,
+    }];
+
+    let mut last_stop = work_bldg;
+    let mut depart =
+        leave_home + rand_time(rng, Duration::minutes(4 * 60), Duration::minutes(9 * 60));
+    if !shops.is_empty() && rng.gen_bool(params.percent_shopping) {
+        let shop = shops.pick(center(work_bldg, map), mode, map, rng);
+        legs.push(TourLeg {
+            depart,
+            from: work_bldg,
+            to: shop,
+        });
+        last_stop = shop;
+        depart += rand_time(rng, Duration::minutes(20), Duration::minutes(60));
+    }
+    legs.push(TourLeg {
+        depart,
+        from: last_stop,
+        to: home,
+    });
+
+    let mode = if mode == TripMode::Drive
+        && legs
+            .iter()
+            .all(|leg| center(leg.from, map).dist_to(center(leg.to, map)) <= MAX_WALKING_DIST)
+    {
+        TripMode::Walk
+    } else {
+        mode
+    };
+    Tour { home, mode, legs }
+}
+
+impl Tour {
+    // The resident becomes a person who takes each leg after finishing the previous one. Somebody
+    // driving without a car at home gets one on the first leg and keeps it for the rest.
+    pub(crate) fn spawn(
+        &self,
+        rng: &mut XorShiftRng,
+        sim: &mut Sim,
+        reserved_cars: &mut HashSet<CarID>,
+    ) {
+        let car = if self.mode == TripMode::Drive {
+            sim.get_parked_cars_by_owner(self.home)
+                .into_iter()
+                .map(|p| p.vehicle.id)
+                .find(|id| !reserved_cars.contains(id))
+        } else {
+            None
+        };
+        if let Some(id) = car {
+            reserved_cars.insert(id);
+        }
+
+        sim.new_person(PersonSpec {
+            home: self.home,
+            car,
+            ped_speed: Scenario::rand_ped_speed(rng),
+            bike: Scenario::rand_bike(rng),
+            car_spec: Scenario::rand_car(rng),
+            schedule: self
+                .legs
+                .iter()
+                .map(|leg| PersonTrip {
+                    depart: leg.depart,
+                    to: leg.to,
+                    mode: self.mode,
+                })
+                .collect(),
+        });
+    }
+}
+
+// Very rough; straight-line distances are shorter than the real path.
+fn estimate_time(mode: TripMode, dist: Distance) -> Duration {
+    let speed = match mode {
+        TripMode::Drive => Speed::miles_per_hour(20.0),
+        TripMode::Bike | TripMode::Bikeshare => Speed::miles_per_hour(9.0),
+        TripMode::Transit => Speed::miles_per_hour(10.0),
+        TripMode::Walk => Speed::miles_per_hour(3.0),
+    };
+    dist / speed
+}
+
+fn center(b: BuildingID, map: &Map) -> Pt2D {
+    map.get_b(b).polygon.center()
+}
+
+fn rand_time(rng: &mut XorShiftRng, low: Duration, high: Duration) -> Duration {
+    Duration::seconds(rng.gen_range(low.inner_seconds(), high.inner_seconds()))
+}
+
+// Buildings that draw some kind of trip, for sampling proportional to how much they draw
+struct Attractions {
+    bldgs: Vec<BuildingID>,
+    // Running total of the weights, so it's strictly increasing
+    cumulative: Vec<usize>,
+}
+
+impl Attractions {
+    fn new<F: Fn(&Building) -> usize>(map: &Map, weight: F) -> Attractions {
+        let mut bldgs = Vec::new();
+        let mut cumulative = Vec::new();
+        let mut total = 0;
+        for b in map.all_buildings() {
+            let w = weight(b);
+            if w > 0 {
+                total += w;
+                bldgs.push(b.id);
+                cumulative.push(total);
+            }
+        }
+        Attractions { bldgs, cumulative }
+    }
+
+    fn is_empty(&self) -> bool {
+        self.bldgs.is_empty()
+    }
+
+    fn pick(&self, from: Pt2D, mode: TripMode, map: &Map, rng: &mut XorShiftRng) -> BuildingID {
+        let total = *self.cumulative.last().unwrap();
+        let candidates: Vec<BuildingID> = (0..NUM_CANDIDATES)
+            .map(|_| {
+                let x = rng.gen_range(0, total);
+                // The first building whose running total exceeds x
+                let idx = match self.cumulative.binary_search(&x) {
+                    Ok(idx) => idx + 1,
+                    Err(idx) => idx,
+                };
+                self.bldgs[idx]
+            })
+            .collect();
+        let weights: Vec<f64> = candidates
+            .iter()
+            .map(|b| {
+                let time = estimate_time(mode, from.dist_to(center(*b, map)));
+                (-(time / TRAVEL_TIME_DECAY)).exp()
+            })
+            .collect();
+        match WeightedIndex::new(&weights) {
+            Ok(dist) => candidates[dist.sample(rng)],
+            // Everything is so far away that the weights vanish
+            Err(_) => candidates[0],
+        }
+    }
+}
@@ -0,0 +1,119 @@
+use derivative::Derivative;
+use geom::{Distance, FindClosest, Pt2D};
+use map_model::{BikeParkingID, BikeParkingType, Map};
+use serde_derive::{Deserialize, Serialize};
+
+// Searches for somewhere to take or leave a bike start this close, then look farther out.
+const INITIAL_SEARCH_RADIUS: Distance = Distance::const_meters(500.0);
+
+// How many bikes are at each rack and bikeshare dock. Racks start empty; docks start half full.
+#[derive(Serialize, Deserialize, Derivative)]
+#[derivative(PartialEq)]
+pub struct BikeParkingSimState {
+    // Indexed by BikeParkingID
+    bikes: Vec<usize>,
+    capacity: Vec<usize>,
+
+    // Only derived from the map, so rebuilt after loading a savestate.
+    #[derivative(PartialEq = "ignore")]
+    #[serde(skip_serializing, skip_deserializing)]
+    closest: Option<FindClosest<BikeParkingID>>,
+}
+
+impl BikeParkingSimState {
+    pub fn new(map: &Map) -> BikeParkingSimState {
+        let mut state = BikeParkingSimState {
+            bikes: Vec::new(),
+            capacity: Vec::new(),
+            closest: None,
+        };
+        for p in map.all_bike_parking() {
+            state.bikes.push(match p.parking_type {
+                BikeParkingType::Rack => 0,
+                BikeParkingType::Bikeshare => p.capacity / 2,
+            });
+            state.capacity.push(p.capacity);
+        }
+        state.index(map);
+        state
+    }
+
+    pub fn index(&mut self, map: &Map) {
+        let mut closest = FindClosest::new(map.get_bounds());
+        for p in map.all_bike_parking() {
+            closest.add_pt(p.id, p.sidewalk_pos.pt(map));
+        }
+        self.closest = Some(closest);
+    }
+
+    pub fn get_bikes(&self, id: BikeParkingID) -> usize {
+        self.bikes[id.0]
+    }
+
+    pub fn has_room(&self, id: BikeParkingID) -> bool {
+        self.bikes[id.0] < self.capacity[id.0]
+    }
+
+    pub fn leave_bike(&mut self, id: BikeParkingID) {
+        assert!(self.has_room(id));
+        self.bikes[id.0] += 1;
+    }
+
+    pub fn take_bike(&mut self, id: BikeParkingID) {
+        assert!(self.bikes[id.0] > 0);
+        self.bikes[id.0] -= 1;
+    }
+
+    // The closest place to leave a bike that isn't full yet
+    pub fn closest_with_room(
+        &self,
+        pt: Pt2D,
+        parking_type: BikeParkingType,
+        max_dist: Option<Distance>,
+        map: &Map,
+    ) -> Option<BikeParkingID> {
+        self.closest(pt, parking_type, max_dist, map, |id| self.has_room(id))
+    }
+
+    // The closest bikeshare dock that still has a bike
+    pub fn closest_with_bike(&self, pt: Pt2D, map: &Map) -> Option<BikeParkingID> {
+        self.closest(pt, BikeParkingType::Bikeshare, None, map, |id| {
+            self.bikes[id.0] > 0
+        })
+    }
+
+    // Looks in a small area first, then keeps doubling it until something matches or the whole
+    // map (or max_dist) has been searched. Skips anything bikes can't currently reach.
+    fn closest<F: Fn(BikeParkingID) -> bool>(
+        &self,
+        pt: Pt2D,
+        parking_type: BikeParkingType,
+        max_dist: Option<Distance>,
+        map: &Map,
+        ok: F,
+    ) -> Option<BikeParkingID> {
+        let closest = self.closest.as_ref().unwrap();
+        let limit = max_dist.unwrap_or_else(|| {
+            let b = map.get_bounds();
+            Pt2D::new(b.min_x, b.min_y).dist_to(Pt2D::new(b.max_x, b.max_y))
+        });
+        let mut radius = INITIAL_SEARCH_RADIUS.min(limit);
+        loop {
+            if let Some((id, _, _)) =
+                closest
+                    .all_close_pts(pt, radius)
+                    .into_iter()
+                    .find(|(id, _, _)| {
+                        let p = map.get_bike_parking(*id);
+                        p.parking_type == parking_type && p.is_reachable(map) && ok(*id)
+                    })
+            {
+                return Some(id);
+            }
+            if radius >= limit {
+                return None;
+            }
+            radius = (radius * 2.0).min(limit);
+        }
+    }
+}
@@ -1,7 +1,8 @@
 use crate::{
     CarStatus, DistanceInterval, DrawCarInput, ParkingSpot, Router, TimeInterval, Vehicle,
+    VehicleType,
 };
-use geom::{Distance, Duration, PolyLine};
+use geom::{Distance, Duration, PolyLine, Speed};
 use map_model::{Map, Traversable, LANE_THICKNESS};
 use serde_derive::{Deserialize, Serialize};
 use std::collections::VecDeque;
@@ -47,6 +48,13 @@ impl Car {
         if let Some(s) = self.vehicle.max_speed {
             speed = speed.min(s);
         }
+        if self.vehicle.vehicle_type == VehicleType::Bike {
+            if let Traversable::Lane(l) = on {
+                // Coasting downhill still doesn't break the speed limit.
+                speed =
+                    bike_speed_on_grade(speed, map.get_l(l).grade(map)).min(on.speed_limit(map));
+            }
+        }
         let dt = (dist_int.end - dist_int.start) / speed;
         CarState::Crossing(TimeInterval::new(start_time, start_time + dt), dist_int)
     }
@@ -149,3 +157,15 @@ impl CarState {
         }
     }
 }
+
+// Cyclists slow down a lot going uphill and speed up some going downhill.
+pub fn bike_speed_on_grade(speed: Speed, grade: f64) -> Speed {
+    if grade > 0.0 {
+        // A 10% climb halves the speed.
+        speed * (1.0 / (1.0 + 10.0 * grade)).max(0.25)
+    } else {
+        // A 10% descent is half again as fast, and steeper ones aren't any faster, since people
+        // brake.
+        speed * (1.0 - 5.0 * grade).min(1.5)
+    }
+}
@@ -1,9 +1,9 @@
 use crate::mechanics::car::{Car, CarState};
 use crate::mechanics::queue::Queue;
 use crate::{
-    ActionAtEnd, AgentID, CarID, Command, CreateCar, DistanceInterval, DrawCarInput,
-    IntersectionSimState, ParkedCar, ParkingSimState, Scheduler, TimeInterval, TransitSimState,
-    TripManager, WalkingSimState, FOLLOWING_DISTANCE,
+    ActionAtEnd, AgentID, BikeParkingSimState, CarID, Command, CreateCar, DistanceInterval,
+    DrawCarInput, Event, IntersectionSimState, ParkedCar, ParkingSimState, Scheduler, TimeInterval,
+    TransitSimState, TripManager, WalkingSimState, FOLLOWING_DISTANCE,
 };
 use abstutil::{deserialize_btreemap, serialize_btreemap};
 use geom::{Distance, Duration, PolyLine, Polygon};
@@ -32,6 +32,11 @@ pub struct DrivingSimState {
         deserialize_with = "deserialize_btreemap"
     )]
     queues: BTreeMap<Traversable, Queue>,
+
+    events: Vec<Event>,
+    // Only while the Sim is recording events, since there are so many
+    #[serde(skip_serializing, skip_deserializing)]
+    record_high_volume_events: bool,
 }
 
 impl DrivingSimState {
@@ -39,10 +44,12 @@ impl DrivingSimState {
         let mut sim = DrivingSimState {
             cars: BTreeMap::new(),
             queues: BTreeMap::new(),
+            events: Vec::new(),
+            record_high_volume_events: false,
         };
 
         for l in map.all_lanes() {
-            if l.lane_type.is_for_moving_vehicles() {
+            if l.lane_type.is_for_moving_vehicles() && !map.is_lane_removed(l.id) {
                 let q = Queue::new(Traversable::Lane(l.id), map);
                 sim.queues.insert(q.id, q);
             }
@@ -125,6 +132,7 @@ impl DrivingSimState {
         time: Duration,
         map: &Map,
         parking: &mut ParkingSimState,
+        bike_parking: &mut BikeParkingSimState,
         intersections: &mut IntersectionSimState,
         trips: &mut TripManager,
         scheduler: &mut Scheduler,
@@ -195,6 +203,7 @@ impl DrivingSimState {
                 time,
                 map,
                 parking,
+                bike_parking,
                 trips,
                 scheduler,
                 transit,
@@ -290,6 +299,13 @@ impl DrivingSimState {
             CarState::WaitingToAdvance => {
                 // 'car' is the leader.
                 let from = car.router.head();
+                if let Traversable::Turn(t) = car.router.next() {
+                    if map.is_closed(Traversable::Turn(t), time)
+                        && car.router.reroute_around_closures(&car.vehicle, time, map)
+                    {
+                        intersections.cancel_request(AgentID::Car(car.vehicle.id), t, time);
+                    }
+                }
                 let goto = car.router.next();
                 assert!(from != goto);
 
@@ -311,6 +327,12 @@ impl DrivingSimState {
 
                 let last_step = car.router.advance(&car.vehicle, parking, map);
                 car.state = car.crossing_state(Distance::ZERO, time, map);
+                if self.record_high_volume_events {
+                    self.events.push(Event::AgentEntersTraversable(
+                        AgentID::Car(car.vehicle.id),
+                        goto,
+                    ));
+                }
                 scheduler.push(car.state.get_end_time(), Command::UpdateCar(car.vehicle.id));
 
                 car.last_steps.push_front(last_step);
@@ -353,6 +375,7 @@ impl DrivingSimState {
         time: Duration,
         map: &Map,
         parking: &mut ParkingSimState,
+        bike_parking: &mut BikeParkingSimState,
         trips: &mut TripManager,
         scheduler: &mut Scheduler,
         transit: &mut TransitSimState,
@@ -400,7 +423,14 @@ impl DrivingSimState {
                         return true;
                     }
                     Some(ActionAtEnd::StopBiking(bike_rack)) => {
-                        trips.bike_reached_end(time, car.vehicle.id, bike_rack, map, scheduler);
+                        trips.bike_reached_end(
+                            time,
+                            car.vehicle.id,
+                            bike_rack,
+                            map,
+                            bike_parking,
+                            scheduler,
+                        );
                     }
                     Some(ActionAtEnd::BusAtStop) => {
                         transit.bus_arrived_at_stop(
@@ -604,6 +634,14 @@ impl DrivingSimState {
         }
     }
 
+    pub fn collect_events(&mut self) -> Vec<Event> {
+        self.events.drain(..).collect()
+    }
+
+    pub fn record_high_volume_events(&mut self, enabled: bool) {
+        self.record_high_volume_events = enabled;
+    }
+
     pub fn get_unzoomed_polygons(&self, map: &Map) -> (Vec<Polygon>, Vec<Polygon>) {
         // These are the max over all lanes
         let mut max_moving: HashMap<DirectedRoadID, Distance> = HashMap::new();
@@ -1,8 +1,9 @@
-use crate::{AgentID, Command, Scheduler};
+use crate::{AgentID, Command, Event, IntersectionStats, Scheduler};
+use abstutil::{deserialize_btreemap, serialize_btreemap};
 use geom::Duration;
 use map_model::{
-    ControlStopSign, ControlTrafficSignal, IntersectionID, IntersectionType, LaneID, Map, TurnID,
-    TurnPriority,
+    ControlStopSign, ControlTrafficSignal, IntersectionID, IntersectionType, LaneID, Map,
+    Traversable, TurnID, TurnPriority, TurnType,
 };
 use serde_derive::{Deserialize, Serialize};
 use std::collections::{BTreeMap, BTreeSet, HashSet};
@@ -10,6 +11,13 @@ use std::collections::{BTreeMap, BTreeSet, HashSet};
 #[derive(Serialize, Deserialize, PartialEq)]
 pub struct IntersectionSimState {
     state: BTreeMap<IntersectionID, State>,
+    // Every closure end that already has a ClosureEnds command pending
+    closure_ends: BTreeSet<(IntersectionID, Duration)>,
+
+    events: Vec<Event>,
+    // Only while the Sim is recording events, since there are so many
+    #[serde(skip_serializing, skip_deserializing)]
+    record_high_volume_events: bool,
 }
 
 #[derive(Serialize, Deserialize, PartialEq)]
@@ -17,12 +25,28 @@ struct State {
     id: IntersectionID,
     accepted: BTreeSet<Request>,
     waiting: Vec<Request>,
+
+    // When each waiting request first asked
+    #[serde(
+        serialize_with = "serialize_btreemap",
+        deserialize_with = "deserialize_btreemap"
+    )]
+    wait_started: BTreeMap<Request, Duration>,
+    // Only for traffic signals: the cycle in effect and when it started
+    current_cycle: Option<(usize, Duration)>,
+    // When somebody last started a turn with nobody else in the intersection, or when the
+    // current cycle started, whichever is later. None when nobody's doing a turn.
+    busy_since: Option<Duration>,
+    stats: IntersectionStats,
 }
 
 impl IntersectionSimState {
     pub fn new(map: &Map, scheduler: &mut Scheduler) -> IntersectionSimState {
         let mut sim = IntersectionSimState {
             state: BTreeMap::new(),
+            closure_ends: BTreeSet::new(),
+            events: Vec::new(),
+            record_high_volume_events: false,
         };
         for i in map.all_intersections() {
             sim.state.insert(
@@ -31,15 +55,48 @@ impl IntersectionSimState {
                     id: i.id,
                     accepted: BTreeSet::new(),
                     waiting: Vec::new(),
+                    wait_started: BTreeMap::new(),
+                    current_cycle: None,
+                    busy_since: None,
+                    stats: IntersectionStats::new(i.id),
                 },
             );
             if i.intersection_type == IntersectionType::TrafficSignal {
                 sim.update_intersection(Duration::ZERO, i.id, map, scheduler);
             }
         }
+        sim.schedule_closure_ends(Duration::ZERO, map, scheduler);
         sim
     }
 
+    // Agents blocked by a closure just wait for it to end. Returns the end of every closure that
+    // hasn't ended yet, including ones scheduled before.
+    fn schedule_closure_ends(
+        &mut self,
+        now: Duration,
+        map: &Map,
+        scheduler: &mut Scheduler,
+    ) -> BTreeSet<(IntersectionID, Duration)> {
+        let mut current = BTreeSet::new();
+        for closure in &map.get_edits().closures {
+            if closure.end <= now {
+                continue;
+            }
+            for i in closure.affected_intersections(map) {
+                current.insert((i, closure.end));
+                if self.closure_ends.insert((i, closure.end)) {
+                    scheduler.push(closure.end, Command::ClosureEnds(i));
+                }
+            }
+        }
+        current
+    }
+
+    pub fn closure_ended(&mut self, now: Duration, id: IntersectionID, scheduler: &mut Scheduler) {
+        self.closure_ends.remove(&(id, now));
+        self.wakeup_waiting(now, id, scheduler);
+    }
+
     pub fn nobody_headed_towards(&self, lane: LaneID, i: IntersectionID) -> bool {
         !self.state[&i]
             .accepted
@@ -57,6 +114,12 @@ impl IntersectionSimState {
         let state = self.state.get_mut(&turn.parent).unwrap();
 
         assert!(state.accepted.remove(&Request { agent, turn }));
+        if state.accepted.is_empty() {
+            if let (Some(since), Some((idx, _))) = (state.busy_since, state.current_cycle) {
+                state.stats.cycle(idx).busy_time += now - since;
+            }
+            state.busy_since = None;
+        }
 
         // TODO Could be smarter here. For both policies, only wake up agents that would then be
         // accepted. For now, wake up everyone -- for traffic signals, maybe we were in overtime,
@@ -77,20 +140,67 @@ impl IntersectionSimState {
 
     // This is only triggered for traffic signals.
     pub fn update_intersection(
-        &self,
+        &mut self,
         now: Duration,
         id: IntersectionID,
         map: &Map,
         scheduler: &mut Scheduler,
     ) {
-        let state = &self.state[&id];
-        let (_, remaining) = map
-            .get_traffic_signal(id)
-            .current_cycle_and_remaining_time(now);
+        let state = self.state.get_mut(&id).unwrap();
+        state.finish_cycle(now);
+
+        // The signal might've been edited away.
+        let signal = match map.maybe_get_traffic_signal(id) {
+            Some(signal) => signal,
+            None => {
+                self.wakeup_waiting(now, id, scheduler);
+                return;
+            }
+        };
+        let (cycle, remaining) = signal.current_cycle_and_remaining_time(now);
+        state.current_cycle = Some((cycle.idx, now));
 
         // TODO Wake up everyone, for now.
+        self.wakeup_waiting(now, id, scheduler);
+
+        scheduler.push(now + remaining, Command::UpdateIntersection(id));
+    }
+
+    // The policy of these intersections changed, so anybody waiting should try again. New traffic
+    // signals have to start cycling. Closures might've been added or removed too.
+    pub fn handle_edits(
+        &mut self,
+        now: Duration,
+        changed: &BTreeSet<IntersectionID>,
+        map: &Map,
+        scheduler: &mut Scheduler,
+    ) {
+        for id in changed {
+            if map.maybe_get_traffic_signal(*id).is_some() {
+                // There might already be an update pending, if this was a signal before.
+                scheduler.update(Command::UpdateIntersection(*id), now);
+            } else {
+                self.wakeup_waiting(now, *id, scheduler);
+            }
+        }
+
+        // Anybody waiting for a closure that was just removed can go now. The ClosureEnds command
+        // for it is still pending, which is harmless.
+        let current = self.schedule_closure_ends(now, map, scheduler);
+        let reopened: BTreeSet<IntersectionID> = self
+            .closure_ends
+            .difference(&current)
+            .map(|(i, _)| *i)
+            .collect();
+        for id in reopened {
+            self.wakeup_waiting(now, id, scheduler);
+        }
+        self.closure_ends = current;
+    }
+
+    pub fn wakeup_waiting(&self, now: Duration, id: IntersectionID, scheduler: &mut Scheduler) {
         // TODO Use update in case turn_finished scheduled an event for them already.
-        for req in &state.waiting {
+        for req in &self.state[&id].waiting {
             scheduler.update(
                 match req.agent {
                     AgentID::Car(id) => Command::UpdateCar(id),
@@ -99,8 +209,15 @@ impl IntersectionSimState {
                 now,
             );
         }
+    }
 
-        scheduler.push(now + remaining, Command::UpdateIntersection(id));
+    // The agent won't attempt this turn after all.
+    pub fn cancel_request(&mut self, agent: AgentID, turn: TurnID, now: Duration) {
+        let state = self.state.get_mut(&turn.parent).unwrap();
+        let req = Request { agent, turn };
+        state.waiting.retain(|r| *r != req);
+        state.wait_started.remove(&req);
+        state.stats.record_queue_length(now, state.waiting.len());
     }
 
     // TODO This API is bad. Need to gather all of the requests at a time before making a decision.
@@ -122,12 +239,16 @@ impl IntersectionSimState {
         let state = self.state.get_mut(&turn.parent).unwrap();
 
         let req = Request { agent, turn };
-        let allowed = if let Some(ref signal) = map.maybe_get_traffic_signal(state.id) {
+        let allowed = if map.is_closed(Traversable::Turn(turn), now) {
+            false
+        } else if let Some(ref signal) = map.maybe_get_traffic_signal(state.id) {
             state.traffic_signal_policy(signal, &req, now, map)
         } else if let Some(ref sign) = map.maybe_get_stop_sign(state.id) {
-            state.stop_sign_policy(sign, &req, map)
+            state.stop_sign_policy(sign, &req, now, map)
+        } else if map.get_i(state.id).intersection_type == IntersectionType::Roundabout {
+            state.roundabout_policy(&req, map)
         } else {
-            // TODO This never gets called right now
+            // Uncontrolled intersections
             state.freeform_policy(&req, map)
         };
 
@@ -137,11 +258,19 @@ impl IntersectionSimState {
             if let Some(idx) = maybe_idx {
                 state.waiting.remove(idx);
             }
-            state.accepted.insert(req);
+            state.accept(req, now);
+            if self.record_high_volume_events {
+                self.events.push(Event::TurnAccepted(agent, turn));
+            }
             true
         } else {
             if maybe_idx.is_none() {
+                state.wait_started.insert(req.clone(), now);
                 state.waiting.push(req);
+                state.stats.record_queue_length(now, state.waiting.len());
+                if self.record_high_volume_events {
+                    self.events.push(Event::TurnDenied(agent, turn));
+                }
             }
             false
         }
@@ -154,7 +283,7 @@ impl IntersectionSimState {
         } else if let Some(ref signal) = map.maybe_get_traffic_signal(id) {
             println!("{}", abstutil::to_json(signal));
         } else {
-            println!("Border");
+            println!("{:?}", map.get_i(id).intersection_type);
         }
     }
 
@@ -166,6 +295,15 @@ impl IntersectionSimState {
             .collect()
     }
 
+    // In the order they started waiting
+    pub fn get_waiting_agents(&self, id: IntersectionID) -> Vec<(AgentID, TurnID)> {
+        self.state[&id]
+            .waiting
+            .iter()
+            .map(|req| (req.agent, req.turn))
+            .collect()
+    }
+
     pub fn is_in_overtime(&self, time: Duration, id: IntersectionID, map: &Map) -> bool {
         if let Some(ref signal) = map.maybe_get_traffic_signal(id) {
             let (cycle, _) = signal.current_cycle_and_remaining_time(time);
@@ -177,9 +315,60 @@ impl IntersectionSimState {
             false
         }
     }
+
+    // Agents still waiting count as having waited until now. The cycle currently in effect is
+    // counted up to now.
+    pub fn get_stats(&self, id: IntersectionID, now: Duration) -> IntersectionStats {
+        let state = &self.state[&id];
+        let mut stats = state.stats.clone();
+        for (req, started) in &state.wait_started {
+            stats.record_delay(req.turn, now - *started);
+        }
+        count_cycle(state.current_cycle, state.busy_since, &mut stats, now);
+        stats.finish_queue_lengths(now);
+        stats
+    }
+
+    pub fn collect_events(&mut self) -> Vec<Event> {
+        self.events.drain(..).collect()
+    }
+
+    pub fn record_high_volume_events(&mut self, enabled: bool) {
+        self.record_high_volume_events = enabled;
+    }
 }
 
 impl State {
+    fn accept(&mut self, req: Request, now: Duration) {
+        let delay = self
+            .wait_started
+            .remove(&req)
+            .map(|started| now - started)
+            .unwrap_or(Duration::ZERO);
+        self.stats.record_delay(req.turn, delay);
+        self.stats.record_queue_length(now, self.waiting.len());
+        if self.accepted.is_empty() {
+            self.busy_since = Some(now);
+        }
+        if let Some((idx, _)) = self.current_cycle {
+            self.stats.cycle(idx).agents_accepted += 1;
+        }
+        self.accepted.insert(req);
+    }
+
+    // Only matters for traffic signals. Called when the cycle changes.
+    fn finish_cycle(&mut self, now: Duration) {
+        count_cycle(
+            self.current_cycle.take(),
+            self.busy_since,
+            &mut self.stats,
+            now,
+        );
+        if self.busy_since.is_some() {
+            self.busy_since = Some(now);
+        }
+    }
+
     fn any_accepted_conflict_with(&self, t: TurnID, map: &Map) -> bool {
         let turn = map.get_t(t);
         self.accepted
@@ -196,7 +385,54 @@ impl State {
         true
     }
 
-    fn stop_sign_policy(&self, sign: &ControlStopSign, req: &Request, map: &Map) -> bool {
+    // Vehicles that've been accepted are circulating until they finish their turn. Entering
+    // vehicles yield to any of them that still have to pass their entry, and pedestrians yield to
+    // any of them entering or leaving through the road they're crossing. Nobody needs an extra
+    // wakeup; turn_finished retries everyone waiting.
+    fn roundabout_policy(&self, req: &Request, map: &Map) -> bool {
+        if self.any_accepted_conflict_with(req.turn, map) {
+            return false;
+        }
+
+        let turn = map.get_t(req.turn);
+        let mut circulating = self.accepted.iter().filter_map(|r| match r.agent {
+            AgentID::Car(_) => Some(map.get_t(r.turn)),
+            AgentID::Pedestrian(_) => None,
+        });
+        match req.agent {
+            AgentID::Car(_) => {
+                let center = map.get_i(self.id).polygon.center();
+                let entry = turn.geom.first_pt();
+                !circulating.any(|other| {
+                    // Traffic circulates counter-clockwise. Angles increase clockwise on screen,
+                    // since y is flipped.
+                    let from = center.angle_to(other.geom.first_pt()).normalized_degrees();
+                    let to = center.angle_to(other.geom.last_pt()).normalized_degrees();
+                    let at = center.angle_to(entry).normalized_degrees();
+                    let sweep = (from - to + 360.0) % 360.0;
+                    let passed = (from - at + 360.0) % 360.0;
+                    passed > 0.0 && passed < sweep
+                })
+            }
+            AgentID::Pedestrian(_) => {
+                if turn.turn_type != TurnType::Crosswalk {
+                    return true;
+                }
+                let road = map.get_l(turn.id.src).parent;
+                !circulating.any(|other| {
+                    map.get_l(other.id.src).parent == road || map.get_l(other.id.dst).parent == road
+                })
+            }
+        }
+    }
+
+    fn stop_sign_policy(
+        &self,
+        sign: &ControlStopSign,
+        req: &Request,
+        time: Duration,
+        map: &Map,
+    ) -> bool {
         if self.any_accepted_conflict_with(req.turn, map) {
             return false;
         }
@@ -206,10 +442,14 @@ impl State {
 
         // TODO Actually make TurnPriority::Stop turns pause briefly.
 
+        // Agents stuck behind a closure shouldn't hold up everybody else.
+        let open = |r: &&Request| !map.is_closed(Traversable::Turn(r.turn), time);
+
         // If there's a higher rank turn waiting, don't allow
         if self
             .waiting
             .iter()
+            .filter(open)
             .any(|r| sign.turns[&r.turn] > this_priority)
         {
             return false;
@@ -223,6 +463,7 @@ impl State {
             .unwrap_or_else(|| self.waiting.len());
         if self.waiting[0..this_idx]
             .iter()
+            .filter(open)
             .any(|r| sign.turns[&r.turn] == this_priority)
         {
             return false;
@@ -277,3 +518,20 @@ struct Request {
     agent: AgentID,
     turn: TurnID,
 }
+
+// Adds the time the current cycle has been in effect, and was busy, up to now.
+fn count_cycle(
+    current_cycle: Option<(usize, Duration)>,
+    busy_since: Option<Duration>,
+    stats: &mut IntersectionStats,
+    now: Duration,
+) {
+    if let Some((idx, started)) = current_cycle {
+        let busy = busy_since
+            .map(|since| now - since)
+            .unwrap_or(Duration::ZERO);
+        let cycle = stats.cycle(idx);
+        cycle.active_time += now - started;
+        cycle.busy_time += busy;
+    }
+}
@@ -0,0 +1,252 @@
+use abstutil::{deserialize_btreemap, serialize_btreemap};
+use geom::Duration;
+use map_model::{IntersectionID, TurnID};
+use serde_derive::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+
+// Queue lengths over time are summarized in windows this long, so long runs don't keep every
+// change.
+const QUEUE_LENGTH_WINDOW: Duration = Duration::const_seconds(5.0 * 60.0);
+
+// What actually happened at one intersection during a run, for finding where agents lose the
+// most time.
+#[derive(Serialize, Deserialize, PartialEq, Clone, Debug)]
+pub struct IntersectionStats {
+    pub id: IntersectionID,
+    #[serde(
+        serialize_with = "serialize_btreemap",
+        deserialize_with = "deserialize_btreemap"
+    )]
+    pub per_turn: BTreeMap<TurnID, DelayStats>,
+    // Only for traffic signals, indexed like the signal's cycles
+    pub cycles: Vec<CycleStats>,
+    // Consecutive windows from the start of the run until now
+    pub queue_lengths: Vec<QueueLengthWindow>,
+    // The number of agents waiting to start a turn, and since when it's been counted
+    queue_length: (Duration, usize),
+    max_queue_length: usize,
+    // The queue length integrated over time, up to when it last changed
+    queue_length_seconds: f64,
+}
+
+// How long each agent waited between first asking to start a turn and being allowed to. Agents
+// that didn't have to wait count as zero. The count, total, mean, and max are exact, but
+// percentiles are rounded down to the whole second, or to the whole minute for delays of a minute
+// or more, so runs of any length take about the same space.
+#[derive(Serialize, Deserialize, PartialEq, Clone, Debug)]
+pub struct DelayStats {
+    count: usize,
+    total: Duration,
+    max: Duration,
+    // How many delays fall in each bucket, keyed by the bucket's lower bound
+    #[serde(
+        serialize_with = "serialize_btreemap",
+        deserialize_with = "deserialize_btreemap"
+    )]
+    buckets: BTreeMap<Duration, usize>,
+}
+
+// How many agents were waiting to start a turn during part of the run
+#[derive(Serialize, Deserialize, PartialEq, Clone, Debug)]
+pub struct QueueLengthWindow {
+    pub start: Duration,
+    pub max: usize,
+    // Weighted by how long each length lasted. The last window only goes up to now.
+    pub mean: f64,
+    // The queue length integrated over the window, up to when it was last counted
+    length_seconds: f64,
+}
+
+#[derive(Serialize, Deserialize, PartialEq, Clone, Debug)]
+pub struct CycleStats {
+    // How long this cycle has been in effect
+    pub active_time: Duration,
+    // Of that, how long at least one agent was in the middle of a turn. This includes agents
+    // finishing a turn started during the previous cycle.
+    pub busy_time: Duration,
+    pub agents_accepted: usize,
+}
+
+impl IntersectionStats {
+    pub(crate) fn new(id: IntersectionID) -> IntersectionStats {
+        IntersectionStats {
+            id,
+            per_turn: BTreeMap::new(),
+            cycles: Vec::new(),
+            queue_lengths: Vec::new(),
+            queue_length: (Duration::ZERO, 0),
+            max_queue_length: 0,
+            queue_length_seconds: 0.0,
+        }
+    }
+
+    // Every turn at the intersection together
+    pub fn delays(&self) -> DelayStats {
+        let mut all = DelayStats::new();
+        for stats in self.per_turn.values() {
+            all.count += stats.count;
+            all.total += stats.total;
+            all.max = all.max.max(stats.max);
+            for (bucket, count) in &stats.buckets {
+                *all.buckets.entry(*bucket).or_insert(0) += count;
+            }
+        }
+        all
+    }
+
+    pub fn max_queue_length(&self) -> usize {
+        self.max_queue_length
+    }
+
+    // Weighted by how long each length lasted, from the start of the run until now
+    pub fn mean_queue_length(&self, now: Duration) -> f64 {
+        if now == Duration::ZERO {
+            return 0.0;
+        }
+        let (since, len) = self.queue_length;
+        let total = self.queue_length_seconds + (now - since).inner_seconds() * (len as f64);
+        total / now.inner_seconds()
+    }
+
+    pub(crate) fn record_delay(&mut self, turn: TurnID, delay: Duration) {
+        let stats = self.per_turn.entry(turn).or_insert_with(DelayStats::new);
+        stats.count += 1;
+        stats.total += delay;
+        stats.max = stats.max.max(delay);
+        *stats.buckets.entry(DelayStats::bucket(delay)).or_insert(0) += 1;
+    }
+
+    pub(crate) fn record_queue_length(&mut self, now: Duration, len: usize) {
+        if len == self.queue_length.1 {
+            return;
+        }
+        self.count_queue_length(now);
+        self.queue_length.1 = len;
+        self.max_queue_length = self.max_queue_length.max(len);
+        let window = self.queue_length_window(now);
+        window.max = window.max.max(len);
+    }
+
+    // Fills in the mean of every queue length window, counting the current length up to now.
+    pub(crate) fn finish_queue_lengths(&mut self, now: Duration) {
+        self.count_queue_length(now);
+        for window in self.queue_lengths.iter_mut() {
+            let length = (window.start + QUEUE_LENGTH_WINDOW).min(now) - window.start;
+            window.mean = if length == Duration::ZERO {
+                0.0
+            } else {
+                window.length_seconds / length.inner_seconds()
+            };
+        }
+    }
+
+    // Integrates the current queue length from when it was last counted until now.
+    fn count_queue_length(&mut self, now: Duration) {
+        let (mut since, len) = self.queue_length;
+        self.queue_length_seconds += (now - since).inner_seconds() * (len as f64);
+        while since < now {
+            let window = self.queue_length_window(since);
+            let end = (window.start + QUEUE_LENGTH_WINDOW).min(now);
+            window.length_seconds += (end - since).inner_seconds() * (len as f64);
+            since = end;
+        }
+        self.queue_length.0 = now;
+    }
+
+    // Windows skipped over start with whatever the queue length was then.
+    fn queue_length_window(&mut self, time: Duration) -> &mut QueueLengthWindow {
+        let idx = (time / QUEUE_LENGTH_WINDOW).floor() as usize;
+        let len = self.queue_length.1;
+        while self.queue_lengths.len() <= idx {
+            self.queue_lengths.push(QueueLengthWindow {
+                start: QUEUE_LENGTH_WINDOW * (self.queue_lengths.len() as f64),
+                max: len,
+                mean: 0.0,
+                length_seconds: 0.0,
+            });
+        }
+        &mut self.queue_lengths[idx]
+    }
+
+    // Edits might change how many cycles a signal has.
+    pub(crate) fn cycle(&mut self, idx: usize) -> &mut CycleStats {
+        if self.cycles.len() <= idx {
+            self.cycles.resize(
+                idx + 1,
+                CycleStats {
+                    active_time: Duration::ZERO,
+                    busy_time: Duration::ZERO,
+                    agents_accepted: 0,
+                },
+            );
+        }
+        &mut self.cycles[idx]
+    }
+}
+
+impl DelayStats {
+    fn new() -> DelayStats {
+        DelayStats {
+            count: 0,
+            total: Duration::ZERO,
+            max: Duration::ZERO,
+            buckets: BTreeMap::new(),
+        }
+    }
+
+    pub fn count(&self) -> usize {
+        self.count
+    }
+
+    pub fn total(&self) -> Duration {
+        self.total
+    }
+
+    pub fn mean(&self) -> Duration {
+        if self.count == 0 {
+            return Duration::ZERO;
+        }
+        self.total * (1.0 / (self.count as f64))
+    }
+
+    // p is from 0 to 100. Uses the nearest rank, so the result is the bucket of one of the delays,
+    // rounded down like the rest.
+    pub fn percentile(&self, p: f64) -> Duration {
+        if self.count == 0 {
+            return Duration::ZERO;
+        }
+        let rank = ((p / 100.0) * (self.count as f64)).ceil() as usize;
+        let rank = rank.max(1).min(self.count);
+        let mut seen = 0;
+        for (bucket, count) in &self.buckets {
+            seen += count;
+            if seen >= rank {
+                return *bucket;
+            }
+        }
+        unreachable!()
+    }
+
+    pub fn max(&self) -> Duration {
+        self.max
+    }
+
+    fn bucket(delay: Duration) -> Duration {
+        let secs = delay.inner_seconds();
+        if secs < 60.0 {
+            Duration::seconds(secs.floor())
+        } else {
+            Duration::seconds((secs / 60.0).floor() * 60.0)
+        }
+    }
+}
+
+impl CycleStats {
+    // How much of the time this cycle was in effect, it was actually used
+    pub fn utilization(&self) -> f64 {
+        if self.active_time == Duration::ZERO {
+            return 0.0;
+        }
+        self.busy_time / self.active_time
+    }
+}
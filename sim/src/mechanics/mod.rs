@@ -1,11 +1,16 @@
+mod bike_parking;
 mod car;
 mod driving;
 mod intersection;
+mod intersection_stats;
 mod parking;
 mod queue;
 mod walking;
 
+pub use self::bike_parking::BikeParkingSimState;
+pub use self::car::bike_speed_on_grade;
 pub use self::driving::DrivingSimState;
 pub use self::intersection::IntersectionSimState;
+pub use self::intersection_stats::{CycleStats, DelayStats, IntersectionStats, QueueLengthWindow};
 pub use self::parking::ParkingSimState;
-pub use self::walking::WalkingSimState;
+pub use self::walking::{walking_speed_on_grade, WalkingSimState};
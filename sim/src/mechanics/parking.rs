@@ -37,6 +37,9 @@ impl ParkingSimState {
             cars_per_building: MultiMap::new(),
         };
         for l in map.all_lanes() {
+            if map.is_lane_removed(l.id) {
+                continue;
+            }
             if let Some(lane) = ParkingLane::new(l, map) {
                 assert!(!sim.driving_to_parking_lane.contains_key(&lane.driving_lane));
                 sim.driving_to_parking_lane.insert(lane.driving_lane, l.id);
@@ -127,8 +130,9 @@ impl ParkingSimState {
         self.lanes[&spot.lane].occupants[spot.idx].is_none() && !self.reserved_spots.contains(&spot)
     }
 
+    // Also None if the spot doesn't exist
     pub fn get_car_at_spot(&self, spot: ParkingSpot) -> Option<ParkedCar> {
-        let car = self.lanes[&spot.lane].occupants[spot.idx]?;
+        let car = (*self.lanes.get(&spot.lane)?.occupants.get(spot.idx)?)?;
         Some(self.cars[&car].clone())
     }
 
@@ -185,6 +189,10 @@ impl ParkingSimState {
             .collect()
     }
 
+    pub fn get_parked_car(&self, id: CarID) -> Option<&ParkedCar> {
+        self.cars.get(&id)
+    }
+
     pub fn get_owner_of_car(&self, id: CarID) -> Option<BuildingID> {
         self.cars.get(&id).and_then(|p| p.vehicle.owner)
     }
@@ -1,11 +1,13 @@
 use crate::{
-    AgentID, Command, CreatePedestrian, DistanceInterval, DrawPedestrianInput,
-    IntersectionSimState, ParkingSimState, PedestrianID, Scheduler, SidewalkPOI, SidewalkSpot,
-    TimeInterval, TransitSimState, TripManager,
+    AgentID, BikeParkingSimState, Command, CreatePedestrian, DistanceInterval, DrawPedestrianInput,
+    Event, IntersectionSimState, ParkingSimState, PedestrianID, Scheduler, SidewalkPOI,
+    SidewalkSpot, TimeInterval, TransitSimState, TripManager,
 };
 use abstutil::{deserialize_multimap, serialize_multimap, MultiMap};
 use geom::{Distance, Duration, Line, PolyLine, Speed};
-use map_model::{BuildingID, Map, Path, PathStep, Traversable, LANE_THICKNESS};
+use map_model::{
+    BuildingID, Map, Path, PathRequest, PathStep, Position, Traversable, LANE_THICKNESS,
+};
 use serde_derive::{Deserialize, Serialize};
 use std::collections::BTreeMap;
 
@@ -21,6 +23,11 @@ pub struct WalkingSimState {
         deserialize_with = "deserialize_multimap"
     )]
     peds_per_traversable: MultiMap<Traversable, PedestrianID>,
+
+    events: Vec<Event>,
+    // Only while the Sim is recording events, since there are so many
+    #[serde(skip_serializing, skip_deserializing)]
+    record_high_volume_events: bool,
 }
 
 impl WalkingSimState {
@@ -28,6 +35,8 @@ impl WalkingSimState {
         WalkingSimState {
             peds: BTreeMap::new(),
             peds_per_traversable: MultiMap::new(),
+            events: Vec::new(),
+            record_high_volume_events: false,
         }
     }
 
@@ -61,11 +70,13 @@ impl WalkingSimState {
                 b,
                 TimeInterval::new(now, now + map.get_b(b).front_path.line.length() / ped.speed),
             ),
-            SidewalkPOI::BikeRack(driving_pos) => PedState::FinishingBiking(
-                params.start.clone(),
-                Line::new(driving_pos.pt(map), params.start.sidewalk_pos.pt(map)),
-                TimeInterval::new(now, now + TIME_TO_FINISH_BIKING),
-            ),
+            SidewalkPOI::BikeRack(driving_pos) | SidewalkPOI::BikeParking(_, driving_pos) => {
+                PedState::FinishingBiking(
+                    params.start.clone(),
+                    Line::new(driving_pos.pt(map), params.start.sidewalk_pos.pt(map)),
+                    TimeInterval::new(now, now + TIME_TO_FINISH_BIKING),
+                )
+            }
             _ => ped.crossing_state(params.start.sidewalk_pos.dist_along(), now, map),
         };
 
@@ -104,6 +115,7 @@ impl WalkingSimState {
         map: &Map,
         intersections: &mut IntersectionSimState,
         parking: &ParkingSimState,
+        bike_parking: &mut BikeParkingSimState,
         scheduler: &mut Scheduler,
         trips: &mut TripManager,
         transit: &mut TransitSimState,
@@ -146,7 +158,8 @@ impl WalkingSimState {
                             trips.ped_reached_border(now, ped.id, i, map);
                             self.peds.remove(&id);
                         }
-                        SidewalkPOI::BikeRack(driving_pos) => {
+                        SidewalkPOI::BikeRack(driving_pos)
+                        | SidewalkPOI::BikeParking(_, driving_pos) => {
                             let pt1 = ped.goal.sidewalk_pos.pt(map);
                             let pt2 = driving_pos.pt(map);
                             ped.state = PedState::StartingToBike(
@@ -164,8 +177,17 @@ impl WalkingSimState {
                     }
 
                     let dist = dist_int.end;
-                    if ped.maybe_transition(now, map, intersections, &mut self.peds_per_traversable)
-                    {
+                    if ped.maybe_transition(
+                        now,
+                        map,
+                        intersections,
+                        &mut self.peds_per_traversable,
+                        if self.record_high_volume_events {
+                            Some(&mut self.events)
+                        } else {
+                            None
+                        },
+                    ) {
                         scheduler.push(ped.state.get_end_time(), Command::UpdatePed(ped.id));
                     } else {
                         // Must've failed because we can't turn yet. Don't schedule a retry here.
@@ -174,7 +196,17 @@ impl WalkingSimState {
                 }
             }
             PedState::WaitingToTurn(_) => {
-                if ped.maybe_transition(now, map, intersections, &mut self.peds_per_traversable) {
+                if ped.maybe_transition(
+                    now,
+                    map,
+                    intersections,
+                    &mut self.peds_per_traversable,
+                    if self.record_high_volume_events {
+                        Some(&mut self.events)
+                    } else {
+                        None
+                    },
+                ) {
                     scheduler.push(ped.state.get_end_time(), Command::UpdatePed(ped.id));
                 }
             }
@@ -186,13 +218,13 @@ impl WalkingSimState {
             PedState::EnteringBuilding(bldg, _) => {
                 self.peds_per_traversable
                     .remove(ped.path.current_step().as_traversable(), ped.id);
-                trips.ped_reached_building(now, ped.id, bldg, map);
+                trips.ped_reached_building(now, ped.id, bldg, map, scheduler);
                 self.peds.remove(&id);
             }
             PedState::StartingToBike(ref spot, _, _) => {
                 self.peds_per_traversable
                     .remove(ped.path.current_step().as_traversable(), ped.id);
-                trips.ped_ready_to_bike(now, ped.id, spot.clone(), map, scheduler);
+                trips.ped_ready_to_bike(now, ped.id, spot.clone(), map, bike_parking, scheduler);
                 self.peds.remove(&id);
             }
             PedState::FinishingBiking(ref spot, _, _) => {
@@ -214,6 +246,14 @@ impl WalkingSimState {
         };
     }
 
+    pub fn collect_events(&mut self) -> Vec<Event> {
+        self.events.drain(..).collect()
+    }
+
+    pub fn record_high_volume_events(&mut self, enabled: bool) {
+        self.record_high_volume_events = enabled;
+    }
+
     pub fn debug_ped(&self, id: PedestrianID) {
         if let Some(ped) = self.peds.get(&id) {
             println!("{}", abstutil::to_json(ped));
@@ -273,8 +313,14 @@ impl Pedestrian {
                 PathStep::Turn(t) => map.get_t(t).geom.length(),
             }
         };
+        let grade = match self.path.current_step() {
+            PathStep::Lane(l) => map.get_l(l).grade(map),
+            PathStep::ContraflowLane(l) => -map.get_l(l).grade(map),
+            PathStep::Turn(_) => 0.0,
+        };
+        let speed = walking_speed_on_grade(self.speed, grade);
         let dist_int = DistanceInterval::new_walking(start_dist, end_dist);
-        let time_int = TimeInterval::new(start_time, start_time + dist_int.length() / self.speed);
+        let time_int = TimeInterval::new(start_time, start_time + dist_int.length() / speed);
         PedState::Crossing(dist_int, time_int)
     }
 
@@ -378,6 +424,31 @@ impl Pedestrian {
         }
     }
 
+    // Only called when the next step is a turn, so the current step is a lane.
+    fn end_of_current_lane(&self, map: &Map) -> Distance {
+        match self.path.current_step() {
+            PathStep::Lane(l) => map.get_l(l).length(),
+            PathStep::ContraflowLane(_) => Distance::ZERO,
+            PathStep::Turn(_) => unreachable!(),
+        }
+    }
+
+    // Called when the next step is closed. Returns true if there's another way to the same place.
+    fn reroute_around_closures(&mut self, dist: Distance, now: Duration, map: &Map) -> bool {
+        let req = PathRequest {
+            start: Position::new(self.path.current_step().as_lane(), dist),
+            end: self.goal.sidewalk_pos,
+            can_use_bike_lanes: false,
+            can_use_bus_lanes: false,
+        };
+        if let Some(path) = map.pathfind_at(req, now) {
+            self.path = path;
+            true
+        } else {
+            false
+        }
+    }
+
     // True if we successfully continued to the next step of our path
     fn maybe_transition(
         &mut self,
@@ -385,8 +456,19 @@ impl Pedestrian {
         map: &Map,
         intersections: &mut IntersectionSimState,
         peds_per_traversable: &mut MultiMap<Traversable, PedestrianID>,
+        // Only while recording high-volume events
+        events: Option<&mut Vec<Event>>,
     ) -> bool {
         if let PathStep::Turn(t) = self.path.next_step() {
+            let dist = self.end_of_current_lane(map);
+            if map.is_closed(Traversable::Turn(t), now)
+                && self.reroute_around_closures(dist, now, map)
+            {
+                intersections.cancel_request(AgentID::Pedestrian(self.id), t, now);
+                // Still on the same lane, but maybe headed back the other way now
+                self.state = self.crossing_state(dist, now, map);
+                return true;
+            }
             if !intersections.maybe_start_turn(AgentID::Pedestrian(self.id), t, now, map) {
                 return false;
             }
@@ -401,6 +483,12 @@ impl Pedestrian {
         };
         self.state = self.crossing_state(start_dist, now, map);
         peds_per_traversable.insert(self.path.current_step().as_traversable(), self.id);
+        if let Some(events) = events {
+            events.push(Event::AgentEntersTraversable(
+                AgentID::Pedestrian(self.id),
+                self.path.current_step().as_traversable(),
+            ));
+        }
         true
     }
 }
@@ -430,3 +518,9 @@ impl PedState {
         }
     }
 }
+
+// Tobler's hiking function, scaled so the speed on flat ground doesn't change. People walk fastest
+// slightly downhill.
+pub fn walking_speed_on_grade(speed: Speed, grade: f64) -> Speed {
+    speed * ((-3.5 * (grade + 0.05).abs()).exp() / (-3.5 * 0.05_f64).exp())
+}
@@ -1,9 +1,10 @@
 use crate::{CarID, PedestrianID, VehicleType};
 use geom::{Angle, Duration, PolyLine, Pt2D};
 use map_model::{Map, Traversable, TurnID};
+use serde_derive::{Deserialize, Serialize};
 
 // Intermediate structures so that sim and editor crates don't have a cyclic dependency.
-#[derive(Clone)]
+#[derive(Clone, Serialize, Deserialize)]
 pub struct DrawPedestrianInput {
     pub id: PedestrianID,
     pub pos: Pt2D,
@@ -13,7 +14,7 @@ pub struct DrawPedestrianInput {
     pub on: Traversable,
 }
 
-#[derive(Clone)]
+#[derive(Clone, Serialize, Deserialize)]
 pub struct DrawCarInput {
     pub id: CarID,
     pub waiting_for_turn: Option<TurnID>,
@@ -26,7 +27,7 @@ pub struct DrawCarInput {
     pub body: PolyLine,
 }
 
-#[derive(Clone, Copy, PartialEq, Eq)]
+#[derive(Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
 pub enum CarStatus {
     Moving,
     Stuck,
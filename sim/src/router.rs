@@ -1,7 +1,8 @@
-use crate::{ParkingSimState, ParkingSpot, SidewalkSpot, Vehicle};
-use geom::Distance;
+use crate::{ParkingSimState, ParkingSpot, SidewalkSpot, Vehicle, VehicleType};
+use geom::{Distance, Duration};
 use map_model::{
-    BuildingID, IntersectionID, Map, Path, PathStep, Position, Traversable, Turn, TurnID,
+    BikeParkingID, BuildingID, IntersectionID, Map, Path, PathRequest, PathStep, Position,
+    Traversable, Turn, TurnID,
 };
 use serde_derive::{Deserialize, Serialize};
 use std::collections::BTreeSet;
@@ -38,6 +39,10 @@ enum Goal {
     BikeThenStop {
         end_dist: Distance,
     },
+    BikeToParking {
+        end_dist: Distance,
+        id: BikeParkingID,
+    },
     FollowBusRoute {
         end_dist: Distance,
     },
@@ -69,6 +74,16 @@ impl Router {
         }
     }
 
+    pub fn bike_to_parking(path: Path, id: BikeParkingID, map: &Map) -> Router {
+        Router {
+            path,
+            goal: Goal::BikeToParking {
+                end_dist: map.get_bike_parking(id).driving_pos.dist_along(),
+                id,
+            },
+        }
+    }
+
     pub fn follow_bus_route(path: Path, end_dist: Distance) -> Router {
         Router {
             path,
@@ -95,6 +110,7 @@ impl Router {
             Goal::EndAtBorder { end_dist, .. } => end_dist,
             Goal::ParkNearBuilding { spot, .. } => spot.unwrap().1,
             Goal::BikeThenStop { end_dist } => end_dist,
+            Goal::BikeToParking { end_dist, .. } => end_dist,
             Goal::FollowBusRoute { end_dist } => end_dist,
         }
     }
@@ -175,6 +191,13 @@ impl Router {
                     None
                 }
             }
+            Goal::BikeToParking { end_dist, id } => {
+                if end_dist == front {
+                    Some(ActionAtEnd::StopBiking(SidewalkSpot::bike_parking(id, map)))
+                } else {
+                    None
+                }
+            }
             Goal::FollowBusRoute { end_dist } => {
                 if end_dist == front {
                     Some(ActionAtEnd::BusAtStop)
@@ -185,6 +208,28 @@ impl Router {
         }
     }
 
+    // Called when the next step is closed. Returns true if there's another way to the same place.
+    pub fn reroute_around_closures(&mut self, vehicle: &Vehicle, now: Duration, map: &Map) -> bool {
+        // Buses have to visit their stops.
+        if let Goal::FollowBusRoute { .. } = self.goal {
+            return false;
+        }
+
+        let current_lane = self.head().as_lane();
+        let req = PathRequest {
+            start: Position::new(current_lane, map.get_l(current_lane).length()),
+            end: Position::new(self.path.last_step().as_lane(), self.path.get_end_dist()),
+            can_use_bike_lanes: vehicle.vehicle_type == VehicleType::Bike,
+            can_use_bus_lanes: false,
+        };
+        if let Some(path) = map.pathfind_at(req, now) {
+            self.path = path;
+            true
+        } else {
+            false
+        }
+    }
+
     fn roam_around_for_parking(&mut self, vehicle: &Vehicle, map: &Map) {
         let turns_attempted_while_roaming = match self.goal {
             Goal::ParkNearBuilding {
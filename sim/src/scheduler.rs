@@ -1,4 +1,4 @@
-use crate::{CarID, CreateCar, CreatePedestrian, PedestrianID};
+use crate::{CarID, CreateCar, CreatePedestrian, PedestrianID, PersonID};
 use derivative::Derivative;
 use geom::Duration;
 use histogram::Histogram;
@@ -13,7 +13,11 @@ pub enum Command {
     // Distinguish this from UpdateCar to avoid confusing things
     UpdateLaggyHead(CarID),
     UpdatePed(PedestrianID),
+    // Somebody is ready to leave for the next trip in their schedule
+    StartPersonTrip(PersonID),
     UpdateIntersection(IntersectionID),
+    // Wake up anybody waiting for a closure at this intersection to end
+    ClosureEnds(IntersectionID),
     CheckForGridlock,
     Savestate(Duration),
 }
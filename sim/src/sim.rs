@@ -1,19 +1,25 @@
 use crate::{
-    AgentID, Benchmark, CarID, Command, CreateCar, DrawCarInput, DrawPedestrianInput, DrivingGoal,
-    DrivingSimState, Event, GetDrawAgents, IntersectionSimState, ParkedCar, ParkingSimState,
-    ParkingSpot, PedestrianID, Router, Scheduler, ScoreSummary, SimStats, Summary, TransitSimState,
-    TripID, TripLeg, TripManager, TripSpawner, TripSpec, VehicleSpec, VehicleType, WalkingSimState,
-    BUS_LENGTH,
+    trajectories_dir, AgentID, Benchmark, BikeParkingSimState, CarID, Command, CreateCar,
+    DrawCarInput, DrawPedestrianInput, DrivingGoal, DrivingSimState, Event, EventLogHeader,
+    EventRecorder, GetDrawAgents, IntersectionSimState, IntersectionStats, ParkedCar,
+    ParkingSimState, ParkingSpot, PedestrianID, Person, PersonID, PersonSpec, Router, Scheduler,
+    ScoreSummary, SimStats, Summary, TrajectoryRecorder, TransitSimState, TripID, TripLeg,
+    TripManager, TripSpawner, TripSpec, VehicleSpec, VehicleType, WalkingSimState, BUS_LENGTH,
 };
 use abstutil::Timer;
 use derivative::Derivative;
 use geom::{Distance, Duration, PolyLine, Polygon, Pt2D};
-use map_model::{BuildingID, BusRoute, IntersectionID, LaneID, Map, Path, Traversable};
+use map_model::{
+    BikeParkingID, BuildingID, BusRoute, IntersectionID, LaneID, Map, Path, Traversable, TurnID,
+};
 use serde_derive::{Deserialize, Serialize};
-use std::collections::{HashSet, VecDeque};
+use std::collections::{BTreeSet, HashSet, VecDeque};
 use std::panic;
 use std::time::Instant;
 
+// Bump this whenever a change to the sim's state makes old savestates unreadable.
+const SAVESTATE_FORMAT_VERSION: usize = 1;
+
 const CHECK_FOR_GRIDLOCK_FREQUENCY: Duration = Duration::const_seconds(5.0 * 60.0);
 // TODO Do something else.
 const BLIND_RETRY_TO_SPAWN: Duration = Duration::const_seconds(5.0);
@@ -23,6 +29,7 @@ const BLIND_RETRY_TO_SPAWN: Duration = Duration::const_seconds(5.0);
 pub struct Sim {
     driving: DrivingSimState,
     parking: ParkingSimState,
+    bike_parking: BikeParkingSimState,
     walking: WalkingSimState,
     intersections: IntersectionSimState,
     transit: TransitSimState,
@@ -36,6 +43,9 @@ pub struct Sim {
     // TODO Reconsider these
     pub(crate) map_name: String,
     pub(crate) edits_name: String,
+    // Only recorded in savestates; the Sim itself doesn't use randomness.
+    #[derivative(PartialEq = "ignore")]
+    rng_seed: Option<u8>,
     // Some tests deliberately set different scenario names for comparisons.
     #[derivative(PartialEq = "ignore")]
     run_name: String,
@@ -50,11 +60,26 @@ pub struct Sim {
     #[derivative(PartialEq = "ignore")]
     #[serde(skip_serializing, skip_deserializing)]
     events_since_last_step: Vec<Event>,
+    #[derivative(PartialEq = "ignore")]
+    #[serde(skip_serializing, skip_deserializing)]
+    event_recorder: Option<EventRecorder>,
+    #[derivative(PartialEq = "ignore")]
+    #[serde(skip_serializing, skip_deserializing)]
+    trajectory_recorder: Option<TrajectoryRecorder>,
+    // Why recorders stopped since the last take_recording_errors
+    #[derivative(PartialEq = "ignore")]
+    #[serde(skip_serializing, skip_deserializing)]
+    recording_errors: Vec<String>,
 }
 
 // Setup
 impl Sim {
-    pub fn new(map: &Map, run_name: String, savestate_every: Option<Duration>) -> Sim {
+    pub fn new(
+        map: &Map,
+        run_name: String,
+        savestate_every: Option<Duration>,
+        rng_seed: Option<u8>,
+    ) -> Sim {
         let mut scheduler = Scheduler::new();
         scheduler.push(CHECK_FOR_GRIDLOCK_FREQUENCY, Command::CheckForGridlock);
         if let Some(d) = savestate_every {
@@ -63,6 +88,7 @@ impl Sim {
         Sim {
             driving: DrivingSimState::new(map),
             parking: ParkingSimState::new(map),
+            bike_parking: BikeParkingSimState::new(map),
             walking: WalkingSimState::new(),
             intersections: IntersectionSimState::new(map, &mut scheduler),
             transit: TransitSimState::new(),
@@ -74,12 +100,15 @@ impl Sim {
             ped_id_counter: 0,
 
             map_name: map.get_name().to_string(),
-            // TODO
-            edits_name: "no_edits".to_string(),
+            edits_name: map.get_edits().edits_name.clone(),
+            rng_seed,
             run_name,
             step_count: 0,
             stats: None,
             events_since_last_step: Vec::new(),
+            event_recorder: None,
+            recording_errors: Vec::new(),
+            trajectory_recorder: None,
         }
     }
 
@@ -88,6 +117,50 @@ impl Sim {
         start_time: Duration,
         spec: TripSpec,
         map: &Map,
+    ) -> (Option<PedestrianID>, Option<CarID>) {
+        self.schedule_trip_for_person(start_time, None, spec, map)
+    }
+
+    // Checks a batch of trips before scheduling any of them, for callers that shouldn't panic on
+    // bad input.
+    pub fn validate_trips(
+        &self,
+        trips: &Vec<(Duration, TripSpec)>,
+        map: &Map,
+    ) -> Result<(), String> {
+        let mut cars = BTreeSet::new();
+        for (time, spec) in trips {
+            if *time < self.time {
+                return Err(format!(
+                    "Can't start a trip at {}; it's already {}",
+                    time, self.time
+                ));
+            }
+            self.spawner.validate_trip(None, spec, map, &self.parking)?;
+            if let TripSpec::UsingParkedCar { spot, .. } = spec {
+                let car = self.parking.get_car_at_spot(*spot).unwrap().vehicle.id;
+                if !cars.insert(car) {
+                    return Err(format!("More than one trip wants to use {}", car));
+                }
+            }
+        }
+        Ok(())
+    }
+
+    // The person starts their first trip at the scheduled time. Each later trip starts once the
+    // previous one is done.
+    pub fn new_person(&mut self, spec: PersonSpec) -> PersonID {
+        let bike = CarID(self.car_id_counter, VehicleType::Bike);
+        self.car_id_counter += 1;
+        self.trips.new_person(spec, bike, &mut self.scheduler)
+    }
+
+    fn schedule_trip_for_person(
+        &mut self,
+        start_time: Duration,
+        person: Option<PersonID>,
+        spec: TripSpec,
+        map: &Map,
     ) -> (Option<PedestrianID>, Option<CarID>) {
         let (ped_id, car_id) = match spec {
             TripSpec::CarAppearing {
@@ -117,14 +190,27 @@ impl Sim {
             TripSpec::UsingBike { .. } => {
                 let ped = PedestrianID(self.ped_id_counter);
                 self.ped_id_counter += 1;
-                let car = CarID(self.car_id_counter, VehicleType::Bike);
+                // People ride the same bike every time.
+                let bike = if let Some(p) = person {
+                    self.trips.get_person(p).unwrap().bike
+                } else {
+                    let id = CarID(self.car_id_counter, VehicleType::Bike);
+                    self.car_id_counter += 1;
+                    id
+                };
+                (Some(ped), Some(bike))
+            }
+            TripSpec::UsingBikeshare { .. } => {
+                let ped = PedestrianID(self.ped_id_counter);
+                self.ped_id_counter += 1;
+                let bike = CarID(self.car_id_counter, VehicleType::Bike);
                 self.car_id_counter += 1;
-                (Some(ped), Some(car))
+                (Some(ped), Some(bike))
             }
         };
 
         self.spawner
-            .schedule_trip(start_time, ped_id, car_id, spec, map, &self.parking);
+            .schedule_trip(start_time, ped_id, car_id, person, spec, map, &self.parking);
         (ped_id, car_id)
     }
 
@@ -192,11 +278,12 @@ impl Sim {
             self.car_id_counter += 1;
 
             // Bypass some layers of abstraction that don't make sense for buses.
+            let start = path.current_step().as_traversable();
 
             // TODO Aww, we create an orphan trip if the bus can't spawn.
-            let trip = self
-                .trips
-                .new_trip(self.time, vec![TripLeg::ServeBusRoute(id, route.id)]);
+            let trip =
+                self.trips
+                    .new_trip(self.time, None, vec![TripLeg::ServeBusRoute(id, route.id)]);
             if self.driving.start_car_on_lane(
                 self.time,
                 CreateCar {
@@ -211,7 +298,8 @@ impl Sim {
                 &self.parking,
                 &mut self.scheduler,
             ) {
-                self.trips.agent_starting_trip_leg(AgentID::Car(id), trip);
+                self.trips
+                    .agent_starting_trip_leg(AgentID::Car(id), trip, start);
                 self.transit.bus_created(id, route.id, next_stop_idx);
                 timer.note(format!(
                     "Spawned bus {} for route {} ({})",
@@ -289,6 +377,8 @@ impl Sim {
             panic!("Forgot to call spawn_all_trips");
         }
 
+        self.events_since_last_step.clear();
+
         let target_time = self.time + dt;
         let mut savestate_at: Option<Duration> = None;
         while let Some((cmd, time)) = self.scheduler.get_next(target_time) {
@@ -301,6 +391,7 @@ impl Sim {
                     savestate_at = None;
                 }
             }
+            self.record_trajectories_until(time, false, map);
 
             self.time = time;
             match cmd {
@@ -316,6 +407,7 @@ impl Sim {
                         self.trips.agent_starting_trip_leg(
                             AgentID::Car(create_car.vehicle.id),
                             create_car.trip,
+                            create_car.router.head(),
                         );
                         if let Some(parked_car) = create_car.maybe_parked_car {
                             self.parking.remove_parked_car(parked_car);
@@ -333,6 +425,7 @@ impl Sim {
                     self.trips.agent_starting_trip_leg(
                         AgentID::Pedestrian(create_ped.id),
                         create_ped.trip,
+                        Traversable::Lane(create_ped.start.sidewalk_pos.lane()),
                     );
                     self.walking
                         .spawn_ped(self.time, create_ped, map, &mut self.scheduler);
@@ -343,6 +436,7 @@ impl Sim {
                         self.time,
                         map,
                         &mut self.parking,
+                        &mut self.bike_parking,
                         &mut self.intersections,
                         &mut self.trips,
                         &mut self.scheduler,
@@ -366,15 +460,39 @@ impl Sim {
                         map,
                         &mut self.intersections,
                         &self.parking,
+                        &mut self.bike_parking,
                         &mut self.scheduler,
                         &mut self.trips,
                         &mut self.transit,
                     );
                 }
+                Command::StartPersonTrip(p) => {
+                    if let Some(spec) = self.trips.start_person_trip(
+                        self.time,
+                        p,
+                        map,
+                        &self.parking,
+                        &self.bike_parking,
+                        &mut self.scheduler,
+                    ) {
+                        self.schedule_trip_for_person(self.time, Some(p), spec, map);
+                        self.spawner.spawn_all(
+                            map,
+                            &self.parking,
+                            &mut self.trips,
+                            &mut self.scheduler,
+                            &mut Timer::throwaway(),
+                        );
+                    }
+                }
                 Command::UpdateIntersection(i) => {
                     self.intersections
                         .update_intersection(self.time, i, map, &mut self.scheduler);
                 }
+                Command::ClosureEnds(i) => {
+                    self.intersections
+                        .closure_ended(self.time, i, &mut self.scheduler);
+                }
                 Command::CheckForGridlock => {
                     if self.driving.detect_gridlock(map) {
                         self.save();
@@ -392,20 +510,135 @@ impl Sim {
                     savestate_at = Some(self.time);
                 }
             }
+            self.collect_events();
         }
         if let Some(t) = savestate_at {
             self.time = t;
             self.save();
         }
         self.time = target_time;
+        self.record_trajectories_until(target_time, true, map);
 
         self.stats = None;
 
-        self.events_since_last_step.clear();
-        self.events_since_last_step
-            .extend(self.trips.collect_events());
-        self.events_since_last_step
-            .extend(self.transit.collect_events());
+        if let Some(Err(e)) = self.event_recorder.as_mut().map(|r| r.flush()) {
+            self.stop_recording_events(e);
+        }
+        if let Some(Err(e)) = self.trajectory_recorder.as_mut().map(|r| r.flush()) {
+            self.trajectory_recorder = None;
+            self.recording_errors.push(e.to_string());
+        }
+    }
+
+    // Called after every command, so events are in the order they happened and recorded with the
+    // right time.
+    fn collect_events(&mut self) {
+        let mut events = self.intersections.collect_events();
+        events.extend(self.driving.collect_events());
+        events.extend(self.walking.collect_events());
+        events.extend(self.trips.collect_events());
+        events.extend(self.transit.collect_events());
+
+        let time = self.time;
+        if let Some(Err(e)) = self
+            .event_recorder
+            .as_mut()
+            .map(|r| events.iter().try_for_each(|ev| r.record(time, ev)))
+        {
+            self.stop_recording_events(e);
+            events.retain(|ev| !ev.is_high_volume());
+        }
+        self.events_since_last_step.extend(events);
+    }
+
+    // The high-volume events are only created while recording, since nobody's going to look
+    // through all of the turns and lanes entered otherwise.
+    fn set_high_volume_events(&mut self, enabled: bool) {
+        self.intersections.record_high_volume_events(enabled);
+        self.driving.record_high_volume_events(enabled);
+        self.walking.record_high_volume_events(enabled);
+    }
+
+    fn stop_recording_events(&mut self, e: std::io::Error) {
+        self.event_recorder = None;
+        self.set_high_volume_events(false);
+        self.recording_errors.push(e.to_string());
+    }
+
+    // Writing a recording can fail partway through a step, which stops that recording. Returns
+    // why, once per failure.
+    pub fn take_recording_errors(&mut self) -> Vec<String> {
+        std::mem::replace(&mut self.recording_errors, Vec::new())
+    }
+
+    // Call after Map::apply_edits changes how some intersections are controlled.
+    pub fn handle_intersection_edits(&mut self, changed: &BTreeSet<IntersectionID>, map: &Map) {
+        // Savestates and recordings from now on are for the new edits.
+        self.edits_name = map.get_edits().edits_name.clone();
+        self.intersections
+            .handle_edits(self.time, changed, map, &mut self.scheduler);
+    }
+
+    // Draw every frame due before this time, or also at this time if every command for it has
+    // happened.
+    fn record_trajectories_until(&mut self, time: Duration, inclusive: bool, map: &Map) {
+        let mut recorder = match self.trajectory_recorder.take() {
+            Some(r) => r,
+            None => {
+                return;
+            }
+        };
+        let orig_time = self.time;
+        while recorder.next_frame_time() < time || (inclusive && recorder.next_frame_time() == time)
+        {
+            self.time = recorder.next_frame_time();
+            if let Err(e) =
+                recorder.record(self.get_all_draw_cars(map), self.get_all_draw_peds(map))
+            {
+                self.time = orig_time;
+                self.recording_errors.push(e.to_string());
+                return;
+            }
+        }
+        self.time = orig_time;
+        self.trajectory_recorder = Some(recorder);
+    }
+
+    // From now on, draw every agent at this frequency and write it to a file. Returns the
+    // directory for Trajectories::load.
+    pub fn record_trajectories(&mut self, frequency: Duration) -> Result<String, std::io::Error> {
+        if frequency <= Duration::ZERO {
+            return Err(std::io::Error::new(
+                std::io::ErrorKind::InvalidInput,
+                format!("Can't record trajectories every {}", frequency),
+            ));
+        }
+        let recorder = TrajectoryRecorder::new(
+            &self.map_name,
+            &self.edits_name,
+            &self.run_name,
+            self.time,
+            frequency,
+        )?;
+        self.trajectory_recorder = Some(recorder);
+        Ok(trajectories_dir(
+            &self.map_name,
+            &self.edits_name,
+            &self.run_name,
+        ))
+    }
+
+    // From now on, append every event to this file. Read it back with EventLog.
+    pub fn record_events(&mut self, path: &str) -> Result<(), std::io::Error> {
+        let header = EventLogHeader {
+            map_name: self.map_name.clone(),
+            edits_name: self.edits_name.clone(),
+            run_name: self.run_name.clone(),
+            start_time: self.time,
+        };
+        self.event_recorder = Some(EventRecorder::new(path, &header)?);
+        self.set_high_volume_events(true);
+        Ok(())
     }
 
     pub fn dump_before_abort(&self) {
@@ -507,11 +740,21 @@ impl Sim {
     }
 }
 
+// Written before the Sim in every savestate, so it can be checked without deserializing a Sim
+// that might not match the current code.
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct SavestateHeader {
+    pub format_version: usize,
+    pub code_version: String,
+    pub map_name: String,
+    pub edits_name: String,
+    pub rng_seed: Option<u8>,
+    pub time: Duration,
+}
+
 // Savestating
 impl Sim {
     pub fn save(&self) -> String {
-        // If we wanted to be even more reproducible, we'd encode RNG seed, version of code, etc,
-        // but that's overkill right now.
         let path = format!(
             "../data/save/{}_{}/{}/{}",
             self.map_name,
@@ -519,11 +762,46 @@ impl Sim {
             self.run_name,
             self.time.as_filename()
         );
-        abstutil::write_json(&path, &self).expect("Writing sim state failed");
+        let header = SavestateHeader {
+            format_version: SAVESTATE_FORMAT_VERSION,
+            code_version: env!("CARGO_PKG_VERSION").to_string(),
+            map_name: self.map_name.clone(),
+            edits_name: self.edits_name.clone(),
+            rng_seed: self.rng_seed,
+            time: self.time,
+        };
+        abstutil::write_binary(&path, &(header, self)).expect("Writing sim state failed");
         println!("Saved to {}", path);
         path
     }
 
+    // Also checks that this build can read the rest of the savestate.
+    pub fn read_savestate_header(path: &str) -> Result<SavestateHeader, std::io::Error> {
+        let header: SavestateHeader = abstutil::read_binary_header(path).map_err(|e| {
+            std::io::Error::new(
+                e.kind(),
+                format!(
+                    "{} isn't a savestate, or is from a much older version: {}",
+                    path, e
+                ),
+            )
+        })?;
+        if header.format_version != SAVESTATE_FORMAT_VERSION {
+            return Err(savestate_mismatch(format!(
+                "{} uses savestate format {}, but this build reads format {}",
+                path, header.format_version, SAVESTATE_FORMAT_VERSION
+            )));
+        }
+        let code_version = env!("CARGO_PKG_VERSION");
+        if header.code_version != code_version {
+            return Err(savestate_mismatch(format!(
+                "{} was saved by sim {}, but this is sim {}",
+                path, header.code_version, code_version
+            )));
+        }
+        Ok(header)
+    }
+
     pub fn find_previous_savestate(&self, base_time: Duration) -> Option<String> {
         abstutil::find_prev_file(&format!(
             "../data/save/{}_{}/{}/{}",
@@ -544,20 +822,54 @@ impl Sim {
         ))
     }
 
+    // If rng_seed is None, the savestate's seed isn't checked.
     pub fn load_savestate(
         path: String,
         new_run_name: Option<String>,
+        map: &Map,
+        rng_seed: Option<u8>,
     ) -> Result<Sim, std::io::Error> {
         println!("Loading {}", path);
-        abstutil::read_json(&path).map(|mut s: Sim| {
-            if let Some(name) = new_run_name {
-                s.run_name = name;
+        let header = Sim::read_savestate_header(&path)?;
+        if header.map_name != *map.get_name() {
+            return Err(savestate_mismatch(format!(
+                "{} is for map {}, but {} is loaded",
+                path,
+                header.map_name,
+                map.get_name()
+            )));
+        }
+        if header.edits_name != map.get_edits().edits_name {
+            return Err(savestate_mismatch(format!(
+                "{} is for edits {}, but the map has {}",
+                path,
+                header.edits_name,
+                map.get_edits().edits_name
+            )));
+        }
+        if let (Some(saved), Some(expected)) = (header.rng_seed, rng_seed) {
+            if saved != expected {
+                return Err(savestate_mismatch(format!(
+                    "{} was made with RNG seed {}, not {}",
+                    path, saved, expected
+                )));
             }
-            s
-        })
+        }
+
+        let (_, mut sim): (SavestateHeader, Sim) =
+            abstutil::read_binary(&path, &mut Timer::throwaway())?;
+        if let Some(name) = new_run_name {
+            sim.run_name = name;
+        }
+        sim.bike_parking.index(map);
+        Ok(sim)
     }
 }
 
+fn savestate_mismatch(msg: String) -> std::io::Error {
+    std::io::Error::new(std::io::ErrorKind::InvalidData, msg)
+}
+
 // Benchmarking
 impl Sim {
     pub fn start_benchmark(&self) -> Benchmark {
@@ -593,6 +905,10 @@ impl Sim {
         &self.run_name
     }
 
+    pub fn get_rng_seed(&self) -> Option<u8> {
+        self.rng_seed
+    }
+
     pub fn is_done(&self) -> bool {
         self.spawner.is_done() && self.trips.is_done()
     }
@@ -683,6 +999,33 @@ impl Sim {
         self.trips.trip_to_agent(id)
     }
 
+    pub fn trip_to_person(&self, id: TripID) -> Option<PersonID> {
+        self.trips.trip_to_person(id)
+    }
+
+    pub fn get_person(&self, id: PersonID) -> Option<&Person> {
+        self.trips.get_person(id)
+    }
+
+    pub fn get_all_people(&self) -> &Vec<Person> {
+        self.trips.get_all_people()
+    }
+
+    // Compare these between two runs to see how somebody's day changed.
+    pub fn person_trip_times(&self, id: PersonID) -> Vec<(TripID, Duration, Option<Duration>)> {
+        self.trips.person_trip_times(id)
+    }
+
+    // Every trip so far besides buses, with when it started and finished
+    pub fn get_all_trip_times(&self) -> Vec<(TripID, Duration, Option<Duration>)> {
+        self.trips.get_all_trip_times()
+    }
+
+    // How many bikes are locked up at a rack or waiting at a bikeshare dock
+    pub fn bikes_parked_at(&self, id: BikeParkingID) -> usize {
+        self.bike_parking.get_bikes(id)
+    }
+
     pub fn lookup_car_id(&self, idx: usize) -> Option<CarID> {
         for vt in &[VehicleType::Car, VehicleType::Bike, VehicleType::Bus] {
             let id = CarID(idx, *vt);
@@ -773,7 +1116,34 @@ impl Sim {
         self.intersections.get_accepted_agents(id)
     }
 
+    pub fn get_waiting_agents(&self, id: IntersectionID) -> Vec<(AgentID, TurnID)> {
+        self.intersections.get_waiting_agents(id)
+    }
+
     pub fn is_in_overtime(&self, id: IntersectionID, map: &Map) -> bool {
         self.intersections.is_in_overtime(self.time, id, map)
     }
+
+    // How long agents have waited at this intersection, how many waited, and how well its signal
+    // cycles were used, from the start of the run until now
+    pub fn get_intersection_stats(&self, id: IntersectionID) -> IntersectionStats {
+        self.intersections.get_stats(id, self.time)
+    }
+
+    pub fn get_all_intersection_stats(&self, map: &Map) -> Vec<IntersectionStats> {
+        map.all_intersections()
+            .iter()
+            .map(|i| self.get_intersection_stats(i.id))
+            .collect()
+    }
+
+    // Returns the path written
+    pub fn write_intersection_stats(&self, map: &Map) -> Result<String, std::io::Error> {
+        let path = format!(
+            "../data/intersection_stats/{}_{}/{}.json",
+            self.map_name, self.edits_name, self.run_name
+        );
+        abstutil::write_json(&path, &self.get_all_intersection_stats(map))?;
+        Ok(path)
+    }
 }
@@ -0,0 +1,321 @@
+use crate::{CarID, DrawCarInput, DrawPedestrianInput, GetDrawAgents, PedestrianID};
+use abstutil::{BinaryStreamReader, BinaryStreamWriter, MultiMap, Timer};
+use geom::Duration;
+use map_model::{Map, Traversable};
+use serde_derive::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+
+// Frames are split into files of this many, so replaying only needs one file in memory at a time.
+const FRAMES_PER_CHUNK: usize = 60;
+
+// Describes a recording. Frame i is at start_time + i * frequency.
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct TrajectoryHeader {
+    pub map_name: String,
+    pub edits_name: String,
+    pub run_name: String,
+    pub start_time: Duration,
+    pub frequency: Duration,
+    pub frames_per_chunk: usize,
+}
+
+impl TrajectoryHeader {
+    pub fn dir(&self) -> String {
+        trajectories_dir(&self.map_name, &self.edits_name, &self.run_name)
+    }
+}
+
+pub fn trajectories_dir(map_name: &str, edits_name: &str, run_name: &str) -> String {
+    format!(
+        "../data/trajectories/{}_{}/{}",
+        map_name, edits_name, run_name
+    )
+}
+
+fn chunk_path(dir: &str, chunk: usize) -> String {
+    format!("{}/{:06}.bin", dir, chunk)
+}
+
+// Everything that'd be drawn at one moment
+#[derive(Serialize, Deserialize)]
+struct Frame {
+    time: Duration,
+    cars: Vec<DrawCarInput>,
+    peds: Vec<DrawPedestrianInput>,
+}
+
+pub(crate) struct TrajectoryRecorder {
+    header: TrajectoryHeader,
+    dir: String,
+    num_frames: usize,
+    chunk: Option<BinaryStreamWriter>,
+}
+
+impl TrajectoryRecorder {
+    // Replaces any old recording with the same name.
+    pub fn new(
+        map_name: &str,
+        edits_name: &str,
+        run_name: &str,
+        start_time: Duration,
+        frequency: Duration,
+    ) -> Result<TrajectoryRecorder, std::io::Error> {
+        let header = TrajectoryHeader {
+            map_name: map_name.to_string(),
+            edits_name: edits_name.to_string(),
+            run_name: run_name.to_string(),
+            start_time,
+            frequency,
+            frames_per_chunk: FRAMES_PER_CHUNK,
+        };
+        let dir = header.dir();
+        if std::path::Path::new(&dir).exists() {
+            std::fs::remove_dir_all(&dir)?;
+        }
+        abstutil::write_binary(&format!("{}/header.bin", dir), &header)?;
+        Ok(TrajectoryRecorder {
+            header,
+            dir,
+            num_frames: 0,
+            chunk: None,
+        })
+    }
+
+    pub fn next_frame_time(&self) -> Duration {
+        self.header.start_time + self.header.frequency * (self.num_frames as f64)
+    }
+
+    pub fn record(
+        &mut self,
+        cars: Vec<DrawCarInput>,
+        peds: Vec<DrawPedestrianInput>,
+    ) -> Result<(), std::io::Error> {
+        if self.num_frames % self.header.frames_per_chunk == 0 {
+            let path = chunk_path(&self.dir, self.num_frames / self.header.frames_per_chunk);
+            self.flush()?;
+            self.chunk = Some(BinaryStreamWriter::new(&path).map_err(|e| {
+                std::io::Error::new(
+                    e.kind(),
+                    format!("Couldn't start recording trajectories to {}: {}", path, e),
+                )
+            })?);
+        }
+        let frame = Frame {
+            time: self.next_frame_time(),
+            cars,
+            peds,
+        };
+        self.chunk
+            .as_mut()
+            .unwrap()
+            .append(&frame)
+            .map_err(|e| self.describe(e))?;
+        self.num_frames += 1;
+        Ok(())
+    }
+
+    pub fn flush(&mut self) -> Result<(), std::io::Error> {
+        if let Some(ref mut f) = self.chunk {
+            if let Err(e) = f.flush() {
+                return Err(self.describe(e));
+            }
+        }
+        Ok(())
+    }
+
+    fn describe(&self, e: std::io::Error) -> std::io::Error {
+        std::io::Error::new(
+            e.kind(),
+            format!("Couldn't record trajectories to {}: {}", self.dir, e),
+        )
+    }
+}
+
+// Plays back a recording made by Sim::record_trajectories.
+pub struct Trajectories {
+    pub header: TrajectoryHeader,
+    dir: String,
+    num_frames: usize,
+    // The chunk containing the current frame
+    chunk_idx: usize,
+    chunk: Vec<Frame>,
+    current: StateAtFrame,
+}
+
+struct StateAtFrame {
+    idx: usize,
+    time: Duration,
+    cars: BTreeMap<CarID, DrawCarInput>,
+    peds: BTreeMap<PedestrianID, DrawPedestrianInput>,
+    cars_per_traversable: MultiMap<Traversable, CarID>,
+    peds_per_traversable: MultiMap<Traversable, PedestrianID>,
+}
+
+impl Trajectories {
+    // Starts at the first frame.
+    pub fn load(dir: &str) -> Result<Trajectories, std::io::Error> {
+        let header: TrajectoryHeader =
+            abstutil::read_binary(&format!("{}/header.bin", dir), &mut Timer::throwaway())?;
+        let mut num_chunks = 0;
+        while std::path::Path::new(&chunk_path(dir, num_chunks)).exists() {
+            num_chunks += 1;
+        }
+        let no_frames = || {
+            std::io::Error::new(
+                std::io::ErrorKind::NotFound,
+                format!("{} has no frames", dir),
+            )
+        };
+        if num_chunks == 0 {
+            return Err(no_frames());
+        }
+        // The last chunk might not be full.
+        let last_chunk = read_chunk(&chunk_path(dir, num_chunks - 1))?;
+        let num_frames = (num_chunks - 1) * header.frames_per_chunk + last_chunk.len();
+        if num_frames == 0 {
+            return Err(no_frames());
+        }
+
+        let mut replay = Trajectories {
+            header,
+            dir: dir.to_string(),
+            num_frames,
+            chunk_idx: num_chunks - 1,
+            chunk: last_chunk,
+            current: StateAtFrame {
+                idx: 0,
+                time: Duration::ZERO,
+                cars: BTreeMap::new(),
+                peds: BTreeMap::new(),
+                cars_per_traversable: MultiMap::new(),
+                peds_per_traversable: MultiMap::new(),
+            },
+        };
+        replay.goto_frame(0)?;
+        Ok(replay)
+    }
+
+    pub fn start_time(&self) -> Duration {
+        self.header.start_time
+    }
+
+    pub fn end_time(&self) -> Duration {
+        self.frame_time(self.num_frames - 1)
+    }
+
+    // Shows the last frame at or before this time. Times outside of the recording show the first
+    // or last frame.
+    pub fn seek(&mut self, time: Duration) -> Result<(), std::io::Error> {
+        let idx = if time <= self.header.start_time {
+            0
+        } else {
+            // Don't let float imprecision land right before a frame's time.
+            let idx = ((time - self.header.start_time) / self.header.frequency + 1e-6).floor();
+            (idx as usize).min(self.num_frames - 1)
+        };
+        self.goto_frame(idx)
+    }
+
+    pub fn prev_frame(&mut self) -> Result<(), std::io::Error> {
+        if self.current.idx > 0 {
+            self.goto_frame(self.current.idx - 1)?;
+        }
+        Ok(())
+    }
+
+    pub fn next_frame(&mut self) -> Result<(), std::io::Error> {
+        if self.current.idx + 1 < self.num_frames {
+            self.goto_frame(self.current.idx + 1)?;
+        }
+        Ok(())
+    }
+
+    fn frame_time(&self, idx: usize) -> Duration {
+        self.header.start_time + self.header.frequency * (idx as f64)
+    }
+
+    fn goto_frame(&mut self, idx: usize) -> Result<(), std::io::Error> {
+        let chunk_idx = idx / self.header.frames_per_chunk;
+        if chunk_idx != self.chunk_idx {
+            self.chunk = read_chunk(&chunk_path(&self.dir, chunk_idx))?;
+            self.chunk_idx = chunk_idx;
+        }
+        let frame = &self.chunk[idx % self.header.frames_per_chunk];
+
+        let mut state = StateAtFrame {
+            idx,
+            time: frame.time,
+            cars: BTreeMap::new(),
+            peds: BTreeMap::new(),
+            cars_per_traversable: MultiMap::new(),
+            peds_per_traversable: MultiMap::new(),
+        };
+        for draw in &frame.cars {
+            state.cars_per_traversable.insert(draw.on, draw.id);
+            state.cars.insert(draw.id, draw.clone());
+        }
+        for draw in &frame.peds {
+            state.peds_per_traversable.insert(draw.on, draw.id);
+            state.peds.insert(draw.id, draw.clone());
+        }
+        self.current = state;
+        Ok(())
+    }
+}
+
+// A frame that can't be read, like one cut off by the recording simulation crashing, is an error.
+fn read_chunk(path: &str) -> Result<Vec<Frame>, std::io::Error> {
+    let mut file = BinaryStreamReader::open(path)?;
+    let mut frames = Vec::new();
+    while let Some(frame) = file
+        .read_next()
+        .map_err(|e| std::io::Error::new(e.kind(), format!("Couldn't read {}: {}", path, e)))?
+    {
+        frames.push(frame);
+    }
+    Ok(frames)
+}
+
+impl GetDrawAgents for Trajectories {
+    fn time(&self) -> Duration {
+        self.current.time
+    }
+
+    fn step_count(&self) -> usize {
+        self.current.idx
+    }
+
+    fn get_draw_car(&self, id: CarID, _map: &Map) -> Option<DrawCarInput> {
+        self.current.cars.get(&id).cloned()
+    }
+
+    fn get_draw_ped(&self, id: PedestrianID, _map: &Map) -> Option<DrawPedestrianInput> {
+        self.current.peds.get(&id).cloned()
+    }
+
+    fn get_draw_cars(&self, on: Traversable, _map: &Map) -> Vec<DrawCarInput> {
+        self.current
+            .cars_per_traversable
+            .get(on)
+            .iter()
+            .map(|id| self.current.cars[id].clone())
+            .collect()
+    }
+
+    fn get_draw_peds(&self, on: Traversable, _map: &Map) -> Vec<DrawPedestrianInput> {
+        self.current
+            .peds_per_traversable
+            .get(on)
+            .iter()
+            .map(|id| self.current.peds[id].clone())
+            .collect()
+    }
+
+    fn get_all_draw_cars(&self, _map: &Map) -> Vec<DrawCarInput> {
+        self.current.cars.values().cloned().collect()
+    }
+
+    fn get_all_draw_peds(&self, _map: &Map) -> Vec<DrawPedestrianInput> {
+        self.current.peds.values().cloned().collect()
+    }
+}
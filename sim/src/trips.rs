@@ -1,14 +1,21 @@
 use crate::{
-    AgentID, CarID, Command, CreateCar, CreatePedestrian, DrivingGoal, Event, ParkingSimState,
-    ParkingSpot, PedestrianID, Scheduler, SidewalkPOI, SidewalkSpot, TransitSimState, TripID,
-    Vehicle, WalkingSimState,
+    AgentID, BikeParkingSimState, CarID, Command, CreateCar, CreatePedestrian, DrivingGoal, Event,
+    ParkingSimState, ParkingSpot, PedestrianID, PersonID, Router, Scheduler, SidewalkPOI,
+    SidewalkSpot, TransitSimState, TripID, TripSpec, Vehicle, VehicleSpec, VehicleType,
+    WalkingSimState,
 };
 use abstutil::{deserialize_btreemap, serialize_btreemap};
-use geom::{Duration, Speed};
-use map_model::{BuildingID, BusRouteID, BusStopID, IntersectionID, Map, PathRequest};
+use geom::{Distance, Duration, Speed};
+use map_model::{
+    BikeParkingID, BikeParkingType, BuildingID, BusRouteID, BusStopID, IntersectionID, Map,
+    PathRequest, Position, Traversable,
+};
 use serde_derive::{Deserialize, Serialize};
 use std::collections::{BTreeMap, VecDeque};
 
+// Nobody locks up their own bike farther than this from where they're going.
+const MAX_DIST_TO_BIKE_RACK: Distance = Distance::const_meters(200.0);
+
 #[derive(Serialize, Deserialize, PartialEq, Debug)]
 pub struct TripManager {
     trips: Vec<Trip>,
@@ -19,6 +26,7 @@ pub struct TripManager {
     )]
     active_trip_mode: BTreeMap<AgentID, TripID>,
     num_bus_trips: usize,
+    people: Vec<Person>,
 
     events: Vec<Event>,
 }
@@ -29,26 +37,109 @@ impl TripManager {
             trips: Vec::new(),
             active_trip_mode: BTreeMap::new(),
             num_bus_trips: 0,
+            people: Vec::new(),
             events: Vec::new(),
         }
     }
 
-    pub fn new_trip(&mut self, spawned_at: Duration, legs: Vec<TripLeg>) -> TripID {
+    pub fn new_trip(
+        &mut self,
+        spawned_at: Duration,
+        person: Option<PersonID>,
+        legs: Vec<TripLeg>,
+    ) -> TripID {
         assert!(!legs.is_empty());
         // TODO Make sure the legs constitute a valid state machine.
 
         let id = TripID(self.trips.len());
+        if let Some(p) = person {
+            let person = &mut self.people[p.0];
+            person.trips.push(id);
+            person.bldg = None;
+            // Somebody without a car who drives once now owns the car, wherever it gets parked.
+            for leg in &legs {
+                if let TripLeg::Drive(vehicle, DrivingGoal::ParkNear(_)) = leg {
+                    if vehicle.vehicle_type == VehicleType::Car {
+                        person.car = Some(vehicle.id);
+                    }
+                }
+            }
+        }
         self.trips.push(Trip {
             id,
             spawned_at,
             finished_at: None,
             legs: VecDeque::from(legs),
+            person,
+        });
+        id
+    }
+
+    pub fn new_person(
+        &mut self,
+        spec: PersonSpec,
+        bike: CarID,
+        scheduler: &mut Scheduler,
+    ) -> PersonID {
+        let id = PersonID(self.people.len());
+        if let Some(first) = spec.schedule.first() {
+            scheduler.push(first.depart, Command::StartPersonTrip(id));
+        }
+        self.people.push(Person {
+            id,
+            home: spec.home,
+            bldg: Some(spec.home),
+            car: spec.car,
+            bike,
+            bike_at: None,
+            ped_speed: spec.ped_speed,
+            bike_spec: spec.bike,
+            car_spec: spec.car_spec,
+            trips: Vec::new(),
+            schedule: VecDeque::from(spec.schedule),
         });
         id
     }
 
-    pub fn agent_starting_trip_leg(&mut self, agent: AgentID, trip: TripID) {
+    // Returns None if there's no trip to actually make right now. The person stays in the building
+    // until the trip actually starts.
+    pub fn start_person_trip(
+        &mut self,
+        time: Duration,
+        id: PersonID,
+        map: &Map,
+        parking: &ParkingSimState,
+        bike_parking: &BikeParkingSimState,
+        scheduler: &mut Scheduler,
+    ) -> Option<TripSpec> {
+        let person = &mut self.people[id.0];
+        let from = person
+            .bldg
+            .expect("Person is starting a trip without having finished the last one");
+        let plan = person.schedule.pop_front().unwrap();
+        if from == plan.to {
+            // Like somebody living above the shop where they work
+            person.ready_for_next_trip(time, scheduler);
+            return None;
+        }
+        Some(person.trip_spec(from, &plan, map, parking, bike_parking))
+    }
+
+    pub fn person_trip_failed_to_start(
+        &mut self,
+        time: Duration,
+        id: PersonID,
+        scheduler: &mut Scheduler,
+    ) {
+        let person = &self.people[id.0];
+        self.events
+            .push(Event::PersonTripSkipped(id, person.bldg.unwrap()));
+        person.ready_for_next_trip(time, scheduler);
+    }
+
+    pub fn agent_starting_trip_leg(&mut self, agent: AgentID, trip: TripID, on: Traversable) {
         assert!(!self.active_trip_mode.contains_key(&agent));
+        self.events.push(Event::AgentSpawned(agent, on));
         // TODO ensure a trip only has one active agent (aka, not walking and driving at the same
         // time)
         self.active_trip_mode.insert(agent, trip);
@@ -67,6 +158,7 @@ impl TripManager {
         scheduler: &mut Scheduler,
     ) {
         self.events.push(Event::CarReachedParkingSpot(car, spot));
+        self.events.push(Event::AgentDespawned(AgentID::Car(car)));
         let trip = &mut self.trips[self.active_trip_mode.remove(&AgentID::Car(car)).unwrap().0];
 
         match trip.legs.pop_front() {
@@ -79,6 +171,7 @@ impl TripManager {
             SidewalkSpot::parking_spot(spot, map, parking),
             map,
             scheduler,
+            &mut self.events,
         );
     }
 
@@ -92,6 +185,8 @@ impl TripManager {
         scheduler: &mut Scheduler,
     ) {
         self.events.push(Event::PedReachedParkingSpot(ped, spot));
+        self.events
+            .push(Event::AgentDespawned(AgentID::Pedestrian(ped)));
         let trip = &mut self.trips[self
             .active_trip_mode
             .remove(&AgentID::Pedestrian(ped))
@@ -108,18 +203,22 @@ impl TripManager {
 
         let start = parked_car.get_driving_pos(parking, map);
         let end = drive_to.goal_pos(map);
-        let path = if let Some(p) = map.pathfind(PathRequest {
-            start,
-            end,
-            can_use_bus_lanes: false,
-            can_use_bike_lanes: false,
-        }) {
+        let path = if let Some(p) = map.pathfind_at(
+            PathRequest {
+                start,
+                end,
+                can_use_bus_lanes: false,
+                can_use_bike_lanes: false,
+            },
+            time,
+        ) {
             p
         } else {
             println!(
                 "Aborting a trip because no path for the car portion! {:?} to {:?}",
                 start, end
             );
+            self.events.push(Event::TripAborted(trip.id));
             return;
         };
 
@@ -138,41 +237,123 @@ impl TripManager {
         ped: PedestrianID,
         spot: SidewalkSpot,
         map: &Map,
+        bike_parking: &mut BikeParkingSimState,
         scheduler: &mut Scheduler,
     ) {
+        self.events
+            .push(Event::AgentDespawned(AgentID::Pedestrian(ped)));
         let trip = &mut self.trips[self
             .active_trip_mode
             .remove(&AgentID::Pedestrian(ped))
             .unwrap()
             .0];
 
-        trip.assert_walking_leg(ped, spot.clone());
+        let ped_speed = trip.assert_walking_leg(ped, spot.clone());
         let (vehicle, drive_to) = match trip.legs[0] {
             TripLeg::Drive(ref vehicle, ref to) => (vehicle.clone(), to.clone()),
             _ => unreachable!(),
         };
-        let driving_pos = match spot.connection {
-            SidewalkPOI::BikeRack(p) => p,
+        // If the pedestrian winds up not biking from here, they start walking again from the same
+        // place.
+        let here = SidewalkSpot::suddenly_appear(
+            spot.sidewalk_pos.lane(),
+            spot.sidewalk_pos.dist_along(),
+            map,
+        );
+        // The dock the bike gets rented from
+        let (driving_pos, dock) = match spot.connection {
+            SidewalkPOI::BikeRack(p) => (p, None),
+            SidewalkPOI::BikeParking(id, p) => {
+                if map.get_bike_parking(id).parking_type == BikeParkingType::Bikeshare {
+                    if bike_parking.get_bikes(id) == 0 {
+                        // Walk to the next closest dock and try again there.
+                        self.events.push(Event::BikeshareDockEmpty(ped, id));
+                        if let Some(next) =
+                            bike_parking.closest_with_bike(spot.sidewalk_pos.pt(map), map)
+                        {
+                            trip.legs.push_front(TripLeg::Walk(
+                                ped,
+                                ped_speed,
+                                SidewalkSpot::bike_parking(next, map),
+                            ));
+                            trip.spawn_ped(time, here, map, scheduler, &mut self.events);
+                        } else {
+                            // Every dock is empty
+                            trip.walk_instead_of_biking(
+                                time,
+                                here,
+                                map,
+                                scheduler,
+                                &mut self.events,
+                            );
+                        }
+                        return;
+                    }
+                    (p, Some(id))
+                } else {
+                    (p, None)
+                }
+            }
             _ => unreachable!(),
         };
 
-        let end = drive_to.goal_pos(map);
-        let path = if let Some(p) = map.pathfind(PathRequest {
-            start: driving_pos,
-            end,
-            can_use_bus_lanes: false,
-            can_use_bike_lanes: true,
-        }) {
-            p
-        } else {
-            println!(
-                "Aborting a trip because no path for the bike portion! {:?} to {:?}",
-                driving_pos, end
-            );
+        // Bikeshare bikes have to be returned to a dock. People with their own bike use a rack if
+        // there's one close enough, and otherwise leave it on the sidewalk.
+        let parking = match drive_to {
+            DrivingGoal::ParkNear(b) => {
+                let pt = map.get_b(b).front_path.sidewalk.pt(map);
+                if dock.is_some() {
+                    bike_parking.closest_with_room(pt, BikeParkingType::Bikeshare, None, map)
+                } else {
+                    bike_parking.closest_with_room(
+                        pt,
+                        BikeParkingType::Rack,
+                        Some(MAX_DIST_TO_BIKE_RACK),
+                        map,
+                    )
+                }
+            }
+            DrivingGoal::Border(_, _) => None,
+        };
+        let router = if let Some(r) =
+            parking.and_then(|id| route_bike_to_parking(time, driving_pos, id, map))
+        {
+            r
+        } else if dock.is_some() {
+            // Don't rent a bike that can't be returned anywhere.
+            trip.walk_instead_of_biking(time, here, map, scheduler, &mut self.events);
             return;
+        } else {
+            let end = drive_to.goal_pos(map);
+            let path = if let Some(p) = map.pathfind_at(
+                PathRequest {
+                    start: driving_pos,
+                    end,
+                    can_use_bus_lanes: false,
+                    can_use_bike_lanes: true,
+                },
+                time,
+            ) {
+                p
+            } else {
+                println!(
+                    "Aborting a trip because no path for the bike portion! {:?} to {:?}",
+                    driving_pos, end
+                );
+                self.events.push(Event::TripAborted(trip.id));
+                return;
+            };
+            drive_to.make_router(path, map, vehicle.vehicle_type)
         };
 
-        let router = drive_to.make_router(path, map, vehicle.vehicle_type);
+        // Only take the bike once it's definitely going somewhere.
+        if let Some(id) = dock {
+            bike_parking.take_bike(id);
+        } else if let Some(p) = trip.person {
+            if let Some(rack) = self.people[p.0].bike_at.take() {
+                bike_parking.take_bike(rack);
+            }
+        }
         scheduler.push(
             time,
             Command::SpawnCar(CreateCar::for_appearing(
@@ -190,20 +371,71 @@ impl TripManager {
         bike: CarID,
         bike_rack: SidewalkSpot,
         map: &Map,
+        bike_parking: &mut BikeParkingSimState,
         scheduler: &mut Scheduler,
     ) {
+        self.events.push(Event::AgentDespawned(AgentID::Car(bike)));
+        let trip = &mut self.trips[self.active_trip_mode.remove(&AgentID::Car(bike)).unwrap().0];
+
+        if let SidewalkPOI::BikeParking(id, driving_pos) = bike_rack.connection {
+            let parking_type = map.get_bike_parking(id).parking_type;
+            if bike_parking.has_room(id) {
+                bike_parking.leave_bike(id);
+                if parking_type == BikeParkingType::Rack {
+                    if let Some(p) = trip.person {
+                        self.people[p.0].bike_at = Some(id);
+                    }
+                }
+            } else {
+                // Ride on to the next closest spot to the destination.
+                self.events.push(Event::BikeParkingFull(bike, id));
+                let (vehicle, goal) = match trip.legs[0] {
+                    TripLeg::Drive(ref vehicle, DrivingGoal::ParkNear(b)) => (vehicle.clone(), b),
+                    _ => unreachable!(),
+                };
+                let max_dist = match parking_type {
+                    BikeParkingType::Rack => Some(MAX_DIST_TO_BIKE_RACK),
+                    BikeParkingType::Bikeshare => None,
+                };
+                if let Some(router) = bike_parking
+                    .closest_with_room(
+                        map.get_b(goal).front_path.sidewalk.pt(map),
+                        parking_type,
+                        max_dist,
+                        map,
+                    )
+                    .and_then(|next| route_bike_to_parking(time, driving_pos, next, map))
+                {
+                    scheduler.push(
+                        time,
+                        Command::SpawnCar(CreateCar::for_appearing(
+                            vehicle,
+                            driving_pos,
+                            router,
+                            trip.id,
+                        )),
+                    );
+                    return;
+                }
+                // Everything reachable is full. People leave their own bike on the sidewalk here,
+                // but a bikeshare bike is lost to the system.
+                if parking_type == BikeParkingType::Bikeshare {
+                    self.events
+                        .push(Event::BikeAbandoned(bike, bike_rack.sidewalk_pos.lane()));
+                }
+            }
+        }
+
         self.events.push(Event::BikeStoppedAtSidewalk(
             bike,
             bike_rack.sidewalk_pos.lane(),
         ));
-        let trip = &mut self.trips[self.active_trip_mode.remove(&AgentID::Car(bike)).unwrap().0];
-
         match trip.legs.pop_front() {
             Some(TripLeg::Drive(vehicle, DrivingGoal::ParkNear(_))) => assert_eq!(vehicle.id, bike),
             _ => unreachable!(),
         };
 
-        trip.spawn_ped(time, bike_rack, map, scheduler);
+        trip.spawn_ped(time, bike_rack, map, scheduler, &mut self.events);
     }
 
     pub fn ped_reached_building(
@@ -212,8 +444,11 @@ impl TripManager {
         ped: PedestrianID,
         bldg: BuildingID,
         map: &Map,
+        scheduler: &mut Scheduler,
     ) {
         self.events.push(Event::PedReachedBuilding(ped, bldg));
+        self.events
+            .push(Event::AgentDespawned(AgentID::Pedestrian(ped)));
         let trip = &mut self.trips[self
             .active_trip_mode
             .remove(&AgentID::Pedestrian(ped))
@@ -223,6 +458,12 @@ impl TripManager {
         assert!(trip.legs.is_empty());
         assert!(!trip.finished_at.is_some());
         trip.finished_at = Some(time);
+
+        if let Some(p) = trip.person {
+            let person = &mut self.people[p.0];
+            person.bldg = Some(bldg);
+            person.ready_for_next_trip(time, scheduler);
+        }
     }
 
     // If true, the pedestrian boarded a bus immediately.
@@ -269,6 +510,8 @@ impl TripManager {
         map: &Map,
         scheduler: &mut Scheduler,
     ) {
+        self.events
+            .push(Event::AgentDespawned(AgentID::Pedestrian(ped)));
         let trip = &mut self.trips[self
             .active_trip_mode
             .remove(&AgentID::Pedestrian(ped))
@@ -279,7 +522,7 @@ impl TripManager {
             _ => unreachable!(),
         };
 
-        trip.spawn_ped(time, start, map, scheduler);
+        trip.spawn_ped(time, start, map, scheduler, &mut self.events);
     }
 
     pub fn ped_reached_border(
@@ -290,6 +533,8 @@ impl TripManager {
         map: &Map,
     ) {
         self.events.push(Event::PedReachedBorder(ped, i));
+        self.events
+            .push(Event::AgentDespawned(AgentID::Pedestrian(ped)));
         let trip = &mut self.trips[self
             .active_trip_mode
             .remove(&AgentID::Pedestrian(ped))
@@ -303,6 +548,7 @@ impl TripManager {
 
     pub fn car_or_bike_reached_border(&mut self, time: Duration, car: CarID, i: IntersectionID) {
         self.events.push(Event::CarOrBikeReachedBorder(car, i));
+        self.events.push(Event::AgentDespawned(AgentID::Car(car)));
         let trip = &mut self.trips[self.active_trip_mode.remove(&AgentID::Car(car)).unwrap().0];
         match trip.legs.pop_front().unwrap() {
             TripLeg::Drive(_, DrivingGoal::Border(int, _)) => assert_eq!(i, int),
@@ -312,6 +558,7 @@ impl TripManager {
                     "Aborting trip {}, because {} couldn't find parking and got stuck",
                     trip.id, car
                 );
+                self.events.push(Event::TripAborted(trip.id));
                 return;
             }
         };
@@ -347,11 +594,55 @@ impl TripManager {
     pub fn tooltip_lines(&self, id: AgentID) -> Vec<String> {
         // Only called for agents that _should_ have trips
         let trip = &self.trips[self.active_trip_mode[&id].0];
-        vec![format!(
+        let mut lines = vec![format!(
             "{} has goal {:?}",
             trip.id,
             trip.legs.back().unwrap()
-        )]
+        )];
+        if let Some(p) = trip.person {
+            let person = &self.people[p.0];
+            lines.push(format!(
+                "{} lives at {}, trip {} of {} today",
+                p,
+                person.home,
+                person.trips.len(),
+                person.trips.len() + person.schedule.len()
+            ));
+        }
+        lines
+    }
+
+    pub fn get_person(&self, id: PersonID) -> Option<&Person> {
+        self.people.get(id.0)
+    }
+
+    pub fn get_all_people(&self) -> &Vec<Person> {
+        &self.people
+    }
+
+    pub fn trip_to_person(&self, id: TripID) -> Option<PersonID> {
+        self.trips.get(id.0)?.person
+    }
+
+    // When each trip the person has started so far began and finished
+    pub fn person_trip_times(&self, id: PersonID) -> Vec<(TripID, Duration, Option<Duration>)> {
+        self.people[id.0]
+            .trips
+            .iter()
+            .map(|t| {
+                let trip = &self.trips[t.0];
+                (trip.id, trip.spawned_at, trip.finished_at)
+            })
+            .collect()
+    }
+
+    // Not including buses
+    pub fn get_all_trip_times(&self) -> Vec<(TripID, Duration, Option<Duration>)> {
+        self.trips
+            .iter()
+            .filter(|t| !t.is_bus_trip())
+            .map(|t| (t.id, t.spawned_at, t.finished_at))
+            .collect()
     }
 
     // Not including buses
@@ -360,7 +651,12 @@ impl TripManager {
     }
 
     pub fn is_done(&self) -> bool {
+        // People whose last trip was aborted are stuck out somewhere and won't go anywhere else.
         self.num_active_trips() == 0
+            && self
+                .people
+                .iter()
+                .all(|p| p.bldg.is_none() || p.schedule.is_empty())
     }
 
     pub fn collect_events(&mut self) -> Vec<Event> {
@@ -374,6 +670,7 @@ struct Trip {
     spawned_at: Duration,
     finished_at: Option<Duration>,
     legs: VecDeque<TripLeg>,
+    person: Option<PersonID>,
 }
 
 impl Trip {
@@ -385,24 +682,35 @@ impl Trip {
             }
     }
 
-    fn spawn_ped(&self, time: Duration, start: SidewalkSpot, map: &Map, scheduler: &mut Scheduler) {
+    fn spawn_ped(
+        &self,
+        time: Duration,
+        start: SidewalkSpot,
+        map: &Map,
+        scheduler: &mut Scheduler,
+        events: &mut Vec<Event>,
+    ) {
         let (ped, speed, walk_to) = match self.legs[0] {
             TripLeg::Walk(ped, speed, ref to) => (ped, speed, to.clone()),
             _ => unreachable!(),
         };
 
-        let path = if let Some(p) = map.pathfind(PathRequest {
-            start: start.sidewalk_pos,
-            end: walk_to.sidewalk_pos,
-            can_use_bus_lanes: false,
-            can_use_bike_lanes: false,
-        }) {
+        let path = if let Some(p) = map.pathfind_at(
+            PathRequest {
+                start: start.sidewalk_pos,
+                end: walk_to.sidewalk_pos,
+                can_use_bus_lanes: false,
+                can_use_bike_lanes: false,
+            },
+            time,
+        ) {
             p
         } else {
             println!(
                 "Aborting a trip because no path for the walking portion! {:?} to {:?}",
                 start, walk_to
             );
+            events.push(Event::TripAborted(self.id));
             return;
         };
 
@@ -419,11 +727,36 @@ impl Trip {
         );
     }
 
-    fn assert_walking_leg(&mut self, ped: PedestrianID, goal: SidewalkSpot) {
+    // The bike leg can't happen, so walk the rest of the way from here.
+    fn walk_instead_of_biking(
+        &mut self,
+        time: Duration,
+        start: SidewalkSpot,
+        map: &Map,
+        scheduler: &mut Scheduler,
+        events: &mut Vec<Event>,
+    ) {
         match self.legs.pop_front() {
-            Some(TripLeg::Walk(p, _, spot)) => {
+            Some(TripLeg::Drive(ref vehicle, _)) => {
+                assert_eq!(vehicle.vehicle_type, VehicleType::Bike)
+            }
+            _ => unreachable!(),
+        };
+        if self.legs.is_empty() {
+            // The bike was going to leave the map, and there's no walking to a border from here.
+            events.push(Event::TripAborted(self.id));
+            return;
+        }
+        self.spawn_ped(time, start, map, scheduler, events);
+    }
+
+    // Returns the walking speed, in case the pedestrian has to walk somewhere else.
+    fn assert_walking_leg(&mut self, ped: PedestrianID, goal: SidewalkSpot) -> Speed {
+        match self.legs.pop_front() {
+            Some(TripLeg::Walk(p, speed, spot)) => {
                 assert_eq!(ped, p);
                 assert_eq!(goal, spot);
+                speed
             }
             _ => unreachable!(),
         }
@@ -439,3 +772,197 @@ pub enum TripLeg {
     RideBus(PedestrianID, BusRouteID, BusStopID),
     ServeBusRoute(CarID, BusRouteID),
 }
+
+#[derive(Clone, Copy, Serialize, Deserialize, Debug, PartialEq)]
+pub enum TripMode {
+    Drive,
+    Bike,
+    // Rent a bike from the nearest dock and return it to the one closest to the destination
+    Bikeshare,
+    Transit,
+    Walk,
+}
+
+// One entry in somebody's daily schedule. It starts from wherever the previous trip ended.
+#[derive(Clone, Serialize, Deserialize, Debug, PartialEq)]
+pub struct PersonTrip {
+    // The person leaves at this time, or as soon as they finish the previous trip
+    pub depart: Duration,
+    pub to: BuildingID,
+    pub mode: TripMode,
+}
+
+#[derive(Clone, Serialize, Deserialize, Debug, PartialEq)]
+pub struct PersonSpec {
+    pub home: BuildingID,
+    // A car already parked somewhere
+    pub car: Option<CarID>,
+    pub ped_speed: Speed,
+    pub bike: VehicleSpec,
+    // Somebody without a car who needs to drive gets a car like this
+    pub car_spec: VehicleSpec,
+    pub schedule: Vec<PersonTrip>,
+}
+
+#[derive(Serialize, Deserialize, PartialEq, Debug)]
+pub struct Person {
+    pub id: PersonID,
+    pub home: BuildingID,
+    // Where the person is between trips. None while they're out.
+    pub bldg: Option<BuildingID>,
+    // Stays wherever the person last parked it
+    pub car: Option<CarID>,
+    // The same bike every time
+    pub bike: CarID,
+    // The rack where the bike is locked up, if it made it to one last time
+    pub bike_at: Option<BikeParkingID>,
+    ped_speed: Speed,
+    bike_spec: VehicleSpec,
+    car_spec: VehicleSpec,
+    // Every trip started so far, in order
+    pub trips: Vec<TripID>,
+    schedule: VecDeque<PersonTrip>,
+}
+
+impl Person {
+    fn ready_for_next_trip(&self, time: Duration, scheduler: &mut Scheduler) {
+        if let Some(next) = self.schedule.front() {
+            scheduler.push(next.depart.max(time), Command::StartPersonTrip(self.id));
+        }
+    }
+
+    // Falls back to walking when the planned mode doesn't work out.
+    fn trip_spec(
+        &self,
+        from: BuildingID,
+        plan: &PersonTrip,
+        map: &Map,
+        parking: &ParkingSimState,
+        bike_parking: &BikeParkingSimState,
+    ) -> TripSpec {
+        let start = SidewalkSpot::building(from, map);
+        let walking_goal = SidewalkSpot::building(plan.to, map);
+        let walk = TripSpec::JustWalking {
+            start: start.clone(),
+            goal: walking_goal.clone(),
+            ped_speed: self.ped_speed,
+        };
+
+        match plan.mode {
+            TripMode::Drive => {
+                let goal = DrivingGoal::ParkNear(plan.to);
+                match self.car {
+                    Some(car) => {
+                        if let Some(parked_car) = parking.get_parked_car(car) {
+                            TripSpec::UsingParkedCar {
+                                start,
+                                spot: parked_car.spot,
+                                goal,
+                                ped_speed: self.ped_speed,
+                            }
+                        } else {
+                            // The car didn't make it back to a parking spot last time.
+                            walk
+                        }
+                    }
+                    None => {
+                        let lane = map.find_driving_lane_near_building(from);
+                        if map.get_l(lane).length() > self.car_spec.length {
+                            TripSpec::CarAppearing {
+                                start_pos: Position::new(lane, self.car_spec.length),
+                                vehicle_spec: self.car_spec.clone(),
+                                goal,
+                                ped_speed: self.ped_speed,
+                            }
+                        } else {
+                            walk
+                        }
+                    }
+                }
+            }
+            TripMode::Bike => {
+                // A bike locked up by the last place they rode to stays there.
+                let bike_nearby = self.bike_at.map_or(true, |rack| {
+                    map.get_bike_parking(rack)
+                        .sidewalk_pos
+                        .pt(map)
+                        .dist_to(start.sidewalk_pos.pt(map))
+                        <= MAX_DIST_TO_BIKE_RACK
+                });
+                if bike_nearby && can_bike_between(from, plan.to, map) {
+                    TripSpec::UsingBike {
+                        start,
+                        vehicle: self.bike_spec.clone(),
+                        goal: DrivingGoal::ParkNear(plan.to),
+                        ped_speed: self.ped_speed,
+                    }
+                } else {
+                    walk
+                }
+            }
+            TripMode::Bikeshare => {
+                match bike_parking.closest_with_bike(start.sidewalk_pos.pt(map), map) {
+                    Some(dock) if can_bike_to(plan.to, map) => TripSpec::UsingBikeshare {
+                        start,
+                        dock,
+                        vehicle: self.bike_spec.clone(),
+                        goal: DrivingGoal::ParkNear(plan.to),
+                        ped_speed: self.ped_speed,
+                    },
+                    _ => walk,
+                }
+            }
+            TripMode::Transit => {
+                if let Some((stop1, stop2, route)) =
+                    map.should_use_transit(start.sidewalk_pos, walking_goal.sidewalk_pos)
+                {
+                    TripSpec::UsingTransit {
+                        start,
+                        route,
+                        stop1,
+                        stop2,
+                        goal: walking_goal,
+                        ped_speed: self.ped_speed,
+                    }
+                } else {
+                    walk
+                }
+            }
+            TripMode::Walk => walk,
+        }
+    }
+}
+
+// Bikes can only start and end on sidewalks next to a road that bikes can use.
+fn can_bike_between(from: BuildingID, to: BuildingID, map: &Map) -> bool {
+    let start_at = map.get_b(from).sidewalk();
+    start_at != map.get_b(to).sidewalk()
+        && map
+            .get_parent(start_at)
+            .sidewalk_to_bike(start_at)
+            .is_some()
+        && can_bike_to(to, map)
+}
+
+fn can_bike_to(to: BuildingID, map: &Map) -> bool {
+    let end_at = map.get_b(to).sidewalk();
+    map.get_parent(end_at).sidewalk_to_bike(end_at).is_some()
+}
+
+fn route_bike_to_parking(
+    time: Duration,
+    start: Position,
+    id: BikeParkingID,
+    map: &Map,
+) -> Option<Router> {
+    let path = map.pathfind_at(
+        PathRequest {
+            start,
+            end: map.get_bike_parking(id).driving_pos,
+            can_use_bus_lanes: false,
+            can_use_bike_lanes: true,
+        },
+        time,
+    )?;
+    Some(Router::bike_to_parking(path, id, map))
+}
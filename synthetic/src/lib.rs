@@ -143,6 +143,10 @@ impl Road {
             LaneType::Parking => Color::grey(0.2),
             LaneType::Sidewalk => Color::grey(0.8),
             LaneType::Biking => Color::rgb(15, 125, 75),
+            LaneType::TwoWayBiking => Color::rgb(10, 95, 55),
+            LaneType::SharedUse => Color::rgb(170, 190, 170),
+            LaneType::CenterTurn => Color::BLACK,
+            LaneType::BusAndBike => Color::rgb(190, 110, 76),
         }
     }
 }
@@ -221,6 +225,8 @@ impl Model {
                     IntersectionType::TrafficSignal => Color::GREEN,
                     IntersectionType::StopSign => Color::RED,
                     IntersectionType::Border => Color::BLUE,
+                    IntersectionType::Uncontrolled => Color::YELLOW,
+                    IntersectionType::Roundabout => Color::PURPLE,
                 }
             };
             g.draw_circle(color, &i.circle());
@@ -352,6 +358,7 @@ impl Model {
                     elevation: Distance::ZERO,
                     intersection_type: i.intersection_type,
                     label: i.label.clone(),
+                    osm_node_id: None,
                 },
             );
         }
@@ -364,9 +371,11 @@ impl Model {
             map.buildings.push(raw_data::Building {
                 // TODO Duplicate points :(
                 points: b.polygon().points().iter().map(|p| pt(*p)).collect(),
+                inner_rings: Vec::new(),
                 osm_tags,
                 osm_way_id: idx as i64,
                 num_residential_units: None,
+                landuse: None,
             });
         }
 
@@ -481,7 +490,9 @@ impl Model {
                     IntersectionType::StopSign
                 }
             }
-            IntersectionType::Border => IntersectionType::StopSign,
+            IntersectionType::Border
+            | IntersectionType::Uncontrolled
+            | IntersectionType::Roundabout => IntersectionType::StopSign,
         };
     }
 
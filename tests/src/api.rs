@@ -0,0 +1,130 @@
+use crate::runner::TestRunner;
+use abstutil::Timer;
+use geom::Duration;
+use headless::api::{Request, Response, Server};
+use map_model::{BuildingID, IntersectionID, IntersectionType, LaneType, PermanentMapEdits};
+use sim::{Scenario, SidewalkSpot, SimFlags, TripSpec};
+
+pub fn run(t: &mut TestRunner) {
+    t.run_slow("api_handles_requests", |_| {
+        let (map, sim, mut rng) =
+            SimFlags::for_test("api_handles_requests").load(None, &mut Timer::throwaway());
+
+        // The server owns the map, so set up everything that refers to it first.
+        let mut walk = |from: usize, to: usize| {
+            (
+                Duration::ZERO,
+                TripSpec::JustWalking {
+                    start: SidewalkSpot::building(BuildingID(from), &map),
+                    goal: SidewalkSpot::building(BuildingID(to), &map),
+                    ped_speed: Scenario::rand_ped_speed(&mut rng),
+                },
+            )
+        };
+        // The second trip doesn't go anywhere, so neither should get scheduled.
+        let bad_trips = vec![walk(0, 1), walk(2, 2)];
+        let good_trips = vec![walk(0, 1)];
+        let past_trips = vec![walk(1, 0)];
+
+        let no_op = PermanentMapEdits::to_permanent(map.get_edits(), &map);
+        let mut wrong_map = PermanentMapEdits::to_permanent(map.get_edits(), &map);
+        wrong_map.map_name = "somewhere_else".to_string();
+        let mut edits = map.get_edits().clone();
+        edits.edits_name = "bus_lane".to_string();
+        let lane = map
+            .all_lanes()
+            .iter()
+            .find(|l| l.lane_type == LaneType::Driving && !map.is_lane_removed(l.id))
+            .unwrap()
+            .id;
+        edits.lane_overrides.insert(lane, LaneType::Bus);
+        let bus_lane = PermanentMapEdits::to_permanent(&edits, &map);
+        let mut edits = map.get_edits().clone();
+        edits.edits_name = "new_signal".to_string();
+        let stop_sign = map
+            .all_intersections()
+            .iter()
+            .find(|i| i.intersection_type == IntersectionType::StopSign)
+            .unwrap()
+            .id;
+        edits
+            .intersection_type_overrides
+            .insert(stop_sign, IntersectionType::TrafficSignal);
+        let new_signal = PermanentMapEdits::to_permanent(&edits, &map);
+
+        let mut server = Server::new(map, sim, rng);
+        assert_eq!(time(server.handle(Request::GetTime)), Duration::ZERO);
+
+        assert!(server.handle(Request::SpawnTrips(bad_trips)).is_err());
+        assert_eq!(num_trips(&mut server), 0);
+        match server.handle(Request::SpawnTrips(good_trips)) {
+            Ok(Response::Spawned(ids)) => {
+                assert_eq!(ids.len(), 1);
+                assert!(ids[0].0.is_some());
+                assert!(ids[0].1.is_none());
+            }
+            x => panic!("{:?}", x),
+        }
+        assert_eq!(num_trips(&mut server), 1);
+
+        assert_eq!(
+            time(server.handle(Request::Step(Duration::seconds(30.0)))),
+            Duration::seconds(30.0)
+        );
+        assert!(server
+            .handle(Request::RunUntil(Duration::seconds(10.0)))
+            .is_err());
+        assert!(server
+            .handle(Request::Step(Duration::seconds(-10.0)))
+            .is_err());
+        assert!(server.handle(Request::SpawnTrips(past_trips)).is_err());
+        match server.handle(Request::GetAgents) {
+            Ok(Response::Agents(_)) => {}
+            x => panic!("{:?}", x),
+        }
+        assert!(server
+            .handle(Request::GetIntersection(IntersectionID(usize::max_value())))
+            .is_err());
+
+        // Edits that don't change anything leave the simulation alone.
+        match server.handle(Request::ApplyEdits(no_op)) {
+            Ok(Response::EditsApplied { sim_reset }) => assert!(!sim_reset),
+            x => panic!("{:?}", x),
+        }
+        assert_eq!(
+            time(server.handle(Request::GetTime)),
+            Duration::seconds(30.0)
+        );
+        assert!(server.handle(Request::ApplyEdits(wrong_map)).is_err());
+        // Intersection changes apply to the running simulation.
+        match server.handle(Request::ApplyEdits(new_signal)) {
+            Ok(Response::EditsApplied { sim_reset }) => assert!(!sim_reset),
+            x => panic!("{:?}", x),
+        }
+        assert_eq!(
+            time(server.handle(Request::GetTime)),
+            Duration::seconds(30.0)
+        );
+        // Changing a lane type doesn't touch road geometry, but still starts over.
+        match server.handle(Request::ApplyEdits(bus_lane)) {
+            Ok(Response::EditsApplied { sim_reset }) => assert!(sim_reset),
+            x => panic!("{:?}", x),
+        }
+        assert_eq!(time(server.handle(Request::GetTime)), Duration::ZERO);
+        assert_eq!(num_trips(&mut server), 0);
+    });
+}
+
+fn time(result: Result<Response, String>) -> Duration {
+    match result {
+        Ok(Response::Time(t)) => t,
+        x => panic!("{:?}", x),
+    }
+}
+
+fn num_trips(server: &mut Server) -> usize {
+    match server.handle(Request::GetTrips) {
+        Ok(Response::Trips(trips)) => trips.len(),
+        x => panic!("{:?}", x),
+    }
+}
@@ -1,5 +1,5 @@
 use crate::runner::TestRunner;
-use geom::{Duration, Line, PolyLine, Pt2D};
+use geom::{Duration, Line, PolyLine, Polygon, Pt2D};
 
 #[allow(clippy::unreadable_literal)]
 pub fn run(t: &mut TestRunner) {
@@ -54,6 +54,30 @@ pub fn run(t: &mut TestRunner) {
             Some(Duration::seconds(3723.5))
         );
     });
+
+    t.run_fast("polygon_with_hole", |_| {
+        let square = |x1: f64, y1: f64, x2: f64, y2: f64| {
+            vec![
+                Pt2D::new(x1, y1),
+                Pt2D::new(x2, y1),
+                Pt2D::new(x2, y2),
+                Pt2D::new(x1, y2),
+            ]
+        };
+        let poly = Polygon::with_holes(
+            &square(0.0, 0.0, 10.0, 10.0),
+            &vec![square(4.0, 4.0, 6.0, 6.0)],
+        );
+
+        assert!(poly.contains_pt(Pt2D::new(1.0, 1.0)));
+        assert!(poly.contains_pt(Pt2D::new(8.0, 5.0)));
+        assert!(!poly.contains_pt(Pt2D::new(5.0, 5.0)));
+        assert_eq!(poly.inner_rings().len(), 1);
+        assert_eq!(poly.outer_ring().len(), 4);
+
+        // The triangles should cover everything except the hole.
+        assert!((poly.area() - 96.0).abs() < 0.001);
+    });
 }
 
 // TODO test that shifting lines and polylines is a reversible operation
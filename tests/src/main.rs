@@ -1,5 +1,7 @@
+mod api;
 mod geom;
 mod map_conversion;
+mod map_edits;
 mod parking;
 mod runner;
 mod sim_completion;
@@ -12,8 +14,10 @@ use structopt::StructOpt;
 fn main() {
     let mut t = runner::TestRunner::new(runner::Flags::from_args());
 
+    api::run(t.suite("api"));
     geom::run(t.suite("geom"));
     map_conversion::run(t.suite("map_conversion"));
+    map_edits::run(t.suite("map_edits"));
     parking::run(t.suite("parking"));
     sim_completion::run(t.suite("sim_completion"));
     sim_determinism::run(t.suite("sim_determinism"));
@@ -1,13 +1,18 @@
 use crate::runner::TestRunner;
 use abstutil;
 use convert_osm;
+use geom::{Distance, GPSBounds, Line, LonLat, Polygon, Pt2D};
 use map_model;
+use map_model::raw_data::{StableIntersectionID, StableRoadID};
+use map_model::{BuildingCapacity, BuildingType, IntersectionType, MergeReason, MergedRoad};
+use std::collections::{BTreeMap, HashMap, HashSet};
 
 pub fn run(t: &mut TestRunner) {
     t.run_slow("convert_osm_twice", |_| {
         let flags = convert_osm::Flags {
             osm: "../data/input/montlake.osm".to_string(),
-            elevation: "../data/input/N47W122.hgt".to_string(),
+            elevation: "../data/input/".to_string(),
+            profile: "".to_string(),
             traffic_signals: "../data/input/traffic_signals.kml".to_string(),
             residential_buildings: "../data/input/residential_buildings.kml".to_string(),
             parking_shapes: "../data/shapes/blockface".to_string(),
@@ -29,6 +34,147 @@ pub fn run(t: &mut TestRunner) {
         }
     });
 
+    t.run_slow("xml_and_pbf_match", |_| {
+        let convert = |osm: &str| {
+            let flags = convert_osm::Flags {
+                osm: osm.to_string(),
+                elevation: "".to_string(),
+                profile: "".to_string(),
+                traffic_signals: "".to_string(),
+                residential_buildings: "".to_string(),
+                parking_shapes: "".to_string(),
+                gtfs: "".to_string(),
+                neighborhoods: "".to_string(),
+                clip: "../data/polygons/montlake.poly".to_string(),
+                output: "xml_and_pbf_match".to_string(),
+                fast_dev: true,
+            };
+            let map = convert_osm::convert(&flags, &mut abstutil::Timer::throwaway());
+            // The two formats list things in different orders, so the stable IDs won't match.
+            let mut roads: Vec<String> = map
+                .roads
+                .values()
+                .map(|r| format!("{} {:?} {:?}", r.osm_way_id, r.points, r.osm_tags))
+                .collect();
+            roads.sort();
+            let mut buildings: Vec<String> = map
+                .buildings
+                .iter()
+                .map(|b| format!("{} {:?} {:?}", b.osm_way_id, b.points, b.osm_tags))
+                .collect();
+            buildings.sort();
+            let mut areas: Vec<String> = map
+                .areas
+                .iter()
+                .map(|a| format!("{} {:?} {:?}", a.osm_id, a.points, a.area_type))
+                .collect();
+            areas.sort();
+            (roads, buildings, areas)
+        };
+
+        let xml = convert("../data/input/montlake.osm");
+        let pbf = convert("../data/input/montlake.osm.pbf");
+        assert_eq!(xml.0, pbf.0, "roads differ");
+        assert_eq!(xml.1, pbf.1, "buildings differ");
+        assert_eq!(xml.2, pbf.2, "areas differ");
+    });
+
+    t.run_fast("clip_polyline", |_| {
+        let boundary = vec![
+            Pt2D::new(0.0, 0.0),
+            Pt2D::new(100.0, 0.0),
+            Pt2D::new(100.0, 100.0),
+            Pt2D::new(0.0, 100.0),
+            Pt2D::new(0.0, 0.0),
+        ];
+        let poly = Polygon::new(&boundary);
+        let lines: Vec<Line> = boundary
+            .windows(2)
+            .map(|pair| Line::new(pair[0], pair[1]))
+            .collect();
+
+        let inside = vec![
+            Pt2D::new(10.0, 10.0),
+            Pt2D::new(50.0, 20.0),
+            Pt2D::new(90.0, 90.0),
+        ];
+        let pieces = convert_osm::clip_polyline(&inside, &poly, &lines);
+        assert_eq!(pieces.len(), 1);
+        assert_eq!(pieces[0].pts, inside);
+        assert!(!pieces[0].starts_at_border && !pieces[0].ends_at_border);
+
+        let outside = vec![Pt2D::new(150.0, 10.0), Pt2D::new(150.0, 90.0)];
+        assert!(convert_osm::clip_polyline(&outside, &poly, &lines).is_empty());
+
+        // Starts inside, leaves through the right edge, and comes back in through the top.
+        let crossing = vec![
+            Pt2D::new(50.0, 50.0),
+            Pt2D::new(150.0, 50.0),
+            Pt2D::new(150.0, 150.0),
+            Pt2D::new(50.0, 150.0),
+            Pt2D::new(50.0, 80.0),
+        ];
+        let pieces = convert_osm::clip_polyline(&crossing, &poly, &lines);
+        assert_eq!(pieces.len(), 2);
+        let same = |pts: &Vec<Pt2D>, expected: Vec<Pt2D>| {
+            pts.len() == expected.len()
+                && pts
+                    .iter()
+                    .zip(expected)
+                    .all(|(pt1, pt2)| pt1.epsilon_eq(pt2))
+        };
+        assert!(same(
+            &pieces[0].pts,
+            vec![Pt2D::new(50.0, 50.0), Pt2D::new(100.0, 50.0)]
+        ));
+        assert!(!pieces[0].starts_at_border && pieces[0].ends_at_border);
+        assert!(same(
+            &pieces[1].pts,
+            vec![Pt2D::new(50.0, 100.0), Pt2D::new(50.0, 80.0)]
+        ));
+        assert!(pieces[1].starts_at_border && !pieces[1].ends_at_border);
+    });
+
+    t.run_fast("clip_polygon_straddling_boundary", |_| {
+        let square = |x1: f64, y1: f64, x2: f64, y2: f64| {
+            vec![
+                LonLat::new(x1, y1),
+                LonLat::new(x2, y1),
+                LonLat::new(x2, y2),
+                LonLat::new(x1, y2),
+                LonLat::new(x1, y1),
+            ]
+        };
+        let boundary = square(-122.31, 47.63, -122.30, 47.64);
+        // Half of this sticks out past the east edge, and so does half of its hole.
+        let outer = square(-122.302, 47.634, -122.298, 47.636);
+        let hole = square(-122.3005, 47.6345, -122.2995, 47.6355);
+
+        let mut all_pts = boundary.clone();
+        all_pts.extend(outer.clone());
+        let bounds = GPSBounds::from(&all_pts);
+        let boundary_pts = bounds.must_convert(&boundary);
+
+        let pieces = convert_osm::clip_polygon(&outer, &vec![hole.clone()], &boundary_pts, &bounds);
+        assert_eq!(pieces.len(), 1);
+        let (points, holes) = &pieces[0];
+        assert_eq!(points[0], *points.last().unwrap());
+        assert_eq!(holes.len(), 1);
+        // Points right on the edge might be a hair outside after converting back and forth.
+        for pt in points.iter().chain(holes.iter().flatten()) {
+            assert!(pt.longitude <= -122.30 + 1e-7, "{} sticks out", pt);
+        }
+
+        let full_area = Polygon::new(&bounds.must_convert(&outer)).area();
+        let clipped_area = Polygon::new(&bounds.must_convert(points)).area();
+        assert!(
+            (clipped_area / full_area - 0.5).abs() < 0.01,
+            "kept {} of {}",
+            clipped_area,
+            full_area
+        );
+    });
+
     t.run_slow("raw_to_map_twice", |_| {
         let map1 = map_model::Map::new(
             "../data/raw_maps/montlake.abst",
@@ -64,4 +210,640 @@ pub fn run(t: &mut TestRunner) {
         )
         .expect("small_seattle broke");
     });
+    t.run_slow("merge_short_roads", |_| {
+        let map = map_model::Map::new(
+            "../data/raw_maps/montlake.abst",
+            &mut abstutil::Timer::throwaway(),
+        )
+        .unwrap();
+        let remaining: HashSet<StableRoadID> =
+            map.all_roads().iter().map(|r| r.stable_id).collect();
+        let merged: HashMap<StableRoadID, &MergedRoad> =
+            map.get_merged_roads().iter().map(|m| (m.road, m)).collect();
+        for m in map.get_merged_roads() {
+            assert!(!remaining.contains(&m.road), "{:?} is still around", m);
+        }
+
+        // Dual carriageway crossings that used to be clusters of tiny intersections
+        for id in vec![311, 240, 91, 59, 389, 22] {
+            let m = merged
+                .get(&StableRoadID(id))
+                .unwrap_or_else(|| panic!("{} wasn't merged", StableRoadID(id)));
+            assert_eq!(m.reason, MergeReason::ShortRoad);
+        }
+
+        for r in map.all_roads() {
+            assert_ne!(r.src_i, r.dst_i, "{} is a loop", r.id);
+        }
+    });
+
+    t.run_slow("merged_roads_are_consistent", |_| {
+        for name in vec!["montlake", "23rd", "small_seattle"] {
+            let map = map_model::Map::new(
+                &format!("../data/raw_maps/{}.abst", name),
+                &mut abstutil::Timer::throwaway(),
+            )
+            .unwrap();
+            let remaining_roads: HashSet<StableRoadID> =
+                map.all_roads().iter().map(|r| r.stable_id).collect();
+            let remaining_intersections: HashSet<StableIntersectionID> = map
+                .all_intersections()
+                .iter()
+                .map(|i| i.stable_id)
+                .collect();
+
+            let mut accounted_for = 0;
+            for i in map.all_intersections() {
+                for m in map.get_merged_roads_into(i.id) {
+                    accounted_for += 1;
+                    if m.reason == MergeReason::ShortRoad {
+                        assert!(
+                            m.trimmed_length < Distance::meters(5.0),
+                            "{}: {:?} wasn't short",
+                            name,
+                            m
+                        );
+                    }
+                }
+            }
+            // Every merge should lead to exactly one surviving intersection.
+            assert_eq!(accounted_for, map.get_merged_roads().len(), "{}", name);
+
+            for m in map.get_merged_roads() {
+                assert!(
+                    !remaining_roads.contains(&m.road),
+                    "{}: {:?} is still around",
+                    name,
+                    m
+                );
+                assert!(
+                    !remaining_intersections.contains(&m.deleted_i),
+                    "{}: {:?} left its intersection behind",
+                    name,
+                    m
+                );
+            }
+            for r in map.all_roads() {
+                assert_ne!(r.src_i, r.dst_i, "{}: {} is a loop", name, r.id);
+            }
+        }
+    });
+
+    t.run_slow("roundabout_becomes_one_intersection", |_| {
+        // Drawn the way OSM does it: a counter-clockwise ring split into two ways, with four
+        // two-way roads entering.
+        let (lon, lat) = (-122.30, 47.64);
+        let mut osm = OsmFixture::new();
+        for idx in 0..8 {
+            let angle = (f64::from(idx) * 45.0).to_radians();
+            osm.node(
+                (idx + 1) as usize,
+                lon + 0.0003 * angle.cos(),
+                lat + 0.0002 * angle.sin(),
+            );
+        }
+        for (idx, (dx, dy)) in [(0.002, 0.0), (0.0, 0.0015), (-0.002, 0.0), (0.0, -0.0015)]
+            .iter()
+            .enumerate()
+        {
+            osm.node(11 + 2 * idx, lon + dx, lat + dy);
+        }
+        let ring = [("highway", "tertiary"), ("junction", "roundabout")];
+        let arm = [("highway", "residential")];
+        osm.way(100, &[1, 2, 3, 4, 5], &ring);
+        osm.way(101, &[5, 6, 7, 8, 1], &ring);
+        osm.way(102, &[1, 11], &arm);
+        osm.way(103, &[3, 13], &arm);
+        osm.way(104, &[5, 15], &arm);
+        osm.way(105, &[7, 17], &arm);
+
+        let raw = osm.convert(
+            "roundabout",
+            format!(
+                "{},{},{},{}",
+                lon - 0.003,
+                lat - 0.002,
+                lon + 0.003,
+                lat + 0.002
+            ),
+        );
+        let map = map_model::Map::create_from_raw(
+            "roundabout".to_string(),
+            raw,
+            &mut abstutil::Timer::throwaway(),
+        );
+
+        let roundabouts: Vec<&map_model::Intersection> = map
+            .all_intersections()
+            .iter()
+            .filter(|i| i.intersection_type == IntersectionType::Roundabout)
+            .collect();
+        assert_eq!(roundabouts.len(), 1);
+        assert_eq!(roundabouts[0].roads.len(), 4);
+        for r in map.all_roads() {
+            assert_ne!(
+                r.osm_tags.get("junction"),
+                Some(&"roundabout".to_string()),
+                "{} is part of the ring",
+                r.id
+            );
+        }
+        assert!(map
+            .get_merged_roads()
+            .iter()
+            .any(|m| m.reason == MergeReason::Roundabout));
+    });
+
+    t.run_slow("multipolygon_inner_rings", |_| {
+        let mut osm = OsmFixture::new();
+        // A road, so there's something to build a map around
+        osm.node(1, -122.31, 47.635);
+        osm.node(2, -122.29, 47.635);
+        osm.way(1, &[1, 2], &[("highway", "residential")]);
+
+        // Two parks, each with an outer ring split into two ways and a square hole. The second
+        // hole is missing a way.
+        for (park, dlon) in [(0, 0.0), (1, 0.005)].iter() {
+            let base = 100 * (park + 1);
+            let (x1, y1, x2, y2) = (-122.309 + dlon, 47.636, -122.305 + dlon, 47.639);
+            osm.node(base, x1, y1);
+            osm.node(base + 1, x2, y1);
+            osm.node(base + 2, x2, y2);
+            osm.node(base + 3, x1, y2);
+            let (x1, y1, x2, y2) = (-122.308 + dlon, 47.637, -122.306 + dlon, 47.638);
+            osm.node(base + 4, x1, y1);
+            osm.node(base + 5, x2, y1);
+            osm.node(base + 6, x2, y2);
+            osm.node(base + 7, x1, y2);
+
+            osm.way(base, &[base, base + 1, base + 2], &[]);
+            osm.way(base + 1, &[base + 2, base + 3, base], &[]);
+            if *park == 0 {
+                osm.way(
+                    base + 2,
+                    &[base + 4, base + 5, base + 6, base + 7, base + 4],
+                    &[],
+                );
+            } else {
+                osm.way(base + 2, &[base + 4, base + 5, base + 6], &[]);
+            }
+            osm.relation(
+                base,
+                &[(base, "outer"), (base + 1, "outer"), (base + 2, "inner")],
+                &[("type", "multipolygon"), ("leisure", "park")],
+            );
+        }
+
+        let raw = osm.convert("multipolygon", "-122.32,47.63,-122.28,47.65".to_string());
+        let holes: BTreeMap<i64, usize> = raw
+            .areas
+            .iter()
+            .map(|a| (a.osm_id, a.inner_rings.len()))
+            .collect();
+        assert_eq!(holes.get(&100), Some(&1));
+        assert_eq!(holes.get(&200), Some(&0));
+        for a in &raw.areas {
+            assert_eq!(a.points[0], *a.points.last().unwrap());
+            for ring in &a.inner_rings {
+                assert_eq!(ring[0], *ring.last().unwrap());
+            }
+        }
+    });
+
+    t.run_slow("buildings_inherit_landuse", |_| {
+        let mut osm = OsmFixture::new();
+        // A retail area with one building inside and one just outside
+        osm.node(1, -122.310, 47.630);
+        osm.node(2, -122.300, 47.630);
+        osm.node(3, -122.300, 47.640);
+        osm.node(4, -122.310, 47.640);
+        osm.way(100, &[1, 2, 3, 4, 1], &[("landuse", "retail")]);
+        for (idx, lon) in [-122.305, -122.295].iter().enumerate() {
+            let base = 10 * (idx + 1);
+            osm.node(base, *lon, 47.635);
+            osm.node(base + 1, *lon + 0.0002, 47.635);
+            osm.node(base + 2, *lon + 0.0002, 47.6352);
+            osm.node(base + 3, *lon, 47.6352);
+            osm.way(
+                200 + idx,
+                &[base, base + 1, base + 2, base + 3, base],
+                &[("building", "yes")],
+            );
+        }
+
+        let raw = osm.convert("landuse", "-122.32,47.62,-122.28,47.65".to_string());
+        let landuse: BTreeMap<i64, Option<String>> = raw
+            .buildings
+            .iter()
+            .map(|b| (b.osm_way_id, b.landuse.clone()))
+            .collect();
+        assert_eq!(landuse[&200], Some("retail".to_string()));
+        assert_eq!(landuse[&201], None);
+        for b in &raw.buildings {
+            assert!(!b.osm_tags.keys().any(|k| k.starts_with("abst:")));
+        }
+    });
+
+    t.run_fast("classify_buildings", |_| {
+        let classify = |units: Option<usize>, landuse: Option<&str>, tags: &[(&str, &str)]| {
+            let tags: BTreeMap<String, String> = tags
+                .iter()
+                .map(|(k, v)| (k.to_string(), v.to_string()))
+                .collect();
+            map_model::classify_building(units, landuse, &tags)
+        };
+        let yes = [("building", "yes")];
+
+        assert_eq!(classify(None, None, &yes), BuildingType::Unknown);
+        assert_eq!(classify(Some(4), None, &yes), BuildingType::Residence);
+        assert_eq!(
+            classify(None, None, &[("amenity", "parking")]),
+            BuildingType::ParkingGarage
+        );
+        assert_eq!(
+            classify(None, None, &[("building", "university")]),
+            BuildingType::School
+        );
+        assert_eq!(
+            classify(None, None, &[("amenity", "clinic")]),
+            BuildingType::Hospital
+        );
+        assert_eq!(
+            classify(
+                None,
+                None,
+                &[("building", "apartments"), ("shop", "bakery")]
+            ),
+            BuildingType::MixedUse
+        );
+        assert_eq!(
+            classify(None, None, &[("amenity", "restaurant")]),
+            BuildingType::Retail
+        );
+        assert_eq!(
+            classify(None, None, &[("building", "office")]),
+            BuildingType::Office
+        );
+
+        // Not every amenity is a shop.
+        assert_eq!(
+            classify(None, None, &[("amenity", "place_of_worship")]),
+            BuildingType::Unknown
+        );
+        assert_eq!(
+            classify(None, Some("residential"), &[("amenity", "library")]),
+            BuildingType::Residence
+        );
+
+        // The surrounding landuse only fills in for plain buildings.
+        assert_eq!(
+            classify(None, Some("residential"), &yes),
+            BuildingType::Residence
+        );
+        assert_eq!(classify(None, Some("retail"), &yes), BuildingType::Retail);
+        assert_eq!(
+            classify(None, Some("commercial"), &yes),
+            BuildingType::Office
+        );
+        assert_eq!(
+            classify(None, Some("industrial"), &yes),
+            BuildingType::Unknown
+        );
+        assert_eq!(
+            classify(None, Some("retail"), &[("building", "house")]),
+            BuildingType::Residence
+        );
+    });
+
+    t.run_fast("estimate_building_capacity", |_| {
+        // 200 square meters
+        let footprint = Polygon::rectangle(Pt2D::new(50.0, 50.0), 20.0, 10.0);
+        let capacity = |building_type: BuildingType, units: Option<usize>, levels: &str| {
+            let mut tags = BTreeMap::new();
+            tags.insert("building:levels".to_string(), levels.to_string());
+            map_model::estimate_building_capacity(building_type, units, &tags, &footprint)
+        };
+
+        assert_eq!(
+            capacity(BuildingType::Residence, None, "3"),
+            BuildingCapacity {
+                residential_units: 7,
+                residents: 15,
+                ..BuildingCapacity::default()
+            }
+        );
+        // Known units win over the footprint.
+        assert_eq!(
+            capacity(BuildingType::Residence, Some(3), "3"),
+            BuildingCapacity {
+                residential_units: 3,
+                residents: 6,
+                ..BuildingCapacity::default()
+            }
+        );
+        assert_eq!(
+            capacity(BuildingType::Retail, None, "1"),
+            BuildingCapacity {
+                jobs: 4,
+                visitors: 40,
+                ..BuildingCapacity::default()
+            }
+        );
+        // Retail on the ground floor, apartments on the other one
+        assert_eq!(
+            capacity(BuildingType::MixedUse, None, "2"),
+            BuildingCapacity {
+                residential_units: 2,
+                residents: 4,
+                jobs: 4,
+                visitors: 40,
+                ..BuildingCapacity::default()
+            }
+        );
+        // Missing or nonsense levels count as one.
+        assert_eq!(
+            capacity(BuildingType::Office, None, "0"),
+            capacity(BuildingType::Office, None, "lots")
+        );
+        assert_eq!(capacity(BuildingType::Office, None, "1").jobs, 8);
+        assert_eq!(
+            capacity(BuildingType::Unknown, None, "10"),
+            BuildingCapacity::default()
+        );
+    });
+
+    t.run_fast("srtm_tile_names", |_| {
+        assert_eq!(convert_osm::tile_name(-123, 47), "N47W123.hgt");
+        assert_eq!(convert_osm::tile_name(7, -1), "S01E007.hgt");
+        assert_eq!(convert_osm::tile_name(0, 0), "N00E000.hgt");
+
+        assert_eq!(
+            convert_osm::parse_tile_name("N47W123.hgt"),
+            Some((-123, 47))
+        );
+        assert_eq!(convert_osm::parse_tile_name("s01e007.hgt"), Some((7, -1)));
+        assert_eq!(convert_osm::parse_tile_name("X47W123.hgt"), None);
+        assert_eq!(convert_osm::parse_tile_name("N47W12"), None);
+        assert_eq!(convert_osm::parse_tile_name("NxxW123.hgt"), None);
+        for (lon, lat) in vec![(-123, 47), (12, -34), (-1, -1), (179, 59)] {
+            assert_eq!(
+                convert_osm::parse_tile_name(&convert_osm::tile_name(lon, lat)),
+                Some((lon, lat))
+            );
+        }
+    });
+
+    t.run_fast("srtm_interpolation", |_| {
+        // A 3-arcsecond tile that rises by a meter per sample going east and going north, so
+        // interpolating should be exact. One sample is void.
+        let dim = 1201;
+        let void = (300, 600);
+        let mut bytes = Vec::with_capacity(dim * dim * 2);
+        for row in 0..dim {
+            for col in 0..dim {
+                let value: i16 = if (col, row) == void {
+                    -32768
+                } else {
+                    (col + (dim - 1 - row)) as i16
+                };
+                bytes.extend_from_slice(&value.to_be_bytes());
+            }
+        }
+        // TODO tmp files
+        std::fs::write("N47W123.hgt", bytes).unwrap();
+        let elevation = convert_osm::Elevation::new(
+            "N47W123.hgt",
+            &Vec::new(),
+            &mut abstutil::Timer::throwaway(),
+        )
+        .unwrap();
+
+        let at = |col: f64, row: f64| {
+            elevation.get(-123.0 + col / 1200.0, 47.0 + (1200.0 - row) / 1200.0)
+        };
+        let close = |actual: f64, expected: f64| {
+            assert!(
+                (actual - expected).abs() < 1e-6,
+                "got {}, expected {}",
+                actual,
+                expected
+            )
+        };
+        // Right on samples
+        close(at(0.0, 1200.0), 0.0);
+        close(at(100.0, 200.0), 1100.0);
+        // Between samples
+        close(at(100.25, 200.5), 1099.75);
+        close(at(1000.9, 10.1), 2190.8);
+        // Halfway between the void sample and its east neighbor only uses the neighbor.
+        close(at(300.5, 600.0), 901.0);
+        // Off the tile is flat.
+        assert_eq!(elevation.get(-121.5, 47.5), 0.0);
+    });
+
+    t.run_fast("inner_rings_default_to_empty", |_| {
+        // Saved before buildings and areas had holes
+        let path = "old_building.json";
+        std::fs::write(
+            path,
+            r#"{"points": [{"longitude": -122.3, "latitude": 47.6}], "osm_tags": {}, "osm_way_id": 1, "num_residential_units": null}"#,
+        )
+        .unwrap();
+        let bldg: map_model::raw_data::Building = abstutil::read_json(path).unwrap();
+        assert!(bldg.inner_rings.is_empty());
+    });
+
+    t.run_slow("traffic_signals_from_osm_and_kml", |_| {
+        // A main road crossing two side streets, at junctions A and B about 150m apart
+        let (lon, lat) = (-122.30, 47.64);
+        let mut osm = OsmFixture::new();
+        osm.node(1, lon, lat);
+        osm.node(2, lon - 0.001, lat);
+        osm.node(3, lon + 0.002, lat);
+        osm.node(4, lon + 0.003, lat);
+        osm.node(5, lon, lat + 0.0007);
+        osm.node(6, lon, lat - 0.0007);
+        osm.node(7, lon + 0.002, lat + 0.0007);
+        osm.node(8, lon + 0.002, lat - 0.0007);
+        let road = [("highway", "residential")];
+        osm.way(100, &[2, 1, 3, 4], &road);
+        osm.way(101, &[5, 1, 6], &road);
+        osm.way(102, &[7, 3, 8], &road);
+        // OSM only knows about A, tagged on the approach a few meters away.
+        osm.tagged_node(20, lon + 0.0001, lat, &[("highway", "traffic_signals")]);
+
+        // The KML only knows about B, plus a signal nowhere near a road.
+        let kml = "traffic_signals_fixture.kml";
+        let placemark = |lon: f64, lat: f64| {
+            format!(
+                "<Placemark><Point><coordinates>{},{}</coordinates></Point></Placemark>\n",
+                lon, lat
+            )
+        };
+        std::fs::write(
+            kml,
+            format!(
+                "<?xml version=\"1.0\" encoding=\"UTF-8\"?>\n<kml><Document>\n{}{}</Document></kml>\n",
+                placemark(lon + 0.0022, lat),
+                placemark(lon - 0.0025, lat + 0.0015)
+            ),
+        )
+        .unwrap();
+
+        let mut flags = osm.flags(
+            "traffic_signals",
+            format!(
+                "{},{},{},{}",
+                lon - 0.004,
+                lat - 0.002,
+                lon + 0.004,
+                lat + 0.002
+            ),
+        );
+        flags.traffic_signals = kml.to_string();
+        let raw = convert_osm::convert(&flags, &mut abstutil::Timer::throwaway());
+
+        let mut signals: Vec<LonLat> = raw
+            .intersections
+            .values()
+            .filter(|i| i.intersection_type == IntersectionType::TrafficSignal)
+            .map(|i| i.point)
+            .collect();
+        signals.sort_by(|a, b| a.longitude.partial_cmp(&b.longitude).unwrap());
+        assert_eq!(signals.len(), 2, "{:?}", signals);
+        assert!(signals[0].gps_dist_meters(LonLat::new(lon, lat)) < Distance::meters(1.0));
+        assert!(signals[1].gps_dist_meters(LonLat::new(lon + 0.002, lat)) < Distance::meters(1.0));
+    });
+
+    t.run_fast("import_profiles", |_| {
+        let flags = |profile: &str, gtfs: &str, parking_shapes: &str| convert_osm::Flags {
+            osm: "".to_string(),
+            elevation: "".to_string(),
+            profile: profile.to_string(),
+            traffic_signals: "".to_string(),
+            residential_buildings: "".to_string(),
+            parking_shapes: parking_shapes.to_string(),
+            gtfs: gtfs.to_string(),
+            neighborhoods: "".to_string(),
+            clip: "".to_string(),
+            output: "".to_string(),
+            fast_dev: false,
+        };
+
+        // Nothing besides OSM
+        let bare = convert_osm::ImportProfile::new(&flags("", "", ""));
+        assert_eq!(bare.parking, convert_osm::ParkingSource::OsmTags);
+        assert!(bare.traffic_signals.is_none() && bare.gtfs.is_none());
+
+        // The sample profile describes the same datasets as import.sh used to pass in flags.
+        let seattle =
+            convert_osm::ImportProfile::new(&flags("../data/profiles/seattle.json", "", ""));
+        let seattle_flags = convert_osm::Flags {
+            traffic_signals: "../data/input/traffic_signals.kml".to_string(),
+            residential_buildings: "../data/input/residential_buildings.kml".to_string(),
+            neighborhoods: "../data/input/neighborhoods.geojson".to_string(),
+            ..flags(
+                "",
+                "../data/input/google_transit_2018_18_08",
+                "../data/shapes/blockface",
+            )
+        };
+        assert_eq!(seattle, convert_osm::ImportProfile::new(&seattle_flags));
+
+        // Flags override the profile
+        let overridden = convert_osm::ImportProfile::new(&flags(
+            "../data/profiles/seattle.json",
+            "other_gtfs",
+            "",
+        ));
+        assert_eq!(overridden.gtfs, Some("other_gtfs".to_string()));
+        assert_eq!(overridden.parking, seattle.parking);
+    });
+}
+
+// Builds up a tiny OSM XML file, for testing import on hand-drawn cases.
+struct OsmFixture {
+    xml: String,
+}
+
+impl OsmFixture {
+    fn new() -> OsmFixture {
+        OsmFixture { xml: String::new() }
+    }
+
+    fn node(&mut self, id: usize, lon: f64, lat: f64) {
+        self.xml.push_str(&format!(
+            "<node id=\"{}\" lon=\"{}\" lat=\"{}\"/>\n",
+            id, lon, lat
+        ));
+    }
+
+    fn tagged_node(&mut self, id: usize, lon: f64, lat: f64, tags: &[(&str, &str)]) {
+        self.xml.push_str(&format!(
+            "<node id=\"{}\" lon=\"{}\" lat=\"{}\">\n",
+            id, lon, lat
+        ));
+        self.tags(tags);
+        self.xml.push_str("</node>\n");
+    }
+
+    fn way(&mut self, id: usize, nodes: &[usize], tags: &[(&str, &str)]) {
+        self.xml.push_str(&format!("<way id=\"{}\">\n", id));
+        for n in nodes {
+            self.xml.push_str(&format!("<nd ref=\"{}\"/>\n", n));
+        }
+        self.tags(tags);
+        self.xml.push_str("</way>\n");
+    }
+
+    fn relation(&mut self, id: usize, ways: &[(usize, &str)], tags: &[(&str, &str)]) {
+        self.xml.push_str(&format!("<relation id=\"{}\">\n", id));
+        for (way, role) in ways {
+            self.xml.push_str(&format!(
+                "<member type=\"way\" ref=\"{}\" role=\"{}\"/>\n",
+                way, role
+            ));
+        }
+        self.tags(tags);
+        self.xml.push_str("</relation>\n");
+    }
+
+    fn tags(&mut self, tags: &[(&str, &str)]) {
+        for (k, v) in tags {
+            self.xml
+                .push_str(&format!("<tag k=\"{}\" v=\"{}\"/>\n", k, v));
+        }
+    }
+
+    fn convert(&self, name: &str, clip: String) -> map_model::raw_data::Map {
+        convert_osm::convert(&self.flags(name, clip), &mut abstutil::Timer::throwaway())
+    }
+
+    // Writes the OSM file and returns flags using only it, for callers to add more inputs.
+    fn flags(&self, name: &str, clip: String) -> convert_osm::Flags {
+        // TODO tmp files
+        let path = format!("{}.osm", name);
+        std::fs::write(
+            &path,
+            format!(
+                "<?xml version=\"1.0\" encoding=\"UTF-8\"?>\n<osm version=\"0.6\">\n{}</osm>\n",
+                self.xml
+            ),
+        )
+        .unwrap();
+
+        convert_osm::Flags {
+            osm: path,
+            elevation: "".to_string(),
+            profile: "".to_string(),
+            traffic_signals: "".to_string(),
+            residential_buildings: "".to_string(),
+            parking_shapes: "".to_string(),
+            gtfs: "".to_string(),
+            neighborhoods: "".to_string(),
+            clip,
+            output: name.to_string(),
+            fast_dev: false,
+        }
+    }
 }
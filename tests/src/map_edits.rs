@@ -0,0 +1,590 @@
+use crate::runner::TestRunner;
+use abstutil::Timer;
+use geom::{Distance, Duration, LonLat};
+use map_model::{
+    contraflow_bike_track, raw_data, Closure, ClosureTarget, IntersectionType, LaneID, LaneType,
+    Map, MapEdits, PermanentMapEdits, RoadID, RoadSpec, Traversable, TurnID,
+};
+use sim::SidewalkSpot;
+use std::collections::BTreeMap;
+
+pub fn run(t: &mut TestRunner) {
+    t.run_slow("merge_disjoint_edits", |_| {
+        let map = Map::new("../data/raw_maps/montlake.abst", &mut Timer::throwaway()).unwrap();
+        let base = MapEdits::new(map.get_name().to_string());
+        let (l1, l2) = (map.all_lanes()[1].id, map.all_lanes()[2].id);
+
+        let mut edits1 = base.clone();
+        edits1.edits_name = "alice".to_string();
+        edits1.lane_overrides.insert(l1, LaneType::Biking);
+
+        let mut edits2 = base.clone();
+        edits2.edits_name = "bob".to_string();
+        edits2.lane_overrides.insert(l2, LaneType::Bus);
+        edits2.closures.push(Closure {
+            target: ClosureTarget::Road(RoadID(3)),
+            start: Duration::minutes(60),
+            end: Duration::minutes(120),
+        });
+
+        let merged = edits1.merge(&edits2, &base, &map).unwrap();
+        assert_eq!(merged.lane_overrides.len(), 2);
+        assert_eq!(merged.closures.len(), 1);
+        assert!(edits1.diff(&edits1).is_empty());
+
+        let diff = edits1.diff(&merged);
+        assert_eq!(diff.lanes.into_iter().collect::<Vec<_>>(), vec![l2]);
+        assert_eq!(diff.closures_added.len(), 1);
+        assert!(diff.closures_removed.is_empty());
+        assert_eq!(edits1.describe_diff(&merged).len(), 2);
+    });
+
+    t.run_slow("merge_keeps_removals", |_| {
+        let map = Map::new("../data/raw_maps/montlake.abst", &mut Timer::throwaway()).unwrap();
+        let (l1, l2) = (map.all_lanes()[1].id, map.all_lanes()[2].id);
+        let mut base = MapEdits::new(map.get_name().to_string());
+        base.lane_overrides.insert(l1, LaneType::Biking);
+        base.closures.push(Closure {
+            target: ClosureTarget::Road(RoadID(3)),
+            start: Duration::minutes(60),
+            end: Duration::minutes(120),
+        });
+
+        // One side undoes everything from the base, the other adds something unrelated.
+        let edits1 = MapEdits::new(map.get_name().to_string());
+        let mut edits2 = base.clone();
+        edits2.lane_overrides.insert(l2, LaneType::Bus);
+
+        let merged = edits1.merge(&edits2, &base, &map).unwrap();
+        assert_eq!(
+            merged.lane_overrides.into_iter().collect::<Vec<_>>(),
+            vec![(l2, LaneType::Bus)]
+        );
+        assert!(merged.closures.is_empty());
+    });
+
+    t.run_slow("merge_conflicting_edits", |_| {
+        let map = Map::new("../data/raw_maps/montlake.abst", &mut Timer::throwaway()).unwrap();
+        let base = MapEdits::new(map.get_name().to_string());
+        let (l1, l2) = (map.all_lanes()[1].id, map.all_lanes()[2].id);
+
+        let mut edits1 = base.clone();
+        edits1.lane_overrides.insert(l1, LaneType::Biking);
+        edits1.lane_overrides.insert(l2, LaneType::Bus);
+
+        let mut edits2 = base.clone();
+        edits2.lane_overrides.insert(l1, LaneType::Parking);
+        edits2.lane_overrides.insert(l2, LaneType::Bus);
+
+        assert!(edits1.merge(&edits2, &base, &map).is_err());
+        assert!(edits1
+            .merge(&MapEdits::new("other_map".to_string()), &base, &map)
+            .is_err());
+
+        // Removing an override on one side and changing it on the other
+        let mut base = base.clone();
+        base.lane_overrides.insert(l1, LaneType::Bus);
+        let mut edits3 = base.clone();
+        edits3.lane_overrides.remove(&l1);
+        assert!(edits3.merge(&edits1, &base, &map).is_err());
+    });
+
+    t.run_slow("merge_lane_and_road_edits", |_| {
+        let map = Map::new("../data/raw_maps/montlake.abst", &mut Timer::throwaway()).unwrap();
+        let base = MapEdits::new(map.get_name().to_string());
+        let lane = &map.all_lanes()[10];
+
+        let mut edits1 = base.clone();
+        edits1.lane_overrides.insert(lane.id, LaneType::Bus);
+
+        let mut edits2 = base.clone();
+        let (mut fwd, back) = map.get_r(lane.parent).get_lane_types();
+        fwd.push(LaneType::Biking);
+        edits2
+            .road_overrides
+            .insert(lane.parent, RoadSpec { fwd, back });
+
+        assert!(edits1.merge(&edits2, &base, &map).is_err());
+        assert!(edits2.merge(&edits1, &base, &map).is_err());
+
+        // A lane that isn't on the map is a conflict, not a panic, and doesn't hide the others.
+        let missing = LaneID(map.all_lanes().len());
+        let mut edits3 = base.clone();
+        edits3.lane_overrides.insert(missing, LaneType::Bus);
+        edits3.lane_overrides.insert(lane.id, LaneType::Biking);
+        let err = edits1.merge(&edits3, &base, &map).unwrap_err().to_string();
+        assert!(err.contains(&missing.to_string()));
+        assert!(err.contains(&lane.id.to_string()));
+    });
+
+    t.run_slow("merge_intersection_type_and_control", |_| {
+        let map = Map::new("../data/raw_maps/montlake.abst", &mut Timer::throwaway()).unwrap();
+        let base = MapEdits::new(map.get_name().to_string());
+        let i = map
+            .all_intersections()
+            .iter()
+            .find(|i| i.intersection_type == IntersectionType::StopSign)
+            .unwrap()
+            .id;
+
+        let mut edits1 = base.clone();
+        edits1
+            .intersection_type_overrides
+            .insert(i, IntersectionType::TrafficSignal);
+
+        let mut edits2 = base.clone();
+        edits2
+            .stop_sign_overrides
+            .insert(i, map.get_stop_sign(i).clone());
+
+        assert!(edits1.merge(&edits2, &base, &map).is_err());
+        assert!(edits2.merge(&edits1, &base, &map).is_err());
+    });
+
+    t.run_fast("edits_without_closures", |_| {
+        // From before closures existed
+        let path = "../data/edits/edits_without_closures.json";
+        std::fs::create_dir_all("../data/edits").unwrap();
+        std::fs::write(
+            path,
+            r#"{
+                "map_name": "montlake",
+                "edits_name": "old",
+                "lane_overrides": [],
+                "road_overrides": [],
+                "intersection_type_overrides": [],
+                "stop_sign_overrides": [],
+                "traffic_signal_overrides": []
+            }"#,
+        )
+        .unwrap();
+        let perma: PermanentMapEdits = abstutil::read_json(path).unwrap();
+        assert!(perma.closures.is_empty());
+        std::fs::remove_file(path).unwrap();
+    });
+
+    t.run_slow("closure_start_and_end", |_| {
+        let mut map = Map::new("../data/raw_maps/montlake.abst", &mut Timer::throwaway()).unwrap();
+        let lane = map.all_lanes()[10].id;
+        assert!(Closure::new(
+            ClosureTarget::Lane(lane),
+            Duration::minutes(60),
+            Duration::minutes(30)
+        )
+        .is_err());
+        assert!(Closure::new(
+            ClosureTarget::Lane(lane),
+            Duration::minutes(60),
+            Duration::minutes(60)
+        )
+        .is_err());
+
+        let mut edits = map.get_edits().clone();
+        edits.closures.push(
+            Closure::new(
+                ClosureTarget::Lane(lane),
+                Duration::minutes(60),
+                Duration::minutes(120),
+            )
+            .unwrap(),
+        );
+        map.apply_edits(edits, &mut Timer::throwaway());
+
+        let on = Traversable::Lane(lane);
+        assert!(!map.is_closed(on, Duration::minutes(59)));
+        assert!(map.is_closed(on, Duration::minutes(60)));
+        assert!(map.is_closed(on, Duration::minutes(119)));
+        assert!(!map.is_closed(on, Duration::minutes(120)));
+        assert!(map.any_closures_at(Duration::minutes(90)));
+        assert!(!map.any_closures_at(Duration::minutes(150)));
+    });
+
+    t.run_slow("edits_survive_map_reconversion", |_| {
+        let map1 = Map::new("../data/raw_maps/montlake.abst", &mut Timer::throwaway()).unwrap();
+        let mut edits = MapEdits::new(map1.get_name().to_string());
+        let lane = &map1.all_lanes()[10];
+        edits.lane_overrides.insert(lane.id, LaneType::Bus);
+        edits.closures.push(Closure {
+            target: ClosureTarget::Road(lane.parent),
+            start: Duration::minutes(60),
+            end: Duration::minutes(120),
+        });
+        let turn = map1.get_i(lane.dst_i).turns[0];
+        edits.closures.push(Closure {
+            target: ClosureTarget::Turn(turn),
+            start: Duration::ZERO,
+            end: Duration::minutes(30),
+        });
+
+        let perma = PermanentMapEdits::to_permanent(&edits, &map1);
+
+        // Reconvert with every road renumbered and everything shifted a few meters north.
+        let mut raw: raw_data::Map =
+            abstutil::read_binary("../data/raw_maps/montlake.abst", &mut Timer::throwaway())
+                .unwrap();
+        let shift = |pt: &mut LonLat| pt.latitude += 0.00003;
+        let num_roads = raw.roads.len();
+        raw.roads = std::mem::replace(&mut raw.roads, BTreeMap::new())
+            .into_iter()
+            .enumerate()
+            .map(|(idx, (_, mut road))| {
+                road.points.iter_mut().for_each(shift);
+                (raw_data::StableRoadID(num_roads - idx), road)
+            })
+            .collect();
+        for i in raw.intersections.values_mut() {
+            shift(&mut i.point);
+        }
+        raw.boundary_polygon.iter_mut().for_each(shift);
+        let map2 = Map::create_from_raw("montlake".to_string(), raw, &mut Timer::throwaway());
+
+        let resolved = perma.from_permanent(&map2).unwrap();
+        assert_eq!(resolved.lane_overrides.len(), 1);
+        let new_lane = *resolved.lane_overrides.keys().next().unwrap();
+        assert_eq!(
+            map2.get_parent(new_lane).osm_way_id,
+            map1.get_parent(lane.id).osm_way_id
+        );
+        assert_eq!(
+            map2.get_parent(new_lane).dir_and_offset(new_lane),
+            map1.get_parent(lane.id).dir_and_offset(lane.id)
+        );
+        assert_eq!(resolved.closures.len(), 2);
+        assert_eq!(
+            resolved.closures[0].target,
+            ClosureTarget::Road(map2.get_l(new_lane).parent)
+        );
+    });
+
+    t.run_slow("edits_match_intersections_by_osm_node", |_| {
+        let load = |shift_by: f64, renumber: i64| {
+            let mut raw: raw_data::Map =
+                abstutil::read_binary("../data/raw_maps/montlake.abst", &mut Timer::throwaway())
+                    .unwrap();
+            let shift = |pt: &mut LonLat| pt.latitude += shift_by;
+            for road in raw.roads.values_mut() {
+                road.points.iter_mut().for_each(shift);
+            }
+            for (idx, i) in raw.intersections.values_mut().enumerate() {
+                shift(&mut i.point);
+                i.osm_node_id = Some(idx as i64 + renumber);
+            }
+            raw.boundary_polygon.iter_mut().for_each(shift);
+            Map::create_from_raw("montlake".to_string(), raw, &mut Timer::throwaway())
+        };
+        let map1 = load(0.0, 0);
+        let i = map1
+            .all_intersections()
+            .iter()
+            .find(|i| i.intersection_type == IntersectionType::StopSign)
+            .unwrap();
+        let mut edits = MapEdits::new(map1.get_name().to_string());
+        edits
+            .intersection_type_overrides
+            .insert(i.id, IntersectionType::TrafficSignal);
+        let perma = PermanentMapEdits::to_permanent(&edits, &map1);
+
+        // Everything moved about 100m, too far to match by position
+        let map2 = load(0.001, 0);
+        let resolved = perma.clone().from_permanent(&map2).unwrap();
+        let id = *resolved.intersection_type_overrides.keys().next().unwrap();
+        assert_eq!(map2.get_i(id).osm_node_id, i.osm_node_id);
+
+        // Different OSM nodes in the same places still match by position.
+        let map3 = load(0.0, 1_000_000);
+        let resolved = perma.from_permanent(&map3).unwrap();
+        let id = *resolved.intersection_type_overrides.keys().next().unwrap();
+        assert_eq!(map3.get_i(id).stable_id, i.stable_id);
+    });
+
+    t.run_slow("edits_on_rearranged_roads_survive_saving", |_| {
+        let mut map1 = Map::new("../data/raw_maps/montlake.abst", &mut Timer::throwaway()).unwrap();
+        let r = map1
+            .all_roads()
+            .iter()
+            .find(|r| {
+                r.children_forwards
+                    .iter()
+                    .any(|(_, lt)| *lt == LaneType::Driving)
+                    && !r.children_backwards.is_empty()
+            })
+            .unwrap()
+            .id;
+        let num_lanes = map1.all_lanes().len();
+        let (mut fwd, back) = map1.get_r(r).get_lane_types();
+        fwd.insert(0, LaneType::Driving);
+
+        let mut edits = MapEdits::new(map1.get_name().to_string());
+        edits.road_overrides.insert(r, RoadSpec { fwd, back });
+        map1.apply_edits(edits, &mut Timer::throwaway());
+        // Only exists after the road is rearranged
+        let new_lane = LaneID(num_lanes);
+        let mut edits = map1.get_edits().clone();
+        edits.lane_overrides.insert(new_lane, LaneType::Bus);
+        edits.closures.push(Closure {
+            target: ClosureTarget::Lane(new_lane),
+            start: Duration::ZERO,
+            end: Duration::minutes(30),
+        });
+        map1.apply_edits(edits.clone(), &mut Timer::throwaway());
+
+        let perma = PermanentMapEdits::to_permanent(&edits, &map1);
+        let mut map2 = Map::new("../data/raw_maps/montlake.abst", &mut Timer::throwaway()).unwrap();
+        let resolved = perma.from_permanent(&map2).unwrap();
+        assert_eq!(resolved.road_overrides, edits.road_overrides);
+        assert_eq!(resolved.lane_overrides, edits.lane_overrides);
+        assert_eq!(resolved.closures, edits.closures);
+        // Resolving doesn't touch the map itself.
+        assert_eq!(map2.all_lanes().len(), num_lanes);
+
+        map2.apply_edits(resolved, &mut Timer::throwaway());
+        assert_eq!(
+            map2.get_r(r).get_lane_types(),
+            map1.get_r(r).get_lane_types()
+        );
+    });
+
+    t.run_slow("deleted_turns_leave_turn_lookup", |_| {
+        let mut map = Map::new("../data/raw_maps/montlake.abst", &mut Timer::throwaway()).unwrap();
+        let lane = map
+            .all_lanes()
+            .iter()
+            .find(|l| l.lane_type == LaneType::Driving && !map.get_turns_from_lane(l.id).is_empty())
+            .unwrap()
+            .id;
+        let old_turns: Vec<(TurnID, usize)> = map
+            .get_turns_from_lane(lane)
+            .into_iter()
+            .map(|t| (t.id, t.lookup_idx))
+            .collect();
+
+        let mut edits = MapEdits::new(map.get_name().to_string());
+        edits.lane_overrides.insert(lane, LaneType::Parking);
+        let effects = map.apply_edits(edits, &mut Timer::throwaway());
+        for (t, idx) in old_turns {
+            assert!(effects.deleted_turns.contains(&t));
+            assert!(map.maybe_get_t(t).is_none());
+            if let Some(other) = map.lookup_turn_by_idx(idx) {
+                assert_ne!(other, t);
+                assert_eq!(map.get_t(other).lookup_idx, idx);
+            }
+        }
+        for t in map.all_turns().values() {
+            assert_eq!(map.lookup_turn_by_idx(t.lookup_idx), Some(t.id));
+        }
+    });
+
+    t.run_slow("load_edits_with_raw_ids", |_| {
+        let map = Map::new("../data/raw_maps/montlake.abst", &mut Timer::throwaway()).unwrap();
+        let lane = map.all_lanes()[10].id;
+        // From before edits were stored in terms of OSM IDs
+        std::fs::create_dir_all("../data/edits/montlake").unwrap();
+        std::fs::write(
+            "../data/edits/montlake/raw_ids.json",
+            format!(
+                r#"{{
+                    "map_name": "montlake",
+                    "edits_name": "raw_ids",
+                    "lane_overrides": {{ "{}": "Bus", "{}": "Bus" }},
+                    "stop_sign_overrides": {{}},
+                    "traffic_signal_overrides": {{}}
+                }}"#,
+                lane.0,
+                map.all_lanes().len()
+            ),
+        )
+        .unwrap();
+        let edits = MapEdits::load(&map, "raw_ids", &mut Timer::throwaway()).unwrap();
+        std::fs::remove_file("../data/edits/montlake/raw_ids.json").unwrap();
+        assert_eq!(
+            edits.lane_overrides.into_iter().collect::<Vec<_>>(),
+            vec![(lane, LaneType::Bus)]
+        );
+
+        assert!(MapEdits::load(&map, "nonexistent", &mut Timer::throwaway()).is_err());
+    });
+    t.run_slow("add_and_remove_lanes", |_| {
+        let mut map = Map::new("../data/raw_maps/montlake.abst", &mut Timer::throwaway()).unwrap();
+        let r = map
+            .all_roads()
+            .iter()
+            .find(|r| {
+                r.children_forwards
+                    .iter()
+                    .any(|(_, lt)| *lt == LaneType::Driving)
+                    && !r.children_backwards.is_empty()
+            })
+            .unwrap()
+            .id;
+        let num_lanes = map.all_lanes().len();
+        let orig_types = map.get_r(r).get_lane_types();
+        let (mut fwd, back) = orig_types.clone();
+        fwd.insert(0, LaneType::Driving);
+
+        let mut edits = MapEdits::new(map.get_name().to_string());
+        edits.road_overrides.insert(
+            r,
+            RoadSpec {
+                fwd: fwd.clone(),
+                back,
+            },
+        );
+        let effects = map.apply_edits(edits, &mut Timer::throwaway());
+        let new_lane = LaneID(num_lanes);
+        assert_eq!(map.all_lanes().len(), num_lanes + 1);
+        assert!(effects.changed_lanes.contains(&new_lane));
+        assert!(effects.changed_roads.contains(&r));
+        assert_eq!(map.get_parent(new_lane).id, r);
+        assert_eq!(map.get_r(r).get_lane_types().0, fwd);
+
+        // Reverting detaches one of the driving lanes again, without renumbering anything.
+        map.apply_edits(
+            MapEdits::new(map.get_name().to_string()),
+            &mut Timer::throwaway(),
+        );
+        assert_eq!(map.all_lanes().len(), num_lanes + 1);
+        assert_eq!(map.get_r(r).get_lane_types(), orig_types);
+        assert_eq!(
+            map.all_lanes()
+                .iter()
+                .filter(|l| map.is_lane_removed(l.id))
+                .count(),
+            1
+        );
+        let removed = map
+            .all_lanes()
+            .iter()
+            .find(|l| map.is_lane_removed(l.id))
+            .unwrap()
+            .id;
+        assert!(map.maybe_get_l(removed).is_none());
+    });
+    t.run_slow("change_intersection_type", |_| {
+        let mut map = Map::new("../data/raw_maps/montlake.abst", &mut Timer::throwaway()).unwrap();
+        let i = map
+            .all_intersections()
+            .iter()
+            .find(|i| i.intersection_type == IntersectionType::StopSign)
+            .unwrap()
+            .id;
+
+        let mut edits = MapEdits::new(map.get_name().to_string());
+        edits
+            .intersection_type_overrides
+            .insert(i, IntersectionType::TrafficSignal);
+        let effects = map.apply_edits(edits, &mut Timer::throwaway());
+        assert!(effects.changed_intersections.contains(&i));
+        assert!(map.maybe_get_stop_sign(i).is_none());
+        assert!(map.maybe_get_traffic_signal(i).is_some());
+
+        let mut edits = map.get_edits().clone();
+        edits
+            .intersection_type_overrides
+            .insert(i, IntersectionType::Uncontrolled);
+        map.apply_edits(edits, &mut Timer::throwaway());
+        assert!(map.maybe_get_traffic_signal(i).is_none());
+        for t in &map.get_i(i).turns {
+            assert!(map.is_turn_allowed(*t));
+        }
+
+        map.apply_edits(
+            MapEdits::new(map.get_name().to_string()),
+            &mut Timer::throwaway(),
+        );
+        assert_eq!(map.get_i(i).intersection_type, IntersectionType::StopSign);
+        assert!(map.maybe_get_stop_sign(i).is_some());
+    });
+
+    t.run_fast("contraflow_bike_track_placement", |_| {
+        use LaneType::{Driving, Sidewalk, TwoWayBiking};
+        assert_eq!(
+            contraflow_bike_track(
+                &[Driving, TwoWayBiking, Sidewalk],
+                &[Driving, Sidewalk, TwoWayBiking]
+            ),
+            Some(true)
+        );
+        assert_eq!(
+            contraflow_bike_track(&[Driving, TwoWayBiking], &[TwoWayBiking, Sidewalk]),
+            Some(false)
+        );
+        // Just one direction
+        assert_eq!(
+            contraflow_bike_track(&[Driving, TwoWayBiking, Sidewalk], &[Driving, Sidewalk]),
+            None
+        );
+    });
+
+    t.run_slow("two_way_bike_track", |_| {
+        let mut map = Map::new("../data/raw_maps/montlake.abst", &mut Timer::throwaway()).unwrap();
+        let r = map
+            .all_roads()
+            .iter()
+            .find(|r| {
+                let (fwd, back) = r.get_lane_types();
+                fwd.contains(&LaneType::Driving)
+                    && back.contains(&LaneType::Driving)
+                    && fwd.last() == Some(&LaneType::Sidewalk)
+            })
+            .unwrap()
+            .id;
+        let old_width = map.get_r(r).lanes_wide();
+
+        // The track goes just inside the sidewalk, with its contraflow half last on the other side.
+        let (mut fwd, mut back) = map.get_r(r).get_lane_types();
+        fwd.insert(fwd.len() - 1, LaneType::TwoWayBiking);
+        back.push(LaneType::TwoWayBiking);
+        let mut edits = MapEdits::new(map.get_name().to_string());
+        edits.road_overrides.insert(r, RoadSpec { fwd, back });
+        map.apply_edits(edits, &mut Timer::throwaway());
+
+        let road = map.get_r(r);
+        let (track, contraflow) = road.two_way_track().unwrap();
+        assert!(road.is_forwards(track));
+        assert!(road.is_backwards(contraflow));
+        // The contraflow half doesn't take up any room of its own.
+        assert_eq!(road.lanes_wide(), (old_width.0 + 1, old_width.1));
+        let (track, contraflow) = (map.get_l(track), map.get_l(contraflow));
+        assert!(track
+            .first_pt()
+            .approx_eq(contraflow.last_pt(), Distance::meters(0.1)));
+        assert!(track
+            .last_pt()
+            .approx_eq(contraflow.first_pt(), Distance::meters(0.1)));
+
+        // Bikes can enter and leave both halves, at both ends.
+        for l in vec![track, contraflow] {
+            if map.get_i(l.dst_i).intersection_type != IntersectionType::Border {
+                assert!(!map.get_turns_from_lane(l.id).is_empty());
+            }
+            if map.get_i(l.src_i).intersection_type != IntersectionType::Border {
+                assert!(map.get_i(l.src_i).turns.iter().any(|t| t.dst == l.id));
+            }
+        }
+    });
+
+    t.run_slow("shared_use_paths_are_walkable", |_| {
+        let mut map = Map::new("../data/raw_maps/montlake.abst", &mut Timer::throwaway()).unwrap();
+        let i = map
+            .all_intersections()
+            .iter()
+            .find(|i| {
+                i.intersection_type == IntersectionType::Border
+                    && !i.get_outgoing_walking_lanes(&map).is_empty()
+            })
+            .unwrap()
+            .id;
+
+        let mut edits = MapEdits::new(map.get_name().to_string());
+        for l in map.get_i(i).get_outgoing_walking_lanes(&map) {
+            edits.lane_overrides.insert(l, LaneType::SharedUse);
+        }
+        map.apply_edits(edits, &mut Timer::throwaway());
+
+        let spot = SidewalkSpot::start_at_border(i, &map).unwrap();
+        assert_eq!(
+            map.get_l(spot.sidewalk_pos.lane()).lane_type,
+            LaneType::SharedUse
+        );
+    });
+}
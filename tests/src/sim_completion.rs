@@ -1,7 +1,9 @@
 use crate::runner::TestRunner;
 use abstutil::Timer;
 use geom::Duration;
-use sim::{Scenario, SimFlags};
+use map_model::IntersectionType;
+use sim::{AgentID, CarID, Event, IntersectionStats, PersonID, Scenario, SimFlags, TourParams};
+use std::collections::{BTreeMap, BTreeSet};
 
 pub fn run(t: &mut TestRunner) {
     t.run_slow("small_spawn_completes", |h| {
@@ -11,4 +13,164 @@ pub fn run(t: &mut TestRunner) {
         h.setup_done(&sim);
         sim.just_run_until_done(&map, Some(Duration::minutes(70)));
     });
+
+    t.run_slow("roundabouts_complete", |h| {
+        let (mut map, mut sim, mut rng) = SimFlags::for_test("roundabouts_complete")
+            .load(Some(Duration::seconds(30.0)), &mut Timer::throwaway());
+        let mut edits = map.get_edits().clone();
+        for i in map.all_intersections() {
+            if i.intersection_type == IntersectionType::StopSign {
+                edits
+                    .intersection_type_overrides
+                    .insert(i.id, IntersectionType::Roundabout);
+            }
+        }
+        map.apply_edits(edits, &mut Timer::throwaway());
+
+        Scenario::small_run(&map).instantiate(&mut sim, &map, &mut rng, &mut Timer::throwaway());
+        h.setup_done(&sim);
+        sim.just_run_until_done(&map, Some(Duration::minutes(70)));
+    });
+
+    t.run_slow("home_based_tours_complete", |h| {
+        let (map, mut sim, mut rng) = SimFlags::for_test("home_based_tours_complete")
+            .load(Some(Duration::seconds(30.0)), &mut Timer::throwaway());
+        let mut params = TourParams::typical_weekday();
+        params.percent_residents = 0.01;
+        Scenario::home_based_tours(&map, &params, &mut rng, &mut Timer::throwaway()).instantiate(
+            &mut sim,
+            &map,
+            &mut rng,
+            &mut Timer::throwaway(),
+        );
+        h.setup_done(&sim);
+        // Nobody stays out for more than 2 + 9 + 1 hours, plus time to get home.
+        sim.just_run_until_done(&map, Some(Duration::minutes(14 * 60)));
+
+        // Nobody starts a trip before finishing the previous one.
+        assert!(!sim.get_all_people().is_empty());
+        for person in sim.get_all_people() {
+            let times = sim.person_trip_times(person.id);
+            for pair in times.windows(2) {
+                if let Some(finished) = pair[0].2 {
+                    assert!(pair[1].1 >= finished);
+                }
+            }
+        }
+    });
+
+    t.run_slow("tour_drivers_keep_one_car", |h| {
+        let (map, mut sim, mut rng) = SimFlags::for_test("tour_drivers_keep_one_car")
+            .load(Some(Duration::seconds(30.0)), &mut Timer::throwaway());
+        let mut params = TourParams::typical_weekday();
+        params.percent_residents = 0.01;
+        // Everybody who doesn't walk drives.
+        params.percent_biking = 0.0;
+        params.percent_use_transit = 0.0;
+        Scenario::home_based_tours(&map, &params, &mut rng, &mut Timer::throwaway()).instantiate(
+            &mut sim,
+            &map,
+            &mut rng,
+            &mut Timer::throwaway(),
+        );
+        h.setup_done(&sim);
+
+        let mut cars_per_person: BTreeMap<PersonID, BTreeSet<CarID>> = BTreeMap::new();
+        while !sim.is_done() {
+            assert!(sim.time() < Duration::minutes(14 * 60), "Time limit hit");
+            sim.step(&map, Duration::seconds(10.0));
+            for ev in sim.get_events_since_last_step() {
+                if let Event::AgentSpawned(AgentID::Car(car), _) = ev {
+                    // Cars that already finished within this step can't be looked up anymore.
+                    if let Some(person) = sim
+                        .agent_to_trip(AgentID::Car(*car))
+                        .and_then(|trip| sim.trip_to_person(trip))
+                    {
+                        cars_per_person
+                            .entry(person)
+                            .or_insert_with(BTreeSet::new)
+                            .insert(*car);
+                    }
+                }
+            }
+        }
+
+        assert!(!cars_per_person.is_empty());
+        for (person, cars) in cars_per_person {
+            assert_eq!(cars.len(), 1, "{} drove {:?}", person, cars);
+        }
+    });
+
+    t.run_slow("intersection_stats", |h| {
+        let (map, mut sim, mut rng) =
+            SimFlags::for_test("intersection_stats").load(None, &mut Timer::throwaway());
+        Scenario::small_run(&map).instantiate(&mut sim, &map, &mut rng, &mut Timer::throwaway());
+        h.setup_done(&sim);
+        // TurnAccepted events are only kept while recording.
+        let events_path = "../data/events/intersection_stats.bin";
+        sim.record_events(events_path).unwrap();
+
+        let mut accepted = 0;
+        for _ in 0..10 {
+            sim.step(&map, Duration::minutes(1));
+            accepted += sim
+                .get_events_since_last_step()
+                .iter()
+                .filter(|ev| match ev {
+                    Event::TurnAccepted(_, _) => true,
+                    _ => false,
+                })
+                .count();
+        }
+
+        // Every accepted turn is measured exactly once, and so is every agent still waiting.
+        let all_stats = sim.get_all_intersection_stats(&map);
+        let still_waiting: usize = map
+            .all_intersections()
+            .iter()
+            .map(|i| sim.get_waiting_agents(i.id).len())
+            .sum();
+        assert!(accepted > 0);
+        assert_eq!(
+            all_stats.iter().map(|s| s.delays().count()).sum::<usize>(),
+            accepted + still_waiting
+        );
+        for stats in &all_stats {
+            let delays = stats.delays();
+            assert!(delays.percentile(50.0) <= delays.percentile(95.0));
+            assert!(delays.percentile(95.0) <= delays.max());
+            for cycle in &stats.cycles {
+                // Allow for float error
+                assert!(cycle.utilization() <= 1.0 + 1e-6);
+            }
+            // Ten minutes fill two windows.
+            assert_eq!(
+                stats
+                    .queue_lengths
+                    .iter()
+                    .map(|w| w.start)
+                    .collect::<Vec<_>>(),
+                vec![Duration::ZERO, Duration::minutes(5)]
+            );
+            for window in &stats.queue_lengths {
+                assert!(window.mean <= (window.max as f64) + 1e-6);
+                assert!(window.max <= stats.max_queue_length());
+            }
+        }
+
+        let path = sim.write_intersection_stats(&map).unwrap();
+        let loaded: Vec<IntersectionStats> = abstutil::read_json(&path).unwrap();
+        assert_eq!(
+            loaded
+                .iter()
+                .map(|s| (s.id, s.delays().count(), s.queue_lengths.len()))
+                .collect::<Vec<_>>(),
+            all_stats
+                .iter()
+                .map(|s| (s.id, s.delays().count(), s.queue_lengths.len()))
+                .collect::<Vec<_>>()
+        );
+        std::fs::remove_file(path).unwrap();
+        std::fs::remove_file(events_path).unwrap();
+    });
 }
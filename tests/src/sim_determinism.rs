@@ -1,7 +1,9 @@
 use crate::runner::TestRunner;
 use abstutil::Timer;
 use geom::Duration;
-use sim::{Scenario, Sim, SimFlags};
+use sim::{
+    CarID, DrawCarInput, Event, EventLog, GetDrawAgents, Scenario, Sim, SimFlags, Trajectories,
+};
 
 pub fn run(t: &mut TestRunner) {
     t.run_slow("serialization", |_| {
@@ -19,7 +21,7 @@ pub fn run(t: &mut TestRunner) {
         println!("Creating two simulations");
         let flags = SimFlags::for_test("from_scratch_1");
         let (map, mut sim1, _) = flags.load(None, &mut Timer::throwaway());
-        let mut sim2 = Sim::new(&map, "from_scratch_2".to_string(), None);
+        let mut sim2 = Sim::new(&map, "from_scratch_2".to_string(), None, flags.rng_seed);
         Scenario::small_run(&map).instantiate(
             &mut sim1,
             &map,
@@ -52,7 +54,7 @@ pub fn run(t: &mut TestRunner) {
         println!("Creating two simulations");
         let flags = SimFlags::for_test("with_savestating_1");
         let (map, mut sim1, _) = flags.load(None, &mut Timer::throwaway());
-        let mut sim2 = Sim::new(&map, "with_savestating_2".to_string(), None);
+        let mut sim2 = Sim::new(&map, "with_savestating_2".to_string(), None, flags.rng_seed);
         Scenario::small_run(&map).instantiate(
             &mut sim1,
             &map,
@@ -89,8 +91,16 @@ pub fn run(t: &mut TestRunner) {
             );
         }
 
-        let sim3: Sim =
-            Sim::load_savestate(sim1_save.clone(), Some("with_savestating_3".to_string())).unwrap();
+        // The header has to match.
+        assert!(Sim::load_savestate(sim1_save.clone(), None, &map, Some(7)).is_err());
+
+        let sim3: Sim = Sim::load_savestate(
+            sim1_save.clone(),
+            Some("with_savestating_3".to_string()),
+            &map,
+            flags.rng_seed,
+        )
+        .unwrap();
         if sim3 != sim2 {
             panic!(
                 "sim state differs between {} and {}",
@@ -101,4 +111,110 @@ pub fn run(t: &mut TestRunner) {
 
         std::fs::remove_file(sim1_save).unwrap();
     });
+
+    t.run_slow("recorded_events", |_| {
+        let (map, mut sim, mut rng) =
+            SimFlags::for_test("recorded_events").load(None, &mut Timer::throwaway());
+        Scenario::small_run(&map).instantiate(&mut sim, &map, &mut rng, &mut Timer::throwaway());
+
+        // Without a recording, the high-volume events aren't kept at all.
+        sim.step(&map, Duration::minutes(1));
+        assert!(!sim
+            .get_events_since_last_step()
+            .iter()
+            .any(|ev| ev.is_high_volume()));
+
+        let path = "../data/events/recorded_events.bin";
+        sim.record_events(path).unwrap();
+        let mut expected = Vec::new();
+        for _ in 0..5 {
+            sim.step(&map, Duration::minutes(1));
+            expected.extend(sim.get_events_since_last_step().iter().cloned());
+        }
+        assert!(expected.iter().any(|ev| ev.is_high_volume()));
+
+        let log = EventLog::open(path).unwrap();
+        assert_eq!(log.header.run_name, "recorded_events");
+        let events: Vec<(Duration, Event)> = log.collect::<Result<_, _>>().unwrap();
+        assert!(!events.is_empty());
+        assert!(events.windows(2).all(|pair| pair[0].0 <= pair[1].0));
+        assert_eq!(
+            events.into_iter().map(|(_, ev)| ev).collect::<Vec<Event>>(),
+            expected
+        );
+
+        // Chop off part of the last event, like a crash partway through writing it. Everything
+        // before that is still there, followed by one error.
+        let mut bytes = std::fs::read(path).unwrap();
+        bytes.truncate(bytes.len() - 1);
+        std::fs::write(path, bytes).unwrap();
+        let results: Vec<Result<(Duration, Event), std::io::Error>> =
+            EventLog::open(path).unwrap().collect();
+        assert_eq!(results.len(), expected.len());
+        assert!(results[..results.len() - 1].iter().all(|r| r.is_ok()));
+        assert!(results.last().unwrap().is_err());
+
+        std::fs::remove_file(path).unwrap();
+
+        // Failing to write stops the recording and reports why, once.
+        sim.record_events("/dev/full").unwrap();
+        sim.step(&map, Duration::minutes(1));
+        assert_eq!(sim.take_recording_errors().len(), 1);
+        sim.step(&map, Duration::minutes(1));
+        assert!(sim.take_recording_errors().is_empty());
+        assert!(!sim
+            .get_events_since_last_step()
+            .iter()
+            .any(|ev| ev.is_high_volume()));
+    });
+    t.run_slow("replay_trajectories", |_| {
+        let flags = SimFlags::for_test("replay_trajectories");
+        let (map, mut sim1, _) = flags.load(None, &mut Timer::throwaway());
+        let mut sim2 = Sim::new(
+            &map,
+            "replay_trajectories_2".to_string(),
+            None,
+            flags.rng_seed,
+        );
+        Scenario::small_run(&map).instantiate(
+            &mut sim1,
+            &map,
+            &mut flags.make_rng(),
+            &mut Timer::throwaway(),
+        );
+        Scenario::small_run(&map).instantiate(
+            &mut sim2,
+            &map,
+            &mut flags.make_rng(),
+            &mut Timer::throwaway(),
+        );
+
+        // Frames shouldn't depend on how big the steps are.
+        let dir = sim1.record_trajectories(Duration::seconds(1.0)).unwrap();
+        sim1.step(&map, Duration::minutes(5));
+        sim2.step(&map, Duration::seconds(90.0));
+
+        let mut replay = Trajectories::load(&dir).unwrap();
+        assert_eq!(replay.start_time(), Duration::ZERO);
+        assert_eq!(replay.end_time(), Duration::minutes(5));
+        replay.seek(Duration::seconds(90.5)).unwrap();
+        assert_eq!(replay.time(), Duration::seconds(90.0));
+        let ids = |cars: Vec<DrawCarInput>| {
+            let mut ids: Vec<CarID> = cars.into_iter().map(|c| c.id).collect();
+            ids.sort();
+            ids
+        };
+        assert_eq!(
+            ids(replay.get_all_draw_cars(&map)),
+            ids(sim2.get_all_draw_cars(&map))
+        );
+
+        // A frame cut off partway through is an error, not a shorter recording.
+        let last_chunk = format!("{}/{:06}.bin", dir, 5);
+        let bytes = std::fs::read(&last_chunk).unwrap();
+        std::fs::write(&last_chunk, &bytes[..bytes.len() - 1]).unwrap();
+        assert!(Trajectories::load(&dir).is_err());
+
+        std::fs::remove_dir_all(dir).unwrap();
+    });
 }
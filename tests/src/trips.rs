@@ -1,10 +1,52 @@
-use crate::runner::TestRunner;
+use crate::runner::{TestHelper, TestRunner};
 use abstutil::Timer;
-use geom::Duration;
-use map_model::{BuildingID, IntersectionID};
-use sim::{DrivingGoal, Event, Scenario, SidewalkSpot, SimFlags, TripSpec};
+use geom::{Distance, Duration, Speed};
+use map_model::{
+    raw_data, BikeParkingID, BuildingID, Closure, ClosureTarget, IntersectionID, IntersectionType,
+    LaneType, Map, PathRequest, PathStep, Position, Traversable, TurnID, TurnType,
+};
+use sim::{
+    AgentID, DrivingGoal, Event, PersonSpec, PersonTrip, Scenario, SidewalkSpot, Sim, SimFlags,
+    TripMode, TripSpec,
+};
+use std::collections::BTreeSet;
 
 pub fn run(t: &mut TestRunner) {
+    t.run_fast("speed_on_grade", |_| {
+        let mps = |s: Speed| s.inner_meters_per_second();
+        let flat = Speed::meters_per_second(4.0);
+
+        // Uphill gets slower the steeper it is, down to a quarter of the speed.
+        assert_eq!(mps(sim::bike_speed_on_grade(flat, 0.0)), 4.0);
+        assert!((mps(sim::bike_speed_on_grade(flat, 0.1)) - 2.0).abs() < 1e-6);
+        let mut last = mps(flat);
+        for grade in vec![0.01, 0.03, 0.05, 0.1, 0.2] {
+            let speed = mps(sim::bike_speed_on_grade(flat, grade));
+            assert!(speed < last, "{} grade isn't slower", grade);
+            last = speed;
+        }
+        assert_eq!(mps(sim::bike_speed_on_grade(flat, 1.0)), 1.0);
+        // Downhill gets faster the steeper it is, up to half again as fast.
+        assert!((mps(sim::bike_speed_on_grade(flat, -0.05)) - 5.0).abs() < 1e-6);
+        let mut last = mps(flat);
+        for grade in vec![-0.01, -0.03, -0.05, -0.1] {
+            let speed = mps(sim::bike_speed_on_grade(flat, grade));
+            assert!(speed > last, "{} grade isn't faster", grade);
+            last = speed;
+        }
+        assert_eq!(mps(sim::bike_speed_on_grade(flat, -0.5)), 6.0);
+
+        // Walking is fastest slightly downhill and unchanged on flat ground.
+        let walk = Speed::meters_per_second(1.34);
+        let walking = |grade| mps(sim::walking_speed_on_grade(walk, grade));
+        assert!((walking(0.0) - 1.34).abs() < 1e-6);
+        assert!(walking(-0.05) > walking(0.0));
+        assert!(walking(-0.05) > walking(-0.1));
+        assert!(walking(0.1) < walking(0.05));
+        assert!(walking(0.05) < walking(0.0));
+        assert!(walking(-0.3) < walking(0.0));
+    });
+
     t.run_slow("bike_from_border", |h| {
         let (map, mut sim, mut rng) = SimFlags::for_test("bike_from_border")
             .load(Some(Duration::seconds(30.0)), &mut Timer::throwaway());
@@ -36,4 +78,428 @@ pub fn run(t: &mut TestRunner) {
         );
         sim.just_run_until_done(&map, Some(Duration::minutes(1)));
     });
+
+    t.run_slow("person_keeps_one_bike", |h| {
+        let (map, mut sim, mut rng) = SimFlags::for_test("person_keeps_one_bike")
+            .load(Some(Duration::seconds(30.0)), &mut Timer::throwaway());
+        // Two buildings far apart, both on roads that bikes can use
+        let bikeable: Vec<BuildingID> = map
+            .all_buildings()
+            .iter()
+            .filter(|b| {
+                map.get_parent(b.sidewalk())
+                    .sidewalk_to_bike(b.sidewalk())
+                    .is_some()
+            })
+            .map(|b| b.id)
+            .collect();
+        let home = bikeable[0];
+        let work = *bikeable.last().unwrap();
+        let person = sim.new_person(PersonSpec {
+            home,
+            car: None,
+            ped_speed: Scenario::rand_ped_speed(&mut rng),
+            bike: Scenario::rand_bike(&mut rng),
+            car_spec: Scenario::rand_car(&mut rng),
+            schedule: vec![
+                PersonTrip {
+                    depart: Duration::ZERO,
+                    to: work,
+                    mode: TripMode::Bike,
+                },
+                PersonTrip {
+                    depart: Duration::minutes(1),
+                    to: home,
+                    mode: TripMode::Bike,
+                },
+            ],
+        });
+        let bike = sim.get_person(person).unwrap().bike;
+        h.setup_done(&sim);
+
+        let mut rides = 0;
+        while !sim.is_done() {
+            assert!(sim.time() < Duration::minutes(60), "Time limit hit");
+            sim.step(&map, Duration::seconds(10.0));
+            for ev in sim.get_events_since_last_step() {
+                match ev {
+                    Event::AgentSpawned(AgentID::Car(id), _) => {
+                        assert_eq!(*id, bike);
+                        rides += 1;
+                    }
+                    Event::TripAborted(_) | Event::PersonTripSkipped(_, _) => {
+                        panic!("{:?}", ev);
+                    }
+                    _ => {}
+                }
+            }
+        }
+        assert!(rides >= 2);
+        let person = sim.get_person(person).unwrap();
+        assert_eq!(person.trips.len(), 2);
+        assert_eq!(person.bldg, Some(home));
+    });
+
+    t.run_slow("bikeshare_dock_runs_out", |h| {
+        let (map, home, work) = map_with_docks(|map, home, work| {
+            // Only one bike to start with at home, and another one down the road
+            let other = closest_bikeable(map, home, &[home, work]);
+            vec![(home, 2), (other, 2), (work, 10)]
+        });
+        let events = race_for_bikes(&map, "bikeshare_dock_runs_out", home, work, h);
+
+        assert_eq!(
+            events
+                .iter()
+                .filter(|ev| match ev {
+                    Event::BikeshareDockEmpty(_, id) => *id == BikeParkingID(0),
+                    _ => false,
+                })
+                .count(),
+            1
+        );
+    });
+
+    t.run_slow("bikeshare_dock_fills_up", |h| {
+        let (map, home, work) = map_with_docks(|map, home, work| {
+            // Only room for one more bike by work, so the second rider goes on to the next dock
+            let other = closest_bikeable(map, work, &[home, work]);
+            vec![(home, 4), (work, 2), (other, 10)]
+        });
+        let events = race_for_bikes(&map, "bikeshare_dock_fills_up", home, work, h);
+
+        assert_eq!(
+            events
+                .iter()
+                .filter(|ev| match ev {
+                    Event::BikeParkingFull(_, id) => *id == BikeParkingID(1),
+                    _ => false,
+                })
+                .count(),
+            1
+        );
+    });
+
+    t.run_slow("cars_reroute_around_closures", |h| {
+        let (mut map, mut sim, mut rng) =
+            SimFlags::for_test("cars_reroute_around_closures").load(None, &mut Timer::throwaway());
+        let candidates: Vec<(Position, DrivingGoal)> = borders(&map)
+            .into_iter()
+            .filter_map(|(i1, i2)| {
+                let start = *map
+                    .get_i(i1)
+                    .get_outgoing_lanes(&map, LaneType::Driving)
+                    .get(0)?;
+                let goal = DrivingGoal::end_at_border(i2, vec![LaneType::Driving], &map)?;
+                Some((Position::new(start, Distance::ZERO), goal))
+            })
+            .collect();
+        let mut found = None;
+        for (start, goal) in candidates {
+            let req = PathRequest {
+                start,
+                end: goal.goal_pos(&map),
+                can_use_bike_lanes: false,
+                can_use_bus_lanes: false,
+            };
+            if let Some(closed) = close_turn_on_path(&mut map, &mut sim, req, |_, _| true) {
+                found = Some((start, goal, closed));
+                break;
+            }
+        }
+        let (start, goal, closed) = found.unwrap();
+        let car = sim
+            .schedule_trip(
+                Duration::ZERO,
+                TripSpec::CarAppearing {
+                    start_pos: start,
+                    vehicle_spec: Scenario::rand_car(&mut rng),
+                    goal,
+                    ped_speed: Scenario::rand_ped_speed(&mut rng),
+                },
+                &map,
+            )
+            .1
+            .unwrap();
+        sim.spawn_all_trips(&map, &mut Timer::throwaway());
+        h.setup_done(&sim);
+
+        let events = run_and_collect_events(&mut sim, &map, AgentID::Car(car));
+        assert!(!events.contains(&Event::AgentEntersTraversable(
+            AgentID::Car(car),
+            Traversable::Turn(closed)
+        )));
+        assert!(events.iter().any(|ev| match ev {
+            Event::CarOrBikeReachedBorder(id, _) => *id == car,
+            _ => false,
+        }));
+    });
+
+    t.run_slow("peds_reroute_around_closures", |h| {
+        let (mut map, mut sim, mut rng) =
+            SimFlags::for_test("peds_reroute_around_closures").load(None, &mut Timer::throwaway());
+        let candidates: Vec<(SidewalkSpot, SidewalkSpot)> = borders(&map)
+            .into_iter()
+            .filter_map(|(i1, i2)| {
+                Some((
+                    SidewalkSpot::start_at_border(i1, &map)?,
+                    SidewalkSpot::end_at_border(i2, &map)?,
+                ))
+            })
+            .collect();
+        let mut found = None;
+        for (start, goal) in candidates {
+            let req = PathRequest {
+                start: start.sidewalk_pos,
+                end: goal.sidewalk_pos,
+                can_use_bike_lanes: false,
+                can_use_bus_lanes: false,
+            };
+            if let Some(closed) = close_turn_on_path(&mut map, &mut sim, req, |map, t| {
+                map.get_t(t).turn_type == TurnType::Crosswalk
+            }) {
+                found = Some((start, goal, closed));
+                break;
+            }
+        }
+        let (start, goal, closed) = found.unwrap();
+        let ped = sim
+            .schedule_trip(
+                Duration::ZERO,
+                TripSpec::JustWalking {
+                    start,
+                    goal,
+                    ped_speed: Scenario::rand_ped_speed(&mut rng),
+                },
+                &map,
+            )
+            .0
+            .unwrap();
+        sim.spawn_all_trips(&map, &mut Timer::throwaway());
+        h.setup_done(&sim);
+
+        let events = run_and_collect_events(&mut sim, &map, AgentID::Pedestrian(ped));
+        assert!(!events.contains(&Event::AgentEntersTraversable(
+            AgentID::Pedestrian(ped),
+            Traversable::Turn(closed)
+        )));
+        assert!(events.iter().any(|ev| match ev {
+            Event::PedReachedBorder(id, _) => *id == ped,
+            _ => false,
+        }));
+    });
+}
+
+// Every pair of different borders
+fn borders(map: &Map) -> Vec<(IntersectionID, IntersectionID)> {
+    let borders: Vec<IntersectionID> = map
+        .all_intersections()
+        .iter()
+        .filter(|i| i.intersection_type == IntersectionType::Border)
+        .map(|i| i.id)
+        .collect();
+    let mut pairs = Vec::new();
+    for i1 in &borders {
+        for i2 in &borders {
+            if i1 != i2 {
+                pairs.push((*i1, *i2));
+            }
+        }
+    }
+    pairs
+}
+
+// Closes the last turn on this path that can be avoided, starting a second into the simulation.
+// By then, the agent has already picked a path through it, so they have to reroute on the way.
+// Applies the edits to the running sim, like the editor does.
+fn close_turn_on_path<F: Fn(&Map, TurnID) -> bool>(
+    map: &mut Map,
+    sim: &mut Sim,
+    req: PathRequest,
+    can_close: F,
+) -> Option<TurnID> {
+    let path = map.pathfind(req.clone())?;
+    let mut turns: Vec<TurnID> = Vec::new();
+    for step in path.get_steps() {
+        if let PathStep::Turn(t) = step {
+            if can_close(map, *t) {
+                turns.push(*t);
+            }
+        }
+    }
+    // Leave plenty of time before the agent reaches the turn.
+    if turns.len() < 4 {
+        return None;
+    }
+    let orig_edits = map.get_edits().clone();
+    for t in turns.into_iter().rev().take(2) {
+        let mut edits = orig_edits.clone();
+        edits.closures.push(
+            Closure::new(
+                ClosureTarget::Turn(t),
+                Duration::seconds(1.0),
+                Duration::minutes(60),
+            )
+            .unwrap(),
+        );
+        map.apply_edits(edits, &mut Timer::throwaway());
+        if map
+            .pathfind_at(req.clone(), Duration::seconds(1.0))
+            .is_some()
+        {
+            sim.handle_intersection_edits(&BTreeSet::new(), map);
+            return Some(t);
+        }
+        map.apply_edits(orig_edits.clone(), &mut Timer::throwaway());
+    }
+    None
+}
+
+// Montlake with bikeshare docks in front of some buildings. The callback picks (building,
+// capacity) pairs given two bikeable buildings far apart, and those two are returned along with
+// the map. Dock IDs follow the order of the pairs.
+fn map_with_docks<F: Fn(&Map, BuildingID, BuildingID) -> Vec<(BuildingID, usize)>>(
+    pick_docks: F,
+) -> (Map, BuildingID, BuildingID) {
+    let path = "../data/raw_maps/montlake.abst";
+    let orig = Map::new(path, &mut Timer::throwaway()).unwrap();
+    let bikeable = bikeable_buildings(&orig);
+    let home = bikeable[0];
+    let work = *bikeable.last().unwrap();
+    let docks = pick_docks(&orig, home, work);
+
+    let mut raw: raw_data::Map = abstutil::read_binary(path, &mut Timer::throwaway()).unwrap();
+    raw.bike_parking = docks
+        .iter()
+        .enumerate()
+        .map(|(idx, (b, capacity))| raw_data::BikeParking {
+            point: orig.get_gps_bounds().must_convert_back(&vec![orig
+                .get_b(*b)
+                .front_path
+                .sidewalk
+                .pt(&orig)])[0],
+            is_bikeshare: true,
+            capacity: Some(*capacity),
+            osm_node_id: idx as i64,
+        })
+        .collect();
+    let map = Map::create_from_raw("montlake".to_string(), raw, &mut Timer::throwaway());
+    assert_eq!(map.all_bike_parking().len(), docks.len());
+    (map, home, work)
+}
+
+fn bikeable_buildings(map: &Map) -> Vec<BuildingID> {
+    map.all_buildings()
+        .iter()
+        .filter(|b| {
+            map.get_parent(b.sidewalk())
+                .sidewalk_to_bike(b.sidewalk())
+                .is_some()
+        })
+        .map(|b| b.id)
+        .collect()
+}
+
+// The closest bikeable building to this one that isn't on the same sidewalk as any of the others
+fn closest_bikeable(map: &Map, to: BuildingID, others: &[BuildingID]) -> BuildingID {
+    let pt = map.get_b(to).front_path.sidewalk.pt(map);
+    bikeable_buildings(map)
+        .into_iter()
+        .filter(|b| {
+            others
+                .iter()
+                .all(|other| map.get_b(*b).sidewalk() != map.get_b(*other).sidewalk())
+        })
+        .min_by_key(|b| map.get_b(*b).front_path.sidewalk.pt(map).dist_to(pt))
+        .unwrap()
+}
+
+// Two people rent bikes from the first dock at the same time and ride them to work. Checks that
+// both make it without abandoning a bike, and that the docks end up with as many bikes as they
+// started with. Returns every event.
+fn race_for_bikes(
+    map: &Map,
+    run_name: &str,
+    home: BuildingID,
+    work: BuildingID,
+    h: &mut TestHelper,
+) -> Vec<Event> {
+    let flags = SimFlags::for_test(run_name);
+    let mut rng = flags.make_rng();
+    let mut sim = Sim::new(map, run_name.to_string(), None, flags.rng_seed);
+    let count_bikes = |sim: &Sim| -> usize {
+        map.all_bike_parking()
+            .iter()
+            .map(|p| sim.bikes_parked_at(p.id))
+            .sum()
+    };
+    let initial_bikes = count_bikes(&sim);
+
+    let ped_speed = Scenario::rand_ped_speed(&mut rng);
+    let vehicle = Scenario::rand_bike(&mut rng);
+    let mut peds = Vec::new();
+    for _ in 0..2 {
+        peds.push(
+            sim.schedule_trip(
+                Duration::ZERO,
+                TripSpec::UsingBikeshare {
+                    start: SidewalkSpot::building(home, map),
+                    dock: BikeParkingID(0),
+                    vehicle: vehicle.clone(),
+                    goal: DrivingGoal::ParkNear(work),
+                    ped_speed,
+                },
+                map,
+            )
+            .0
+            .unwrap(),
+        );
+    }
+    sim.spawn_all_trips(map, &mut Timer::throwaway());
+    h.setup_done(&sim);
+
+    let mut events = Vec::new();
+    while !sim.is_done() {
+        assert!(sim.time() < Duration::minutes(60), "Time limit hit");
+        sim.step(map, Duration::seconds(10.0));
+        for ev in sim.get_events_since_last_step() {
+            match ev {
+                Event::TripAborted(_) | Event::BikeAbandoned(_, _) => {
+                    panic!("{:?}", ev);
+                }
+                _ => {}
+            }
+            events.push(ev.clone());
+        }
+    }
+    for ped in peds {
+        assert!(events.contains(&Event::PedReachedBuilding(ped, work)));
+    }
+    assert_eq!(count_bikes(&sim), initial_bikes);
+    events
+}
+
+// Everything that happens to one agent until the simulation is done. Events are recorded to a
+// file meanwhile, since otherwise the sim doesn't keep track of every lane and turn entered.
+fn run_and_collect_events(sim: &mut Sim, map: &Map, agent: AgentID) -> Vec<Event> {
+    let path = format!("../data/events/{}.bin", sim.get_name());
+    sim.record_events(&path).unwrap();
+    let mut events = Vec::new();
+    for _ in 0..30 {
+        sim.step(map, Duration::minutes(1));
+        events.extend(
+            sim.get_events_since_last_step()
+                .iter()
+                .filter(|ev| match ev {
+                    Event::AgentEntersTraversable(a, _) => *a == agent,
+                    _ => true,
+                })
+                .cloned(),
+        );
+        if sim.is_done() {
+            break;
+        }
+    }
+    std::fs::remove_file(path).unwrap();
+    events
 }